@@ -0,0 +1,584 @@
+//! Derive macros for Grapht
+//!
+//! `#[derive(GraphtEntity)]` implements `grapht::prelude::GraphtEntity` for a struct or an enum,
+//! writing each field as a property of the same name. Enum variants are each their own type of
+//! entity, labelled with the variant's name.
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Hash, PartialEq, Eq, GraphtEntity)]
+//! #[grapht(label = "Organization", key_from(pretty_id))]
+//! pub struct Organization {
+//!   pretty_id: String,
+//!   #[grapht(rename = "name")]
+//!   org_name: String,
+//!   balance: Decimal,
+//! }
+//!
+//! #[derive(Debug, Clone, Hash, PartialEq, Eq, GraphtEntity)]
+//! pub enum FhlNode {
+//!   Organization(Organization),
+//!   Invoice { #[grapht(key)] guid: Uuid, amount: Decimal },
+//! }
+//! ```
+//!
+//! Attributes:
+//! - `label = "..."` on a struct or variant replaces the type label taken from its name
+//! - `key` on a field of type Uuid uses it as the entity's key
+//! - `key_from(a, b)` on a struct or variant derives a v5 key from the Display of those fields,
+//!   joined by ':'
+//! - `namespace = "path::to::NAMESPACE"` on the struct or enum sets the Uuid namespace for derived
+//!   keys, which defaults to `Uuid::NAMESPACE_OID`
+//! - `rename = "..."` on a field changes the name of its property. Tuple fields need one, unless a
+//!   variant has a single unnamed field, in which case it wraps another entity and its properties
+//!   and key are those of the inner entity.
+//!
+//! Without a key, one is derived from the type label and the properties. Fields are written using
+//! `Literal::from` and read back with `Literal: TryFrom`, so `Option` fields may be left out.
+//!
+//! `get_inner` cannot be derived, since it would need to name the requested type.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::{format_ident, quote};
+use syn::{
+  parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, Ident, Lit,
+  Member, Meta, NestedMeta, Path, Result, Type,
+};
+
+#[proc_macro_derive(GraphtEntity, attributes(grapht))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match expand(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+// ---  Attributes
+
+/// The settings read from the `#[grapht(...)]` attributes of an item
+#[derive(Default)]
+struct Options {
+  label: Option<String>,
+  key: bool,
+  key_from: Option<Vec<Ident>>,
+  namespace: Option<Path>,
+  rename: Option<String>,
+}
+
+impl Options {
+  /// Read the grapht attributes, rejecting any setting not in allowed
+  fn new(attrs: &[Attribute], allowed: &[&str]) -> Result<Options> {
+    let mut options = Options::default();
+    for attr in attrs.iter().filter(|x| x.path.is_ident("grapht")) {
+      let nested = match attr.parse_meta()? {
+        Meta::List(list) => list.nested,
+        meta => return Err(Error::new(meta.span(), "Expected #[grapht(...)]")),
+      };
+
+      for item in nested {
+        let meta = match item {
+          NestedMeta::Meta(meta) => meta,
+          NestedMeta::Lit(lit) => return Err(Error::new(lit.span(), "Expected a grapht setting")),
+        };
+
+        let name = meta
+          .path()
+          .get_ident()
+          .map(|x| x.to_string())
+          .unwrap_or_default();
+        if !allowed.contains(&name.as_str()) {
+          return Err(Error::new(
+            meta.span(),
+            format!(
+              "Unknown grapht setting here, expected one of: {}",
+              allowed.join(", ")
+            ),
+          ));
+        }
+
+        match (name.as_str(), meta) {
+          ("key", Meta::Path(_)) => options.key = true,
+          ("label", meta) => options.label = Some(string_value(&meta)?),
+          ("rename", meta) => options.rename = Some(string_value(&meta)?),
+          ("namespace", meta) => {
+            let value = string_value(&meta)?;
+            let path = syn::parse_str(&value)
+              .map_err(|_| Error::new(meta.span(), "Expected the path to a Uuid namespace"))?;
+            options.namespace = Some(path);
+          }
+          ("key_from", Meta::List(list)) => {
+            let fields = list
+              .nested
+              .iter()
+              .map(|item| match item {
+                NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                  Ok(path.get_ident().unwrap().clone())
+                }
+                x => Err(Error::new(x.span(), "Expected a field name")),
+              })
+              .collect::<Result<Vec<_>>>()?;
+            options.key_from = Some(fields);
+          }
+          (_, meta) => return Err(Error::new(meta.span(), "Malformed grapht setting")),
+        }
+      }
+    }
+    Ok(options)
+  }
+}
+
+fn string_value(meta: &Meta) -> Result<String> {
+  match meta {
+    Meta::NameValue(value) => match &value.lit {
+      Lit::Str(value) => Ok(value.value()),
+      lit => Err(Error::new(lit.span(), "Expected a string")),
+    },
+    meta => Err(Error::new(meta.span(), "Expected a string value")),
+  }
+}
+
+// ---  The shape of the entity
+
+/// A single struct or enum variant, which is a type of entity
+struct Shape {
+  /// The path used to match and build the shape, `Self` or `Self::Variant`
+  path: Tokens,
+  label: String,
+  body: Body,
+  key: Key,
+}
+
+enum Body {
+  Unit,
+  Properties(Vec<Prop>),
+  /// A wrapped entity, which provides the properties and key
+  Inner(Type),
+}
+
+/// A field that is stored as a property
+struct Prop {
+  member: Member,
+  binding: Ident,
+  ident: Option<Ident>,
+  name: String,
+  ty: Type,
+}
+
+enum Key {
+  Field(Ident),
+  From(Vec<Ident>),
+  Inner,
+  Properties,
+}
+
+impl Shape {
+  fn new(path: Tokens, name: &Ident, options: Options, fields: &Fields) -> Result<Shape> {
+    let label = options.label.unwrap_or_else(|| name.to_string());
+    let mut key_field = None;
+
+    let body = match fields {
+      Fields::Unit => Body::Unit,
+      Fields::Unnamed(unnamed)
+        if unnamed.unnamed.len() == 1 && Options::new(&unnamed.unnamed[0].attrs, &[]).is_ok() =>
+      {
+        Body::Inner(unnamed.unnamed[0].ty.clone())
+      }
+      fields => {
+        let mut props = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+          let field_options = Options::new(&field.attrs, &["key", "rename"])?;
+          let binding = format_ident!("__field{}", i);
+          let (member, name) = match (&field.ident, field_options.rename) {
+            (_, Some(name)) => (member(field.ident.as_ref(), i), name),
+            (Some(ident), None) => (Member::Named(ident.clone()), ident.to_string()),
+            (None, None) => {
+              return Err(Error::new(
+                field.span(),
+                "Tuple fields need a #[grapht(rename = \"...\")] to name their property",
+              ))
+            }
+          };
+
+          if field_options.key {
+            if key_field.is_some() {
+              return Err(Error::new(field.span(), "Only one field can be the key"));
+            }
+            key_field = Some(binding.clone());
+          }
+
+          props.push(Prop {
+            member,
+            binding,
+            ident: field.ident.clone(),
+            name,
+            ty: field.ty.clone(),
+          });
+        }
+        Body::Properties(props)
+      }
+    };
+
+    let key = match (&body, key_field, options.key_from) {
+      (_, Some(_), Some(_)) => {
+        return Err(Error::new(
+          name.span(),
+          "Use either a key field or key_from, not both",
+        ))
+      }
+      (_, Some(field), None) => Key::Field(field),
+      (Body::Properties(props), None, Some(fields)) => Key::From(
+        fields
+          .iter()
+          .map(|field| {
+            props
+              .iter()
+              .find(|prop| prop.ident.as_ref() == Some(field) || *field == prop.name)
+              .map(|prop| prop.binding.clone())
+              .ok_or_else(|| Error::new(field.span(), "key_from names an unknown field"))
+          })
+          .collect::<Result<Vec<_>>>()?,
+      ),
+      (_, None, Some(_)) => {
+        return Err(Error::new(
+          name.span(),
+          "key_from needs the fields it is derived from",
+        ))
+      }
+      (Body::Inner(_), None, None) => Key::Inner,
+      (_, None, None) => Key::Properties,
+    };
+
+    Ok(Shape {
+      path,
+      label,
+      body,
+      key,
+    })
+  }
+
+  fn props(&self) -> &[Prop] {
+    match &self.body {
+      Body::Properties(props) => props,
+      _ => &[],
+    }
+  }
+
+  /// A pattern binding only the listed fields
+  fn pattern(&self, bindings: &[&Ident]) -> Tokens {
+    let path = &self.path;
+    let bound = match &self.body {
+      Body::Inner(_) if !bindings.is_empty() => vec![quote!(0: __field0)],
+      _ => self
+        .props()
+        .iter()
+        .filter(|prop| bindings.contains(&&prop.binding))
+        .map(
+          |Prop {
+             member, binding, ..
+           }| quote!(#member: #binding),
+        )
+        .collect(),
+    };
+    quote!(#path { #(#bound,)* .. })
+  }
+
+  /// An expression for the properties of the shape, with all its fields bound
+  fn properties(&self) -> Tokens {
+    match &self.body {
+      Body::Unit => quote!(::std::collections::BTreeMap::new()),
+      Body::Inner(_) => quote!(::grapht::prelude::GraphtEntity::get_properties(__field0)?),
+      Body::Properties(props) => {
+        let names = props.iter().map(|x| &x.name);
+        let bindings = props.iter().map(|x| &x.binding);
+        quote!({
+          let mut properties = ::std::collections::BTreeMap::new();
+          #(
+            properties.insert(
+              #names.to_string(),
+              ::grapht::prelude::Literal::from(::core::clone::Clone::clone(#bindings)),
+            );
+          )*
+          properties
+        })
+      }
+    }
+  }
+
+  /// An expression building the shape from a mutable map named properties, which has the read
+  /// properties removed from it
+  fn build(&self) -> Tokens {
+    let path = &self.path;
+    let label = &self.label;
+    match &self.body {
+      Body::Unit => quote!(#path {}),
+      Body::Inner(ty) => quote!(#path {
+        0: <#ty as ::grapht::prelude::GraphtEntity>::from_properties(type_label, properties)?
+      }),
+      Body::Properties(props) => {
+        let reads = props.iter().map(
+          |Prop {
+             binding, name, ty, ..
+           }| {
+            quote! {
+              let #binding: #ty = ::core::convert::TryFrom::try_from(
+                properties.remove(#name).unwrap_or(::grapht::prelude::Literal::Null)
+              )
+              .map_err(|err| {
+                ::grapht::prelude::GraphtError::from(err)
+                  .context(format!("Reading the property '{}' of a {}", #name, #label))
+              })?;
+            }
+          },
+        );
+        let members = props.iter().map(|x| &x.member);
+        let bindings = props.iter().map(|x| &x.binding);
+        quote!({
+          #(#reads)*
+          #path { #(#members: #bindings),* }
+        })
+      }
+    }
+  }
+}
+
+fn member(ident: Option<&Ident>, index: usize) -> Member {
+  match ident {
+    Some(ident) => Member::Named(ident.clone()),
+    None => Member::Unnamed(index.into()),
+  }
+}
+
+// ---  Generating the impl
+
+fn expand(input: &DeriveInput) -> Result<Tokens> {
+  let name = &input.ident;
+  let is_enum = matches!(input.data, Data::Enum(_));
+
+  let options = match is_enum {
+    true => Options::new(&input.attrs, &["namespace"])?,
+    false => Options::new(&input.attrs, &["label", "key_from", "namespace"])?,
+  };
+  let namespace = match &options.namespace {
+    Some(path) => quote!(#path),
+    None => quote!(::uuid::Uuid::NAMESPACE_OID),
+  };
+
+  let shapes = match &input.data {
+    Data::Struct(data) => vec![Shape::new(quote!(Self), name, options, &data.fields)?],
+    Data::Enum(data) => {
+      let mut labels = HashSet::new();
+      let mut shapes = Vec::new();
+      for variant in &data.variants {
+        let ident = &variant.ident;
+        let options = Options::new(&variant.attrs, &["label", "key_from"])?;
+        let shape = Shape::new(quote!(Self::#ident), ident, options, &variant.fields)?;
+        if !labels.insert(shape.label.clone()) {
+          return Err(Error::new(
+            variant.span(),
+            format!("The label '{}' is used more than once", shape.label),
+          ));
+        }
+        shapes.push(shape);
+      }
+      shapes
+    }
+    Data::Union(_) => {
+      return Err(Error::new(
+        name.span(),
+        "GraphtEntity can only be derived for structs and enums",
+      ))
+    }
+  };
+
+  let labels = shapes.iter().map(|shape| {
+    let pattern = shape.pattern(&[]);
+    let label = &shape.label;
+    quote!(#pattern => #label.to_string())
+  });
+
+  let properties = shapes.iter().map(|shape| {
+    let bindings = match &shape.body {
+      Body::Inner(_) => vec![format_ident!("__field0")],
+      _ => shape.props().iter().map(|x| x.binding.clone()).collect(),
+    };
+    let pattern = shape.pattern(&bindings.iter().collect::<Vec<_>>());
+    let properties = shape.properties();
+    quote!(#pattern => Ok(#properties))
+  });
+
+  let keys = shapes.iter().map(|shape| {
+    let label = &shape.label;
+    let (bindings, key) = match &shape.key {
+      Key::Field(field) => (
+        vec![field.clone()],
+        quote!(::core::clone::Clone::clone(#field)),
+      ),
+      Key::From(fields) => (
+        fields.clone(),
+        quote!(::uuid::Uuid::new_v5(
+          &#namespace,
+          [#(::std::string::ToString::to_string(#fields)),*].join(":").as_bytes(),
+        )),
+      ),
+      Key::Inner => (
+        vec![format_ident!("__field0")],
+        quote!(::grapht::prelude::GraphtEntity::get_key(__field0)),
+      ),
+      Key::Properties => {
+        let properties = shape.properties();
+        (
+          shape.props().iter().map(|x| x.binding.clone()).collect(),
+          quote!(::uuid::Uuid::new_v5(
+            &#namespace,
+            format!("{}{}", #label, ::grapht::prelude::Literal::Map(#properties)).as_bytes(),
+          )),
+        )
+      }
+    };
+    let pattern = shape.pattern(&bindings.iter().collect::<Vec<_>>());
+    quote!(#pattern => #key)
+  });
+
+  let from_properties = match is_enum {
+    false => {
+      let build = shapes[0].build();
+      quote!({
+        let mut properties = properties;
+        Ok(#build)
+      })
+    }
+    true => {
+      let arms = shapes.iter().map(|shape| {
+        let label = &shape.label;
+        let build = shape.build();
+        quote!(#label => {
+          let mut properties = properties;
+          Ok(#build)
+        })
+      });
+      quote!(match type_label {
+        #(#arms,)*
+        _ => Err(
+          ::grapht::prelude::GraphtError::new(::grapht::prelude::Kind::InvalidItem).comment(format!(
+            "'{}' is not a type of {}",
+            type_label,
+            stringify!(#name)
+          ))
+        ),
+      })
+    }
+  };
+
+  // Enums don't write which variant they are, so try them all and keep the only one that fits
+  let from_gql = match is_enum {
+    false => {
+      let label = &shapes[0].label;
+      quote!(Self::from_properties(#label, properties))
+    }
+    true => {
+      let attempts = shapes.iter().map(|shape| {
+        let label = &shape.label;
+        let attempt = match &shape.body {
+          Body::Inner(ty) => {
+            let path = &shape.path;
+            quote!(
+              <#ty as ::grapht::prelude::GraphtEntity>::from_gql(value).map(|inner| #path { 0: inner })
+            )
+          }
+          _ => {
+            let build = shape.build();
+            quote!({
+              let type_label = #label;
+              let mut properties = properties.clone();
+              let entity = #build;
+              match properties.is_empty() {
+                true => Ok(entity),
+                false => Err(::grapht::prelude::GraphtError::new(
+                  ::grapht::prelude::Kind::InvalidItem,
+                )),
+              }
+            })
+          }
+        };
+        quote! {
+          let attempt = (|| -> ::grapht::prelude::GraphtResult<Self> { #attempt })();
+          if let Ok(entity) = attempt {
+            found.push(entity);
+          }
+        }
+      });
+      quote!({
+        let mut found = Vec::new();
+        #(#attempts)*
+        match found.len() {
+          1 => Ok(found.remove(0)),
+          0 => Err(
+            ::grapht::prelude::GraphtError::new(::grapht::prelude::Kind::NotFound).comment(format!(
+              "No type of {} has the properties {}",
+              stringify!(#name),
+              text
+            ))
+          ),
+          _ => Err(
+            ::grapht::prelude::GraphtError::new(::grapht::prelude::Kind::AmbiguousMatch).comment(format!(
+              "More than one type of {} has the properties {}, so it needs to be built with from_properties",
+              stringify!(#name),
+              text
+            ))
+          ),
+        }
+      })
+    }
+  };
+
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::grapht::prelude::GraphtEntity for #name #ty_generics #where_clause {
+      fn get_type_label(&self) -> String {
+        match self {
+          #(#labels,)*
+        }
+      }
+
+      fn get_key(&self) -> ::uuid::Uuid {
+        match self {
+          #(#keys,)*
+        }
+      }
+
+      fn get_inner<T: Clone + ::core::fmt::Debug>(&self) -> ::std::borrow::Cow<'_, T> {
+        unimplemented!("get_inner is not derived for {}", stringify!(#name))
+      }
+
+      fn to_gql(&self) -> ::grapht::prelude::GraphtResult<String> {
+        Ok(::grapht::prelude::Literal::Map(self.get_properties()?).to_string())
+      }
+
+      #[allow(unused_variables)]
+      fn from_gql(value: &[u8]) -> ::grapht::prelude::GraphtResult<Self> {
+        let text = ::std::str::from_utf8(value)?;
+        let properties = ::grapht::gquery::grammars::cypher::parse_map(text)?;
+        #from_gql
+      }
+
+      #[allow(unused_mut, unused_variables)]
+      fn from_properties(
+        type_label: &str,
+        properties: ::std::collections::BTreeMap<String, ::grapht::prelude::Literal>,
+      ) -> ::grapht::prelude::GraphtResult<Self> {
+        #from_properties
+      }
+
+      fn get_properties(
+        &self,
+      ) -> ::grapht::prelude::GraphtResult<::std::collections::BTreeMap<String, ::grapht::prelude::Literal>> {
+        match self {
+          #(#properties,)*
+        }
+      }
+    }
+  })
+}
@@ -0,0 +1,140 @@
+//! How central each node is to the graph
+//!
+//! Each measure scores every node, returning them highest first. Parallel edges between the same
+//! two nodes only count once, except for the degree.
+
+use super::{Adjacency, Ranking};
+use crate::{gquery::ast::Direction, local::*, prelude::*};
+
+use std::collections::VecDeque;
+
+/// The scores stop changing once the total change in an iteration is below this
+const PAGE_RANK_TOLERANCE: f64 = 1e-10;
+
+/// The chance of ending up at each node when following edges at random, with the damping factor
+/// being the chance of following an edge rather than jumping to any node (usually 0.85)
+///
+/// Nodes without outgoing edges share their score with every node. The scores add up to 1.
+pub fn page_rank<G, S>(source: &S, damping: f64, iterations: usize) -> Ranking<G>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let count = graph.len() as f64;
+  let successors: Vec<Vec<usize>> = (0..graph.len())
+    .map(|node| graph.neighbours(node, Direction::Outgoing))
+    .collect();
+
+  let mut scores = vec![1.0 / count; graph.len()];
+  for iteration in 0..iterations {
+    let dangling: f64 = (0..graph.len())
+      .filter(|node| successors[*node].is_empty())
+      .map(|node| scores[node])
+      .sum();
+
+    let mut next = vec![(1.0 - damping + damping * dangling) / count; graph.len()];
+    for (node, targets) in successors.iter().enumerate() {
+      for target in targets {
+        next[*target] += damping * scores[node] / targets.len() as f64;
+      }
+    }
+
+    let change: f64 = next.iter().zip(&scores).map(|(x, y)| (x - y).abs()).sum();
+    scores = next;
+    if change < PAGE_RANK_TOLERANCE {
+      debug!("PageRank converged after {} iterations", iteration + 1);
+      break;
+    }
+  }
+
+  graph.ranking(scores)
+}
+
+/// The share of the shortest paths between other nodes that go through each node, following the
+/// edges forwards
+///
+/// Scores are normalized by the number of ordered pairs of other nodes, so they are between 0 and
+/// 1. This is Brandes' algorithm.
+pub fn betweenness<G, S>(source: &S) -> Ranking<G>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let successors: Vec<Vec<usize>> = (0..graph.len())
+    .map(|node| {
+      let mut next = graph.neighbours(node, Direction::Outgoing);
+      next.retain(|x| *x != node);
+      next
+    })
+    .collect();
+
+  let mut scores = vec![0.0; graph.len()];
+  for start in 0..graph.len() {
+    // Count the shortest paths from the start to every node, remembering the order they were
+    // reached and the nodes just before each one
+    let mut order = Vec::new();
+    let mut previous: Vec<Vec<usize>> = vec![Vec::new(); graph.len()];
+    let mut paths = vec![0.0; graph.len()];
+    let mut distance: Vec<Option<usize>> = vec![None; graph.len()];
+    paths[start] = 1.0;
+    distance[start] = Some(0);
+
+    let mut pending = VecDeque::from([start]);
+    while let Some(node) = pending.pop_front() {
+      order.push(node);
+      let hops = distance[node].unwrap_or_default() + 1;
+      for next in &successors[node] {
+        if distance[*next].is_none() {
+          distance[*next] = Some(hops);
+          pending.push_back(*next);
+        }
+        if distance[*next] == Some(hops) {
+          paths[*next] += paths[node];
+          previous[*next].push(node);
+        }
+      }
+    }
+
+    // Then share each node's paths back out to the nodes they went through
+    let mut dependency = vec![0.0; graph.len()];
+    for node in order.into_iter().rev() {
+      for prior in &previous[node] {
+        dependency[*prior] += paths[*prior] / paths[node] * (1.0 + dependency[node]);
+      }
+      if node != start {
+        scores[node] += dependency[node];
+      }
+    }
+  }
+
+  if graph.len() > 2 {
+    let pairs = ((graph.len() - 1) * (graph.len() - 2)) as f64;
+    scores.iter_mut().for_each(|score| *score /= pairs);
+  }
+  graph.ranking(scores)
+}
+
+/// The number of edges each node has in the direction, as a share of the other nodes
+///
+/// Self loops count once for each end in either direction, so scores can be over 1.
+pub fn degree_centrality<G, S>(source: &S, direction: Direction) -> Ranking<G>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let others = graph.len().saturating_sub(1).max(1) as f64;
+  let scores = (0..graph.len())
+    .map(|node| {
+      let degree = match direction {
+        Direction::Outgoing => graph.outgoing[node].len(),
+        Direction::Incoming => graph.incoming[node].len(),
+        Direction::Either => graph.outgoing[node].len() + graph.incoming[node].len(),
+      };
+      degree as f64 / others
+    })
+    .collect();
+  graph.ranking(scores)
+}
@@ -0,0 +1,135 @@
+//! Groups of nodes that can reach each other
+//!
+//! Components are returned largest first, with ties kept in a stable order, so tagging the same
+//! graph twice gives each node the same component.
+
+use super::Adjacency;
+use crate::{gquery::ast::Direction, prelude::*};
+
+/// Groups of nodes connected by edges followed either way
+pub fn weak_components<G, S>(source: &S) -> Vec<Vec<Node<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let mut component = vec![None; graph.len()];
+  let mut groups: Vec<Vec<usize>> = Vec::new();
+
+  for root in 0..graph.len() {
+    if component[root].is_some() {
+      continue;
+    }
+    let mut group = vec![root];
+    component[root] = Some(groups.len());
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+      for next in graph.neighbours(node, Direction::Either) {
+        if component[next].is_none() {
+          component[next] = Some(groups.len());
+          group.push(next);
+          pending.push(next);
+        }
+      }
+    }
+    groups.push(group);
+  }
+
+  collect(&graph, groups)
+}
+
+/// Groups of nodes where every node can reach every other one following the edges forwards
+///
+/// This is Tarjan's algorithm, run with an explicit stack so deep graphs can't overflow the call
+/// stack.
+pub fn strong_components<G, S>(source: &S) -> Vec<Vec<Node<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let successors: Vec<Vec<usize>> = (0..graph.len())
+    .map(|node| graph.neighbours(node, Direction::Outgoing))
+    .collect();
+
+  let mut next_index = 0;
+  let mut index: Vec<Option<usize>> = vec![None; graph.len()];
+  let mut low_link = vec![0; graph.len()];
+  let mut on_stack = vec![false; graph.len()];
+  let mut stack = Vec::new();
+  let mut groups = Vec::new();
+
+  for root in 0..graph.len() {
+    if index[root].is_some() {
+      continue;
+    }
+
+    // Each visit holds the node and the position of the next successor to look at
+    let mut visits = vec![(root, 0)];
+    index[root] = Some(next_index);
+    low_link[root] = next_index;
+    next_index += 1;
+    stack.push(root);
+    on_stack[root] = true;
+
+    while let Some((node, position)) = visits.last_mut() {
+      let node = *node;
+      if let Some(&next) = successors[node].get(*position) {
+        *position += 1;
+        match index[next] {
+          None => {
+            index[next] = Some(next_index);
+            low_link[next] = next_index;
+            next_index += 1;
+            stack.push(next);
+            on_stack[next] = true;
+            visits.push((next, 0));
+          }
+          Some(next_index) if on_stack[next] => {
+            low_link[node] = low_link[node].min(next_index);
+          }
+          Some(_) => (),
+        }
+        continue;
+      }
+
+      // Every successor has been seen, so pass the low link back up to the caller
+      visits.pop();
+      if let Some((caller, _)) = visits.last() {
+        low_link[*caller] = low_link[*caller].min(low_link[node]);
+      }
+
+      if Some(low_link[node]) == index[node] {
+        let mut group = Vec::new();
+        while let Some(member) = stack.pop() {
+          on_stack[member] = false;
+          group.push(member);
+          if member == node {
+            break;
+          }
+        }
+        groups.push(group);
+      }
+    }
+  }
+
+  collect(&graph, groups)
+}
+
+/// Turn groups of positions into groups of nodes, largest first
+fn collect<G: Graph>(graph: &Adjacency<G>, mut groups: Vec<Vec<usize>>) -> Vec<Vec<Node<G>>> {
+  for group in &mut groups {
+    group.sort_unstable();
+  }
+  groups.sort_by(|lhs, rhs| rhs.len().cmp(&lhs.len()).then_with(|| lhs.cmp(rhs)));
+
+  groups
+    .into_iter()
+    .map(|group| {
+      group
+        .into_iter()
+        .map(|node| graph.nodes[node].clone())
+        .collect()
+    })
+    .collect()
+}
@@ -0,0 +1,216 @@
+//! Graph analytics run in process over the nodes and edges of a DataSet
+//!
+//! Every algorithm works on anything [Matchable], so it can run over a whole DataSet or over a
+//! [View] of it that only follows edges with some labels. The results can be tagged onto the nodes
+//! as annotations, which later queries read like properties:
+//!
+//! ```ignore
+//! let view = View::new(&data_set).label("ParentOf");
+//! algo::tag_scores("rank", &algo::page_rank(&view, 0.85, 100));
+//! data_set.query("MATCH (o:Organization) WHERE o.rank > 0.2 RETURN o")?;
+//! ```
+
+use crate::{gquery::ast::Direction, local::*, prelude::*};
+
+use std::collections::HashMap;
+
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use uuid::Uuid;
+
+// Weakly and strongly connected components
+pub mod components;
+pub use components::{strong_components, weak_components};
+
+// Cycles and topological order
+pub mod ordering;
+pub use ordering::{find_cycle, topological_sort};
+
+// PageRank, betweenness, and degree
+pub mod centrality;
+pub use centrality::{betweenness, degree_centrality, page_rank};
+
+// The nodes within a number of hops
+pub mod neighbourhood;
+pub use neighbourhood::neighbourhood;
+
+/// The score given to each node by a centrality measure, highest first
+pub type Ranking<G> = Vec<(Node<G>, f64)>;
+
+/// A DataSet that only follows the edges with some labels
+#[derive(Debug, Clone)]
+pub struct View<'a, G>
+where
+  G: Graph,
+{
+  data_set: &'a DataSet<G>,
+
+  /// The labels of the edges that can be followed, or every edge if empty
+  labels: Vec<String>,
+}
+
+impl<'a, G> View<'a, G>
+where
+  G: Graph,
+{
+  pub fn new(data_set: &'a DataSet<G>) -> View<'a, G> {
+    View {
+      data_set,
+      labels: Vec::new(),
+    }
+  }
+
+  /// Follow the edges with this label. Calling it again allows more labels
+  pub fn label(mut self, label: &str) -> Self {
+    self.labels.push(label.to_string());
+    self
+  }
+
+  fn follows(&self, label: &str) -> bool {
+    self.labels.is_empty() || self.labels.iter().any(|x| x == label)
+  }
+}
+
+impl<'a, G> Matchable<G> for View<'a, G>
+where
+  G: Graph,
+{
+  fn get_node(&self, guid: &Uuid) -> Option<Node<G>> {
+    self.data_set.get_node(guid)
+  }
+
+  fn scan_nodes(&self, label: Option<&str>) -> Vec<Node<G>> {
+    self.data_set.scan_nodes(label)
+  }
+
+  fn out_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    let edges = self.data_set.out_edges(node);
+    edges
+      .into_iter()
+      .filter(|x| self.follows(&x.get_label()))
+      .collect()
+  }
+
+  fn in_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    let edges = self.data_set.in_edges(&node.get_guid());
+    edges
+      .into_iter()
+      .filter(|x| self.follows(&x.get_label()))
+      .collect()
+  }
+
+  fn scan_edges(&self, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    match edge_type {
+      Some(edge_type) if !self.follows(edge_type) => Vec::new(),
+      None if !self.labels.is_empty() => self
+        .labels
+        .iter()
+        .flat_map(|label| self.data_set.scan_edges(Some(label)))
+        .collect(),
+      edge_type => self.data_set.scan_edges(edge_type),
+    }
+  }
+}
+
+/// Annotate each node with its value
+pub fn tag<G, V>(name: &str, values: impl IntoIterator<Item = (Node<G>, V)>)
+where
+  G: Graph,
+  V: Into<Literal>,
+{
+  for (mut node, value) in values {
+    node.annotate(name, value);
+  }
+}
+
+/// Annotate each node with the position of its component, counting from 0
+pub fn tag_components<G: Graph>(name: &str, components: &[Vec<Node<G>>]) {
+  for (position, component) in components.iter().enumerate() {
+    let values = component.iter().map(|node| (node.clone(), position as i64));
+    tag(name, values);
+  }
+}
+
+/// Annotate each node with its score, as a decimal so queries can compare it
+pub fn tag_scores<G: Graph>(name: &str, scores: &[(Node<G>, f64)]) {
+  let values = scores.iter().map(|(node, score)| {
+    let score = Decimal::from_f64(*score).map(Literal::Decimal);
+    (node.clone(), score.unwrap_or(Literal::Null))
+  });
+  tag(name, values);
+}
+
+/// The nodes of a source numbered in a stable order, along with the edges between them
+///
+/// The algorithms work on the positions, so they only look up each node's guid once.
+struct Adjacency<G>
+where
+  G: Graph,
+{
+  nodes: Vec<Node<G>>,
+
+  /// The edges leaving each node, with the position of the node at the other end
+  outgoing: Vec<Vec<(usize, Edge<G>)>>,
+
+  /// The edges arriving at each node, with the position of the node at the other end
+  incoming: Vec<Vec<(usize, Edge<G>)>>,
+}
+
+impl<G> Adjacency<G>
+where
+  G: Graph,
+{
+  fn new<S: Matchable<G>>(source: &S) -> Adjacency<G> {
+    let mut nodes = source.scan_nodes(None);
+    nodes.sort_by_key(|node| node.get_guid());
+    let positions: HashMap<Uuid, usize> = nodes
+      .iter()
+      .enumerate()
+      .map(|(position, node)| (node.get_guid(), position))
+      .collect();
+
+    let mut outgoing = vec![Vec::new(); nodes.len()];
+    let mut incoming = vec![Vec::new(); nodes.len()];
+    for edge in source.scan_edges(None) {
+      let source = positions.get(&edge.get_source().get_guid());
+      let target = positions.get(&edge.get_target().get_guid());
+      if let (Some(&source), Some(&target)) = (source, target) {
+        outgoing[source].push((target, edge.clone()));
+        incoming[target].push((source, edge));
+      }
+    }
+    for edges in outgoing.iter_mut().chain(incoming.iter_mut()) {
+      edges.sort_by_key(|(position, edge)| (*position, edge.get_guid()));
+    }
+
+    Adjacency {
+      nodes,
+      outgoing,
+      incoming,
+    }
+  }
+
+  fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /// The distinct nodes next to a node in the direction, in order of position
+  fn neighbours(&self, node: usize, direction: Direction) -> Vec<usize> {
+    let mut next = Vec::new();
+    if direction != Direction::Incoming {
+      next.extend(self.outgoing[node].iter().map(|(position, _)| *position));
+    }
+    if direction != Direction::Outgoing {
+      next.extend(self.incoming[node].iter().map(|(position, _)| *position));
+    }
+    next.sort_unstable();
+    next.dedup();
+    next
+  }
+
+  /// Pair each score with its node, highest first
+  fn ranking(&self, scores: Vec<f64>) -> Ranking<G> {
+    let mut ranking: Ranking<G> = self.nodes.iter().cloned().zip(scores).collect();
+    ranking.sort_by(|(_, lhs), (_, rhs)| rhs.total_cmp(lhs));
+    ranking
+  }
+}
@@ -0,0 +1,36 @@
+//! The nodes close to a node
+
+use crate::{gquery::ast::Direction, prelude::*, store::query::neighbours};
+
+use std::collections::{HashSet, VecDeque};
+
+/// Every node within the number of hops of a node, paired with the fewest hops it takes to reach
+/// it. The node itself is not included.
+///
+/// Only the edges around the nodes that are reached are looked at, so this doesn't scan the graph.
+pub fn neighbourhood<G, S>(
+  source: &S,
+  node: &Node<G>,
+  hops: u32,
+  direction: Direction,
+) -> Vec<(Node<G>, u32)>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let mut seen = HashSet::from([node.get_guid()]);
+  let mut found = Vec::new();
+  let mut pending = VecDeque::from([(node.clone(), 0)]);
+  while let Some((node, distance)) = pending.pop_front() {
+    if distance >= hops {
+      continue;
+    }
+    for (_, next) in neighbours(source, &node, direction) {
+      if seen.insert(next.get_guid()) {
+        found.push((next.clone(), distance + 1));
+        pending.push_back((next, distance + 1));
+      }
+    }
+  }
+  found
+}
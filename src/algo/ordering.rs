@@ -0,0 +1,104 @@
+//! Cycles and the order of nodes along the direction of their edges
+
+use super::Adjacency;
+use crate::prelude::*;
+
+use std::collections::BTreeSet;
+
+use uuid::Uuid;
+
+/// Find a cycle following the edges forwards, returned as a path starting and ending at the same
+/// node. A self loop is a cycle of one edge.
+pub fn find_cycle<G, S>(source: &S) -> Option<Path<G>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+
+  // Nodes that are done have had everything reachable from them searched without finding a cycle
+  let mut done = vec![false; graph.len()];
+  let mut on_trail = vec![false; graph.len()];
+
+  for root in 0..graph.len() {
+    if done[root] {
+      continue;
+    }
+
+    // Each visit holds the node, the position of the next edge to look at, and the edge used to
+    // reach the node
+    let mut visits: Vec<(usize, usize, Option<Edge<G>>)> = vec![(root, 0, None)];
+    on_trail[root] = true;
+
+    while let Some((node, position, _)) = visits.last_mut() {
+      let node = *node;
+      let (next, edge) = match graph.outgoing[node].get(*position) {
+        Some(step) => step.clone(),
+        None => {
+          visits.pop();
+          on_trail[node] = false;
+          done[node] = true;
+          continue;
+        }
+      };
+      *position += 1;
+
+      if on_trail[next] {
+        // The cycle runs from where the trail reached the next node, back around to it
+        let start = visits
+          .iter()
+          .position(|(visited, _, _)| *visited == next)
+          .expect("A node on the trail has a visit");
+        let mut edges: Vec<Edge<G>> = visits[start + 1..]
+          .iter()
+          .filter_map(|(_, _, edge)| edge.clone())
+          .collect();
+        edges.push(edge);
+        return Some(Path::new(Some(graph.nodes[next].clone()), edges));
+      }
+      if !done[next] {
+        on_trail[next] = true;
+        visits.push((next, 0, Some(edge)));
+      }
+    }
+  }
+  None
+}
+
+/// Order the nodes so every edge points from an earlier node to a later one
+///
+/// Nodes that could go in either order keep a stable order. A graph with a cycle can't be sorted,
+/// so the error lists the nodes around one.
+pub fn topological_sort<G, S>(source: &S) -> GraphtResult<Vec<Node<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let graph = Adjacency::new(source);
+  let mut waiting_on: Vec<usize> = graph.incoming.iter().map(|edges| edges.len()).collect();
+  let mut ready: BTreeSet<usize> = (0..graph.len()).filter(|x| waiting_on[*x] == 0).collect();
+
+  let mut sorted = Vec::new();
+  while let Some(node) = ready.pop_first() {
+    sorted.push(graph.nodes[node].clone());
+    for (next, _) in &graph.outgoing[node] {
+      waiting_on[*next] -= 1;
+      if waiting_on[*next] == 0 {
+        ready.insert(*next);
+      }
+    }
+  }
+
+  if sorted.len() < graph.len() {
+    let cycle: Vec<Uuid> = find_cycle(source)
+      .map(|path| path.nodes().iter().map(|x| x.get_guid()).collect())
+      .unwrap_or_default();
+    return Err(err!(
+      InvalidItem,
+      "Cannot sort the nodes, since {} of them are in or after a cycle, such as: {:?}",
+      graph.len() - sorted.len(),
+      cycle
+    ));
+  }
+  Ok(sorted)
+}
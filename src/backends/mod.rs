@@ -4,7 +4,7 @@
 //! turns it into a queryable graph, so it must know how to speak multiple languages.
 
 use super::prelude::*;
-use crate::{local::*, prelude::*};
+use crate::{gquery::grammars::Dialect, local::*, prelude::*};
 
 pub mod backend;
 // pub use Backend;
@@ -12,6 +12,9 @@ pub mod backend;
 pub trait Backend {
   type RawResponse;
 
+  /// The query language the backend speaks
+  const DIALECT: Dialect = Dialect::Cypher;
+
   /// Get the name the backend knows itself as
   fn name(&self) -> String;
 
@@ -37,9 +40,10 @@ pub trait Backend {
   /// Receive process a RawResponse and add it to the query set
   fn parse(&mut self, query_set: &mut QuerySet, value: Self::RawResponse) -> GraphtResult<()>;
 
-  /// Convert a GQuery into the raw message using a grammar
-  fn translate(query: GQuery) -> String {
-    unimplemented!("Backend grammar not set");
+  /// Convert a GQuery and the values for its parameters into the raw message using the backend's
+  /// dialect. Constructs the dialect can't express are a GrammarError.
+  fn translate(query: &GQuery, params: &Params) -> GraphtResult<String> {
+    Self::DIALECT.print_with(query, params)
   }
 
   /// Get statistics (nodes, edges, paths, indices, etc.)
@@ -1,6 +1,7 @@
 //! Use an external copy of redis graph as a backend
 
 use super::Backend;
+use crate::gquery::grammars::Dialect;
 use crate::{local::*, prelude::*};
 
 use redis::{FromRedisValue, RedisError, RedisResult, Value};
@@ -91,6 +92,8 @@ impl RedisGraph {
 impl Backend for RedisGraph {
   type RawResponse = RawValue;
 
+  const DIALECT: Dialect = Dialect::RedisGraph;
+
   fn name(&self) -> String {
     self.config.get_name()
   }
@@ -1,6 +1,6 @@
 //! Parsing errors
 
-use crate::{local::*, utils::diff::*};
+use crate::{local::*, model::Violation, utils::diff::*};
 
 use std::collections::HashMap;
 
@@ -45,6 +45,15 @@ impl GraphtError {
     self.is(Kind::DuplicateKey)
   }
 
+  /// The constraint that was broken and the guids of the nodes involved, if this is a
+  /// ConstraintViolation error
+  pub fn violation(&self) -> Option<&Violation> {
+    match &self.kind {
+      Kind::ConstraintViolation(violation) => Some(violation),
+      _ => None,
+    }
+  }
+
   pub fn comment(mut self, comment: String) -> GraphtError {
     self.comment = Some(comment);
     self
@@ -105,6 +114,9 @@ pub enum Kind {
   #[error("Received an unexpected/incorrect encapsulating variant")]
   TypeMismatch,
 
+  #[error("A node breaks the constraint {0}")]
+  ConstraintViolation(Box<Violation>),
+
   #[error("Could not convert between data types")]
   ConversionError,
 
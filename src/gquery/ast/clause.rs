@@ -0,0 +1,302 @@
+//! The individual steps that make up a query
+
+use super::*;
+
+/// A single step in a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+  /// Find all the places in the graph where the pattern fits
+  Match(Match),
+
+  /// Turn each item of a list into its own row
+  Unwind(Unwind),
+
+  /// Choose the values that are passed on to the next part of the query
+  With(With),
+
+  /// Choose the values that make up the result of the query
+  Return(Projection),
+
+  /// Add the nodes and edges in the pattern to the graph
+  Create(Pattern),
+
+  /// Match the pattern, creating it if it doesn't exist
+  Merge(Merge),
+
+  /// Update properties and labels
+  Set(Vec<SetItem>),
+
+  /// Remove properties and labels
+  Remove(Vec<RemoveItem>),
+
+  /// Remove nodes and edges from the graph
+  Delete(Delete),
+}
+
+impl Clause {
+  /// Check if the clause changes the graph
+  pub fn is_updating(&self) -> bool {
+    matches!(
+      self,
+      Clause::Create(_) | Clause::Merge(_) | Clause::Set(_) | Clause::Remove(_) | Clause::Delete(_)
+    )
+  }
+}
+
+/// A pattern to look for in the graph, optionally filtered by a predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+  /// Keep rows that don't match, setting the new variables to null
+  pub optional: bool,
+
+  pub pattern: Pattern,
+
+  /// The WHERE predicate which each match must satisfy
+  pub filter: Option<Expression>,
+}
+
+/// Expand a list into one row per item (eg. "UNWIND [1, 2, 3] AS x")
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unwind {
+  pub expression: Expression,
+  pub variable: String,
+}
+
+/// A projection in the middle of a query, which can then be filtered
+#[derive(Debug, Clone, PartialEq)]
+pub struct With {
+  pub projection: Projection,
+
+  /// The WHERE predicate applied to the projected rows
+  pub filter: Option<Expression>,
+}
+
+/// A list of expressions that get turned into a row of named values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+  /// Only keep the unique rows
+  pub distinct: bool,
+
+  /// Include every variable currently in scope ("RETURN *")
+  pub all: bool,
+
+  pub items: Vec<ProjectionItem>,
+
+  /// How to sort the rows
+  pub order: Vec<SortItem>,
+
+  /// The number of rows to drop from the start
+  pub skip: Option<Expression>,
+
+  /// The maximum number of rows to keep
+  pub limit: Option<Expression>,
+}
+
+impl Projection {
+  /// The names of each explicitly listed value in the projected row
+  pub fn columns(&self) -> Vec<String> {
+    self.items.iter().map(|item| item.name()).collect()
+  }
+}
+
+/// A single named value in a projection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectionItem {
+  pub expression: Expression,
+
+  /// The name given by "AS"
+  pub alias: Option<String>,
+}
+
+impl ProjectionItem {
+  /// The column name of the item, which is the alias or the text of the expression
+  pub fn name(&self) -> String {
+    match &self.alias {
+      Some(alias) => alias.clone(),
+      None => self.expression.to_string(),
+    }
+  }
+}
+
+/// One of the keys used by ORDER BY
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortItem {
+  pub expression: Expression,
+  pub descending: bool,
+}
+
+/// A single pattern that is created if it can't be found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+  pub pattern: PatternPart,
+
+  /// Updates applied when the pattern was created
+  pub on_create: Vec<SetItem>,
+
+  /// Updates applied when the pattern already existed
+  pub on_match: Vec<SetItem>,
+}
+
+/// A single update made by SET
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetItem {
+  /// Set one property (eg. "org.name = 'Root'"). The first expression is a property lookup
+  Property(Expression, Expression),
+
+  /// Replace all the properties of an item with a map (eg. "org = {name: 'Root'}")
+  Replace(String, Expression),
+
+  /// Update the properties of an item with the values in a map (eg. "org += {name: 'Root'}")
+  Update(String, Expression),
+
+  /// Add labels to a node (eg. "org:Active")
+  Labels(String, Vec<String>),
+}
+
+/// A single update made by REMOVE
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoveItem {
+  /// Remove a property, given as a property lookup
+  Property(Expression),
+
+  /// Remove labels from a node
+  Labels(String, Vec<String>),
+}
+
+/// The items to remove from the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+  /// Also delete the edges attached to any deleted nodes
+  pub detach: bool,
+
+  pub items: Vec<Expression>,
+}
+
+impl Display for Clause {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Clause::Match(matched) => {
+        if matched.optional {
+          write!(f, "OPTIONAL ")?;
+        }
+        write!(f, "MATCH {}", matched.pattern)?;
+        if let Some(filter) = &matched.filter {
+          write!(f, " WHERE {}", filter)?;
+        }
+        Ok(())
+      }
+      Clause::Unwind(unwind) => write!(
+        f,
+        "UNWIND {} AS {}",
+        unwind.expression,
+        escape_name(&unwind.variable)
+      ),
+      Clause::With(with) => {
+        write!(f, "WITH {}", with.projection)?;
+        if let Some(filter) = &with.filter {
+          write!(f, " WHERE {}", filter)?;
+        }
+        Ok(())
+      }
+      Clause::Return(projection) => write!(f, "RETURN {}", projection),
+      Clause::Create(pattern) => write!(f, "CREATE {}", pattern),
+      Clause::Merge(merge) => {
+        write!(f, "MERGE {}", merge.pattern)?;
+        if !merge.on_match.is_empty() {
+          write!(f, " ON MATCH SET {}", join(&merge.on_match))?;
+        }
+        if !merge.on_create.is_empty() {
+          write!(f, " ON CREATE SET {}", join(&merge.on_create))?;
+        }
+        Ok(())
+      }
+      Clause::Set(items) => write!(f, "SET {}", join(items)),
+      Clause::Remove(items) => write!(f, "REMOVE {}", join(items)),
+      Clause::Delete(delete) => {
+        if delete.detach {
+          write!(f, "DETACH ")?;
+        }
+        write!(f, "DELETE {}", join(&delete.items))
+      }
+    }
+  }
+}
+
+impl Display for Projection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.distinct {
+      write!(f, "DISTINCT ")?;
+    }
+
+    let mut items: Vec<String> = self.items.iter().map(|x| x.to_string()).collect();
+    if self.all {
+      items.insert(0, String::from("*"));
+    }
+    write!(f, "{}", items.join(", "))?;
+
+    if !self.order.is_empty() {
+      write!(f, " ORDER BY {}", join(&self.order))?;
+    }
+    if let Some(skip) = &self.skip {
+      write!(f, " SKIP {}", skip)?;
+    }
+    if let Some(limit) = &self.limit {
+      write!(f, " LIMIT {}", limit)?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for ProjectionItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.alias {
+      Some(alias) => write!(f, "{} AS {}", self.expression, escape_name(alias)),
+      None => write!(f, "{}", self.expression),
+    }
+  }
+}
+
+impl Display for SortItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.descending {
+      true => write!(f, "{} DESC", self.expression),
+      false => write!(f, "{}", self.expression),
+    }
+  }
+}
+
+impl Display for SetItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SetItem::Property(property, value) => write!(f, "{} = {}", property, value),
+      SetItem::Replace(name, value) => write!(f, "{} = {}", escape_name(name), value),
+      SetItem::Update(name, value) => write!(f, "{} += {}", escape_name(name), value),
+      SetItem::Labels(name, labels) => write!(f, "{}{}", escape_name(name), format_labels(labels)),
+    }
+  }
+}
+
+impl Display for RemoveItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RemoveItem::Property(property) => write!(f, "{}", property),
+      RemoveItem::Labels(name, labels) => {
+        write!(f, "{}{}", escape_name(name), format_labels(labels))
+      }
+    }
+  }
+}
+
+/// Print a list of items separated by commas
+fn join<T: Display>(items: &[T]) -> String {
+  let items: Vec<String> = items.iter().map(|x| x.to_string()).collect();
+  items.join(", ")
+}
+
+/// Print labels in the ":A:B" form
+pub fn format_labels(labels: &[String]) -> String {
+  labels
+    .iter()
+    .map(|x| format!(":{}", escape_name(x)))
+    .collect()
+}
@@ -0,0 +1,356 @@
+//! Calculations and predicates that are evaluated against each matched row
+
+use super::*;
+
+/// Anything that can be evaluated into a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+  /// A constant value
+  Literal(Literal),
+
+  /// A value that is supplied separately from the query text (eg. $name)
+  Parameter(String),
+
+  /// A reference to a named item in the current row
+  Variable(String),
+
+  /// A lookup of a property on a node, edge, or map (eg. org.name)
+  Property(Box<Expression>, String),
+
+  /// A list built from other expressions
+  List(Vec<Expression>),
+
+  /// A map built from other expressions
+  Map(Vec<(String, Expression)>),
+
+  Unary(UnaryOperator, Box<Expression>),
+
+  Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+
+  /// Check if the value is null. The flag is true for "IS NOT NULL"
+  IsNull(Box<Expression>, bool),
+
+  /// Check that a node has all of the given labels (eg. org:Organization)
+  HasLabels(Box<Expression>, Vec<String>),
+
+  /// Call a function by name
+  Function(FunctionCall),
+
+  /// The special "count(*)" aggregate
+  CountAll,
+
+  /// Choose a value based on a list of conditions
+  Case(Case),
+
+  /// True if the pattern can be found, using the variables from the current row
+  /// (eg. "WHERE NOT (org)-[:ChildOf]->()")
+  PatternPredicate(Box<PatternPart>),
+}
+
+impl Expression {
+  pub fn variable(name: &str) -> Expression {
+    Expression::Variable(name.to_string())
+  }
+
+  pub fn property(self, name: &str) -> Expression {
+    Expression::Property(Box::new(self), name.to_string())
+  }
+
+  pub fn binary(op: BinaryOperator, lhs: Expression, rhs: Expression) -> Expression {
+    Expression::Binary(op, Box::new(lhs), Box::new(rhs))
+  }
+
+  /// Get the value of an expression that is made up only of literals
+  pub fn as_constant(&self) -> Option<Literal> {
+    match self {
+      Expression::Literal(value) => Some(value.clone()),
+      Expression::List(values) => {
+        let values: Option<Vec<Literal>> = values.iter().map(|x| x.as_constant()).collect();
+        Some(Literal::List(values?))
+      }
+      Expression::Map(values) => {
+        let mut result = std::collections::BTreeMap::new();
+        for (key, value) in values {
+          let _ = result.insert(key.clone(), value.as_constant()?);
+        }
+        Some(Literal::Map(result))
+      }
+      _ => None,
+    }
+  }
+
+  /// Check if the expression calculates a value over a group of rows, such as count() or sum(),
+  /// anywhere outside of a pattern predicate
+  pub fn is_aggregate(&self) -> bool {
+    match self {
+      Expression::CountAll => true,
+      Expression::Function(call) if call.is_aggregate() => true,
+      expression => expression.children().iter().any(|x| x.is_aggregate()),
+    }
+  }
+
+  /// The expressions directly inside this one. Pattern predicates are left out, since their
+  /// properties belong to the pattern.
+  pub fn children(&self) -> Vec<&Expression> {
+    match self {
+      Expression::Literal(_)
+      | Expression::Parameter(_)
+      | Expression::Variable(_)
+      | Expression::CountAll
+      | Expression::PatternPredicate(_) => Vec::new(),
+      Expression::Property(inner, _)
+      | Expression::Unary(_, inner)
+      | Expression::IsNull(inner, _)
+      | Expression::HasLabels(inner, _) => vec![inner.as_ref()],
+      Expression::Binary(_, lhs, rhs) => vec![lhs.as_ref(), rhs.as_ref()],
+      Expression::List(values) => values.iter().collect(),
+      Expression::Map(values) => values.iter().map(|(_, value)| value).collect(),
+      Expression::Function(call) => call.arguments.iter().collect(),
+      Expression::Case(case) => {
+        let mut children: Vec<&Expression> = case.subject.iter().map(|x| x.as_ref()).collect();
+        for (condition, result) in &case.alternatives {
+          children.push(condition);
+          children.push(result);
+        }
+        children.extend(case.default.iter().map(|x| x.as_ref()));
+        children
+      }
+    }
+  }
+
+  /// The expressions directly inside this one, for rewriting them in place
+  pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+    match self {
+      Expression::Literal(_)
+      | Expression::Parameter(_)
+      | Expression::Variable(_)
+      | Expression::CountAll
+      | Expression::PatternPredicate(_) => Vec::new(),
+      Expression::Property(inner, _)
+      | Expression::Unary(_, inner)
+      | Expression::IsNull(inner, _)
+      | Expression::HasLabels(inner, _) => vec![inner.as_mut()],
+      Expression::Binary(_, lhs, rhs) => vec![lhs.as_mut(), rhs.as_mut()],
+      Expression::List(values) => values.iter_mut().collect(),
+      Expression::Map(values) => values.iter_mut().map(|(_, value)| value).collect(),
+      Expression::Function(call) => call.arguments.iter_mut().collect(),
+      Expression::Case(case) => {
+        let mut children: Vec<&mut Expression> =
+          case.subject.iter_mut().map(|x| x.as_mut()).collect();
+        for (condition, result) in &mut case.alternatives {
+          children.push(condition);
+          children.push(result);
+        }
+        children.extend(case.default.iter_mut().map(|x| x.as_mut()));
+        children
+      }
+    }
+  }
+
+  /// Binding strength, used to decide when the printed expression needs parentheses
+  pub(crate) fn precedence(&self) -> u8 {
+    match self {
+      Expression::Binary(op, _, _) => op.precedence(),
+      Expression::Unary(UnaryOperator::Not, _) => 4,
+      Expression::Unary(_, _) => 10,
+      Expression::IsNull(_, _) => 11,
+      _ => 12,
+    }
+  }
+
+  /// Print a child expression, wrapping it in parentheses if it binds looser than its parent
+  fn wrap(&self, parent: u8) -> String {
+    match self.precedence() < parent {
+      true => format!("({})", self),
+      false => self.to_string(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+  Not,
+  Negate,
+  Plus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+  Or,
+  Xor,
+  And,
+  Eq,
+  Ne,
+  Lt,
+  Gt,
+  Le,
+  Ge,
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+  Modulo,
+  Power,
+  In,
+  StartsWith,
+  EndsWith,
+  Contains,
+}
+
+impl BinaryOperator {
+  pub(crate) fn precedence(&self) -> u8 {
+    use BinaryOperator::*;
+    match self {
+      Or => 1,
+      Xor => 2,
+      And => 3,
+      Eq | Ne | Lt | Gt | Le | Ge => 5,
+      Add | Subtract => 6,
+      Multiply | Divide | Modulo => 7,
+      Power => 8,
+      In | StartsWith | EndsWith | Contains => 11,
+    }
+  }
+
+  pub fn symbol(&self) -> &'static str {
+    use BinaryOperator::*;
+    match self {
+      Or => "OR",
+      Xor => "XOR",
+      And => "AND",
+      Eq => "=",
+      Ne => "<>",
+      Lt => "<",
+      Gt => ">",
+      Le => "<=",
+      Ge => ">=",
+      Add => "+",
+      Subtract => "-",
+      Multiply => "*",
+      Divide => "/",
+      Modulo => "%",
+      Power => "^",
+      In => "IN",
+      StartsWith => "STARTS WITH",
+      EndsWith => "ENDS WITH",
+      Contains => "CONTAINS",
+    }
+  }
+}
+
+/// A named function and its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+  pub name: String,
+
+  /// Aggregates can be told to only use unique values (eg. count(DISTINCT org))
+  pub distinct: bool,
+
+  pub arguments: Vec<Expression>,
+}
+
+impl FunctionCall {
+  /// Check if the function calculates a value over a group of rows
+  pub fn is_aggregate(&self) -> bool {
+    matches!(
+      self.name.to_lowercase().as_str(),
+      "count" | "sum" | "avg" | "min" | "max" | "collect"
+    )
+  }
+}
+
+/// A CASE expression.
+///
+/// With a subject, each alternative's condition is compared to the subject
+/// ("CASE x WHEN 1 THEN ..."). Otherwise each condition is a predicate ("CASE WHEN x = 1 THEN ...").
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+  pub subject: Option<Box<Expression>>,
+
+  /// Each condition and its result, checked in order
+  pub alternatives: Vec<(Expression, Expression)>,
+
+  /// The ELSE value, which is null if missing
+  pub default: Option<Box<Expression>>,
+}
+
+impl Display for Expression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Expression::Literal(value) => write!(f, "{}", value),
+      Expression::Parameter(name) => write!(f, "${}", escape_name(name)),
+      Expression::Variable(name) => write!(f, "{}", escape_name(name)),
+      Expression::Property(inner, name) => write!(f, "{}.{}", inner.wrap(12), escape_name(name)),
+      Expression::List(values) => {
+        let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        write!(f, "[{}]", values.join(", "))
+      }
+      Expression::Map(values) => write!(f, "{}", format_map(values)),
+      Expression::Unary(op, inner) => match op {
+        UnaryOperator::Not => write!(f, "NOT {}", inner.wrap(4)),
+        UnaryOperator::Negate => write!(f, "-{}", inner.wrap(10)),
+        UnaryOperator::Plus => write!(f, "+{}", inner.wrap(10)),
+      },
+      Expression::Binary(op, lhs, rhs) => {
+        // All binary operators are left associative
+        let precedence = op.precedence();
+        write!(
+          f,
+          "{} {} {}",
+          lhs.wrap(precedence),
+          op.symbol(),
+          rhs.wrap(precedence + 1)
+        )
+      }
+      Expression::IsNull(inner, negated) => match negated {
+        true => write!(f, "{} IS NOT NULL", inner.wrap(12)),
+        false => write!(f, "{} IS NULL", inner.wrap(12)),
+      },
+      Expression::HasLabels(inner, labels) => {
+        write!(f, "{}", inner.wrap(12))?;
+        for label in labels {
+          write!(f, ":{}", escape_name(label))?;
+        }
+        Ok(())
+      }
+      Expression::Function(call) => {
+        let args: Vec<String> = call.arguments.iter().map(|x| x.to_string()).collect();
+        let distinct = match call.distinct {
+          true => "DISTINCT ",
+          false => "",
+        };
+        write!(f, "{}({}{})", call.name, distinct, args.join(", "))
+      }
+      Expression::CountAll => write!(f, "count(*)"),
+      Expression::Case(case) => {
+        write!(f, "CASE")?;
+        if let Some(subject) = &case.subject {
+          write!(f, " {}", subject)?;
+        }
+        for (condition, result) in &case.alternatives {
+          write!(f, " WHEN {} THEN {}", condition, result)?;
+        }
+        if let Some(default) = &case.default {
+          write!(f, " ELSE {}", default)?;
+        }
+        write!(f, " END")
+      }
+      Expression::PatternPredicate(pattern) => write!(f, "{}", pattern),
+    }
+  }
+}
+
+/// Print a list of key/value pairs as a map
+pub fn format_map(values: &[(String, Expression)]) -> String {
+  let values: Vec<String> = values
+    .iter()
+    .map(|(key, value)| format!("{}: {}", escape_name(key), value))
+    .collect();
+  format!("{{{}}}", values.join(", "))
+}
+
+impl From<Literal> for Expression {
+  fn from(value: Literal) -> Self {
+    Expression::Literal(value)
+  }
+}
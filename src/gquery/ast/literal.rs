@@ -0,0 +1,437 @@
+//! Constant values that can be written directly into a query or read from a property
+
+use crate::{local::*, prelude::*};
+
+use std::{
+  cmp::Ordering,
+  collections::{BTreeMap, HashMap},
+  str::FromStr,
+};
+
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// A scalar or collection value
+///
+/// Floats are stored as Decimals so values such as balances stay exact when they are compared or
+/// summed.
+#[derive(Debug, Clone)]
+pub enum Literal {
+  Null,
+  Boolean(bool),
+  Integer(i64),
+  Decimal(Decimal),
+  String(String),
+  List(Vec<Literal>),
+  Map(BTreeMap<String, Literal>),
+}
+
+impl Literal {
+  pub fn is_null(&self) -> bool {
+    matches!(self, Literal::Null)
+  }
+
+  /// The ternary truth value of the literal, where None is unknown (null)
+  pub fn as_bool(&self) -> GraphtResult<Option<bool>> {
+    match self {
+      Literal::Null => Ok(None),
+      Literal::Boolean(value) => Ok(Some(*value)),
+      x => Err(err!(
+        TypeMismatch,
+        "Expected a boolean value but received {}",
+        x
+      )),
+    }
+  }
+
+  /// Get the value as an integer, used for items such as SKIP and LIMIT
+  pub fn as_integer(&self) -> GraphtResult<i64> {
+    match self {
+      Literal::Integer(value) => Ok(*value),
+      x => Err(err!(
+        TypeMismatch,
+        "Expected an integer value but received {}",
+        x
+      )),
+    }
+  }
+
+  /// Get the value as a Decimal, promoting integers when needed
+  pub fn as_decimal(&self) -> Option<Decimal> {
+    match self {
+      Literal::Integer(value) => Some(Decimal::from(*value)),
+      Literal::Decimal(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  /// The name of the literal's type, for use in error messages
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Literal::Null => "Null",
+      Literal::Boolean(_) => "Boolean",
+      Literal::Integer(_) => "Integer",
+      Literal::Decimal(_) => "Float",
+      Literal::String(_) => "String",
+      Literal::List(_) => "List",
+      Literal::Map(_) => "Map",
+    }
+  }
+
+  /// Compare two literals the way a predicate would
+  ///
+  /// This returns None when the two values cannot be compared, such as a string and an integer or
+  /// anything compared to null.
+  pub fn compare(&self, rhs: &Literal) -> Option<Ordering> {
+    use Literal::*;
+    match (self, rhs) {
+      (Null, _) | (_, Null) => None,
+      (Boolean(lhs), Boolean(rhs)) => Some(lhs.cmp(rhs)),
+      (Integer(lhs), Integer(rhs)) => Some(lhs.cmp(rhs)),
+      (Integer(_), Decimal(_)) | (Decimal(_), Integer(_)) | (Decimal(_), Decimal(_)) => {
+        Some(self.as_decimal()?.cmp(&rhs.as_decimal()?))
+      }
+      (String(lhs), String(rhs)) => Some(lhs.cmp(rhs)),
+      (List(lhs), List(rhs)) => {
+        for (l, r) in lhs.iter().zip(rhs.iter()) {
+          match l.compare(r)? {
+            Ordering::Equal => continue,
+            x => return Some(x),
+          }
+        }
+        Some(lhs.len().cmp(&rhs.len()))
+      }
+      _ => None,
+    }
+  }
+
+  /// Test equality the way a predicate would, where null is unknown
+  pub fn equals(&self, rhs: &Literal) -> Option<bool> {
+    use Literal::*;
+    match (self, rhs) {
+      (Null, _) | (_, Null) => None,
+      (List(lhs), List(rhs)) => {
+        if lhs.len() != rhs.len() {
+          return Some(false);
+        }
+        let mut result = Some(true);
+        for (l, r) in lhs.iter().zip(rhs.iter()) {
+          match l.equals(r) {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => (),
+          }
+        }
+        result
+      }
+      (Map(lhs), Map(rhs)) => {
+        if lhs.len() != rhs.len() || lhs.keys().any(|key| !rhs.contains_key(key)) {
+          return Some(false);
+        }
+        let mut result = Some(true);
+        for (key, l) in lhs {
+          match l.equals(&rhs[key]) {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => (),
+          }
+        }
+        result
+      }
+      _ => match self.compare(rhs) {
+        Some(ordering) => Some(ordering == Ordering::Equal),
+        None => Some(false),
+      },
+    }
+  }
+
+  /// A total ordering of all literals, used for sorting
+  ///
+  /// Values of different types are grouped by type and nulls always come last.
+  pub fn order(&self, rhs: &Literal) -> Ordering {
+    fn rank(value: &Literal) -> u8 {
+      match value {
+        Literal::Map(_) => 0,
+        Literal::List(_) => 1,
+        Literal::String(_) => 2,
+        Literal::Boolean(_) => 3,
+        Literal::Integer(_) | Literal::Decimal(_) => 4,
+        Literal::Null => 5,
+      }
+    }
+
+    match self.compare(rhs) {
+      Some(ordering) => ordering,
+      None => match (self, rhs) {
+        (Literal::Map(lhs), Literal::Map(rhs)) => format!("{:?}", lhs).cmp(&format!("{:?}", rhs)),
+        _ => rank(self).cmp(&rank(rhs)),
+      },
+    }
+  }
+
+  /// Parse a numeric string into an Integer, or a Decimal if it has a fractional part
+  pub fn parse_number(value: &str) -> GraphtResult<Literal> {
+    match value.parse::<i64>() {
+      Ok(int) => Ok(Literal::Integer(int)),
+      Err(_) => match Decimal::from_str(value).or_else(|_| Decimal::from_scientific(value)) {
+        Ok(dec) => Ok(Literal::Decimal(dec)),
+        Err(err) => Err(err!(
+          ParsingError,
+          "Could not convert '{}' into a number: {:?}",
+          value,
+          err
+        )),
+      },
+    }
+  }
+}
+
+impl PartialEq for Literal {
+  /// Structural equality, where unlike `equals` null is equal to null
+  fn eq(&self, rhs: &Self) -> bool {
+    match (self, rhs) {
+      (Literal::Null, Literal::Null) => true,
+      _ => self.order(rhs) == Ordering::Equal && self.type_name() == rhs.type_name(),
+    }
+  }
+}
+
+impl Display for Literal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Literal::Null => write!(f, "null"),
+      Literal::Boolean(value) => write!(f, "{}", value),
+      Literal::Integer(value) => write!(f, "{}", value),
+      Literal::Decimal(value) => write!(f, "{}", value),
+      Literal::String(value) => write!(f, "{}", quote(value)),
+      Literal::List(values) => {
+        let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        write!(f, "[{}]", values.join(", "))
+      }
+      Literal::Map(values) => {
+        let values: Vec<String> = values
+          .iter()
+          .map(|(key, value)| format!("{}: {}", super::escape_name(key), value))
+          .collect();
+        write!(f, "{{{}}}", values.join(", "))
+      }
+    }
+  }
+}
+
+/// Wrap a string in single quotes, escaping any characters that would break the literal
+pub fn quote(value: &str) -> String {
+  let mut result = String::with_capacity(value.len() + 2);
+  result.push('\'');
+  for c in value.chars() {
+    match c {
+      '\'' => result.push_str("\\'"),
+      '\\' => result.push_str("\\\\"),
+      '\n' => result.push_str("\\n"),
+      '\r' => result.push_str("\\r"),
+      '\t' => result.push_str("\\t"),
+      c => result.push(c),
+    }
+  }
+  result.push('\'');
+  result
+}
+
+impl From<bool> for Literal {
+  fn from(value: bool) -> Self {
+    Literal::Boolean(value)
+  }
+}
+
+impl From<i64> for Literal {
+  fn from(value: i64) -> Self {
+    Literal::Integer(value)
+  }
+}
+
+impl From<Decimal> for Literal {
+  fn from(value: Decimal) -> Self {
+    Literal::Decimal(value)
+  }
+}
+
+impl From<&str> for Literal {
+  fn from(value: &str) -> Self {
+    Literal::String(value.to_string())
+  }
+}
+
+impl From<String> for Literal {
+  fn from(value: String) -> Self {
+    Literal::String(value)
+  }
+}
+
+impl<T> From<Vec<T>> for Literal
+where
+  T: Into<Literal>,
+{
+  fn from(values: Vec<T>) -> Self {
+    Literal::List(values.into_iter().map(|x| x.into()).collect())
+  }
+}
+
+impl From<i32> for Literal {
+  fn from(value: i32) -> Self {
+    Literal::Integer(value as i64)
+  }
+}
+
+impl From<u32> for Literal {
+  fn from(value: u32) -> Self {
+    Literal::Integer(value as i64)
+  }
+}
+
+/// Guids are written the same way they are stored in properties
+impl From<Uuid> for Literal {
+  fn from(value: Uuid) -> Self {
+    Literal::String(value.to_string())
+  }
+}
+
+impl<T> From<Option<T>> for Literal
+where
+  T: Into<Literal>,
+{
+  fn from(value: Option<T>) -> Self {
+    match value {
+      Some(value) => value.into(),
+      None => Literal::Null,
+    }
+  }
+}
+
+impl<T> From<BTreeMap<String, T>> for Literal
+where
+  T: Into<Literal>,
+{
+  fn from(values: BTreeMap<String, T>) -> Self {
+    Literal::Map(values.into_iter().map(|(k, v)| (k, v.into())).collect())
+  }
+}
+
+impl<T> From<HashMap<String, T>> for Literal
+where
+  T: Into<Literal>,
+{
+  fn from(values: HashMap<String, T>) -> Self {
+    Literal::Map(values.into_iter().map(|(k, v)| (k, v.into())).collect())
+  }
+}
+
+// ---  Reading values back out of literals, as done when deserializing an entity's properties
+
+fn mismatch(expected: &str, value: &Literal) -> GraphtError {
+  err!(
+    TypeMismatch,
+    "Expected {} value but received {}",
+    expected,
+    value
+  )
+}
+
+impl TryFrom<Literal> for bool {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    match value {
+      Literal::Boolean(value) => Ok(value),
+      x => Err(mismatch("a boolean", &x)),
+    }
+  }
+}
+
+impl TryFrom<Literal> for i64 {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    value.as_integer()
+  }
+}
+
+impl TryFrom<Literal> for i32 {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    i32::try_from(value.as_integer()?)
+      .map_err(|_| err!(TypeMismatch, "Integer {} does not fit in an i32", value))
+  }
+}
+
+impl TryFrom<Literal> for u32 {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    u32::try_from(value.as_integer()?)
+      .map_err(|_| err!(TypeMismatch, "Integer {} does not fit in a u32", value))
+  }
+}
+
+/// Integers are promoted, the same as `as_decimal`
+impl TryFrom<Literal> for Decimal {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    value
+      .as_decimal()
+      .ok_or_else(|| mismatch("a decimal", &value))
+  }
+}
+
+impl TryFrom<Literal> for String {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    match value {
+      Literal::String(value) => Ok(value),
+      x => Err(mismatch("a string", &x)),
+    }
+  }
+}
+
+impl TryFrom<Literal> for Uuid {
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    match &value {
+      Literal::String(guid) => Uuid::from_str(guid)
+        .map_err(|_| err!(TypeMismatch, "Expected a guid but received {}", value)),
+      x => Err(mismatch("a guid", x)),
+    }
+  }
+}
+
+/// Null is read as None, so optional properties may be left out entirely
+impl<T> TryFrom<Literal> for Option<T>
+where
+  T: TryFrom<Literal, Error = GraphtError>,
+{
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    match value {
+      Literal::Null => Ok(None),
+      x => Ok(Some(T::try_from(x)?)),
+    }
+  }
+}
+
+impl<T> TryFrom<Literal> for Vec<T>
+where
+  T: TryFrom<Literal, Error = GraphtError>,
+{
+  type Error = GraphtError;
+
+  fn try_from(value: Literal) -> GraphtResult<Self> {
+    match value {
+      Literal::List(values) => values.into_iter().map(T::try_from).collect(),
+      x => Err(mismatch("a list", &x)),
+    }
+  }
+}
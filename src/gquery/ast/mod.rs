@@ -2,3 +2,94 @@
 //!
 //! This is meant to be complete for GQL and may have some additional features pulled from other
 //! languages, if they make sense to implement
+
+use crate::{local::*, prelude::*};
+
+pub mod literal;
+pub use literal::Literal;
+
+pub mod expression;
+pub use expression::*;
+
+pub mod pattern;
+pub use pattern::*;
+
+pub mod clause;
+pub use clause::*;
+
+/// A full query, made up of an ordered list of clauses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GQuery {
+  pub clauses: Vec<Clause>,
+}
+
+impl GQuery {
+  pub fn new() -> GQuery {
+    GQuery {
+      clauses: Vec::new(),
+    }
+  }
+
+  /// Parse an OpenCypher query string into a GQuery
+  pub fn parse(query: &str) -> GraphtResult<GQuery> {
+    crate::gquery::grammars::cypher::parse(query)
+  }
+
+  /// Parse a query string written in the given query language
+  pub fn from_dialect(
+    query: &str,
+    dialect: crate::gquery::grammars::Dialect,
+  ) -> GraphtResult<GQuery> {
+    dialect.parse(query)
+  }
+
+  /// Print the query in the given query language
+  pub fn to_dialect(&self, dialect: crate::gquery::grammars::Dialect) -> GraphtResult<String> {
+    dialect.print(self)
+  }
+
+  /// Replace each $parameter with its value, for backends that can't take parameters separately
+  pub fn bind(&self, params: &crate::gquery::Params) -> GraphtResult<GQuery> {
+    let mut query = self.clone();
+    for clause in &mut query.clauses {
+      crate::gquery::params::bind_clause(clause, params)?;
+    }
+    Ok(query)
+  }
+
+  /// The names of the columns produced by the final RETURN clause, if there is one
+  pub fn columns(&self) -> Vec<String> {
+    match self.clauses.last() {
+      Some(Clause::Return(projection)) => projection.columns(),
+      _ => Vec::new(),
+    }
+  }
+
+  /// Check if any of the clauses change the graph
+  pub fn is_updating(&self) -> bool {
+    self.clauses.iter().any(|clause| clause.is_updating())
+  }
+}
+
+impl Display for GQuery {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let clauses: Vec<String> = self.clauses.iter().map(|x| x.to_string()).collect();
+    write!(f, "{}", clauses.join(" "))
+  }
+}
+
+/// Wrap a name in backticks if it cannot be written as a bare identifier
+pub fn escape_name(name: &str) -> String {
+  let mut chars = name.chars();
+  let bare = match chars.next() {
+    Some(first) => {
+      (first.is_alphabetic() || first == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+    }
+    None => false,
+  };
+
+  match bare {
+    true => name.to_string(),
+    false => format!("`{}`", name.replace('`', "``")),
+  }
+}
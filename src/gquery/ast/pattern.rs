@@ -0,0 +1,216 @@
+//! The shapes of nodes and edges to look for in a graph
+
+use super::*;
+
+/// A comma separated list of pattern parts, all of which must match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+  pub parts: Vec<PatternPart>,
+}
+
+impl Pattern {
+  /// Every variable named in the pattern, in the order they appear
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = Vec::new();
+    for part in &self.parts {
+      for name in part.variables() {
+        if !names.contains(&name) {
+          names.push(name);
+        }
+      }
+    }
+    names
+  }
+}
+
+/// A single chain of nodes connected by relationships
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternPart {
+  /// The name given to the whole path (eg. "p = (a)-->(b)")
+  pub variable: Option<String>,
+
+  /// The first node in the chain
+  pub start: NodePattern,
+
+  /// Each relationship and the node it leads to
+  pub chain: Vec<PatternStep>,
+
+  /// Only keep the shortest matches (eg. "shortestPath((a)-[*]->(b))")
+  pub shortest: Option<Shortest>,
+}
+
+impl PatternPart {
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(name) = &self.variable {
+      names.push(name.clone());
+    }
+    if let Some(name) = &self.start.variable {
+      names.push(name.clone());
+    }
+    for step in &self.chain {
+      if let Some(name) = &step.relationship.variable {
+        names.push(name.clone());
+      }
+      if let Some(name) = &step.node.variable {
+        names.push(name.clone());
+      }
+    }
+    names
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternStep {
+  pub relationship: RelationshipPattern,
+  pub node: NodePattern,
+}
+
+/// A node to be matched (eg. "(org:Organization {name: 'Root'})")
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodePattern {
+  pub variable: Option<String>,
+
+  /// The node must have all of these labels
+  pub labels: Vec<String>,
+
+  /// The node's properties must be equal to each of these values
+  pub properties: Vec<(String, Expression)>,
+}
+
+/// An edge to be matched (eg. "-[r:ParentOf]->")
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelationshipPattern {
+  pub variable: Option<String>,
+
+  pub direction: Direction,
+
+  /// The edge must have one of these labels, or any label if empty
+  pub types: Vec<String>,
+
+  /// The edge's properties must be equal to each of these values
+  pub properties: Vec<(String, Expression)>,
+
+  /// Match a chain of edges instead of a single one (eg. "-[*1..3]->")
+  pub length: Option<VarLength>,
+}
+
+/// The number of hops allowed by a variable length relationship. Missing bounds are open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VarLength {
+  pub min: Option<u64>,
+  pub max: Option<u64>,
+}
+
+/// Which of the matches between the same two nodes are kept when looking for the shortest path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortest {
+  /// A single one of the shortest matches: shortestPath(...)
+  Any,
+
+  /// Every match tied for the shortest: allShortestPaths(...)
+  All,
+}
+
+/// Which way an edge must point to fit a pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  /// From the left node to the right node: ()-[]->()
+  Outgoing,
+
+  /// From the right node to the left node: ()<-[]-()
+  Incoming,
+
+  /// Either way: ()-[]-()
+  Either,
+}
+
+impl Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let parts: Vec<String> = self.parts.iter().map(|x| x.to_string()).collect();
+    write!(f, "{}", parts.join(", "))
+  }
+}
+
+impl Display for PatternPart {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(name) = &self.variable {
+      write!(f, "{} = ", escape_name(name))?;
+    }
+    let mut chain = self.start.to_string();
+    for step in &self.chain {
+      chain.push_str(&format!("{}{}", step.relationship, step.node));
+    }
+    match self.shortest {
+      Some(Shortest::Any) => write!(f, "shortestPath({})", chain),
+      Some(Shortest::All) => write!(f, "allShortestPaths({})", chain),
+      None => write!(f, "{}", chain),
+    }
+  }
+}
+
+impl Display for NodePattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "(")?;
+    if let Some(name) = &self.variable {
+      write!(f, "{}", escape_name(name))?;
+    }
+    for label in &self.labels {
+      write!(f, ":{}", escape_name(label))?;
+    }
+    if !self.properties.is_empty() {
+      if self.variable.is_some() || !self.labels.is_empty() {
+        write!(f, " ")?;
+      }
+      write!(f, "{}", format_map(&self.properties))?;
+    }
+    write!(f, ")")
+  }
+}
+
+impl Display for RelationshipPattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut detail = String::new();
+    if let Some(name) = &self.variable {
+      detail.push_str(&escape_name(name));
+    }
+    let types: Vec<String> = self.types.iter().map(|x| escape_name(x)).collect();
+    if !types.is_empty() {
+      detail.push_str(&format!(":{}", types.join("|")));
+    }
+    if let Some(length) = &self.length {
+      detail.push_str(&length.to_string());
+    }
+    if !self.properties.is_empty() {
+      if !detail.is_empty() {
+        detail.push(' ');
+      }
+      detail.push_str(&format_map(&self.properties));
+    }
+
+    let body = match detail.is_empty() {
+      true => String::from("--"),
+      false => format!("-[{}]-", detail),
+    };
+
+    match self.direction {
+      Direction::Outgoing => write!(f, "{}>", body),
+      Direction::Incoming => write!(f, "<{}", body),
+      Direction::Either => write!(f, "{}", body),
+    }
+  }
+}
+
+impl Display for VarLength {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.min, self.max) {
+      (None, None) => write!(f, "*"),
+      (Some(min), Some(max)) if min == max => write!(f, "*{}", min),
+      (min, max) => {
+        let min = min.map(|x| x.to_string()).unwrap_or_default();
+        let max = max.map(|x| x.to_string()).unwrap_or_default();
+        write!(f, "*{}..{}", min, max)
+      }
+    }
+  }
+}
@@ -0,0 +1,831 @@
+//! A fluent interface for building queries in code
+//!
+//! The builder produces the same AST as the Cypher parser, so a built query can be run or printed
+//! back out as text with `to_string`.
+//!
+//! ```ignore
+//! use grapht::gquery::builder::*;
+//!
+//! let query = GQuery::match_(node("o").label("Organization"))
+//!   .edge(out("ParentOf").var_len(0..=1))
+//!   .node("c")
+//!   .where_(prop("o", "name").eq(param("n")))
+//!   .ret(["o", "c"]);
+//!
+//! assert_eq!(
+//!   query.to_string(),
+//!   "MATCH (o:Organization)-[:ParentOf*0..1]->(c) WHERE o.name = $n RETURN o, c"
+//! );
+//! ```
+//!
+//! Using the builder out of order, such as adding an edge straight after a RETURN, is a programming
+//! error and panics.
+
+use crate::gquery::ast::*;
+
+use std::ops::{Bound, RangeBounds};
+
+use rust_decimal::Decimal;
+
+//--- Patterns
+
+/// A node pattern bound to a variable. An empty name makes an anonymous node
+pub fn node(name: &str) -> NodePattern {
+  NodePattern {
+    variable: optional_name(name),
+    ..NodePattern::default()
+  }
+}
+
+/// An edge pointing from the left node to the right node. An empty type matches any edge
+pub fn out(edge_type: &str) -> RelationshipPattern {
+  relationship(edge_type, Direction::Outgoing)
+}
+
+/// An edge pointing from the right node to the left node
+pub fn in_(edge_type: &str) -> RelationshipPattern {
+  relationship(edge_type, Direction::Incoming)
+}
+
+/// An edge pointing either way
+pub fn either(edge_type: &str) -> RelationshipPattern {
+  relationship(edge_type, Direction::Either)
+}
+
+fn relationship(edge_type: &str, direction: Direction) -> RelationshipPattern {
+  RelationshipPattern {
+    variable: None,
+    direction,
+    types: optional_name(edge_type).into_iter().collect(),
+    properties: Vec::new(),
+    length: None,
+  }
+}
+
+fn optional_name(name: &str) -> Option<String> {
+  match name.is_empty() {
+    true => None,
+    false => Some(name.to_string()),
+  }
+}
+
+impl NodePattern {
+  pub fn label(mut self, label: &str) -> Self {
+    self.labels.push(label.to_string());
+    self
+  }
+
+  /// Require a property to be equal to a value
+  pub fn prop(mut self, key: &str, value: impl Into<Expression>) -> Self {
+    self.properties.push((key.to_string(), value.into()));
+    self
+  }
+}
+
+impl From<&str> for NodePattern {
+  fn from(name: &str) -> Self {
+    node(name)
+  }
+}
+
+impl RelationshipPattern {
+  /// Bind the edge to a variable
+  pub fn var(mut self, name: &str) -> Self {
+    self.variable = Some(name.to_string());
+    self
+  }
+
+  /// Allow another edge type
+  pub fn or_type(mut self, edge_type: &str) -> Self {
+    self.types.push(edge_type.to_string());
+    self
+  }
+
+  /// Require a property to be equal to a value
+  pub fn prop(mut self, key: &str, value: impl Into<Expression>) -> Self {
+    self.properties.push((key.to_string(), value.into()));
+    self
+  }
+
+  /// Match a chain of edges, with the number of hops in the range (eg. "0..=1" or "1..")
+  pub fn var_len(mut self, hops: impl RangeBounds<u64>) -> Self {
+    let min = match hops.start_bound() {
+      Bound::Included(x) => Some(*x),
+      Bound::Excluded(x) => Some(x + 1),
+      Bound::Unbounded => None,
+    };
+    let max = match hops.end_bound() {
+      Bound::Included(x) => Some(*x),
+      Bound::Excluded(x) => Some(x.saturating_sub(1)),
+      Bound::Unbounded => None,
+    };
+    self.length = Some(VarLength { min, max });
+    self
+  }
+}
+
+/// Build a chain of nodes and edges outside of a query, such as for a pattern predicate
+pub fn path(start: impl Into<NodePattern>) -> PathBuilder {
+  PathBuilder {
+    part: PatternPart {
+      variable: None,
+      start: start.into(),
+      chain: Vec::new(),
+      shortest: None,
+    },
+    pending: None,
+  }
+}
+
+/// A pattern part under construction, which may be waiting for the node at the end of an edge
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathBuilder {
+  part: PatternPart,
+  pending: Option<RelationshipPattern>,
+}
+
+impl PathBuilder {
+  /// Name the whole path (eg. "p = (a)-->(b)")
+  pub fn named(mut self, name: &str) -> Self {
+    self.part.variable = Some(name.to_string());
+    self
+  }
+
+  /// Only match a single shortest path between the ends (eg. "shortestPath((a)-[*]->(b))")
+  pub fn shortest(mut self) -> Self {
+    self.part.shortest = Some(Shortest::Any);
+    self
+  }
+
+  /// Match every path tied for the shortest between the ends
+  pub fn all_shortest(mut self) -> Self {
+    self.part.shortest = Some(Shortest::All);
+    self
+  }
+
+  pub fn edge(mut self, relationship: RelationshipPattern) -> Self {
+    self.push_edge(relationship);
+    self
+  }
+
+  pub fn node(mut self, node: impl Into<NodePattern>) -> Self {
+    self.push_node(node.into());
+    self
+  }
+
+  fn push_edge(&mut self, relationship: RelationshipPattern) {
+    self.flush();
+    self.pending = Some(relationship);
+  }
+
+  fn push_node(&mut self, node: NodePattern) {
+    let relationship = self
+      .pending
+      .take()
+      .expect("A node can only be added to a path after an edge");
+    self.part.chain.push(PatternStep { relationship, node });
+  }
+
+  /// An edge without an end node points at an anonymous node
+  fn flush(&mut self) {
+    if let Some(relationship) = self.pending.take() {
+      self.part.chain.push(PatternStep {
+        relationship,
+        node: NodePattern::default(),
+      });
+    }
+  }
+
+  pub fn build(mut self) -> PatternPart {
+    self.flush();
+    self.part
+  }
+
+  /// Use the path as a predicate, which is true if it can be found
+  pub fn exists(self) -> Expression {
+    Expression::PatternPredicate(Box::new(self.build()))
+  }
+}
+
+impl From<PathBuilder> for PatternPart {
+  fn from(path: PathBuilder) -> Self {
+    path.build()
+  }
+}
+
+impl From<NodePattern> for PatternPart {
+  fn from(start: NodePattern) -> Self {
+    path(start).build()
+  }
+}
+
+//--- Expressions
+
+/// A reference to a variable
+pub fn var(name: &str) -> Expression {
+  Expression::variable(name)
+}
+
+/// A property of a variable (eg. "o.name")
+pub fn prop(name: &str, key: &str) -> Expression {
+  Expression::variable(name).property(key)
+}
+
+/// A query parameter (eg. "$name")
+pub fn param(name: &str) -> Expression {
+  Expression::Parameter(name.to_string())
+}
+
+/// A constant value
+pub fn lit(value: impl Into<Literal>) -> Expression {
+  Expression::Literal(value.into())
+}
+
+/// A list of expressions
+pub fn list<T: Into<Expression>>(values: impl IntoIterator<Item = T>) -> Expression {
+  Expression::List(values.into_iter().map(Into::into).collect())
+}
+
+/// A map of expressions
+pub fn map<T: Into<Expression>>(values: impl IntoIterator<Item = (&'static str, T)>) -> Expression {
+  Expression::Map(
+    values
+      .into_iter()
+      .map(|(key, value)| (key.to_string(), value.into()))
+      .collect(),
+  )
+}
+
+/// Call a function by name
+pub fn call<T: Into<Expression>>(name: &str, arguments: impl IntoIterator<Item = T>) -> Expression {
+  Expression::Function(FunctionCall {
+    name: name.to_string(),
+    distinct: false,
+    arguments: arguments.into_iter().map(Into::into).collect(),
+  })
+}
+
+/// The "count(*)" aggregate
+pub fn count_all() -> Expression {
+  Expression::CountAll
+}
+
+impl Expression {
+  fn compare(self, op: BinaryOperator, rhs: impl Into<Expression>) -> Expression {
+    Expression::binary(op, self, rhs.into())
+  }
+
+  /// Equality, which makes a new expression rather than comparing the two expressions
+  #[allow(clippy::should_implement_trait)]
+  pub fn eq(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Eq, rhs)
+  }
+
+  #[allow(clippy::should_implement_trait)]
+  pub fn ne(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Ne, rhs)
+  }
+
+  #[allow(clippy::should_implement_trait)]
+  pub fn lt(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Lt, rhs)
+  }
+
+  #[allow(clippy::should_implement_trait)]
+  pub fn gt(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Gt, rhs)
+  }
+
+  #[allow(clippy::should_implement_trait)]
+  pub fn le(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Le, rhs)
+  }
+
+  #[allow(clippy::should_implement_trait)]
+  pub fn ge(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Ge, rhs)
+  }
+
+  pub fn and(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::And, rhs)
+  }
+
+  pub fn or(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Or, rhs)
+  }
+
+  pub fn xor(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Xor, rhs)
+  }
+
+  pub fn in_(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::In, rhs)
+  }
+
+  pub fn starts_with(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::StartsWith, rhs)
+  }
+
+  pub fn ends_with(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::EndsWith, rhs)
+  }
+
+  pub fn contains(self, rhs: impl Into<Expression>) -> Expression {
+    self.compare(BinaryOperator::Contains, rhs)
+  }
+
+  /// Check that a node has all of the labels
+  pub fn has_labels(self, labels: &[&str]) -> Expression {
+    Expression::HasLabels(Box::new(self), to_strings(labels))
+  }
+
+  pub fn is_null(self) -> Expression {
+    Expression::IsNull(Box::new(self), false)
+  }
+
+  pub fn is_not_null(self) -> Expression {
+    Expression::IsNull(Box::new(self), true)
+  }
+
+  /// Name the expression in a projection
+  pub fn as_(self, alias: &str) -> ProjectionItem {
+    ProjectionItem {
+      expression: self,
+      alias: Some(alias.to_string()),
+    }
+  }
+
+  /// Sort by the expression, smallest first
+  pub fn asc(self) -> SortItem {
+    SortItem {
+      expression: self,
+      descending: false,
+    }
+  }
+
+  /// Sort by the expression, largest first
+  pub fn desc(self) -> SortItem {
+    SortItem {
+      expression: self,
+      descending: true,
+    }
+  }
+}
+
+macro_rules! expression_operator {
+  ($trait:ident, $method:ident, $op:ident) => {
+    impl<T: Into<Expression>> std::ops::$trait<T> for Expression {
+      type Output = Expression;
+
+      fn $method(self, rhs: T) -> Expression {
+        Expression::binary(BinaryOperator::$op, self, rhs.into())
+      }
+    }
+  };
+}
+
+expression_operator!(Add, add, Add);
+expression_operator!(Sub, sub, Subtract);
+expression_operator!(Mul, mul, Multiply);
+expression_operator!(Div, div, Divide);
+expression_operator!(Rem, rem, Modulo);
+
+impl std::ops::Not for Expression {
+  type Output = Expression;
+
+  fn not(self) -> Expression {
+    Expression::Unary(UnaryOperator::Not, Box::new(self))
+  }
+}
+
+impl std::ops::Neg for Expression {
+  type Output = Expression;
+
+  fn neg(self) -> Expression {
+    Expression::Unary(UnaryOperator::Negate, Box::new(self))
+  }
+}
+
+macro_rules! expression_from_literal {
+  ($($from:ty),*) => {
+    $(
+      impl From<$from> for Expression {
+        fn from(value: $from) -> Self {
+          Expression::Literal(value.into())
+        }
+      }
+    )*
+  };
+}
+
+expression_from_literal!(bool, i64, Decimal, &str, String);
+
+impl From<&str> for ProjectionItem {
+  fn from(name: &str) -> Self {
+    ProjectionItem {
+      expression: Expression::variable(name),
+      alias: None,
+    }
+  }
+}
+
+impl From<Expression> for ProjectionItem {
+  fn from(expression: Expression) -> Self {
+    ProjectionItem {
+      expression,
+      alias: None,
+    }
+  }
+}
+
+impl From<Expression> for SortItem {
+  fn from(expression: Expression) -> Self {
+    expression.asc()
+  }
+}
+
+impl From<&str> for SortItem {
+  fn from(name: &str) -> Self {
+    Expression::variable(name).asc()
+  }
+}
+
+//--- Clauses
+
+impl GQuery {
+  /// Start a query with a MATCH
+  pub fn match_(start: impl Into<NodePattern>) -> QueryBuilder {
+    QueryBuilder::new().match_(start)
+  }
+
+  /// Start a query with an OPTIONAL MATCH
+  pub fn optional_match(start: impl Into<NodePattern>) -> QueryBuilder {
+    QueryBuilder::new().optional_match(start)
+  }
+
+  /// Start a query with a CREATE
+  pub fn create(start: impl Into<NodePattern>) -> QueryBuilder {
+    QueryBuilder::new().create(start)
+  }
+
+  /// Start a query with a MERGE
+  pub fn merge(start: impl Into<NodePattern>) -> QueryBuilder {
+    QueryBuilder::new().merge(start)
+  }
+
+  /// Start a query with an UNWIND
+  pub fn unwind(list: impl Into<Expression>, name: &str) -> QueryBuilder {
+    QueryBuilder::new().unwind(list, name)
+  }
+
+  /// Only return unique rows
+  pub fn distinct(mut self) -> Self {
+    last_projection(&mut self.clauses, "distinct").distinct = true;
+    self
+  }
+
+  /// Sort the returned rows
+  pub fn order_by<T: Into<SortItem>>(mut self, items: impl IntoIterator<Item = T>) -> Self {
+    let projection = last_projection(&mut self.clauses, "order_by");
+    projection.order.extend(items.into_iter().map(Into::into));
+    self
+  }
+
+  /// Drop rows from the start of the results
+  pub fn skip(mut self, count: i64) -> Self {
+    last_projection(&mut self.clauses, "skip").skip = Some(count.into());
+    self
+  }
+
+  /// Return at most this many rows
+  pub fn limit(mut self, count: i64) -> Self {
+    last_projection(&mut self.clauses, "limit").limit = Some(count.into());
+    self
+  }
+}
+
+/// The projection of the last clause, which needs to be a WITH or RETURN
+fn last_projection<'a>(clauses: &'a mut [Clause], method: &str) -> &'a mut Projection {
+  match clauses.last_mut() {
+    Some(Clause::With(with)) => &mut with.projection,
+    Some(Clause::Return(projection)) => projection,
+    _ => panic!("{}() can only be used after a WITH or RETURN", method),
+  }
+}
+
+/// Builds up a query one clause at a time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryBuilder {
+  clauses: Vec<Clause>,
+
+  /// The edge waiting on its end node
+  pending: Option<RelationshipPattern>,
+}
+
+impl QueryBuilder {
+  pub fn new() -> QueryBuilder {
+    QueryBuilder {
+      clauses: Vec::new(),
+      pending: None,
+    }
+  }
+
+  /// Finish the last pattern and add a new clause
+  fn push(mut self, clause: Clause) -> Self {
+    self.flush();
+    self.clauses.push(clause);
+    self
+  }
+
+  /// An edge without an end node points at an anonymous node
+  fn flush(&mut self) {
+    if let Some(relationship) = self.pending.take() {
+      self.current_part("edge").chain.push(PatternStep {
+        relationship,
+        node: NodePattern::default(),
+      });
+    }
+  }
+
+  /// The pattern part currently being built
+  fn current_part(&mut self, method: &str) -> &mut PatternPart {
+    let part = match self.clauses.last_mut() {
+      Some(Clause::Match(matched)) => matched.pattern.parts.last_mut(),
+      Some(Clause::Create(pattern)) => pattern.parts.last_mut(),
+      Some(Clause::Merge(merge)) => Some(&mut merge.pattern),
+      _ => None,
+    };
+    match part {
+      Some(part) => part,
+      None => panic!(
+        "{}() can only be used after a MATCH, CREATE or MERGE",
+        method
+      ),
+    }
+  }
+
+  pub fn match_(self, start: impl Into<NodePattern>) -> Self {
+    self.push(Clause::Match(Match {
+      optional: false,
+      pattern: Pattern {
+        parts: vec![start.into().into()],
+      },
+      filter: None,
+    }))
+  }
+
+  pub fn optional_match(self, start: impl Into<NodePattern>) -> Self {
+    self.push(Clause::Match(Match {
+      optional: true,
+      pattern: Pattern {
+        parts: vec![start.into().into()],
+      },
+      filter: None,
+    }))
+  }
+
+  pub fn create(self, start: impl Into<NodePattern>) -> Self {
+    self.push(Clause::Create(Pattern {
+      parts: vec![start.into().into()],
+    }))
+  }
+
+  pub fn merge(self, start: impl Into<NodePattern>) -> Self {
+    self.push(Clause::Merge(Merge {
+      pattern: start.into().into(),
+      on_create: Vec::new(),
+      on_match: Vec::new(),
+    }))
+  }
+
+  /// Add a whole pattern part to the current MATCH or CREATE (eg. the ", (b)" in "MATCH (a), (b)")
+  pub fn part(mut self, part: impl Into<PatternPart>) -> Self {
+    self.flush();
+    match self.clauses.last_mut() {
+      Some(Clause::Match(Match { pattern, .. })) | Some(Clause::Create(pattern)) => {
+        pattern.parts.push(part.into())
+      }
+      _ => panic!("part() can only be used after a MATCH or CREATE"),
+    }
+    self
+  }
+
+  /// Name the path currently being built
+  pub fn named(mut self, name: &str) -> Self {
+    self.current_part("named").variable = Some(name.to_string());
+    self
+  }
+
+  /// Continue the current pattern with an edge
+  pub fn edge(mut self, relationship: RelationshipPattern) -> Self {
+    self.flush();
+    let _ = self.current_part("edge");
+    self.pending = Some(relationship);
+    self
+  }
+
+  /// Add the node at the end of the last edge, or start a new pattern part if there is no edge
+  pub fn node(mut self, node: impl Into<NodePattern>) -> Self {
+    let node = node.into();
+    match self.pending.take() {
+      Some(relationship) => {
+        self
+          .current_part("node")
+          .chain
+          .push(PatternStep { relationship, node });
+        self
+      }
+      None => self.part(node),
+    }
+  }
+
+  /// Filter the last MATCH or WITH. Calling it again combines the predicates with AND
+  pub fn where_(mut self, predicate: impl Into<Expression>) -> Self {
+    self.flush();
+    let filter = match self.clauses.last_mut() {
+      Some(Clause::Match(matched)) => &mut matched.filter,
+      Some(Clause::With(with)) => &mut with.filter,
+      _ => panic!("where_() can only be used after a MATCH or WITH"),
+    };
+    *filter = Some(match filter.take() {
+      Some(existing) => existing.and(predicate),
+      None => predicate.into(),
+    });
+    self
+  }
+
+  pub fn unwind(self, list: impl Into<Expression>, name: &str) -> Self {
+    self.push(Clause::Unwind(Unwind {
+      expression: list.into(),
+      variable: name.to_string(),
+    }))
+  }
+
+  pub fn with<T: Into<ProjectionItem>>(self, items: impl IntoIterator<Item = T>) -> Self {
+    self.push(Clause::With(With {
+      projection: projection(items),
+      filter: None,
+    }))
+  }
+
+  /// Only keep unique rows in the last WITH
+  pub fn distinct(mut self) -> Self {
+    last_projection(&mut self.clauses, "distinct").distinct = true;
+    self
+  }
+
+  /// Sort the rows of the last WITH
+  pub fn order_by<T: Into<SortItem>>(mut self, items: impl IntoIterator<Item = T>) -> Self {
+    let projection = last_projection(&mut self.clauses, "order_by");
+    projection.order.extend(items.into_iter().map(Into::into));
+    self
+  }
+
+  pub fn skip(mut self, count: i64) -> Self {
+    last_projection(&mut self.clauses, "skip").skip = Some(count.into());
+    self
+  }
+
+  pub fn limit(mut self, count: i64) -> Self {
+    last_projection(&mut self.clauses, "limit").limit = Some(count.into());
+    self
+  }
+
+  /// Set a property, given as a property lookup such as `prop("o", "name")`
+  pub fn set(self, property: Expression, value: impl Into<Expression>) -> Self {
+    self.set_item(SetItem::Property(property, value.into()))
+  }
+
+  /// Add labels to a node
+  pub fn set_labels(self, name: &str, labels: &[&str]) -> Self {
+    self.set_item(SetItem::Labels(name.to_string(), to_strings(labels)))
+  }
+
+  /// Update the properties of an item with the values in a map ("+=")
+  pub fn set_properties(self, name: &str, values: impl Into<Expression>) -> Self {
+    self.set_item(SetItem::Update(name.to_string(), values.into()))
+  }
+
+  /// Replace all the properties of an item ("=")
+  pub fn replace_properties(self, name: &str, values: impl Into<Expression>) -> Self {
+    self.set_item(SetItem::Replace(name.to_string(), values.into()))
+  }
+
+  /// Add to the last SET clause, or start a new one
+  fn set_item(mut self, item: SetItem) -> Self {
+    self.flush();
+    match self.clauses.last_mut() {
+      Some(Clause::Set(items)) => {
+        items.push(item);
+        self
+      }
+      _ => self.push(Clause::Set(vec![item])),
+    }
+  }
+
+  /// Set a property when the last MERGE creates its pattern
+  pub fn on_create_set(mut self, property: Expression, value: impl Into<Expression>) -> Self {
+    self
+      .last_merge("on_create_set")
+      .on_create
+      .push(SetItem::Property(property, value.into()));
+    self
+  }
+
+  /// Set a property when the last MERGE finds its pattern
+  pub fn on_match_set(mut self, property: Expression, value: impl Into<Expression>) -> Self {
+    self
+      .last_merge("on_match_set")
+      .on_match
+      .push(SetItem::Property(property, value.into()));
+    self
+  }
+
+  fn last_merge(&mut self, method: &str) -> &mut Merge {
+    self.flush();
+    match self.clauses.last_mut() {
+      Some(Clause::Merge(merge)) => merge,
+      _ => panic!("{}() can only be used after a MERGE", method),
+    }
+  }
+
+  /// Remove a property, given as a property lookup
+  pub fn remove(self, property: Expression) -> Self {
+    self.remove_item(RemoveItem::Property(property))
+  }
+
+  /// Remove labels from a node
+  pub fn remove_labels(self, name: &str, labels: &[&str]) -> Self {
+    self.remove_item(RemoveItem::Labels(name.to_string(), to_strings(labels)))
+  }
+
+  fn remove_item(mut self, item: RemoveItem) -> Self {
+    self.flush();
+    match self.clauses.last_mut() {
+      Some(Clause::Remove(items)) => {
+        items.push(item);
+        self
+      }
+      _ => self.push(Clause::Remove(vec![item])),
+    }
+  }
+
+  /// Delete the named nodes and edges
+  pub fn delete(self, names: &[&str]) -> Self {
+    self.push(Clause::Delete(Delete {
+      detach: false,
+      items: names
+        .iter()
+        .map(|name| Expression::variable(name))
+        .collect(),
+    }))
+  }
+
+  /// Delete the named nodes along with any attached edges
+  pub fn detach_delete(self, names: &[&str]) -> Self {
+    self.push(Clause::Delete(Delete {
+      detach: true,
+      items: names
+        .iter()
+        .map(|name| Expression::variable(name))
+        .collect(),
+    }))
+  }
+
+  /// Finish the query by returning the given values
+  pub fn ret<T: Into<ProjectionItem>>(self, items: impl IntoIterator<Item = T>) -> GQuery {
+    self.push(Clause::Return(projection(items))).build()
+  }
+
+  /// Finish the query by returning every variable ("RETURN *")
+  pub fn ret_all(self) -> GQuery {
+    let mut projection = projection(Vec::<ProjectionItem>::new());
+    projection.all = true;
+    self.push(Clause::Return(projection)).build()
+  }
+
+  /// Finish a query that doesn't return anything, such as a CREATE
+  pub fn build(mut self) -> GQuery {
+    self.flush();
+    GQuery {
+      clauses: self.clauses,
+    }
+  }
+}
+
+impl From<QueryBuilder> for GQuery {
+  fn from(builder: QueryBuilder) -> Self {
+    builder.build()
+  }
+}
+
+fn projection<T: Into<ProjectionItem>>(items: impl IntoIterator<Item = T>) -> Projection {
+  Projection {
+    items: items.into_iter().map(Into::into).collect(),
+    ..Projection::default()
+  }
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+  values.iter().map(|x| x.to_string()).collect()
+}
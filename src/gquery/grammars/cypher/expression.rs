@@ -0,0 +1,417 @@
+//! Expressions, ordered from the loosest binding operator to the tightest
+
+use super::*;
+
+use nom::{
+  character::complete::{anychar, digit1, one_of},
+  combinator::value,
+};
+
+pub(crate) fn expression(input: &str) -> PResult<'_, Expression> {
+  or_expression(input)
+}
+
+/// Fold a list of left associative binary operations into a single expression
+fn fold_binary<'a>(
+  input: &'a str,
+  operand: fn(&'a str) -> PResult<'a, Expression>,
+  operator: fn(&'a str) -> PResult<'a, BinaryOperator>,
+) -> PResult<'a, Expression> {
+  let (mut input, mut lhs) = operand(input)?;
+  loop {
+    match operator(input) {
+      Ok((remaining, op)) => {
+        let (remaining, rhs) = cut(operand)(remaining)?;
+        lhs = Expression::binary(op, lhs, rhs);
+        input = remaining;
+      }
+      Err(NomErr::Error(_)) => return Ok((input, lhs)),
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+fn or_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, xor_expression, |i| {
+    value(BinaryOperator::Or, keyword("OR"))(i)
+  })
+}
+
+fn xor_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, and_expression, |i| {
+    value(BinaryOperator::Xor, keyword("XOR"))(i)
+  })
+}
+
+fn and_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, not_expression, |i| {
+    value(BinaryOperator::And, keyword("AND"))(i)
+  })
+}
+
+fn not_expression(input: &str) -> PResult<'_, Expression> {
+  match keyword("NOT")(input) {
+    Ok((remaining, _)) => {
+      let (remaining, inner) = cut(not_expression)(remaining)?;
+      Ok((
+        remaining,
+        Expression::Unary(UnaryOperator::Not, Box::new(inner)),
+      ))
+    }
+    Err(NomErr::Error(_)) => comparison_expression(input),
+    Err(err) => Err(err),
+  }
+}
+
+fn comparison_operator(input: &str) -> PResult<'_, BinaryOperator> {
+  ws(alt((
+    value(BinaryOperator::Ne, tag("<>")),
+    value(BinaryOperator::Le, tag("<=")),
+    value(BinaryOperator::Ge, tag(">=")),
+    value(BinaryOperator::Eq, tag("=")),
+    value(BinaryOperator::Lt, tag("<")),
+    value(BinaryOperator::Gt, tag(">")),
+  )))(input)
+}
+
+/// Comparisons can be chained, so "a < b < c" is the same as "a < b AND b < c"
+fn comparison_expression(input: &str) -> PResult<'_, Expression> {
+  let (mut input, first) = add_expression(input)?;
+  let mut comparisons: Vec<Expression> = Vec::new();
+  let mut lhs = first.clone();
+
+  loop {
+    match comparison_operator(input) {
+      Ok((remaining, op)) => {
+        let (remaining, rhs) = cut(add_expression)(remaining)?;
+        comparisons.push(Expression::binary(op, lhs, rhs.clone()));
+        lhs = rhs;
+        input = remaining;
+      }
+      Err(NomErr::Error(_)) => break,
+      Err(err) => return Err(err),
+    }
+  }
+
+  let mut comparisons = comparisons.into_iter();
+  let result = match comparisons.next() {
+    None => first,
+    Some(head) => comparisons.fold(head, |acc, x| {
+      Expression::binary(BinaryOperator::And, acc, x)
+    }),
+  };
+  Ok((input, result))
+}
+
+fn add_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, multiply_expression, |i| {
+    ws(alt((
+      value(BinaryOperator::Add, char('+')),
+      // Don't mistake the start of an arrow for subtraction
+      value(
+        BinaryOperator::Subtract,
+        terminated(char('-'), nom::combinator::not(one_of("-[>"))),
+      ),
+    )))(i)
+  })
+}
+
+fn multiply_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, power_expression, |i| {
+    ws(alt((
+      value(BinaryOperator::Multiply, char('*')),
+      value(BinaryOperator::Divide, char('/')),
+      value(BinaryOperator::Modulo, char('%')),
+    )))(i)
+  })
+}
+
+fn power_expression(input: &str) -> PResult<'_, Expression> {
+  fold_binary(input, unary_expression, |i| {
+    value(BinaryOperator::Power, symbol("^"))(i)
+  })
+}
+
+fn unary_expression(input: &str) -> PResult<'_, Expression> {
+  let sign = ws(alt((
+    value(UnaryOperator::Negate, char('-')),
+    value(UnaryOperator::Plus, char('+')),
+  )))(input);
+
+  match sign {
+    Ok((remaining, op)) => {
+      let (remaining, inner) = cut(unary_expression)(remaining)?;
+      // Fold negative numbers directly into the literal
+      let result = match (op, inner) {
+        (UnaryOperator::Negate, Expression::Literal(Literal::Integer(x))) => {
+          Expression::Literal(Literal::Integer(-x))
+        }
+        (UnaryOperator::Negate, Expression::Literal(Literal::Decimal(x))) => {
+          Expression::Literal(Literal::Decimal(-x))
+        }
+        (op, inner) => Expression::Unary(op, Box::new(inner)),
+      };
+      Ok((remaining, result))
+    }
+    Err(NomErr::Error(_)) => string_list_null_expression(input),
+    Err(err) => Err(err),
+  }
+}
+
+/// The operators which apply to strings, lists and nulls
+fn string_list_null_expression(input: &str) -> PResult<'_, Expression> {
+  let (mut input, mut lhs) = property_or_labels_expression(input)?;
+
+  loop {
+    let op = alt((
+      value(
+        Some(BinaryOperator::StartsWith),
+        pair(keyword("STARTS"), keyword("WITH")),
+      ),
+      value(
+        Some(BinaryOperator::EndsWith),
+        pair(keyword("ENDS"), keyword("WITH")),
+      ),
+      value(Some(BinaryOperator::Contains), keyword("CONTAINS")),
+      value(Some(BinaryOperator::In), keyword("IN")),
+      value(None, keyword("IS")),
+    ))(input);
+
+    match op {
+      Ok((remaining, Some(op))) => {
+        let (remaining, rhs) = cut(property_or_labels_expression)(remaining)?;
+        lhs = Expression::binary(op, lhs, rhs);
+        input = remaining;
+      }
+      Ok((remaining, None)) => {
+        let (remaining, negated) =
+          cut(terminated(opt(keyword("NOT")), keyword("NULL")))(remaining)?;
+        lhs = Expression::IsNull(Box::new(lhs), negated.is_some());
+        input = remaining;
+      }
+      Err(NomErr::Error(_)) => return Ok((input, lhs)),
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+fn property_or_labels_expression(input: &str) -> PResult<'_, Expression> {
+  let (mut input, mut result) = atom(input)?;
+
+  // Property lookups
+  loop {
+    match preceded(symbol("."), cut(schema_name))(input) {
+      Ok((remaining, name)) => {
+        result = Expression::Property(Box::new(result), name);
+        input = remaining;
+      }
+      Err(NomErr::Error(_)) => break,
+      Err(err) => return Err(err),
+    }
+  }
+
+  // Label checks
+  let (input, labels) = many0(preceded(symbol(":"), cut(schema_name)))(input)?;
+  if !labels.is_empty() {
+    result = Expression::HasLabels(Box::new(result), labels);
+  }
+
+  Ok((input, result))
+}
+
+fn atom(input: &str) -> PResult<'_, Expression> {
+  alt((
+    map(literal, Expression::Literal),
+    parameter,
+    count_all,
+    case_expression,
+    list,
+    map_literal,
+    function_call,
+    pattern::pattern_predicate,
+    parenthesized,
+    map(symbolic_name, Expression::Variable),
+  ))(input)
+}
+
+fn parenthesized(input: &str) -> PResult<'_, Expression> {
+  let (input, _) = symbol("(")(input)?;
+  let (input, inner) = expression(input)?;
+  let (input, _) = cut(symbol(")"))(input)?;
+  Ok((input, inner))
+}
+
+fn case_expression(input: &str) -> PResult<'_, Expression> {
+  let (input, _) = keyword("CASE")(input)?;
+
+  // Check for WHEN first, so it isn't mistaken for the subject
+  let (input, subject) = match keyword("WHEN")(input) {
+    Ok(_) => (input, None),
+    Err(NomErr::Error(_)) => map(cut(expression), Some)(input)?,
+    Err(err) => return Err(err),
+  };
+
+  let (input, alternatives) = cut(many1(pair(
+    preceded(keyword("WHEN"), expression),
+    preceded(keyword("THEN"), expression),
+  )))(input)?;
+  let (input, default) = opt(preceded(keyword("ELSE"), cut(expression)))(input)?;
+  let (input, _) = cut(keyword("END"))(input)?;
+
+  Ok((
+    input,
+    Expression::Case(Case {
+      subject: subject.map(Box::new),
+      alternatives,
+      default: default.map(Box::new),
+    }),
+  ))
+}
+
+fn parameter(input: &str) -> PResult<'_, Expression> {
+  let (input, _) = symbol("$")(input)?;
+  let (input, name) = cut(alt((schema_name, map(digit1, |x: &str| x.to_string()))))(input)?;
+  Ok((input, Expression::Parameter(name)))
+}
+
+fn count_all(input: &str) -> PResult<'_, Expression> {
+  value(
+    Expression::CountAll,
+    tuple((keyword("count"), symbol("("), symbol("*"), symbol(")"))),
+  )(input)
+}
+
+fn function_call(input: &str) -> PResult<'_, Expression> {
+  // Namespaced functions such as "date.truncate"
+  let (input, name) = recognize(pair(schema_name, many0(pair(char('.'), schema_name))))(input)?;
+  let (input, _) = symbol("(")(input)?;
+  let (input, distinct) = opt(keyword("DISTINCT"))(input)?;
+  let (input, arguments) = cut(terminated(
+    nom::multi::separated_list0(symbol(","), expression),
+    symbol(")"),
+  ))(input)?;
+
+  Ok((
+    input,
+    Expression::Function(FunctionCall {
+      name: name.trim().to_string(),
+      distinct: distinct.is_some(),
+      arguments,
+    }),
+  ))
+}
+
+fn list(input: &str) -> PResult<'_, Expression> {
+  let (input, _) = symbol("[")(input)?;
+  let (input, values) = cut(terminated(
+    nom::multi::separated_list0(symbol(","), expression),
+    symbol("]"),
+  ))(input)?;
+  Ok((input, Expression::List(values)))
+}
+
+pub(crate) fn map_literal(input: &str) -> PResult<'_, Expression> {
+  let (input, _) = symbol("{")(input)?;
+  let (input, values) = cut(terminated(
+    nom::multi::separated_list0(
+      symbol(","),
+      pair(terminated(schema_name, symbol(":")), expression),
+    ),
+    symbol("}"),
+  ))(input)?;
+  Ok((input, Expression::Map(values)))
+}
+
+//--- Literals
+
+fn literal(input: &str) -> PResult<'_, Literal> {
+  alt((
+    value(Literal::Null, keyword("NULL")),
+    value(Literal::Boolean(true), keyword("TRUE")),
+    value(Literal::Boolean(false), keyword("FALSE")),
+    number,
+    map(string, Literal::String),
+  ))(input)
+}
+
+fn number(input: &str) -> PResult<'_, Literal> {
+  let (start, _) = sp(input)?;
+  let (remaining, text) = recognize(tuple((
+    alt((
+      recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+      recognize(pair(char('.'), digit1)),
+    )),
+    opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+  )))(start)?;
+
+  // A number can't run straight into a name (eg. 1abc)
+  if let Some(c) = remaining.chars().next() {
+    if c.is_alphabetic() || c == '_' {
+      return Err(NomErr::Error(NomError::new(start, NomErrorKind::Digit)));
+    }
+  }
+
+  match Literal::parse_number(text) {
+    Ok(value) => Ok((remaining, value)),
+    Err(_) => Err(NomErr::Failure(NomError::new(start, NomErrorKind::Digit))),
+  }
+}
+
+/// A single or double quoted string, with backslash escapes
+pub(crate) fn string(input: &str) -> PResult<'_, String> {
+  let (start, _) = sp(input)?;
+  let (mut remaining, quote) = one_of("'\"")(start)?;
+  let mut result = String::new();
+
+  loop {
+    let (rest, chunk) = take_while(|c| c != quote && c != '\\')(remaining)?;
+    result.push_str(chunk);
+    remaining = rest;
+
+    match anychar::<&str, NomError<&str>>(remaining) {
+      Err(_) => return Err(NomErr::Failure(NomError::new(start, NomErrorKind::Char))),
+      Ok((rest, c)) if c == quote => return Ok((rest, result)),
+      Ok((rest, _)) => {
+        // An escape sequence
+        let (rest, escaped) = match anychar::<&str, NomError<&str>>(rest) {
+          Ok(x) => x,
+          Err(_) => {
+            return Err(NomErr::Failure(NomError::new(
+              remaining,
+              NomErrorKind::Escaped,
+            )))
+          }
+        };
+        remaining = rest;
+        match escaped {
+          'n' => result.push('\n'),
+          'r' => result.push('\r'),
+          't' => result.push('\t'),
+          'b' => result.push('\u{8}'),
+          'f' => result.push('\u{c}'),
+          'u' | 'U' => {
+            let length = if escaped == 'u' { 4 } else { 8 };
+            let code = remaining
+              .get(..length)
+              .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+              .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+              .and_then(char::from_u32);
+            match code {
+              Some(c) => {
+                result.push(c);
+                remaining = &remaining[length..];
+              }
+              None => {
+                return Err(NomErr::Failure(NomError::new(
+                  remaining,
+                  NomErrorKind::Escaped,
+                )))
+              }
+            }
+          }
+          c => result.push(c),
+        }
+      }
+    }
+  }
+}
@@ -1,5 +1,635 @@
 //! The OpenCypher AST
 //!
-//!
+//! A nom based parser that turns OpenCypher query strings into a GQuery. The grammar follows
+//! docs/cypher.ebnf, covering the reading and updating clauses of a single query. UNION, CALL and
+//! list comprehensions are not supported yet.
 //!
 //! Spec: https://opencypher.org/resources/
+
+use crate::{
+  gquery::{ast::*, grammars::Printer},
+  local::*,
+  prelude::*,
+};
+
+use std::collections::BTreeMap;
+
+use nom::{
+  branch::alt,
+  bytes::complete::{tag, tag_no_case, take_while},
+  character::complete::{char, satisfy},
+  combinator::{cut, eof, map, opt, recognize, value},
+  error::{Error as NomError, ErrorKind as NomErrorKind},
+  multi::{many0, many1, separated_list1},
+  sequence::{pair, preceded, terminated, tuple},
+  Err as NomErr, IResult,
+};
+
+mod expression;
+pub(crate) use expression::{expression, map_literal};
+
+mod pattern;
+pub(crate) use pattern::{hops, pattern, pattern_part, properties};
+
+pub(crate) type PResult<'a, T> = IResult<&'a str, T>;
+
+/// Prints queries as OpenCypher. Everything in the AST can be written, so nothing is overridden
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cypher;
+
+impl Printer for Cypher {
+  fn name(&self) -> &'static str {
+    "OpenCypher"
+  }
+}
+
+/// Parse a full OpenCypher query
+pub fn parse(query: &str) -> GraphtResult<GQuery> {
+  let result = terminated(many0(clause), tuple((sp, opt(char(';')), sp, eof)))(query);
+
+  let clauses = match result {
+    Ok((_, clauses)) => clauses,
+    Err(NomErr::Error(err)) | Err(NomErr::Failure(err)) => {
+      return Err(parse_error(query, err.input))
+    }
+    Err(NomErr::Incomplete(_)) => {
+      return Err(err!(
+        ParsingError,
+        "Received an incomplete query: {:?}",
+        query
+      ))
+    }
+  };
+
+  validate(GQuery { clauses })
+}
+
+/// Parse the text of a map literal, such as the output of `GraphtEntity::to_gql`
+pub fn parse_map(value: &str) -> GraphtResult<BTreeMap<String, Literal>> {
+  if value.trim().is_empty() {
+    return Ok(BTreeMap::new());
+  }
+
+  let parsed = match terminated(map_literal, pair(sp, eof))(value) {
+    Ok((_, parsed)) => parsed,
+    Err(NomErr::Error(err)) | Err(NomErr::Failure(err)) => {
+      return Err(parse_error(value, err.input))
+    }
+    Err(NomErr::Incomplete(_)) => {
+      return Err(err!(
+        ParsingError,
+        "Received an incomplete map: {:?}",
+        value
+      ))
+    }
+  };
+
+  match parsed.as_constant() {
+    Some(Literal::Map(values)) => Ok(values),
+    _ => Err(err!(
+      ParsingError,
+      "Expected a map of constant values but found {}",
+      parsed
+    )),
+  }
+}
+
+/// Check the clauses are in an order that makes a runnable query
+pub(crate) fn validate(query: GQuery) -> GraphtResult<GQuery> {
+  let last = match query.clauses.last() {
+    Some(last) => last,
+    None => {
+      return Err(err!(
+        ParsingError,
+        "A query must contain at least one clause"
+      ))
+    }
+  };
+
+  if !(matches!(last, Clause::Return(_)) || last.is_updating()) {
+    return Err(err!(
+      ParsingError,
+      "A query must end with RETURN or an updating clause, not: {}",
+      last
+    ));
+  }
+
+  for clause in &query.clauses[..query.clauses.len() - 1] {
+    if let Clause::Return(_) = clause {
+      return Err(err!(
+        ParsingError,
+        "RETURN can only be used at the end of the query"
+      ));
+    }
+  }
+
+  for clause in &query.clauses {
+    match clause {
+      Clause::Create(pattern) => {
+        for part in &pattern.parts {
+          validate_created(part, "CREATE", true)?;
+        }
+      }
+      Clause::Merge(merge) => validate_created(&merge.pattern, "MERGE", false)?,
+      Clause::Match(matched) => {
+        for part in &matched.pattern.parts {
+          validate_shortest(part)?;
+        }
+      }
+      Clause::With(with) => {
+        for item in &with.projection.items {
+          if item.alias.is_none() && !matches!(item.expression, Expression::Variable(_)) {
+            return Err(err!(
+              ParsingError,
+              "Expressions in WITH must be given a name with AS: {}",
+              item.expression
+            ));
+          }
+        }
+      }
+      _ => (),
+    }
+  }
+
+  Ok(query)
+}
+
+/// The shortest path can only be found between two nodes, along a single variable length
+/// relationship starting with at most one hop
+fn validate_shortest(part: &PatternPart) -> GraphtResult<()> {
+  if part.shortest.is_none() {
+    return Ok(());
+  }
+  match part.chain.as_slice() {
+    [step] if matches!(step.relationship.length, Some(length) if length.min.unwrap_or(1) <= 1) => {
+      Ok(())
+    }
+    _ => Err(err!(
+      ParsingError,
+      "A shortest path must be a single variable length relationship with a minimum of 0 or 1 \
+       hops: {}",
+      part
+    )),
+  }
+}
+
+/// Edges that get created need to be fully defined
+fn validate_created(part: &PatternPart, clause: &str, directed: bool) -> GraphtResult<()> {
+  if part.shortest.is_some() {
+    return Err(err!(
+      ParsingError,
+      "Shortest paths can only be matched, not used in {}: {}",
+      clause,
+      part
+    ));
+  }
+  for step in &part.chain {
+    let relationship = &step.relationship;
+    if relationship.types.len() != 1 {
+      return Err(err!(
+        ParsingError,
+        "Each relationship in {} must have exactly one type: {}",
+        clause,
+        relationship
+      ));
+    }
+    if relationship.length.is_some() {
+      return Err(err!(
+        ParsingError,
+        "Variable length relationships cannot be used in {}: {}",
+        clause,
+        relationship
+      ));
+    }
+    if directed && relationship.direction == Direction::Either {
+      return Err(err!(
+        ParsingError,
+        "Each relationship in {} must have a direction: {}",
+        clause,
+        relationship
+      ));
+    }
+  }
+  Ok(())
+}
+
+/// Convert the remaining input at a failure into an error with a line and column
+///
+/// The context holds the span of the unexpected token as "line:column-line:column"
+pub(crate) fn parse_error(query: &str, remaining: &str) -> GraphtError {
+  let offset = query.len() - remaining.len();
+  let (line, column) = line_column(query, offset);
+
+  let token = remaining
+    .find(char::is_whitespace)
+    .unwrap_or(remaining.len())
+    .max(remaining.chars().next().map_or(0, |c| c.len_utf8()));
+  let (end_line, end_column) = line_column(query, offset + token);
+
+  let snippet: String = remaining.chars().take(20).collect();
+  err!(
+    ParsingError,
+    "Invalid query at line {}, column {}: unexpected {:?}",
+    line,
+    column,
+    snippet
+  )
+  .context(format!("{}:{}-{}:{}", line, column, end_line, end_column))
+}
+
+/// Find the one based line and column of a byte offset into the text
+pub(crate) fn line_column(text: &str, offset: usize) -> (usize, usize) {
+  let before = &text[..offset];
+  let line = before.matches('\n').count() + 1;
+  let column = match before.rfind('\n') {
+    Some(newline) => before[newline + 1..].chars().count() + 1,
+    None => before.chars().count() + 1,
+  };
+  (line, column)
+}
+
+//--- Clauses
+
+fn clause(input: &str) -> PResult<'_, Clause> {
+  alt((
+    match_clause,
+    unwind_clause,
+    with_clause,
+    return_clause,
+    create_clause,
+    merge_clause,
+    set_clause,
+    remove_clause,
+    delete_clause,
+  ))(input)
+}
+
+fn match_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, optional) = opt(keyword("OPTIONAL"))(input)?;
+  let (input, _) = match optional {
+    Some(_) => cut(keyword("MATCH"))(input)?,
+    None => keyword("MATCH")(input)?,
+  };
+  let (input, pattern) = cut(pattern)(input)?;
+  let (input, filter) = opt(preceded(keyword("WHERE"), cut(expression)))(input)?;
+  Ok((
+    input,
+    Clause::Match(Match {
+      optional: optional.is_some(),
+      pattern,
+      filter,
+    }),
+  ))
+}
+
+fn unwind_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("UNWIND")(input)?;
+  let (input, (expression, variable)) =
+    cut(pair(expression, preceded(keyword("AS"), symbolic_name)))(input)?;
+  Ok((
+    input,
+    Clause::Unwind(Unwind {
+      expression,
+      variable,
+    }),
+  ))
+}
+
+fn with_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("WITH")(input)?;
+  let (input, projection) = cut(projection)(input)?;
+  let (input, filter) = opt(preceded(keyword("WHERE"), cut(expression)))(input)?;
+  Ok((input, Clause::With(With { projection, filter })))
+}
+
+fn return_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("RETURN")(input)?;
+  let (input, projection) = cut(projection)(input)?;
+  Ok((input, Clause::Return(projection)))
+}
+
+fn create_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("CREATE")(input)?;
+  let (input, pattern) = cut(pattern)(input)?;
+  Ok((input, Clause::Create(pattern)))
+}
+
+fn merge_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("MERGE")(input)?;
+  let (mut input, pattern) = cut(pattern_part)(input)?;
+  let mut merge = Merge {
+    pattern,
+    on_create: Vec::new(),
+    on_match: Vec::new(),
+  };
+
+  loop {
+    match keyword("ON")(input) {
+      Ok((remaining, _)) => {
+        let (remaining, (on_create, items)) = cut(pair(
+          alt((
+            value(false, keyword("MATCH")),
+            value(true, keyword("CREATE")),
+          )),
+          preceded(keyword("SET"), set_items),
+        ))(remaining)?;
+        match on_create {
+          true => merge.on_create.extend(items),
+          false => merge.on_match.extend(items),
+        }
+        input = remaining;
+      }
+      Err(NomErr::Error(_)) => return Ok((input, Clause::Merge(merge))),
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+pub(crate) fn set_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("SET")(input)?;
+  let (input, items) = cut(set_items)(input)?;
+  Ok((input, Clause::Set(items)))
+}
+
+fn set_items(input: &str) -> PResult<'_, Vec<SetItem>> {
+  separated_list1(symbol(","), set_item)(input)
+}
+
+fn set_item(input: &str) -> PResult<'_, SetItem> {
+  let (input, name) = symbolic_name(input)?;
+  let (input, keys) = many0(preceded(symbol("."), cut(schema_name)))(input)?;
+
+  if !keys.is_empty() {
+    let property = property_lookup(name, keys);
+    let (input, value) = cut(preceded(symbol("="), expression))(input)?;
+    return Ok((input, SetItem::Property(property, value)));
+  }
+
+  cut(alt((
+    map(preceded(symbol("+="), expression), {
+      let name = name.clone();
+      move |value| SetItem::Update(name.clone(), value)
+    }),
+    map(preceded(symbol("="), expression), {
+      let name = name.clone();
+      move |value| SetItem::Replace(name.clone(), value)
+    }),
+    map(labels, {
+      let name = name.clone();
+      move |labels| SetItem::Labels(name.clone(), labels)
+    }),
+  )))(input)
+}
+
+pub(crate) fn remove_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("REMOVE")(input)?;
+  let (input, items) = cut(separated_list1(symbol(","), remove_item))(input)?;
+  Ok((input, Clause::Remove(items)))
+}
+
+fn remove_item(input: &str) -> PResult<'_, RemoveItem> {
+  let (input, name) = symbolic_name(input)?;
+  let (input, keys) = many0(preceded(symbol("."), cut(schema_name)))(input)?;
+
+  match keys.is_empty() {
+    false => Ok((input, RemoveItem::Property(property_lookup(name, keys)))),
+    true => {
+      let (input, labels) = cut(labels)(input)?;
+      Ok((input, RemoveItem::Labels(name, labels)))
+    }
+  }
+}
+
+pub(crate) fn delete_clause(input: &str) -> PResult<'_, Clause> {
+  let (input, detach) = opt(keyword("DETACH"))(input)?;
+  let (input, _) = match detach {
+    Some(_) => cut(keyword("DELETE"))(input)?,
+    None => keyword("DELETE")(input)?,
+  };
+  let (input, items) = cut(separated_list1(symbol(","), expression))(input)?;
+  Ok((
+    input,
+    Clause::Delete(Delete {
+      detach: detach.is_some(),
+      items,
+    }),
+  ))
+}
+
+/// A chain of property lookups starting at a variable (eg. "org.address.city")
+fn property_lookup(name: String, keys: Vec<String>) -> Expression {
+  keys
+    .into_iter()
+    .fold(Expression::Variable(name), |inner, key| {
+      Expression::Property(Box::new(inner), key)
+    })
+}
+
+/// One or more labels, such as ":Organization:Active"
+pub(crate) fn labels(input: &str) -> PResult<'_, Vec<String>> {
+  many1(preceded(symbol(":"), cut(schema_name)))(input)
+}
+
+fn projection(input: &str) -> PResult<'_, Projection> {
+  let (input, distinct) = opt(keyword("DISTINCT"))(input)?;
+  let (input, all) = opt(symbol("*"))(input)?;
+  let (input, items) = match all {
+    Some(_) => map(
+      opt(preceded(
+        symbol(","),
+        cut(separated_list1(symbol(","), projection_item)),
+      )),
+      |items| items.unwrap_or_default(),
+    )(input)?,
+    None => separated_list1(symbol(","), projection_item)(input)?,
+  };
+
+  let (input, order) = opt(preceded(
+    pair(keyword("ORDER"), cut(keyword("BY"))),
+    cut(separated_list1(symbol(","), sort_item)),
+  ))(input)?;
+  let (input, skip) = opt(preceded(keyword("SKIP"), cut(expression)))(input)?;
+  let (input, limit) = opt(preceded(keyword("LIMIT"), cut(expression)))(input)?;
+
+  Ok((
+    input,
+    Projection {
+      distinct: distinct.is_some(),
+      all: all.is_some(),
+      items,
+      order: order.unwrap_or_default(),
+      skip,
+      limit,
+    },
+  ))
+}
+
+pub(crate) fn projection_item(input: &str) -> PResult<'_, ProjectionItem> {
+  let (input, expression) = expression(input)?;
+  let (input, alias) = opt(preceded(keyword("AS"), cut(symbolic_name)))(input)?;
+  Ok((input, ProjectionItem { expression, alias }))
+}
+
+pub(crate) fn sort_item(input: &str) -> PResult<'_, SortItem> {
+  let (input, expression) = expression(input)?;
+  let (input, descending) = opt(alt((
+    value(true, alt((keyword("DESCENDING"), keyword("DESC")))),
+    value(false, alt((keyword("ASCENDING"), keyword("ASC")))),
+  )))(input)?;
+  Ok((
+    input,
+    SortItem {
+      expression,
+      descending: descending.unwrap_or(false),
+    },
+  ))
+}
+
+//--- Lexical helpers
+
+/// Words that cannot be used as variable names without escaping them in backticks
+pub(crate) const RESERVED: &[&str] = &[
+  "ALL",
+  "AND",
+  "AS",
+  "ASC",
+  "ASCENDING",
+  "BY",
+  "CASE",
+  "CONTAINS",
+  "CREATE",
+  "DELETE",
+  "DESC",
+  "DESCENDING",
+  "DETACH",
+  "DISTINCT",
+  "ELSE",
+  "END",
+  "ENDS",
+  "EXISTS",
+  "FALSE",
+  "IN",
+  "IS",
+  "LIMIT",
+  "MATCH",
+  "MERGE",
+  "NOT",
+  "NULL",
+  "ON",
+  "OPTIONAL",
+  "OR",
+  "ORDER",
+  "REMOVE",
+  "RETURN",
+  "SET",
+  "SKIP",
+  "STARTS",
+  "THEN",
+  "TRUE",
+  "UNION",
+  "UNWIND",
+  "WHEN",
+  "WHERE",
+  "WITH",
+  "XOR",
+];
+
+/// Skip any whitespace and comments
+pub(crate) fn sp(input: &str) -> PResult<'_, ()> {
+  let mut input = input;
+  loop {
+    let trimmed = input.trim_start();
+    if let Some(rest) = trimmed.strip_prefix("//") {
+      input = match rest.find('\n') {
+        Some(newline) => &rest[newline..],
+        None => "",
+      };
+    } else if let Some(rest) = trimmed.strip_prefix("/*") {
+      match rest.find("*/") {
+        Some(end) => input = &rest[end + 2..],
+        None => return Err(NomErr::Failure(NomError::new(trimmed, NomErrorKind::Eof))),
+      }
+    } else {
+      return Ok((trimmed, ()));
+    }
+  }
+}
+
+/// Strip out whitespace and then run the next expected function
+pub(crate) fn ws<'a, F, O>(inner: F) -> impl FnMut(&'a str) -> PResult<'a, O>
+where
+  F: FnMut(&'a str) -> PResult<'a, O>,
+{
+  preceded(sp, inner)
+}
+
+/// Match a punctuation token
+pub(crate) fn symbol<'a>(value: &'static str) -> impl FnMut(&'a str) -> PResult<'a, &'a str> {
+  ws(tag(value))
+}
+
+/// Match a case insensitive keyword that isn't just the start of a longer word
+pub(crate) fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> PResult<'a, &'a str> {
+  move |input: &'a str| {
+    let (start, _) = sp(input)?;
+    let (remaining, matched) = tag_no_case(word)(start)?;
+    match remaining.chars().next() {
+      Some(c) if is_word_char(c) => Err(NomErr::Error(NomError::new(start, NomErrorKind::Tag))),
+      _ => Ok((remaining, matched)),
+    }
+  }
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+/// An identifier that may be a keyword, such as a label or a property key
+pub(crate) fn schema_name(input: &str) -> PResult<'_, String> {
+  ws(alt((
+    escaped_name,
+    map(
+      recognize(pair(
+        satisfy(|c: char| c.is_alphabetic() || c == '_'),
+        take_while(is_word_char),
+      )),
+      |name: &str| name.to_string(),
+    ),
+  )))(input)
+}
+
+/// An identifier that can be used for a variable or alias
+pub(crate) fn symbolic_name(input: &str) -> PResult<'_, String> {
+  let (start, _) = sp(input)?;
+  if start.starts_with('`') {
+    return escaped_name(start);
+  }
+
+  let (remaining, name) = schema_name(start)?;
+  match RESERVED.contains(&name.to_uppercase().as_str()) {
+    true => Err(NomErr::Error(NomError::new(start, NomErrorKind::Verify))),
+    false => Ok((remaining, name)),
+  }
+}
+
+/// A name wrapped in backticks, where a double backtick is an escaped backtick
+fn escaped_name(input: &str) -> PResult<'_, String> {
+  let (mut remaining, _) = char('`')(input)?;
+  let mut name = String::new();
+  loop {
+    match remaining.find('`') {
+      None => return Err(NomErr::Failure(NomError::new(input, NomErrorKind::Char))),
+      Some(end) => {
+        name.push_str(&remaining[..end]);
+        remaining = &remaining[end + 1..];
+        match remaining.strip_prefix('`') {
+          Some(rest) => {
+            name.push('`');
+            remaining = rest;
+          }
+          None => return Ok((remaining, name)),
+        }
+      }
+    }
+  }
+}
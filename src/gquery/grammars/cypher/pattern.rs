@@ -0,0 +1,162 @@
+//! Node and relationship patterns
+
+use super::*;
+
+pub(crate) fn pattern(input: &str) -> PResult<'_, Pattern> {
+  map(separated_list1(symbol(","), pattern_part), |parts| {
+    Pattern { parts }
+  })(input)
+}
+
+pub(crate) fn pattern_part(input: &str) -> PResult<'_, PatternPart> {
+  let (input, variable) = opt(terminated(symbolic_name, symbol("=")))(input)?;
+  let (input, mut part) = match variable {
+    Some(_) => cut(alt((shortest_pattern_part, anonymous_pattern_part)))(input)?,
+    None => alt((shortest_pattern_part, anonymous_pattern_part))(input)?,
+  };
+  part.variable = variable;
+  Ok((input, part))
+}
+
+/// A chain wrapped in shortestPath(...) or allShortestPaths(...)
+fn shortest_pattern_part(input: &str) -> PResult<'_, PatternPart> {
+  let (input, shortest) = terminated(
+    alt((
+      value(Shortest::Any, keyword("shortestPath")),
+      value(Shortest::All, keyword("allShortestPaths")),
+    )),
+    symbol("("),
+  )(input)?;
+  let (input, mut part) = cut(terminated(anonymous_pattern_part, symbol(")")))(input)?;
+  part.shortest = Some(shortest);
+  Ok((input, part))
+}
+
+fn anonymous_pattern_part(input: &str) -> PResult<'_, PatternPart> {
+  let (input, start) = node_pattern(input)?;
+  let (input, chain) = many0(pattern_step)(input)?;
+  Ok((
+    input,
+    PatternPart {
+      variable: None,
+      start,
+      chain,
+      shortest: None,
+    },
+  ))
+}
+
+/// A pattern used as a boolean expression, which needs at least one relationship so it isn't
+/// mistaken for a parenthesized expression
+pub(crate) fn pattern_predicate(input: &str) -> PResult<'_, Expression> {
+  let (remaining, part) = anonymous_pattern_part(input)?;
+  match part.chain.is_empty() {
+    true => Err(NomErr::Error(NomError::new(input, NomErrorKind::Verify))),
+    false => Ok((remaining, Expression::PatternPredicate(Box::new(part)))),
+  }
+}
+
+fn pattern_step(input: &str) -> PResult<'_, PatternStep> {
+  let (input, relationship) = relationship_pattern(input)?;
+  let (input, node) = cut(node_pattern)(input)?;
+  Ok((input, PatternStep { relationship, node }))
+}
+
+pub(crate) fn node_pattern(input: &str) -> PResult<'_, NodePattern> {
+  let (input, _) = symbol("(")(input)?;
+  let (input, variable) = opt(symbolic_name)(input)?;
+  let (input, labels) = opt(labels)(input)?;
+  let (input, properties) = opt(properties)(input)?;
+  let (input, _) = symbol(")")(input)?;
+
+  Ok((
+    input,
+    NodePattern {
+      variable,
+      labels: labels.unwrap_or_default(),
+      properties: properties.unwrap_or_default(),
+    },
+  ))
+}
+
+/// The property map for a node or relationship pattern
+pub(crate) fn properties(input: &str) -> PResult<'_, Vec<(String, Expression)>> {
+  match map_literal(input)? {
+    (input, Expression::Map(values)) => Ok((input, values)),
+    _ => Err(NomErr::Failure(NomError::new(input, NomErrorKind::MapRes))),
+  }
+}
+
+fn relationship_pattern(input: &str) -> PResult<'_, RelationshipPattern> {
+  let (input, left) = opt(symbol("<"))(input)?;
+  let (input, _) = symbol("-")(input)?;
+  let (input, detail) = opt(relationship_detail)(input)?;
+  // Not cut, since a parenthesized expression followed by a minus looks like the start of a
+  // relationship
+  let (input, _) = symbol("-")(input)?;
+  let (input, right) = opt(symbol(">"))(input)?;
+
+  let direction = match (left.is_some(), right.is_some()) {
+    (false, true) => Direction::Outgoing,
+    (true, false) => Direction::Incoming,
+    _ => Direction::Either,
+  };
+
+  let mut relationship = detail.unwrap_or(RelationshipPattern {
+    variable: None,
+    direction,
+    types: Vec::new(),
+    properties: Vec::new(),
+    length: None,
+  });
+  relationship.direction = direction;
+  Ok((input, relationship))
+}
+
+/// The part of the relationship inside the square brackets
+fn relationship_detail(input: &str) -> PResult<'_, RelationshipPattern> {
+  let (input, _) = symbol("[")(input)?;
+  let (input, variable) = opt(symbolic_name)(input)?;
+  let (input, types) = opt(preceded(
+    symbol(":"),
+    cut(separated_list1(
+      symbol("|"),
+      preceded(opt(symbol(":")), schema_name),
+    )),
+  ))(input)?;
+  let (input, length) = opt(preceded(symbol("*"), cut(var_length)))(input)?;
+  let (input, properties) = opt(properties)(input)?;
+  let (input, _) = cut(symbol("]"))(input)?;
+
+  Ok((
+    input,
+    RelationshipPattern {
+      variable,
+      direction: Direction::Either,
+      types: types.unwrap_or_default(),
+      properties: properties.unwrap_or_default(),
+      length,
+    },
+  ))
+}
+
+/// The hop range following the "*" (eg. "*", "*2", "*1..5", "*..3")
+fn var_length(input: &str) -> PResult<'_, VarLength> {
+  let (input, min) = opt(hops)(input)?;
+  let (input, range) = opt(preceded(symbol(".."), opt(hops)))(input)?;
+
+  let length = match range {
+    Some(max) => VarLength { min, max },
+    None => VarLength { min, max: min },
+  };
+  Ok((input, length))
+}
+
+pub(crate) fn hops(input: &str) -> PResult<'_, u64> {
+  let (start, _) = sp(input)?;
+  let (remaining, digits) = nom::character::complete::digit1(start)?;
+  match digits.parse() {
+    Ok(value) => Ok((remaining, value)),
+    Err(_) => Err(NomErr::Failure(NomError::new(start, NomErrorKind::Digit))),
+  }
+}
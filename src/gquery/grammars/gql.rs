@@ -0,0 +1,363 @@
+//! The GQL draft
+//!
+//! Queries follow the graph pattern subset of the outline in docs/gql.ebnf, mapped onto the same
+//! AST as OpenCypher. It is close to OpenCypher, but creates with INSERT, joins multiple labels
+//! with "&" (or "IS A&B"), skips rows with OFFSET, and puts variable lengths after the edge as a
+//! quantifier (eg. "-[:ParentOf]->{1,3}"). Shortest paths are matched with an "ANY SHORTEST" or
+//! "ALL SHORTEST" prefix. The draft has no MERGE or UNWIND.
+//!
+//! Expressions are shared with the OpenCypher parser, so pattern predicates inside a WHERE use the
+//! OpenCypher form. Declarations, procedures, and catalog statements are not supported.
+//!
+//! Spec: https://s3.amazonaws.com/artifacts.opencypher.org/website/materials/sql-pg-2018-0046r3-GQL-Scope-and-Features.pdf
+
+use crate::{
+  gquery::{
+    ast::*,
+    grammars::{cypher::*, printer::*},
+  },
+  prelude::*,
+};
+
+use nom::{
+  branch::alt,
+  character::complete::char,
+  combinator::{cut, eof, map, opt, value},
+  multi::{many0, separated_list1},
+  sequence::{pair, preceded, separated_pair, terminated, tuple},
+  Err as NomErr,
+};
+
+/// Parse a GQL query
+pub fn parse(query: &str) -> GraphtResult<GQuery> {
+  let result = terminated(many0(statement), tuple((sp, opt(char(';')), sp, eof)))(query);
+
+  let clauses = match result {
+    Ok((_, clauses)) => clauses,
+    Err(NomErr::Error(err)) | Err(NomErr::Failure(err)) => {
+      return Err(parse_error(query, err.input))
+    }
+    Err(NomErr::Incomplete(_)) => {
+      return Err(err!(
+        ParsingError,
+        "Received an incomplete query: {:?}",
+        query
+      ))
+    }
+  };
+
+  validate(GQuery { clauses })
+}
+
+//--- Statements
+
+fn statement(input: &str) -> PResult<'_, Clause> {
+  alt((
+    match_statement,
+    with_statement,
+    return_statement,
+    insert_statement,
+    set_clause,
+    remove_clause,
+    delete_clause,
+  ))(input)
+}
+
+fn match_statement(input: &str) -> PResult<'_, Clause> {
+  let (input, optional) = opt(keyword("OPTIONAL"))(input)?;
+  let (input, _) = match optional {
+    Some(_) => cut(keyword("MATCH"))(input)?,
+    None => keyword("MATCH")(input)?,
+  };
+  let (input, pattern) = cut(graph_pattern)(input)?;
+  let (input, filter) = opt(preceded(keyword("WHERE"), cut(expression)))(input)?;
+  Ok((
+    input,
+    Clause::Match(Match {
+      optional: optional.is_some(),
+      pattern,
+      filter,
+    }),
+  ))
+}
+
+fn with_statement(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("WITH")(input)?;
+  let (input, projection) = cut(projection)(input)?;
+  let (input, filter) = opt(preceded(keyword("WHERE"), cut(expression)))(input)?;
+  Ok((input, Clause::With(With { projection, filter })))
+}
+
+fn return_statement(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("RETURN")(input)?;
+  let (input, projection) = cut(projection)(input)?;
+  Ok((input, Clause::Return(projection)))
+}
+
+fn insert_statement(input: &str) -> PResult<'_, Clause> {
+  let (input, _) = keyword("INSERT")(input)?;
+  let (input, pattern) = cut(graph_pattern)(input)?;
+  Ok((input, Clause::Create(pattern)))
+}
+
+/// The projection arguments, where OFFSET is another name for SKIP
+fn projection(input: &str) -> PResult<'_, Projection> {
+  let (input, distinct) = opt(keyword("DISTINCT"))(input)?;
+  let (input, all) = opt(symbol("*"))(input)?;
+  let (input, items) = match all {
+    Some(_) => map(
+      opt(preceded(
+        symbol(","),
+        cut(separated_list1(symbol(","), projection_item)),
+      )),
+      |items| items.unwrap_or_default(),
+    )(input)?,
+    None => separated_list1(symbol(","), projection_item)(input)?,
+  };
+
+  let (input, order) = opt(preceded(
+    pair(keyword("ORDER"), cut(keyword("BY"))),
+    cut(separated_list1(symbol(","), sort_item)),
+  ))(input)?;
+  let (input, skip) = opt(preceded(
+    alt((keyword("OFFSET"), keyword("SKIP"))),
+    cut(expression),
+  ))(input)?;
+  let (input, limit) = opt(preceded(keyword("LIMIT"), cut(expression)))(input)?;
+
+  Ok((
+    input,
+    Projection {
+      distinct: distinct.is_some(),
+      all: all.is_some(),
+      items,
+      order: order.unwrap_or_default(),
+      skip,
+      limit,
+    },
+  ))
+}
+
+//--- Patterns
+
+fn graph_pattern(input: &str) -> PResult<'_, Pattern> {
+  map(separated_list1(symbol(","), path_pattern), |parts| {
+    Pattern { parts }
+  })(input)
+}
+
+fn path_pattern(input: &str) -> PResult<'_, PatternPart> {
+  let (input, variable) = opt(terminated(symbolic_name, symbol("=")))(input)?;
+  let (input, shortest) = opt(path_search_prefix)(input)?;
+  let (input, start) = match variable.is_some() || shortest.is_some() {
+    true => cut(node_pattern)(input)?,
+    false => node_pattern(input)?,
+  };
+  let (input, chain) = many0(path_step)(input)?;
+  Ok((
+    input,
+    PatternPart {
+      variable,
+      start,
+      chain,
+      shortest,
+    },
+  ))
+}
+
+/// Only keeping the shortest paths (eg. "ANY SHORTEST (a)-[]->{1,}(b)")
+fn path_search_prefix(input: &str) -> PResult<'_, Shortest> {
+  terminated(
+    alt((
+      value(Shortest::Any, keyword("ANY")),
+      value(Shortest::All, keyword("ALL")),
+    )),
+    keyword("SHORTEST"),
+  )(input)
+}
+
+fn path_step(input: &str) -> PResult<'_, PatternStep> {
+  let (input, relationship) = edge_pattern(input)?;
+  let (input, node) = cut(node_pattern)(input)?;
+  Ok((input, PatternStep { relationship, node }))
+}
+
+fn node_pattern(input: &str) -> PResult<'_, NodePattern> {
+  let (input, _) = symbol("(")(input)?;
+  let (input, variable) = opt(symbolic_name)(input)?;
+  let (input, labels) = opt(label_expression)(input)?;
+  let (input, properties) = opt(properties)(input)?;
+  let (input, _) = cut(symbol(")"))(input)?;
+
+  Ok((
+    input,
+    NodePattern {
+      variable,
+      labels: labels.unwrap_or_default(),
+      properties: properties.unwrap_or_default(),
+    },
+  ))
+}
+
+/// Labels that must all be present, such as ":Organization&Active" or "IS Organization"
+fn label_expression(input: &str) -> PResult<'_, Vec<String>> {
+  preceded(
+    alt((symbol(":"), keyword("IS"))),
+    cut(separated_list1(symbol("&"), schema_name)),
+  )(input)
+}
+
+fn edge_pattern(input: &str) -> PResult<'_, RelationshipPattern> {
+  let (input, left) = opt(symbol("<"))(input)?;
+  let (input, _) = symbol("-")(input)?;
+  let (input, detail) = opt(edge_detail)(input)?;
+  let (input, _) = cut(symbol("-"))(input)?;
+  let (input, right) = opt(symbol(">"))(input)?;
+  let (input, length) = opt(quantifier)(input)?;
+
+  let direction = match (left.is_some(), right.is_some()) {
+    (false, true) => Direction::Outgoing,
+    (true, false) => Direction::Incoming,
+    _ => Direction::Either,
+  };
+
+  let (variable, types, properties) = detail.unwrap_or_default();
+  Ok((
+    input,
+    RelationshipPattern {
+      variable,
+      direction,
+      types,
+      properties,
+      length,
+    },
+  ))
+}
+
+type EdgeDetail = (Option<String>, Vec<String>, Vec<(String, Expression)>);
+
+/// The variable, types, and properties inside the square brackets
+fn edge_detail(input: &str) -> PResult<'_, EdgeDetail> {
+  let (input, _) = symbol("[")(input)?;
+  let (input, variable) = opt(symbolic_name)(input)?;
+  let (input, types) = opt(preceded(
+    alt((symbol(":"), keyword("IS"))),
+    cut(separated_list1(symbol("|"), schema_name)),
+  ))(input)?;
+  let (input, properties) = opt(properties)(input)?;
+  let (input, _) = cut(symbol("]"))(input)?;
+  Ok((
+    input,
+    (
+      variable,
+      types.unwrap_or_default(),
+      properties.unwrap_or_default(),
+    ),
+  ))
+}
+
+/// How many times an edge repeats: "{2}", "{1,3}", "{2,}", "{,3}", "*" (any) or "+" (at least one)
+fn quantifier(input: &str) -> PResult<'_, VarLength> {
+  alt((
+    value(
+      VarLength {
+        min: Some(0),
+        max: None,
+      },
+      symbol("*"),
+    ),
+    value(
+      VarLength {
+        min: Some(1),
+        max: None,
+      },
+      symbol("+"),
+    ),
+    preceded(
+      symbol("{"),
+      cut(terminated(
+        alt((
+          map(
+            separated_pair(opt(hops), symbol(","), opt(hops)),
+            |(min, max)| VarLength {
+              min: Some(min.unwrap_or(0)),
+              max,
+            },
+          ),
+          map(hops, |hops| VarLength {
+            min: Some(hops),
+            max: Some(hops),
+          }),
+        )),
+        symbol("}"),
+      )),
+    ),
+  ))(input)
+}
+
+//--- Printing
+
+/// Prints queries as GQL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gql;
+
+impl Printer for Gql {
+  fn name(&self) -> &'static str {
+    "GQL"
+  }
+
+  fn unwind(&self, _unwind: &Unwind) -> GraphtResult<String> {
+    Err(self.unsupported("UNWIND"))
+  }
+
+  fn create(&self, pattern: &Pattern) -> GraphtResult<String> {
+    Ok(format!("INSERT {}", self.pattern(pattern)?))
+  }
+
+  fn merge(&self, _merge: &Merge) -> GraphtResult<String> {
+    Err(self.unsupported("MERGE"))
+  }
+
+  fn relationship(&self, relationship: &RelationshipPattern) -> GraphtResult<String> {
+    let mut detail = self.relationship_detail(relationship)?;
+    if !relationship.properties.is_empty() {
+      if !detail.is_empty() {
+        detail.push(' ');
+      }
+      detail.push_str(&self.map(&relationship.properties)?);
+    }
+
+    let mut text = arrow(relationship.direction, &detail);
+    if let Some(length) = &relationship.length {
+      text.push_str(&format_quantifier(length));
+    }
+    Ok(text)
+  }
+
+  fn shortest(&self, shortest: Shortest, chain: &str) -> GraphtResult<String> {
+    match shortest {
+      Shortest::Any => Ok(format!("ANY SHORTEST {}", chain)),
+      Shortest::All => Ok(format!("ALL SHORTEST {}", chain)),
+    }
+  }
+
+  fn labels(&self, labels: &[String]) -> GraphtResult<String> {
+    match labels.is_empty() {
+      true => Ok(String::new()),
+      false => {
+        let labels: Vec<String> = labels.iter().map(|x| escape_name(x)).collect();
+        Ok(format!(":{}", labels.join("&")))
+      }
+    }
+  }
+}
+
+/// A GQL quantifier for a variable length. A missing minimum is one hop, as in OpenCypher
+fn format_quantifier(length: &VarLength) -> String {
+  let min = length.min.unwrap_or(1);
+  match length.max {
+    Some(max) if max == min => format!("{{{}}}", min),
+    Some(max) => format!("{{{},{}}}", min, max),
+    None => format!("{{{},}}", min),
+  }
+}
@@ -4,8 +4,15 @@
 //! I'm going to pull
 //! GQL: https://s3.amazonaws.com/artifacts.opencypher.org/website/materials/sql-pg-2018-0046r3-GQL-Scope-and-Features.pdf
 
-// The official GQL language implementation
-// pub mod gql;
+// Rendering a GQuery as text for a given dialect
+pub mod printer;
+pub use printer::{Dialect, Printer};
+
+// The official GQL language implementation. Only printing is supported until the spec settles
+pub mod gql;
 
 // An implementation of OpenCypher
 pub mod cypher;
+
+// The subset of OpenCypher understood by RedisGraph
+pub mod redis_graph;
@@ -0,0 +1,426 @@
+//! Render a GQuery as text in the dialect of a particular backend, or parse it back
+//!
+//! The default methods of [Printer] write OpenCypher, which is what the AST's `Display` produces.
+//! Each dialect overrides the parts it writes differently and returns a `GrammarError` for the
+//! constructs it can't express.
+
+use crate::{
+  gquery::ast::*,
+  gquery::grammars::{
+    cypher::{self, Cypher},
+    gql::{self, Gql},
+    redis_graph::RedisGraph,
+  },
+  local::*,
+  prelude::*,
+};
+
+/// The query languages that a GQuery can be printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+  Cypher,
+  Gql,
+  RedisGraph,
+}
+
+impl Dialect {
+  /// Parse query text written in the dialect. RedisGraph reads OpenCypher
+  pub fn parse(&self, query: &str) -> GraphtResult<GQuery> {
+    match self {
+      Dialect::Cypher | Dialect::RedisGraph => cypher::parse(query),
+      Dialect::Gql => gql::parse(query),
+    }
+  }
+
+  pub fn print(&self, query: &GQuery) -> GraphtResult<String> {
+    match self {
+      Dialect::Cypher => Cypher.query(query),
+      Dialect::Gql => Gql.query(query),
+      Dialect::RedisGraph => RedisGraph.query(query),
+    }
+  }
+
+  /// Print a query along with the values for its parameters
+  pub fn print_with(&self, query: &GQuery, params: &Params) -> GraphtResult<String> {
+    match self {
+      Dialect::Cypher => Cypher.query_with(query, params),
+      Dialect::Gql => Gql.query_with(query, params),
+      Dialect::RedisGraph => RedisGraph.query_with(query, params),
+    }
+  }
+}
+
+/// Writes each part of the AST as text
+pub trait Printer {
+  /// The name of the dialect, used in error messages
+  fn name(&self) -> &'static str;
+
+  /// The error for a construct the dialect can't express
+  fn unsupported(&self, construct: &str) -> GraphtError {
+    err!(
+      GrammarError,
+      "{} does not support {}",
+      self.name(),
+      construct
+    )
+  }
+
+  fn query(&self, query: &GQuery) -> GraphtResult<String> {
+    let clauses = query
+      .clauses
+      .iter()
+      .map(|x| self.clause(x))
+      .collect::<GraphtResult<Vec<String>>>()?;
+    Ok(clauses.join(" "))
+  }
+
+  /// Print a query with values for its parameters. Parameters are replaced with their values by
+  /// default, which the literal printing escapes, since the text is all a dialect can send.
+  fn query_with(&self, query: &GQuery, params: &Params) -> GraphtResult<String> {
+    self.query(&query.bind(params)?)
+  }
+
+  fn clause(&self, clause: &Clause) -> GraphtResult<String> {
+    match clause {
+      Clause::Match(matched) => self.match_clause(matched),
+      Clause::Unwind(unwind) => self.unwind(unwind),
+      Clause::With(with) => {
+        let mut text = format!("WITH {}", self.projection(&with.projection)?);
+        if let Some(filter) = &with.filter {
+          text.push_str(&format!(" WHERE {}", self.expression(filter)?));
+        }
+        Ok(text)
+      }
+      Clause::Return(projection) => Ok(format!("RETURN {}", self.projection(projection)?)),
+      Clause::Create(pattern) => self.create(pattern),
+      Clause::Merge(merge) => self.merge(merge),
+      Clause::Set(items) => Ok(format!("SET {}", self.set_items(items)?)),
+      Clause::Remove(items) => {
+        let items = items
+          .iter()
+          .map(|x| self.remove_item(x))
+          .collect::<GraphtResult<Vec<String>>>()?;
+        Ok(format!("REMOVE {}", items.join(", ")))
+      }
+      Clause::Delete(delete) => {
+        let detach = match delete.detach {
+          true => "DETACH ",
+          false => "",
+        };
+        Ok(format!(
+          "{}DELETE {}",
+          detach,
+          self.expressions(&delete.items)?
+        ))
+      }
+    }
+  }
+
+  fn match_clause(&self, matched: &Match) -> GraphtResult<String> {
+    let optional = match matched.optional {
+      true => "OPTIONAL ",
+      false => "",
+    };
+    let mut text = format!("{}MATCH {}", optional, self.pattern(&matched.pattern)?);
+    if let Some(filter) = &matched.filter {
+      text.push_str(&format!(" WHERE {}", self.expression(filter)?));
+    }
+    Ok(text)
+  }
+
+  fn unwind(&self, unwind: &Unwind) -> GraphtResult<String> {
+    Ok(format!(
+      "UNWIND {} AS {}",
+      self.expression(&unwind.expression)?,
+      escape_name(&unwind.variable)
+    ))
+  }
+
+  fn create(&self, pattern: &Pattern) -> GraphtResult<String> {
+    Ok(format!("CREATE {}", self.pattern(pattern)?))
+  }
+
+  fn merge(&self, merge: &Merge) -> GraphtResult<String> {
+    let mut text = format!("MERGE {}", self.pattern_part(&merge.pattern)?);
+    if !merge.on_match.is_empty() {
+      text.push_str(&format!(
+        " ON MATCH SET {}",
+        self.set_items(&merge.on_match)?
+      ));
+    }
+    if !merge.on_create.is_empty() {
+      text.push_str(&format!(
+        " ON CREATE SET {}",
+        self.set_items(&merge.on_create)?
+      ));
+    }
+    Ok(text)
+  }
+
+  fn projection(&self, projection: &Projection) -> GraphtResult<String> {
+    let mut text = String::new();
+    if projection.distinct {
+      text.push_str("DISTINCT ");
+    }
+
+    let mut items = Vec::new();
+    if projection.all {
+      items.push(String::from("*"));
+    }
+    for item in &projection.items {
+      let expression = self.expression(&item.expression)?;
+      items.push(match &item.alias {
+        Some(alias) => format!("{} AS {}", expression, escape_name(alias)),
+        None => expression,
+      });
+    }
+    text.push_str(&items.join(", "));
+
+    if !projection.order.is_empty() {
+      let mut order = Vec::new();
+      for item in &projection.order {
+        let expression = self.expression(&item.expression)?;
+        order.push(match item.descending {
+          true => format!("{} DESC", expression),
+          false => expression,
+        });
+      }
+      text.push_str(&format!(" ORDER BY {}", order.join(", ")));
+    }
+    if let Some(skip) = &projection.skip {
+      text.push_str(&format!(" SKIP {}", self.expression(skip)?));
+    }
+    if let Some(limit) = &projection.limit {
+      text.push_str(&format!(" LIMIT {}", self.expression(limit)?));
+    }
+    Ok(text)
+  }
+
+  fn set_items(&self, items: &[SetItem]) -> GraphtResult<String> {
+    let items = items
+      .iter()
+      .map(|x| self.set_item(x))
+      .collect::<GraphtResult<Vec<String>>>()?;
+    Ok(items.join(", "))
+  }
+
+  fn set_item(&self, item: &SetItem) -> GraphtResult<String> {
+    Ok(match item {
+      SetItem::Property(property, value) => {
+        format!(
+          "{} = {}",
+          self.expression(property)?,
+          self.expression(value)?
+        )
+      }
+      SetItem::Replace(name, value) => {
+        format!("{} = {}", escape_name(name), self.expression(value)?)
+      }
+      SetItem::Update(name, value) => {
+        format!("{} += {}", escape_name(name), self.expression(value)?)
+      }
+      SetItem::Labels(name, labels) => format!("{}{}", escape_name(name), self.labels(labels)?),
+    })
+  }
+
+  fn remove_item(&self, item: &RemoveItem) -> GraphtResult<String> {
+    match item {
+      RemoveItem::Property(property) => self.expression(property),
+      RemoveItem::Labels(name, labels) => {
+        Ok(format!("{}{}", escape_name(name), self.labels(labels)?))
+      }
+    }
+  }
+
+  //--- Patterns
+
+  fn pattern(&self, pattern: &Pattern) -> GraphtResult<String> {
+    let parts = pattern
+      .parts
+      .iter()
+      .map(|x| self.pattern_part(x))
+      .collect::<GraphtResult<Vec<String>>>()?;
+    Ok(parts.join(", "))
+  }
+
+  fn pattern_part(&self, part: &PatternPart) -> GraphtResult<String> {
+    let mut text = String::new();
+    if let Some(name) = &part.variable {
+      text.push_str(&format!("{} = ", escape_name(name)));
+    }
+    let mut chain = self.node(&part.start)?;
+    for step in &part.chain {
+      chain.push_str(&self.relationship(&step.relationship)?);
+      chain.push_str(&self.node(&step.node)?);
+    }
+    match part.shortest {
+      Some(shortest) => text.push_str(&self.shortest(shortest, &chain)?),
+      None => text.push_str(&chain),
+    }
+    Ok(text)
+  }
+
+  /// Wrap a printed chain so only its shortest matches are kept
+  fn shortest(&self, shortest: Shortest, chain: &str) -> GraphtResult<String> {
+    match shortest {
+      Shortest::Any => Ok(format!("shortestPath({})", chain)),
+      Shortest::All => Ok(format!("allShortestPaths({})", chain)),
+    }
+  }
+
+  fn node(&self, node: &NodePattern) -> GraphtResult<String> {
+    let mut text = String::from("(");
+    if let Some(name) = &node.variable {
+      text.push_str(&escape_name(name));
+    }
+    text.push_str(&self.labels(&node.labels)?);
+    if !node.properties.is_empty() {
+      if node.variable.is_some() || !node.labels.is_empty() {
+        text.push(' ');
+      }
+      text.push_str(&self.map(&node.properties)?);
+    }
+    text.push(')');
+    Ok(text)
+  }
+
+  /// The details inside the brackets of a relationship, without the variable length
+  fn relationship_detail(&self, relationship: &RelationshipPattern) -> GraphtResult<String> {
+    let mut detail = String::new();
+    if let Some(name) = &relationship.variable {
+      detail.push_str(&escape_name(name));
+    }
+    let types: Vec<String> = relationship.types.iter().map(|x| escape_name(x)).collect();
+    if !types.is_empty() {
+      detail.push_str(&format!(":{}", types.join("|")));
+    }
+    Ok(detail)
+  }
+
+  fn relationship(&self, relationship: &RelationshipPattern) -> GraphtResult<String> {
+    let mut detail = self.relationship_detail(relationship)?;
+    if let Some(length) = &relationship.length {
+      detail.push_str(&length.to_string());
+    }
+    if !relationship.properties.is_empty() {
+      if !detail.is_empty() {
+        detail.push(' ');
+      }
+      detail.push_str(&self.map(&relationship.properties)?);
+    }
+    Ok(arrow(relationship.direction, &detail))
+  }
+
+  /// Labels that a node must have all of (eg. ":A:B")
+  fn labels(&self, labels: &[String]) -> GraphtResult<String> {
+    Ok(format_labels(labels))
+  }
+
+  //--- Expressions
+
+  fn expressions(&self, expressions: &[Expression]) -> GraphtResult<String> {
+    let expressions = expressions
+      .iter()
+      .map(|x| self.expression(x))
+      .collect::<GraphtResult<Vec<String>>>()?;
+    Ok(expressions.join(", "))
+  }
+
+  fn map(&self, values: &[(String, Expression)]) -> GraphtResult<String> {
+    let mut entries = Vec::new();
+    for (key, value) in values {
+      entries.push(format!("{}: {}", escape_name(key), self.expression(value)?));
+    }
+    Ok(format!("{{{}}}", entries.join(", ")))
+  }
+
+  /// Print a child expression, wrapping it in parentheses if it binds looser than its parent
+  fn wrap(&self, expression: &Expression, parent: u8) -> GraphtResult<String> {
+    let text = self.expression(expression)?;
+    Ok(match expression.precedence() < parent {
+      true => format!("({})", text),
+      false => text,
+    })
+  }
+
+  fn expression(&self, expression: &Expression) -> GraphtResult<String> {
+    Ok(match expression {
+      Expression::Literal(value) => value.to_string(),
+      Expression::Parameter(name) => format!("${}", escape_name(name)),
+      Expression::Variable(name) => escape_name(name),
+      Expression::Property(inner, name) => {
+        format!("{}.{}", self.wrap(inner, 12)?, escape_name(name))
+      }
+      Expression::List(values) => format!("[{}]", self.expressions(values)?),
+      Expression::Map(values) => self.map(values)?,
+      Expression::Unary(op, inner) => match op {
+        UnaryOperator::Not => format!("NOT {}", self.wrap(inner, 4)?),
+        UnaryOperator::Negate => format!("-{}", self.wrap(inner, 10)?),
+        UnaryOperator::Plus => format!("+{}", self.wrap(inner, 10)?),
+      },
+      Expression::Binary(op, lhs, rhs) => {
+        // All binary operators are left associative
+        let precedence = op.precedence();
+        format!(
+          "{} {} {}",
+          self.wrap(lhs, precedence)?,
+          op.symbol(),
+          self.wrap(rhs, precedence + 1)?
+        )
+      }
+      Expression::IsNull(inner, negated) => match negated {
+        true => format!("{} IS NOT NULL", self.wrap(inner, 12)?),
+        false => format!("{} IS NULL", self.wrap(inner, 12)?),
+      },
+      Expression::HasLabels(inner, labels) => {
+        format!("{}{}", self.wrap(inner, 12)?, self.labels(labels)?)
+      }
+      Expression::Function(call) => {
+        let distinct = match call.distinct {
+          true => "DISTINCT ",
+          false => "",
+        };
+        format!(
+          "{}({}{})",
+          call.name,
+          distinct,
+          self.expressions(&call.arguments)?
+        )
+      }
+      Expression::CountAll => String::from("count(*)"),
+      Expression::Case(case) => {
+        let mut text = String::from("CASE");
+        if let Some(subject) = &case.subject {
+          text.push_str(&format!(" {}", self.expression(subject)?));
+        }
+        for (condition, result) in &case.alternatives {
+          text.push_str(&format!(
+            " WHEN {} THEN {}",
+            self.expression(condition)?,
+            self.expression(result)?
+          ));
+        }
+        if let Some(default) = &case.default {
+          text.push_str(&format!(" ELSE {}", self.expression(default)?));
+        }
+        text.push_str(" END");
+        text
+      }
+      Expression::PatternPredicate(part) => self.pattern_part(part)?,
+    })
+  }
+}
+
+/// Draw the lines and arrow heads around a relationship's details
+pub fn arrow(direction: Direction, detail: &str) -> String {
+  let body = match detail.is_empty() {
+    true => String::from("--"),
+    false => format!("-[{}]-", detail),
+  };
+
+  match direction {
+    Direction::Outgoing => format!("{}>", body),
+    Direction::Incoming => format!("<{}", body),
+    Direction::Either => body,
+  }
+}
@@ -0,0 +1,55 @@
+//! The subset of OpenCypher supported by RedisGraph
+//!
+//! RedisGraph writes queries the same way as OpenCypher, but labels can only be given when a node
+//! is created, so setting or removing them is rejected. Parameters are sent in a "CYPHER" header
+//! before the query, so the query text stays the same and RedisGraph can reuse its cached plan.
+//!
+//! Coverage: https://redis.io/docs/stack/graph/cypher_support/
+
+use crate::{
+  gquery::{
+    ast::*,
+    grammars::{cypher::Cypher, Printer},
+  },
+  prelude::*,
+};
+
+/// Prints queries for RedisGraph
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RedisGraph;
+
+impl Printer for RedisGraph {
+  fn name(&self) -> &'static str {
+    "RedisGraph"
+  }
+
+  fn query_with(&self, query: &GQuery, params: &Params) -> GraphtResult<String> {
+    let query = self.query(query)?;
+    if params.is_empty() {
+      return Ok(query);
+    }
+
+    let values = params
+      .iter()
+      .map(|(name, value)| {
+        let value = self.expression(&Expression::Literal(value.clone()))?;
+        Ok(format!("{}={}", escape_name(name), value))
+      })
+      .collect::<GraphtResult<Vec<String>>>()?;
+    Ok(format!("CYPHER {} {}", values.join(" "), query))
+  }
+
+  fn set_item(&self, item: &SetItem) -> GraphtResult<String> {
+    match item {
+      SetItem::Labels(_, _) => Err(self.unsupported("setting labels")),
+      _ => Cypher.set_item(item),
+    }
+  }
+
+  fn remove_item(&self, item: &RemoveItem) -> GraphtResult<String> {
+    match item {
+      RemoveItem::Labels(_, _) => Err(self.unsupported("removing labels")),
+      _ => Cypher.remove_item(item),
+    }
+  }
+}
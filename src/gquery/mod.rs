@@ -5,10 +5,21 @@
 
 // The internal representation of a query
 pub mod ast;
+pub use ast::{GQuery, Literal};
+
+// Values supplied separately from the query text
+pub mod params;
+pub use params::Params;
 
 // Translation definitions for parsing and printing out queries as strings
 pub mod grammars;
 
+// A fluent interface for building queries in code
+pub mod builder;
+
+// A lossless syntax tree for editor tooling, which tolerates syntax errors
+pub mod syntax;
+
 /* Create
   CREATE
 
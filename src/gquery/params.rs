@@ -0,0 +1,182 @@
+//! Values for the $parameters of a query
+//!
+//! Parameters keep user supplied values out of the query text, so they never need escaping and
+//! the same parsed query can be run again with different values.
+
+use crate::{gquery::ast::*, prelude::*};
+
+use std::collections::{btree_map, BTreeMap};
+
+/// The typed values for each parameter name, without the leading "$"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params {
+  values: BTreeMap<String, Literal>,
+}
+
+impl Params {
+  pub fn new() -> Params {
+    Params {
+      values: BTreeMap::new(),
+    }
+  }
+
+  /// Add a value while building the params (eg. `Params::new().set("id", "root")`)
+  pub fn set<V: Into<Literal>>(mut self, name: &str, value: V) -> Params {
+    self.insert(name, value);
+    self
+  }
+
+  /// Add a value, returning the one it replaced
+  pub fn insert<V: Into<Literal>>(&mut self, name: &str, value: V) -> Option<Literal> {
+    self.values.insert(name.to_string(), value.into())
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Literal> {
+    self.values.get(name)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  pub fn iter(&self) -> btree_map::Iter<'_, String, Literal> {
+    self.values.iter()
+  }
+
+  /// Look up a parameter used by a query, failing if no value was given for it
+  pub fn value(&self, name: &str) -> GraphtResult<&Literal> {
+    self.get(name).ok_or_else(|| {
+      err!(
+        NotSet,
+        "Query parameter ${} was used, but no value was given for it",
+        name
+      )
+    })
+  }
+}
+
+impl<K, V> FromIterator<(K, V)> for Params
+where
+  K: Into<String>,
+  V: Into<Literal>,
+{
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    Params {
+      values: iter
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect(),
+    }
+  }
+}
+
+impl<'a> IntoIterator for &'a Params {
+  type Item = (&'a String, &'a Literal);
+  type IntoIter = btree_map::Iter<'a, String, Literal>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.values.iter()
+  }
+}
+
+//--- Binding
+
+/// Replace the parameters in a clause with their values
+pub(crate) fn bind_clause(clause: &mut Clause, params: &Params) -> GraphtResult<()> {
+  match clause {
+    Clause::Match(matched) => {
+      bind_pattern(&mut matched.pattern, params)?;
+      bind_option(&mut matched.filter, params)
+    }
+    Clause::Unwind(unwind) => bind_expression(&mut unwind.expression, params),
+    Clause::With(with) => {
+      bind_projection(&mut with.projection, params)?;
+      bind_option(&mut with.filter, params)
+    }
+    Clause::Return(projection) => bind_projection(projection, params),
+    Clause::Create(pattern) => bind_pattern(pattern, params),
+    Clause::Merge(merge) => {
+      bind_part(&mut merge.pattern, params)?;
+      for item in merge.on_create.iter_mut().chain(merge.on_match.iter_mut()) {
+        bind_set_item(item, params)?;
+      }
+      Ok(())
+    }
+    Clause::Set(items) => items
+      .iter_mut()
+      .try_for_each(|item| bind_set_item(item, params)),
+    Clause::Remove(items) => items.iter_mut().try_for_each(|item| match item {
+      RemoveItem::Property(expression) => bind_expression(expression, params),
+      RemoveItem::Labels(_, _) => Ok(()),
+    }),
+    Clause::Delete(delete) => delete
+      .items
+      .iter_mut()
+      .try_for_each(|item| bind_expression(item, params)),
+  }
+}
+
+fn bind_set_item(item: &mut SetItem, params: &Params) -> GraphtResult<()> {
+  match item {
+    SetItem::Property(lhs, rhs) => {
+      bind_expression(lhs, params)?;
+      bind_expression(rhs, params)
+    }
+    SetItem::Replace(_, value) | SetItem::Update(_, value) => bind_expression(value, params),
+    SetItem::Labels(_, _) => Ok(()),
+  }
+}
+
+fn bind_projection(projection: &mut Projection, params: &Params) -> GraphtResult<()> {
+  for item in &mut projection.items {
+    bind_expression(&mut item.expression, params)?;
+  }
+  for item in &mut projection.order {
+    bind_expression(&mut item.expression, params)?;
+  }
+  bind_option(&mut projection.skip, params)?;
+  bind_option(&mut projection.limit, params)
+}
+
+fn bind_pattern(pattern: &mut Pattern, params: &Params) -> GraphtResult<()> {
+  pattern
+    .parts
+    .iter_mut()
+    .try_for_each(|part| bind_part(part, params))
+}
+
+fn bind_part(part: &mut PatternPart, params: &Params) -> GraphtResult<()> {
+  bind_properties(&mut part.start.properties, params)?;
+  for step in &mut part.chain {
+    bind_properties(&mut step.relationship.properties, params)?;
+    bind_properties(&mut step.node.properties, params)?;
+  }
+  Ok(())
+}
+
+fn bind_properties(properties: &mut [(String, Expression)], params: &Params) -> GraphtResult<()> {
+  properties
+    .iter_mut()
+    .try_for_each(|(_, value)| bind_expression(value, params))
+}
+
+fn bind_option(expression: &mut Option<Expression>, params: &Params) -> GraphtResult<()> {
+  match expression {
+    Some(expression) => bind_expression(expression, params),
+    None => Ok(()),
+  }
+}
+
+fn bind_expression(expression: &mut Expression, params: &Params) -> GraphtResult<()> {
+  match expression {
+    Expression::Parameter(name) => {
+      *expression = Expression::Literal(params.value(name)?.clone());
+      Ok(())
+    }
+    Expression::PatternPredicate(part) => bind_part(part, params),
+    expression => expression
+      .children_mut()
+      .into_iter()
+      .try_for_each(|child| bind_expression(child, params)),
+  }
+}
@@ -0,0 +1,157 @@
+//! Split the query text into tokens, keeping whitespace and comments
+
+use super::SyntaxKind::{self, *};
+
+use crate::gquery::grammars::cypher::RESERVED;
+
+/// Break the text into tokens. Joining the text of the tokens back together gives the original text
+pub fn lex(text: &str) -> Vec<(SyntaxKind, &str)> {
+  let mut tokens = Vec::new();
+  let mut remaining = text;
+  while !remaining.is_empty() {
+    let (kind, length) = next_token(remaining);
+    tokens.push((kind, &remaining[..length]));
+    remaining = &remaining[length..];
+  }
+  tokens
+}
+
+/// Find the kind and byte length of the token at the start of the text
+fn next_token(text: &str) -> (SyntaxKind, usize) {
+  let mut chars = text.chars();
+  let first = match chars.next() {
+    Some(c) => c,
+    None => return (ERROR_TOKEN, 0),
+  };
+  let second = chars.next();
+
+  if first.is_whitespace() {
+    return (WHITESPACE, take_while(text, char::is_whitespace));
+  }
+
+  if first.is_alphabetic() || first == '_' {
+    let length = take_while(text, |c| c.is_alphanumeric() || c == '_');
+    let kind = match RESERVED.contains(&text[..length].to_uppercase().as_str()) {
+      true => KEYWORD,
+      false => IDENT,
+    };
+    return (kind, length);
+  }
+
+  if first.is_ascii_digit() || (first == '.' && matches!(second, Some(c) if c.is_ascii_digit())) {
+    return number(text);
+  }
+
+  match (first, second) {
+    ('/', Some('/')) => return (COMMENT, text.find('\n').unwrap_or(text.len())),
+    ('/', Some('*')) => {
+      return match text[2..].find("*/") {
+        Some(end) => (COMMENT, end + 4),
+        None => (ERROR_TOKEN, text.len()),
+      }
+    }
+    ('\'', _) | ('"', _) => return string(text, first),
+    ('`', _) => return escaped_ident(text),
+    _ => (),
+  }
+
+  let double = match (first, second) {
+    ('.', Some('.')) => Some(DOT_DOT),
+    ('<', Some('>')) => Some(NEQ),
+    ('<', Some('=')) => Some(LE),
+    ('>', Some('=')) => Some(GE),
+    ('+', Some('=')) => Some(PLUS_EQ),
+    _ => None,
+  };
+  if let Some(kind) = double {
+    return (kind, 2);
+  }
+
+  let kind = match first {
+    '(' => L_PAREN,
+    ')' => R_PAREN,
+    '[' => L_BRACK,
+    ']' => R_BRACK,
+    '{' => L_BRACE,
+    '}' => R_BRACE,
+    ',' => COMMA,
+    '.' => DOT,
+    ':' => COLON,
+    ';' => SEMICOLON,
+    '|' => PIPE,
+    '$' => DOLLAR,
+    '*' => STAR,
+    '+' => PLUS,
+    '-' => MINUS,
+    '/' => SLASH,
+    '%' => PERCENT,
+    '^' => CARET,
+    '=' => EQ,
+    '<' => LT,
+    '>' => GT,
+    _ => ERROR_TOKEN,
+  };
+  (kind, first.len_utf8())
+}
+
+/// The byte length of the leading characters that match
+fn take_while(text: &str, predicate: impl Fn(char) -> bool) -> usize {
+  text.find(|c| !predicate(c)).unwrap_or(text.len())
+}
+
+fn number(text: &str) -> (SyntaxKind, usize) {
+  let bytes = text.as_bytes();
+  let digits = |start: usize| start + take_while(&text[start..], |c| c.is_ascii_digit());
+
+  let mut kind = INTEGER;
+  let mut end = digits(0);
+
+  // A fraction, but not the ".." of a range
+  if bytes.get(end) == Some(&b'.') && matches!(bytes.get(end + 1), Some(c) if c.is_ascii_digit()) {
+    kind = DECIMAL;
+    end = digits(end + 1);
+  }
+
+  if let Some(b'e') | Some(b'E') = bytes.get(end) {
+    let mut exponent = end + 1;
+    if let Some(b'+') | Some(b'-') = bytes.get(exponent) {
+      exponent += 1;
+    }
+    if matches!(bytes.get(exponent), Some(c) if c.is_ascii_digit()) {
+      kind = DECIMAL;
+      end = digits(exponent);
+    }
+  }
+
+  (kind, end)
+}
+
+fn string(text: &str, quote: char) -> (SyntaxKind, usize) {
+  let mut escaped = false;
+  for (i, c) in text.char_indices().skip(1) {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      c if c == quote => return (STRING, i + 1),
+      _ => (),
+    }
+  }
+  (ERROR_TOKEN, text.len())
+}
+
+fn escaped_ident(text: &str) -> (SyntaxKind, usize) {
+  let mut offset = 1;
+  loop {
+    match text[offset..].find('`') {
+      None => return (ERROR_TOKEN, text.len()),
+      Some(end) => {
+        offset += end + 1;
+        // A doubled backtick is an escaped backtick
+        match text[offset..].starts_with('`') {
+          true => offset += 1,
+          false => return (ESCAPED_IDENT, offset),
+        }
+      }
+    }
+  }
+}
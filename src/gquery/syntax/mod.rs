@@ -0,0 +1,212 @@
+//! A lossless concrete syntax tree for Cypher queries
+//!
+//! Unlike the nom parser, which turns a valid query straight into a GQuery, this keeps every byte of
+//! the input (including comments and whitespace) in a rowan tree, and keeps going after a syntax
+//! error. This makes it usable on half typed queries, such as for highlighting or autocomplete in an
+//! editor. Errors are collected as diagnostics alongside the tree.
+//!
+//! The GQL parts that share Cypher's syntax (MATCH, WHERE, RETURN, ...) parse into the same tree.
+
+use crate::{gquery::ast::GQuery, local::*, prelude::*};
+
+use rowan::{GreenNode, TextRange};
+
+mod lexer;
+mod parser;
+
+pub use lexer::lex;
+
+/// Every kind of token and node in the tree
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum SyntaxKind {
+  //--- Tokens
+  WHITESPACE = 0,
+  COMMENT,
+  /// A reserved word, such as MATCH or WHERE
+  KEYWORD,
+  IDENT,
+  /// A name wrapped in backticks
+  ESCAPED_IDENT,
+  INTEGER,
+  DECIMAL,
+  STRING,
+  L_PAREN,
+  R_PAREN,
+  L_BRACK,
+  R_BRACK,
+  L_BRACE,
+  R_BRACE,
+  COMMA,
+  DOT,
+  DOT_DOT,
+  COLON,
+  SEMICOLON,
+  PIPE,
+  DOLLAR,
+  STAR,
+  PLUS,
+  MINUS,
+  SLASH,
+  PERCENT,
+  CARET,
+  EQ,
+  NEQ,
+  LT,
+  GT,
+  LE,
+  GE,
+  PLUS_EQ,
+  /// Text the lexer could not make sense of, such as an unterminated string
+  ERROR_TOKEN,
+
+  //--- Nodes
+  MATCH_CLAUSE,
+  UNWIND_CLAUSE,
+  WITH_CLAUSE,
+  RETURN_CLAUSE,
+  CREATE_CLAUSE,
+  MERGE_CLAUSE,
+  MERGE_ACTION,
+  SET_CLAUSE,
+  SET_ITEM,
+  REMOVE_CLAUSE,
+  REMOVE_ITEM,
+  DELETE_CLAUSE,
+  WHERE_CLAUSE,
+  ORDER_BY,
+  SORT_ITEM,
+  SKIP_CLAUSE,
+  LIMIT_CLAUSE,
+  PROJECTION_ITEM,
+  PATTERN,
+  PATTERN_PART,
+  NODE_PATTERN,
+  RELATIONSHIP_PATTERN,
+  RELATIONSHIP_DETAIL,
+  RELATIONSHIP_TYPES,
+  VAR_LENGTH,
+  LABELS,
+  NAME,
+  LITERAL,
+  PARAMETER,
+  VARIABLE,
+  LIST_EXPR,
+  MAP_EXPR,
+  MAP_ENTRY,
+  FUNCTION_CALL,
+  ARG_LIST,
+  PAREN_EXPR,
+  CASE_EXPR,
+  CASE_ALTERNATIVE,
+  UNARY_EXPR,
+  BINARY_EXPR,
+  IS_NULL_EXPR,
+  PROPERTY_EXPR,
+  LABEL_EXPR,
+  /// Tokens that were skipped while recovering from a syntax error
+  ERROR,
+  ROOT,
+}
+
+use SyntaxKind::*;
+
+impl SyntaxKind {
+  /// Whitespace and comments, which the parser skips over
+  pub fn is_trivia(self) -> bool {
+    matches!(self, WHITESPACE | COMMENT)
+  }
+
+  pub fn is_token(self) -> bool {
+    self <= ERROR_TOKEN
+  }
+}
+
+impl From<SyntaxKind> for rowan::SyntaxKind {
+  fn from(kind: SyntaxKind) -> Self {
+    rowan::SyntaxKind(kind as u16)
+  }
+}
+
+/// Ties the SyntaxKind to rowan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CypherLanguage {}
+
+impl rowan::Language for CypherLanguage {
+  type Kind = SyntaxKind;
+
+  fn kind_from_raw(raw: rowan::SyntaxKind) -> SyntaxKind {
+    assert!(raw.0 <= ROOT as u16, "Unknown syntax kind: {}", raw.0);
+    // SAFETY: SyntaxKind is a repr(u16) enum with no gaps, and the value was checked above
+    unsafe { std::mem::transmute::<u16, SyntaxKind>(raw.0) }
+  }
+
+  fn kind_to_raw(kind: SyntaxKind) -> rowan::SyntaxKind {
+    kind.into()
+  }
+}
+
+pub type SyntaxNode = rowan::SyntaxNode<CypherLanguage>;
+pub type SyntaxToken = rowan::SyntaxToken<CypherLanguage>;
+pub type SyntaxElement = rowan::SyntaxElement<CypherLanguage>;
+
+/// A problem found while parsing, and where it is in the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub message: String,
+  pub range: TextRange,
+}
+
+impl Diagnostic {
+  /// The one based line and column where the problem starts
+  pub fn position(&self, text: &str) -> (usize, usize) {
+    crate::gquery::grammars::cypher::line_column(text, usize::from(self.range.start()))
+  }
+}
+
+/// The result of parsing: a tree that always covers the full text, and any errors found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parse {
+  green: GreenNode,
+  errors: Vec<Diagnostic>,
+}
+
+impl Parse {
+  pub fn syntax(&self) -> SyntaxNode {
+    SyntaxNode::new_root(self.green.clone())
+  }
+
+  pub fn errors(&self) -> &[Diagnostic] {
+    &self.errors
+  }
+
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty()
+  }
+
+  /// Convert the tree into a GQuery, failing with the first diagnostic if there are any
+  pub fn to_query(&self) -> GraphtResult<GQuery> {
+    let text = self.syntax().text().to_string();
+    match self.errors.first() {
+      None => GQuery::parse(&text),
+      Some(error) => {
+        let (line, column) = error.position(&text);
+        Err(err!(
+          ParsingError,
+          "Invalid query at line {}, column {}: {}",
+          line,
+          column,
+          error.message
+        ))
+      }
+    }
+  }
+}
+
+/// Parse a query into a lossless syntax tree
+pub fn parse(text: &str) -> Parse {
+  let (green, errors) = parser::Parser::new(text).parse();
+  debug!("Parsed syntax tree with {} error(s)", errors.len());
+  Parse { green, errors }
+}
@@ -0,0 +1,798 @@
+//! A hand written recursive descent parser that builds the syntax tree
+//!
+//! Each grammar rule opens a node, consumes tokens, and closes it again. When an expected token is
+//! missing, a diagnostic is recorded and the parser carries on as if it was there. Tokens that can't
+//! be used are wrapped in an ERROR node, so the tree always holds the full text.
+
+use super::{
+  lexer::lex,
+  Diagnostic,
+  SyntaxKind::{self, *},
+};
+
+use rowan::{Checkpoint, GreenNode, GreenNodeBuilder, TextRange, TextSize};
+
+/// Keywords that start a new clause, used to resynchronize after an error
+const CLAUSE_KEYWORDS: &[&str] = &[
+  "MATCH", "OPTIONAL", "UNWIND", "WITH", "RETURN", "CREATE", "MERGE", "SET", "REMOVE", "DELETE",
+  "DETACH",
+];
+
+/// Binding strengths for the expression operators, from loosest to tightest
+const OR: u8 = 1;
+const XOR: u8 = 2;
+const AND: u8 = 3;
+const NOT: u8 = 4;
+const COMPARISON: u8 = 5;
+const ADD: u8 = 6;
+const MULTIPLY: u8 = 7;
+const POWER: u8 = 8;
+const SIGN: u8 = 9;
+const STRING_LIST_NULL: u8 = 10;
+
+pub(crate) struct Parser<'a> {
+  tokens: Vec<(SyntaxKind, &'a str)>,
+
+  /// The byte offset each token starts at
+  offsets: Vec<TextSize>,
+
+  pos: usize,
+  builder: GreenNodeBuilder<'static>,
+  errors: Vec<Diagnostic>,
+}
+
+impl<'a> Parser<'a> {
+  pub fn new(text: &'a str) -> Parser<'a> {
+    let tokens = lex(text);
+    let mut offsets = Vec::with_capacity(tokens.len() + 1);
+    let mut offset = TextSize::from(0);
+    for (_, token) in &tokens {
+      offsets.push(offset);
+      offset += TextSize::of(*token);
+    }
+    offsets.push(offset);
+
+    Parser {
+      tokens,
+      offsets,
+      pos: 0,
+      builder: GreenNodeBuilder::new(),
+      errors: Vec::new(),
+    }
+  }
+
+  pub fn parse(mut self) -> (GreenNode, Vec<Diagnostic>) {
+    self.builder.start_node(ROOT.into());
+    loop {
+      match self.current() {
+        None => break,
+        Some(SEMICOLON) => self.bump(),
+        Some(_) if self.at_clause() => self.clause(),
+        Some(_) => {
+          self.error("Expected the start of a clause");
+          self.start(ERROR);
+          while self.current().is_some() && !self.at_clause() {
+            self.bump();
+          }
+          self.finish();
+        }
+      }
+    }
+
+    // Trailing whitespace and comments
+    self.skip_trivia();
+    self.builder.finish_node();
+    (self.builder.finish(), self.errors)
+  }
+
+  //--- Token handling
+
+  /// The position of the nth token after the current one, ignoring trivia
+  fn nth_pos(&self, n: usize) -> Option<usize> {
+    self
+      .tokens
+      .iter()
+      .enumerate()
+      .skip(self.pos)
+      .filter(|(_, (kind, _))| !kind.is_trivia())
+      .map(|(i, _)| i)
+      .nth(n)
+  }
+
+  fn nth(&self, n: usize) -> Option<SyntaxKind> {
+    self.nth_pos(n).map(|i| self.tokens[i].0)
+  }
+
+  fn current(&self) -> Option<SyntaxKind> {
+    self.nth(0)
+  }
+
+  fn at(&self, kind: SyntaxKind) -> bool {
+    self.current() == Some(kind)
+  }
+
+  fn nth_keyword(&self, n: usize, word: &str) -> bool {
+    match self.nth_pos(n) {
+      Some(i) => self.tokens[i].0 == KEYWORD && self.tokens[i].1.eq_ignore_ascii_case(word),
+      None => false,
+    }
+  }
+
+  fn at_keyword(&self, word: &str) -> bool {
+    self.nth_keyword(0, word)
+  }
+
+  fn at_clause(&self) -> bool {
+    CLAUSE_KEYWORDS.iter().any(|word| self.at_keyword(word))
+  }
+
+  /// Tokens an expression can't start with, which are left for an outer rule to deal with
+  fn at_recovery(&self) -> bool {
+    match self.current() {
+      None => true,
+      Some(R_PAREN | R_BRACK | R_BRACE | COMMA | SEMICOLON) => true,
+      Some(_) => self.at_clause(),
+    }
+  }
+
+  /// Add any whitespace and comments to the current node
+  fn skip_trivia(&mut self) {
+    while let Some((kind, text)) = self.tokens.get(self.pos) {
+      if !kind.is_trivia() {
+        break;
+      }
+      self.builder.token((*kind).into(), text);
+      self.pos += 1;
+    }
+  }
+
+  /// Add the next token to the current node
+  fn bump(&mut self) {
+    self.skip_trivia();
+    if let Some((kind, text)) = self.tokens.get(self.pos) {
+      self.builder.token((*kind).into(), text);
+      self.pos += 1;
+    }
+  }
+
+  fn eat(&mut self, kind: SyntaxKind) -> bool {
+    match self.at(kind) {
+      true => {
+        self.bump();
+        true
+      }
+      false => false,
+    }
+  }
+
+  fn eat_keyword(&mut self, word: &str) -> bool {
+    match self.at_keyword(word) {
+      true => {
+        self.bump();
+        true
+      }
+      false => false,
+    }
+  }
+
+  fn expect(&mut self, kind: SyntaxKind, what: &str) {
+    if !self.eat(kind) {
+      self.error(&format!("Expected {}", what));
+    }
+  }
+
+  fn expect_keyword(&mut self, word: &str) {
+    if !self.eat_keyword(word) {
+      self.error(&format!("Expected {}", word));
+    }
+  }
+
+  fn start(&mut self, kind: SyntaxKind) {
+    self.skip_trivia();
+    self.builder.start_node(kind.into());
+  }
+
+  fn finish(&mut self) {
+    self.builder.finish_node();
+  }
+
+  fn checkpoint(&mut self) -> Checkpoint {
+    self.skip_trivia();
+    self.builder.checkpoint()
+  }
+
+  fn start_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+    self.builder.start_node_at(checkpoint, kind.into());
+  }
+
+  /// Record a problem at the next token
+  fn error(&mut self, message: &str) {
+    let range = match self.nth_pos(0) {
+      Some(i) => TextRange::new(self.offsets[i], self.offsets[i + 1]),
+      None => TextRange::empty(*self.offsets.last().unwrap_or(&TextSize::from(0))),
+    };
+    self.errors.push(Diagnostic {
+      message: message.to_string(),
+      range,
+    });
+  }
+
+  /// Record a problem, and skip the next token unless an outer rule can use it
+  fn error_recover(&mut self, message: &str) {
+    self.error(message);
+    if !self.at_recovery() {
+      self.start(ERROR);
+      self.bump();
+      self.finish();
+    }
+  }
+
+  //--- Clauses
+
+  fn clause(&mut self) {
+    if self.at_keyword("MATCH") || self.at_keyword("OPTIONAL") {
+      self.match_clause()
+    } else if self.at_keyword("UNWIND") {
+      self.unwind_clause()
+    } else if self.at_keyword("WITH") {
+      self.with_clause()
+    } else if self.at_keyword("RETURN") {
+      self.return_clause()
+    } else if self.at_keyword("CREATE") {
+      self.create_clause()
+    } else if self.at_keyword("MERGE") {
+      self.merge_clause()
+    } else if self.at_keyword("SET") {
+      self.set_clause()
+    } else if self.at_keyword("REMOVE") {
+      self.remove_clause()
+    } else {
+      self.delete_clause()
+    }
+  }
+
+  fn match_clause(&mut self) {
+    self.start(MATCH_CLAUSE);
+    self.eat_keyword("OPTIONAL");
+    self.expect_keyword("MATCH");
+    self.pattern();
+    self.where_clause();
+    self.finish();
+  }
+
+  fn where_clause(&mut self) {
+    if self.at_keyword("WHERE") {
+      self.start(WHERE_CLAUSE);
+      self.bump();
+      self.expression();
+      self.finish();
+    }
+  }
+
+  fn unwind_clause(&mut self) {
+    self.start(UNWIND_CLAUSE);
+    self.bump();
+    self.expression();
+    self.expect_keyword("AS");
+    self.name();
+    self.finish();
+  }
+
+  fn with_clause(&mut self) {
+    self.start(WITH_CLAUSE);
+    self.bump();
+    self.projection();
+    self.where_clause();
+    self.finish();
+  }
+
+  fn return_clause(&mut self) {
+    self.start(RETURN_CLAUSE);
+    self.bump();
+    self.projection();
+    self.finish();
+  }
+
+  fn projection(&mut self) {
+    self.eat_keyword("DISTINCT");
+    match self.eat(STAR) {
+      true => {
+        while self.eat(COMMA) {
+          self.projection_item();
+        }
+      }
+      false => {
+        self.projection_item();
+        while self.eat(COMMA) {
+          self.projection_item();
+        }
+      }
+    }
+
+    if self.at_keyword("ORDER") {
+      self.start(ORDER_BY);
+      self.bump();
+      self.expect_keyword("BY");
+      self.sort_item();
+      while self.eat(COMMA) {
+        self.sort_item();
+      }
+      self.finish();
+    }
+
+    for (word, kind) in [("SKIP", SKIP_CLAUSE), ("LIMIT", LIMIT_CLAUSE)] {
+      if self.at_keyword(word) {
+        self.start(kind);
+        self.bump();
+        self.expression();
+        self.finish();
+      }
+    }
+  }
+
+  fn projection_item(&mut self) {
+    self.start(PROJECTION_ITEM);
+    self.expression();
+    if self.eat_keyword("AS") {
+      self.name();
+    }
+    self.finish();
+  }
+
+  fn sort_item(&mut self) {
+    self.start(SORT_ITEM);
+    self.expression();
+    for word in ["ASC", "ASCENDING", "DESC", "DESCENDING"] {
+      if self.eat_keyword(word) {
+        break;
+      }
+    }
+    self.finish();
+  }
+
+  fn create_clause(&mut self) {
+    self.start(CREATE_CLAUSE);
+    self.bump();
+    self.pattern();
+    self.finish();
+  }
+
+  fn merge_clause(&mut self) {
+    self.start(MERGE_CLAUSE);
+    self.bump();
+    self.pattern_part();
+    while self.at_keyword("ON") {
+      self.start(MERGE_ACTION);
+      self.bump();
+      if !(self.eat_keyword("MATCH") || self.eat_keyword("CREATE")) {
+        self.error("Expected MATCH or CREATE");
+      }
+      self.expect_keyword("SET");
+      self.set_items();
+      self.finish();
+    }
+    self.finish();
+  }
+
+  fn set_clause(&mut self) {
+    self.start(SET_CLAUSE);
+    self.bump();
+    self.set_items();
+    self.finish();
+  }
+
+  fn set_items(&mut self) {
+    self.set_item();
+    while self.eat(COMMA) {
+      self.set_item();
+    }
+  }
+
+  fn set_item(&mut self) {
+    self.start(SET_ITEM);
+    // Either a property lookup or a variable with labels
+    self.postfix_expression();
+    if self.eat(EQ) || self.eat(PLUS_EQ) {
+      self.expression();
+    }
+    self.finish();
+  }
+
+  fn remove_clause(&mut self) {
+    self.start(REMOVE_CLAUSE);
+    self.bump();
+    loop {
+      self.start(REMOVE_ITEM);
+      self.postfix_expression();
+      self.finish();
+      if !self.eat(COMMA) {
+        break;
+      }
+    }
+    self.finish();
+  }
+
+  fn delete_clause(&mut self) {
+    self.start(DELETE_CLAUSE);
+    self.eat_keyword("DETACH");
+    self.expect_keyword("DELETE");
+    self.expression();
+    while self.eat(COMMA) {
+      self.expression();
+    }
+    self.finish();
+  }
+
+  //--- Patterns
+
+  fn pattern(&mut self) {
+    self.start(PATTERN);
+    self.pattern_part();
+    while self.eat(COMMA) {
+      self.pattern_part();
+    }
+    self.finish();
+  }
+
+  fn pattern_part(&mut self) {
+    self.start(PATTERN_PART);
+    if matches!(self.current(), Some(IDENT | ESCAPED_IDENT)) && self.nth(1) == Some(EQ) {
+      self.name();
+      self.bump();
+    }
+    self.node_pattern();
+    while self.at_relationship() {
+      self.relationship_pattern();
+      self.node_pattern();
+    }
+    self.finish();
+  }
+
+  fn at_relationship(&self) -> bool {
+    self.at(MINUS) || (self.at(LT) && self.nth(1) == Some(MINUS))
+  }
+
+  fn node_pattern(&mut self) {
+    self.start(NODE_PATTERN);
+    self.expect(L_PAREN, "'('");
+    if matches!(self.current(), Some(IDENT | ESCAPED_IDENT)) {
+      self.name();
+    }
+    if self.at(COLON) {
+      self.labels();
+    }
+    if self.at(L_BRACE) {
+      self.map();
+    }
+    self.expect(R_PAREN, "')'");
+    self.finish();
+  }
+
+  fn relationship_pattern(&mut self) {
+    self.start(RELATIONSHIP_PATTERN);
+    self.eat(LT);
+    self.expect(MINUS, "'-'");
+    if self.at(L_BRACK) {
+      self.start(RELATIONSHIP_DETAIL);
+      self.bump();
+      if matches!(self.current(), Some(IDENT | ESCAPED_IDENT)) {
+        self.name();
+      }
+      if self.at(COLON) {
+        self.start(RELATIONSHIP_TYPES);
+        self.bump();
+        self.schema_name();
+        while self.eat(PIPE) {
+          self.eat(COLON);
+          self.schema_name();
+        }
+        self.finish();
+      }
+      if self.at(STAR) {
+        self.start(VAR_LENGTH);
+        self.bump();
+        self.eat(INTEGER);
+        if self.eat(DOT_DOT) {
+          self.eat(INTEGER);
+        }
+        self.finish();
+      }
+      if self.at(L_BRACE) {
+        self.map();
+      }
+      self.expect(R_BRACK, "']'");
+      self.finish();
+    }
+    self.expect(MINUS, "'-'");
+    self.eat(GT);
+    self.finish();
+  }
+
+  fn labels(&mut self) {
+    self.start(LABELS);
+    while self.eat(COLON) {
+      self.schema_name();
+    }
+    self.finish();
+  }
+
+  /// A variable or alias
+  fn name(&mut self) {
+    match self.current() {
+      Some(IDENT | ESCAPED_IDENT) => {
+        self.start(NAME);
+        self.bump();
+        self.finish();
+      }
+      _ => self.error_recover("Expected a name"),
+    }
+  }
+
+  /// A label, type or property key, which can also be a keyword
+  fn schema_name(&mut self) {
+    match self.current() {
+      Some(IDENT | ESCAPED_IDENT | KEYWORD) => {
+        self.start(NAME);
+        self.bump();
+        self.finish();
+      }
+      _ => self.error_recover("Expected a name"),
+    }
+  }
+
+  //--- Expressions
+
+  fn expression(&mut self) {
+    self.expression_bp(OR)
+  }
+
+  /// Parse an expression made of operators that bind at least as tightly as the minimum
+  fn expression_bp(&mut self, min: u8) {
+    let checkpoint = self.checkpoint();
+
+    if self.at_keyword("NOT") {
+      self.start(UNARY_EXPR);
+      self.bump();
+      self.expression_bp(NOT);
+      self.finish();
+    } else if self.at(MINUS) || self.at(PLUS) {
+      self.start(UNARY_EXPR);
+      self.bump();
+      self.expression_bp(SIGN);
+      self.finish();
+    } else {
+      self.postfix_expression();
+    }
+
+    loop {
+      let (strength, tokens) = match self.current() {
+        Some(KEYWORD) if self.at_keyword("OR") => (OR, 1),
+        Some(KEYWORD) if self.at_keyword("XOR") => (XOR, 1),
+        Some(KEYWORD) if self.at_keyword("AND") => (AND, 1),
+        Some(EQ | NEQ | LT | GT | LE | GE) => (COMPARISON, 1),
+        Some(PLUS | MINUS) => (ADD, 1),
+        Some(STAR | SLASH | PERCENT) => (MULTIPLY, 1),
+        Some(CARET) => (POWER, 1),
+        Some(KEYWORD) if self.at_keyword("CONTAINS") || self.at_keyword("IN") => {
+          (STRING_LIST_NULL, 1)
+        }
+        Some(KEYWORD) if self.at_keyword("STARTS") || self.at_keyword("ENDS") => {
+          (STRING_LIST_NULL, 2)
+        }
+        Some(KEYWORD) if self.at_keyword("IS") => (STRING_LIST_NULL, 0),
+        _ => break,
+      };
+      if strength < min {
+        break;
+      }
+
+      // IS [NOT] NULL is a postfix operator
+      if tokens == 0 {
+        self.start_at(checkpoint, IS_NULL_EXPR);
+        self.bump();
+        self.eat_keyword("NOT");
+        self.expect_keyword("NULL");
+        self.finish();
+        continue;
+      }
+
+      self.start_at(checkpoint, BINARY_EXPR);
+      self.bump();
+      if tokens == 2 {
+        self.expect_keyword("WITH");
+      }
+      // All the operators are left associative
+      self.expression_bp(strength + 1);
+      self.finish();
+    }
+  }
+
+  /// An atom followed by any property lookups or label checks
+  fn postfix_expression(&mut self) {
+    let checkpoint = self.checkpoint();
+    self.atom();
+    loop {
+      if self.at(DOT) {
+        self.start_at(checkpoint, PROPERTY_EXPR);
+        self.bump();
+        // Leave whatever follows a dangling "." for the operators
+        match self.current() {
+          Some(IDENT | ESCAPED_IDENT | KEYWORD) => self.schema_name(),
+          _ => self.error("Expected a property name"),
+        }
+        self.finish();
+      } else if self.at(COLON) {
+        self.start_at(checkpoint, LABEL_EXPR);
+        self.labels();
+        self.finish();
+      } else {
+        break;
+      }
+    }
+  }
+
+  fn atom(&mut self) {
+    match self.current() {
+      Some(INTEGER | DECIMAL | STRING) => self.literal(),
+      Some(KEYWORD) if ["TRUE", "FALSE", "NULL"].iter().any(|x| self.at_keyword(x)) => {
+        self.literal()
+      }
+      Some(KEYWORD) if self.at_keyword("CASE") => self.case_expression(),
+      Some(DOLLAR) => {
+        self.start(PARAMETER);
+        self.bump();
+        match self.current() {
+          Some(IDENT | ESCAPED_IDENT | KEYWORD | INTEGER) => self.bump(),
+          _ => self.error("Expected a parameter name"),
+        }
+        self.finish();
+      }
+      Some(L_BRACK) => {
+        self.start(LIST_EXPR);
+        self.bump();
+        if !self.at(R_BRACK) {
+          self.expression();
+          while self.eat(COMMA) {
+            self.expression();
+          }
+        }
+        self.expect(R_BRACK, "']'");
+        self.finish();
+      }
+      Some(L_BRACE) => self.map(),
+      Some(L_PAREN) if self.at_pattern() => {
+        // A pattern used as a predicate
+        self.start(PATTERN_PART);
+        self.node_pattern();
+        while self.at_relationship() {
+          self.relationship_pattern();
+          self.node_pattern();
+        }
+        self.finish();
+      }
+      Some(L_PAREN) => {
+        self.start(PAREN_EXPR);
+        self.bump();
+        self.expression();
+        self.expect(R_PAREN, "')'");
+        self.finish();
+      }
+      Some(IDENT) | Some(KEYWORD) if self.at_function() => self.function_call(),
+      Some(IDENT | ESCAPED_IDENT) => {
+        self.start(VARIABLE);
+        self.bump();
+        self.finish();
+      }
+      _ => self.error_recover("Expected an expression"),
+    }
+  }
+
+  fn literal(&mut self) {
+    self.start(LITERAL);
+    self.bump();
+    self.finish();
+  }
+
+  fn map(&mut self) {
+    self.start(MAP_EXPR);
+    self.expect(L_BRACE, "'{'");
+    if !self.at(R_BRACE) {
+      loop {
+        self.start(MAP_ENTRY);
+        self.schema_name();
+        self.expect(COLON, "':'");
+        self.expression();
+        self.finish();
+        if !self.eat(COMMA) {
+          break;
+        }
+      }
+    }
+    self.expect(R_BRACE, "'}'");
+    self.finish();
+  }
+
+  fn case_expression(&mut self) {
+    self.start(CASE_EXPR);
+    self.bump();
+    if !self.at_keyword("WHEN") {
+      self.expression();
+    }
+    if !self.at_keyword("WHEN") {
+      self.error("Expected WHEN");
+    }
+    while self.at_keyword("WHEN") {
+      self.start(CASE_ALTERNATIVE);
+      self.bump();
+      self.expression();
+      self.expect_keyword("THEN");
+      self.expression();
+      self.finish();
+    }
+    if self.eat_keyword("ELSE") {
+      self.expression();
+    }
+    self.expect_keyword("END");
+    self.finish();
+  }
+
+  /// Check if the parenthesis starts a node pattern that is followed by a relationship
+  fn at_pattern(&self) -> bool {
+    let mut kinds = self.tokens[self.pos..]
+      .iter()
+      .map(|(kind, _)| *kind)
+      .filter(|kind| !kind.is_trivia());
+
+    let mut depth = 0;
+    for kind in kinds.by_ref() {
+      match kind {
+        L_PAREN => depth += 1,
+        R_PAREN if depth == 1 => break,
+        R_PAREN => depth -= 1,
+        _ => (),
+      }
+    }
+
+    matches!(
+      (kinds.next(), kinds.next()),
+      (Some(LT), Some(MINUS)) | (Some(MINUS), Some(MINUS | L_BRACK))
+    )
+  }
+
+  /// Check for a (possibly namespaced) name followed by an opening parenthesis
+  fn at_function(&self) -> bool {
+    if self.current() == Some(KEYWORD) && !self.at_keyword("EXISTS") {
+      return false;
+    }
+
+    let mut n = 1;
+    while self.nth(n) == Some(DOT) && self.nth(n + 1) == Some(IDENT) {
+      n += 2;
+    }
+    self.nth(n) == Some(L_PAREN)
+  }
+
+  fn function_call(&mut self) {
+    self.start(FUNCTION_CALL);
+    self.start(NAME);
+    self.bump();
+    while self.at(DOT) {
+      self.bump();
+      self.bump();
+    }
+    self.finish();
+
+    self.start(ARG_LIST);
+    self.bump();
+    self.eat_keyword("DISTINCT");
+    if !self.eat(STAR) && !self.at(R_PAREN) {
+      self.expression();
+      while self.eat(COMMA) {
+        self.expression();
+      }
+    }
+    self.expect(R_PAREN, "')'");
+    self.finish();
+    self.finish();
+  }
+}
@@ -19,6 +19,9 @@ pub mod stats;
 // In-memory cache of queryable data
 pub mod store;
 
+// Graph analytics over the data in a store
+pub mod algo;
+
 // Transport layer for communicating with remote servers
 // pub mod connection;
 
@@ -29,7 +32,7 @@ pub mod store;
 // pub mod grapht;
 
 // The query language syntax tree
-// pub mod gquery;
+pub mod gquery;
 
 // Subscription messaging
 // pub mod messages;
@@ -42,6 +45,7 @@ pub mod prelude {
     err,
     err_into,
     errors::{GraphtError, Kind, Result as GraphtResult},
+    gquery::{GQuery, Literal, Params},
     // grapht::Grapht,
     model::*,
     stats::*,
@@ -50,6 +54,9 @@ pub mod prelude {
     // connection::Pool,
     utils::*,
   };
+
+  #[cfg(feature = "derive")]
+  pub use grapht_derive::GraphtEntity;
 }
 
 /// Toggles std and alternate implementations that are used throughout the crate
@@ -53,7 +53,19 @@ impl<G> Edge<G>
 where
   G: Graph,
 {
-  pub fn new(source: &Node<G>, target: &Node<G>, properties: G::Edge) -> Edge<G> {
+  /// Build an edge from the source to the target
+  ///
+  /// Edges that don't fit the Graph's schema are rejected as an InvalidItem.
+  pub fn new(source: &Node<G>, target: &Node<G>, properties: G::Edge) -> GraphtResult<Edge<G>> {
+    let edge = Edge::build(source, target, properties);
+    if let Some(schema) = G::schema() {
+      schema.check_edge(&edge)?;
+    }
+    Ok(edge)
+  }
+
+  /// Build an edge without checking it against the schema
+  fn build(source: &Node<G>, target: &Node<G>, properties: G::Edge) -> Edge<G> {
     Edge {
       guid: Self::make_guid(source.get_guid(), target.get_guid(), properties.get_key()),
       source: source.clone(),
@@ -94,9 +106,83 @@ where
     self.properties.clone()
   }
 
+  /// Look up a single property value by name
+  pub fn get_property(&self, name: &str) -> GraphtResult<Literal> {
+    self.properties.get_property(name)
+  }
+
   pub fn get_type_label(&self) -> String {
     self.properties.get_type_label()
   }
+
+  /// The weight given to the edge, if any. Weighted path searches treat it as the cost of walking
+  /// the edge.
+  pub fn get_weight(&self) -> Option<f32> {
+    self.options.weight
+  }
+
+  /// Set the weight while building the edge (eg. `Edge::new(&a, &b, props)?.with_weight(2.5)`)
+  pub fn with_weight(mut self, weight: f32) -> Edge<G> {
+    self.options.weight = Some(weight);
+    self
+  }
+
+  pub fn get_direction(&self) -> EdgeDirection {
+    self.options.direction
+  }
+
+  pub fn is_two_way(&self) -> bool {
+    self.options.direction == EdgeDirection::TwoWay
+  }
+
+  /// Make the edge TwoWay, so storing it also stores an edge going back from the target to the
+  /// source
+  pub fn two_way(mut self) -> Edge<G> {
+    self.options.direction = EdgeDirection::TwoWay;
+    self
+  }
+
+  /// Make the edge TwoWay, with a different weight for the edge going back
+  ///
+  /// An edge without a weight of its own is given a weight of 1, which is what walking it would
+  /// cost anyway.
+  pub fn with_reverse_weight(mut self, weight: f32) -> Edge<G> {
+    self.options.direction = EdgeDirection::TwoWay;
+    self.options.weight = self.options.weight.or(Some(1.0));
+    self.options.reverse_weight = Some(weight);
+    self
+  }
+
+  /// The weight of the edge going back, which is the same as this edge's unless it was set
+  pub fn get_reverse_weight(&self) -> Option<f32> {
+    self.options.reverse_weight.or(self.options.weight)
+  }
+
+  /// The edge going back from the target to the source of a TwoWay edge
+  ///
+  /// Its properties come from `Graph::reciprocal`, and its weights are swapped with this edge's.
+  /// A self loop that would be its own reciprocal doesn't have one. The reciprocal isn't checked
+  /// against the schema until it is inserted.
+  pub fn reciprocal(&self) -> Option<Edge<G>> {
+    if !self.is_two_way() {
+      return None;
+    }
+
+    let mut edge = Edge::build(&self.target, &self.source, G::reciprocal(&self.properties));
+    edge.options = EdgeOpts {
+      weight: self.get_reverse_weight(),
+      reverse_weight: self.options.reverse_weight.and(self.options.weight),
+      direction: EdgeDirection::TwoWay,
+    };
+    match edge.guid == self.guid {
+      true => None,
+      false => Some(edge),
+    }
+  }
+
+  pub(crate) fn get_options(&self) -> &EdgeOpts {
+    &self.options
+  }
 }
 
 impl<G> std::cmp::PartialEq for Edge<G>
@@ -119,20 +205,24 @@ where
 }
 
 /// Generic options that can be placed on each edge
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EdgeOpts {
-  /// An optional weight that can be used order similar edges
-  _weight: Option<f32>,
+  /// An optional weight that can be used order similar edges, or as the cost of walking the edge
+  weight: Option<f32>,
+
+  /// The weight of the reciprocal edge, when it differs from this one
+  reverse_weight: Option<f32>,
 
   /// Whether there is a reciprocal edge going from target to source
-  _direction: EdgeDirection,
+  direction: EdgeDirection,
 }
 
 impl EdgeOpts {
   pub fn new() -> EdgeOpts {
     EdgeOpts {
-      _weight: None,
-      _direction: EdgeDirection::OneWay,
+      weight: None,
+      reverse_weight: None,
+      direction: EdgeDirection::OneWay,
     }
   }
 }
@@ -141,7 +231,7 @@ impl EdgeOpts {
 ///
 /// Any bi-directional edges should have paired entries, one for each direction. Undirected means
 /// we can use the edge to go from target to source, if needed.
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EdgeDirection {
   /// The edge can only be traversed in one direction
   ///
@@ -154,18 +244,20 @@ pub enum EdgeDirection {
   ///
   /// We don't necessarily want this to be automatic, as the weight might be different for each
   /// direction. This is more of a tag to let the user/graph know that there is another edge going
-  /// the opposite direction.
+  /// the opposite direction. A DataSet creates, updates, and deletes that edge along with this one.
   TwoWay,
 }
 
 /// A searchable index for related nodes
 ///
+/// Edges are keyed by their guid, so a node can have several edges with the same label to one
+/// target as long as their properties differ, such as a payment for each invoice.
 #[derive(Debug, Clone)]
 pub struct EdgeMap<G>
 where
   G: Graph,
 {
-  // Look up all the targets that have this label
+  // Look up all the edges that have this label, by the edge's guid
   by_label: HashMap<String, HashMap<Uuid, Edge<G>>>,
   // by_type: HashMap<E::Edge, Arc<RwLock<Edge<G::Edge>>>>,
 }
@@ -204,27 +296,50 @@ where
     result
   }
 
+  /// Every edge with the label going to the target
+  pub fn find_to(&self, label: &str, target: &Uuid) -> Vec<Edge<G>> {
+    self
+      .find_all(label)
+      .into_iter()
+      .filter(|edge| edge.target.get_guid() == *target)
+      .collect()
+  }
+
   pub fn insert(&mut self, edge: Edge<G>) -> GraphtResult<()> {
     match self.by_label.entry(edge.get_type_label()) {
       Entry::Vacant(entry) => {
-        let mut targets = HashMap::new();
-        targets.insert(edge.get_target().get_guid(), edge);
-        // info!("Inserting new targets: {:?}", targets);
-        entry.insert(targets);
+        let mut edges = HashMap::new();
+        edges.insert(edge.get_guid(), edge);
+        entry.insert(edges);
         Ok(())
       }
-      Entry::Occupied(mut targets) => match targets.get_mut().entry(edge.target.get_guid()) {
+      Entry::Occupied(mut edges) => match edges.get_mut().entry(edge.get_guid()) {
         Entry::Vacant(entry) => {
-          // info!("Adding existing targets {:?}: {:?}", entry, edge);
           entry.insert(edge);
           Ok(())
         }
         Entry::Occupied(_) => Err(err!(
           DuplicateKey,
-          "Duplicate return value {}. Please drop the existing value before trying to add it",
-          edge.get_type_label()
+          "Duplicate {} edge {}. Please drop the existing edge before trying to add it",
+          edge.get_type_label(),
+          edge.get_guid()
         )),
       },
     }
   }
+
+  /// Drop the edge with the guid, returning it if it was in the map
+  pub fn remove(&mut self, guid: &Uuid) -> Option<Edge<G>> {
+    let label = self
+      .by_label
+      .iter()
+      .find(|(_, edges)| edges.contains_key(guid))
+      .map(|(label, _)| label.clone())?;
+    let edges = self.by_label.get_mut(&label)?;
+    let edge = edges.remove(guid);
+    if edges.is_empty() {
+      self.by_label.remove(&label);
+    }
+    edge
+  }
 }
@@ -2,7 +2,7 @@
 
 use crate::{local::*, prelude::*};
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 use uuid::Uuid;
 
 /// A trait allowing a struct to be used as a data payload for nodes and/or edges
@@ -26,6 +26,30 @@ pub trait GraphtEntity: Clone + Debug + std::hash::Hash + std::cmp::Eq + Sized {
 
   /// Deserialize the entity from a u8 array as returned by the database
   fn from_gql(value: &[u8]) -> GraphtResult<Self>;
+
+  /// Build an entity of the given type from its properties, as done by CREATE, MERGE, and SET
+  ///
+  /// By default the properties are written as a GQL map and passed to `from_gql`, so entities whose
+  /// type can't be told from their properties alone should override this.
+  fn from_properties(
+    _type_label: &str,
+    properties: BTreeMap<String, Literal>,
+  ) -> GraphtResult<Self> {
+    Self::from_gql(Literal::Map(properties).to_string().as_bytes())
+  }
+
+  /// Get all the properties of the entity, keyed by name
+  ///
+  /// By default this parses the output of `to_gql`, so it only needs to be overridden when there is
+  /// a cheaper way of getting the values.
+  fn get_properties(&self) -> GraphtResult<BTreeMap<String, Literal>> {
+    crate::gquery::grammars::cypher::parse_map(&self.to_gql()?)
+  }
+
+  /// Get a single property by name, returning Null if the entity does not have it
+  fn get_property(&self, name: &str) -> GraphtResult<Literal> {
+    Ok(self.get_properties()?.remove(name).unwrap_or(Literal::Null))
+  }
 }
 
 // ---  Primitive entities
@@ -2,6 +2,9 @@
 
 use crate::{local::*, prelude::*};
 
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 /// Define all the possible nodes and edges available in a graph
 pub trait Graph: Debug + Clone + PartialEq {
   type Node: GraphtEntity;
@@ -20,6 +23,96 @@ pub trait Graph: Debug + Clone + PartialEq {
     );
     Ok(CrudResultStats::new())
   }
+
+  /// The properties of the edge going back the other way from a TwoWay edge
+  ///
+  /// By default it is a copy of the edge. Pairs such as ParentOf and ChildOf can be swapped here,
+  /// as long as each maps back to the other.
+  fn reciprocal(edge: &Self::Edge) -> Self::Edge {
+    edge.clone()
+  }
+
+  /// Rules the nodes of the graph must follow, checked each time a node is stored or updated
+  ///
+  /// Nodes are only ever unique by their key otherwise, so values like a human readable id need a
+  /// Unique constraint to keep two nodes from sharing them.
+  fn constraints() -> Vec<Constraint> {
+    Vec::new()
+  }
+
+  /// The node types and edge labels the graph's data must fit, if it has a schema
+  ///
+  /// This is asked for each time an edge is built or a value is inserted, so it should be cheap to
+  /// build.
+  fn schema() -> Option<Schema> {
+    None
+  }
+}
+
+/// A rule on a property of the nodes with a label
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Constraint {
+  /// No two nodes with the label may have the same value for the property. Nodes without the
+  /// property don't conflict with each other.
+  Unique { label: String, property: String },
+
+  /// Every node with the label must have the property set
+  Exists { label: String, property: String },
+}
+
+impl Constraint {
+  pub fn unique(label: &str, property: &str) -> Constraint {
+    Constraint::Unique {
+      label: label.to_string(),
+      property: property.to_string(),
+    }
+  }
+
+  pub fn exists(label: &str, property: &str) -> Constraint {
+    Constraint::Exists {
+      label: label.to_string(),
+      property: property.to_string(),
+    }
+  }
+
+  /// The label of the nodes the constraint applies to
+  pub fn label(&self) -> &str {
+    match self {
+      Constraint::Unique { label, .. } | Constraint::Exists { label, .. } => label,
+    }
+  }
+
+  /// The property the constraint checks
+  pub fn property(&self) -> &str {
+    match self {
+      Constraint::Unique { property, .. } | Constraint::Exists { property, .. } => property,
+    }
+  }
+}
+
+impl Display for Constraint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Constraint::Unique { label, property } => write!(f, "UNIQUE {}.{}", label, property),
+      Constraint::Exists { label, property } => write!(f, "EXISTS {}.{}", label, property),
+    }
+  }
+}
+
+/// The details of a node breaking a constraint, carried by a ConstraintViolation error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+  pub constraint: Constraint,
+
+  /// The node breaking the constraint, followed by the stored node it conflicts with, if any
+  pub guids: Vec<Uuid>,
+}
+
+impl Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let guids: Vec<String> = self.guids.iter().map(|x| x.to_string()).collect();
+    write!(f, "{} by {}", self.constraint, guids.join(" and "))
+  }
 }
 
 /// The basic building blocks of data in a graph
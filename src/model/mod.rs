@@ -6,7 +6,7 @@ pub mod node;
 pub use node::Node;
 
 pub mod edge;
-pub use edge::{Edge, EdgeMap};
+pub use edge::{Edge, EdgeDirection, EdgeMap};
 
 pub mod path;
 pub use path::Path;
@@ -15,4 +15,7 @@ pub mod entity;
 pub use entity::GraphtEntity;
 
 pub mod graph;
-pub use graph::{Graph, GraphItem};
+pub use graph::{Constraint, Graph, GraphItem, Violation};
+
+pub mod schema;
+pub use schema::{EdgeRule, Schema};
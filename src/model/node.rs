@@ -2,12 +2,16 @@
 //!
 //!
 
-use crate::{local::*, prelude::*};
+use crate::{
+  gquery::{ast::NodePattern, builder, grammars::Dialect},
+  local::*,
+  prelude::*,
+};
 use sync::{Arc, RwLock};
 
 use std::collections::{
   hash_map::Entry,
-  {HashMap, HashSet},
+  {BTreeMap, HashMap, HashSet},
 };
 
 use uuid::Uuid;
@@ -84,6 +88,65 @@ where
     self.inner.read().unwrap().properties.clone()
   }
 
+  /// Look up a single property value by name, falling back to an annotation with the name when
+  /// the entity doesn't have it
+  pub fn get_property(&self, name: &str) -> GraphtResult<Literal> {
+    match self.get_props().get_property(name)? {
+      Literal::Null => Ok(self.get_annotation(name).unwrap_or(Literal::Null)),
+      value => Ok(value),
+    }
+  }
+
+  /// Attach a calculated value to the node, returning the value it replaced
+  ///
+  /// Annotations are shared with every copy of the node, like labels, but aren't indexed. Queries
+  /// can read them as properties.
+  pub fn annotate<V: Into<Literal>>(&mut self, name: &str, value: V) -> Option<Literal> {
+    let mut inner = self.inner.write().unwrap();
+    inner.annotations.insert(name.to_string(), value.into())
+  }
+
+  pub fn get_annotation(&self, name: &str) -> Option<Literal> {
+    self.inner.read().unwrap().annotations.get(name).cloned()
+  }
+
+  pub fn get_annotations(&self) -> BTreeMap<String, Literal> {
+    self.inner.read().unwrap().annotations.clone()
+  }
+
+  /// Replace the node's properties, unbinding it first so the DataSet's copy isn't changed
+  ///
+  /// The new properties must keep the same key, otherwise the node's guid would no longer match.
+  pub(crate) fn set_props(&mut self, props: G::Node) -> GraphtResult<()> {
+    if props.get_key() != self.guid {
+      return Err(err!(
+        InvalidItem,
+        "Cannot change the key of node {} to {}",
+        self.guid,
+        props.get_key()
+      ));
+    }
+
+    if self.is_bound() {
+      self.unbind()?;
+    }
+    self.inner.write().unwrap().properties = Arc::new(props);
+    Ok(())
+  }
+
+  /// Copy the properties and labels of another version of the node into this one, so every copy
+  /// sharing this node's contents sees the change
+  pub(crate) fn assign(&self, other: &Node<G>) {
+    let (properties, labels) = {
+      let other = other.inner.read().unwrap();
+      (other.properties.clone(), other.labels.clone())
+    };
+
+    let mut inner = self.inner.write().unwrap();
+    inner.properties = properties;
+    inner.labels = labels;
+  }
+
   /// Detaches the current copy of the node from the DataSet, so changes don't propagate
   ///
   /// Clones the inner value of the node so there is no reference to it outside of the DataSet. As
@@ -98,6 +161,8 @@ where
     let props = Arc::make_mut(&mut inner.properties.clone()).clone();
     let labels = inner.labels.clone();
     let edges = inner.edges.clone();
+    let incoming = inner.incoming.clone();
+    let annotations = inner.annotations.clone();
     drop(inner);
 
     let new_inner = Arc::new(RwLock::new(InnerNode {
@@ -105,6 +170,8 @@ where
       properties: Arc::new(props),
       labels,
       edges,
+      incoming,
+      annotations,
     }));
 
     self.inner = new_inner;
@@ -130,9 +197,14 @@ where
     self.unbind()?;
 
     // Create the new edge from current node to the target
-    let edge: Edge<G> = Edge::new(self, &target.into(), props.into());
+    let edge: Edge<G> = Edge::new(self, &target, props.into())?;
 
     self.add_edge(edge.clone())?;
+
+    // A bound target gets the edge from its DataSet when the edge is inserted
+    if !target.is_bound() && target.get_guid() != self.guid {
+      target.add_incoming(edge.clone())?;
+    }
     Ok(edge)
   }
 
@@ -143,6 +215,42 @@ where
     Ok(())
   }
 
+  /// Drop one of the node's edges, returning false if it didn't have it
+  pub(crate) fn remove_edge(&self, guid: &Uuid) -> bool {
+    let mut inner = self.inner.write().unwrap();
+    inner.edges.delete(guid).is_ok()
+  }
+
+  /// Record an edge that ends at this node
+  pub(crate) fn add_incoming(&self, edge: Edge<G>) -> GraphtResult<()> {
+    let mut inner = self.inner.write().unwrap();
+    inner.incoming.insert(&edge)?;
+    Ok(())
+  }
+
+  /// Forget an edge that ended at this node, returning false if it wasn't known
+  pub(crate) fn remove_incoming(&self, guid: &Uuid) -> bool {
+    let mut inner = self.inner.write().unwrap();
+    inner.incoming.delete(guid).is_ok()
+  }
+
+  /// Replace the edges ending at this node, such as with the ones known to a DataSet
+  pub(crate) fn set_incoming(&self, edges: Vec<Edge<G>>) -> GraphtResult<()> {
+    let mut incoming = EdgeSet::new();
+    for edge in edges {
+      incoming.insert(&edge)?;
+    }
+    self.inner.write().unwrap().incoming = incoming;
+    Ok(())
+  }
+
+  /// The edges ending at this node, limited to the label if there is one
+  ///
+  /// Bound nodes are kept up to date by their DataSet, so this doesn't need to scan for them.
+  pub fn incoming(&self, label: Option<&str>) -> Vec<Edge<G>> {
+    self.inner.read().unwrap().incoming.get_typed(label)
+  }
+
   /// Retrieve a list of all the edges connecting this node
   ///
   /// FIXME: Make the query mean something
@@ -157,6 +265,14 @@ where
       .collect()
   }
 
+  /// The node's edges going to the target, limited to the label if there is one
+  ///
+  /// Edges are keyed by their own guid, so there can be several with the same label to one target,
+  /// such as a payment for each invoice.
+  pub fn edges_to(&self, target: &Uuid, label: Option<&str>) -> Vec<Edge<G>> {
+    self.inner.read().unwrap().edges.incoming(target, label)
+  }
+
   // pub fn find_edges(&self, filter: &str) -> Vec<Edge<G>> {
   //   let inner = self.inner.read().unwrap();
   //   inner.edges.find_all(filter)
@@ -167,34 +283,43 @@ where
 
   //---   Query functionality
 
-  // Serialize the key/values into a format that GQuery recognizes
-  fn to_gql(&self, name: Option<&str>) -> GraphtResult<String> {
-    let labels = self
-      .get_labels()
+  // The pattern for creating the node, with its labels. Property values are added to the params
+  fn to_pattern(&self, name: &str, params: &mut Params) -> GraphtResult<NodePattern> {
+    let mut labels: Vec<String> = self.get_labels().into_iter().collect();
+    labels.sort();
+
+    let mut pattern = labels
       .iter()
-      .fold("".to_string(), |acc, label| format!("{}:{}", acc, label));
+      .fold(builder::node(name), |acc, label| acc.label(label));
+    for (key, value) in self.get_props().get_properties()? {
+      let param = format!("{}_{}", name, key);
+      pattern = pattern.prop(&key, builder::param(&param));
+      params.insert(&param, value);
+    }
+    Ok(pattern)
+  }
 
-    Ok(format!(
-      "({}{} {})",
-      name.unwrap_or(""),
-      labels,
-      self.get_props().to_gql()?
-    ))
+  /// Make a CREATE query for the node and all related edges and nodes, printed as OpenCypher
+  pub fn to_create(&self, max_depth: Option<u16>) -> GraphtResult<String> {
+    let (query, params) = self.to_create_query(max_depth)?;
+    let query = Dialect::Cypher.print_with(&query, &params)?;
+    info!("Built query\n{}", query);
+    Ok(query)
   }
 
-  /// make a CREATE query for all node and all related edges and nodes
+  /// Make a CREATE query for the node and all related edges and nodes, where the property values
+  /// are passed as parameters
   ///
-  /// FIXME: This needs to move into the GQuery AST. It should take a query result such as
-  /// "(nodes)->[edges *0..]" and then all the named spots are decomposed into a distinct list of
-  /// nodes, edges, and paths. From there, its a simple iteration to make the create query string.
-  pub fn to_create(&self, _max_depth: Option<u16>) -> GraphtResult<String> {
+  /// FIXME: It should take a query result such as "(nodes)->[edges *0..]" and then all the named
+  /// spots are decomposed into a distinct list of nodes, edges, and paths.
+  pub fn to_create_query(&self, _max_depth: Option<u16>) -> GraphtResult<(GQuery, Params)> {
     // Track the tags and nodes seen
     let mut counter = EntityCounter::new();
-    // All the created node strings
-    let mut nodes: Vec<String> = Vec::new();
+    // All the created node patterns
+    let mut nodes: Vec<NodePattern> = Vec::new();
+    let mut params = Params::new();
 
     // Edge info
-    // let labels = self.get_labels();
     let mut edges: Vec<Edge<G>> = Vec::new();
 
     // A queue of nodes that haven't been run yet
@@ -209,7 +334,7 @@ where
         };
 
         // Add the node to processed
-        nodes.push(node.to_gql(Some(&tag))?);
+        nodes.push(node.to_pattern(&tag, &mut params)?);
 
         // Add all the edges their targets to the unprocessed queue
         for edge in node.edges("()->[]") {
@@ -219,29 +344,34 @@ where
       }
     }
 
-    let mut query = format!("CREATE {}", nodes.pop().unwrap());
+    let mut query = GQuery::create(nodes.pop().unwrap());
     for node in nodes {
-      query = format!("{}, {}", query, node)
+      query = query.part(node);
     }
 
-    for edge in edges {
+    for (index, edge) in edges.into_iter().enumerate() {
       // Now we append all the edges
-      query = format!(
-        "{}, ({})-[:{} {}]->({})",
-        query,
-        counter
-          .get_tag("Organization", edge.get_source().get_guid())
-          .1,
-        edge.get_label(),
-        edge.get_properties().to_gql()?,
-        counter
-          .get_tag("Organization", edge.get_target().get_guid())
-          .1
+      let mut relationship = builder::out(&edge.get_label());
+      for (key, value) in edge.get_properties().get_properties()? {
+        let param = format!("edge{}_{}", index, key);
+        relationship = relationship.prop(&key, builder::param(&param));
+        params.insert(&param, value);
+      }
+
+      let source = counter
+        .get_tag("Organization", edge.get_source().get_guid())
+        .1;
+      let target = counter
+        .get_tag("Organization", edge.get_target().get_guid())
+        .1;
+      query = query.part(
+        builder::path(source.as_str())
+          .edge(relationship)
+          .node(target.as_str()),
       );
     }
 
-    info!("Built query\n{}", query);
-    Ok(query)
+    Ok((query.build(), params))
   }
 }
 
@@ -322,6 +452,13 @@ where
 
   /// A lookup for the edges that have this node as a starting point
   pub(self) edges: EdgeSet<G>,
+
+  /// A lookup for the edges that end at this node, so they can be followed backwards
+  pub(self) incoming: EdgeSet<G>,
+
+  /// Values calculated about the node, such as its component or rank, which aren't part of the
+  /// entity
+  pub(self) annotations: BTreeMap<String, Literal>,
   // Calculations based on the current values of the node and its edges
   // pub(self) aggregates: HashMap<String, Aggregate>,
 }
@@ -340,6 +477,8 @@ where
       properties: Arc::new(props),
       labels,
       edges: EdgeSet::new(),
+      incoming: EdgeSet::new(),
+      annotations: BTreeMap::new(),
     }
   }
 
@@ -57,91 +57,109 @@ where
 
   /// Get the first node in the path, if it exists
   pub fn start(&self) -> Option<Node<G>> {
-    if let Some(first) = self.steps.first() {
-      match first {
-        PathStep::Nil => None,
-        PathStep::Node(node) => Some(node.clone()),
-        PathStep::Edge(edge) => Some(edge.get_source()),
-      }
-    } else {
-      None
+    match self.steps.first() {
+      Some(PathStep::Node(node)) => Some(node.clone()),
+      _ => None,
     }
   }
 
   /// Get the final node in the path, if it exists
   pub fn end(&self) -> Option<Node<G>> {
-    if let Some(last) = self.steps.last() {
-      match last {
-        PathStep::Nil => None,
-        PathStep::Node(node) => Some(node.clone()),
-        PathStep::Edge(edge) => Some(edge.get_source()),
+    self.nodes().pop()
+  }
+
+  /// The nodes visited by the path, in order. Edges can be walked from either end, so each node
+  /// is the end of its edge that the path didn't arrive from.
+  pub fn nodes(&self) -> Vec<Node<G>> {
+    let mut nodes: Vec<Node<G>> = Vec::new();
+    for step in &self.steps {
+      match step {
+        PathStep::Nil => (),
+        PathStep::Node(node) => nodes.push(node.clone()),
+        PathStep::Edge(edge) => {
+          let last = nodes
+            .last()
+            .expect("A path always has a start node before its edges")
+            .get_guid();
+          nodes.push(match edge.get_source().get_guid() == last {
+            true => edge.get_target(),
+            false => edge.get_source(),
+          });
+        }
       }
-    } else {
-      None
     }
+    nodes
+  }
+
+  /// The edges walked by the path, in order
+  pub fn edges(&self) -> Vec<Edge<G>> {
+    self
+      .steps
+      .iter()
+      .filter_map(|step| match step {
+        PathStep::Edge(edge) => Some(edge.clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// The number of edges in the path
+  pub fn length(&self) -> usize {
+    self.edges().len()
   }
 
   /// An internal method for building a path via mutation.
+  ///
+  /// The first step is always the start node, followed by edges that each share a node with the
+  /// end of the path so far.
   fn add_step(&mut self, step: PathStep<G>) -> GraphtResult<()> {
-    let mut hasher = DefaultHasher::new();
-
-    // Get the current guid:
     match &step {
       // Nothing to be done for the identity
       PathStep::Nil => return Ok(()),
 
-      // A node can only be a single entity path, with no
-      PathStep::Node(node) => match self.steps.first() {
-        Some(PathStep::Nil) | None => {
-          assert!(
-            self.steps.len() > 1,
-            "The path must only contain a path step of Nil to add a node"
-          );
-          self.steps = vec![PathStep::Node(node.clone())];
-          hasher.write(node.get_guid().as_bytes());
-        }
-        _ => {
-          // This case doesn't make sense, as there is no edge
-          return Err(err!(
-            InvalidItem,
-            "Tried to add node {:?} to existing path {:?}",
-            step,
-            self.steps
-          ));
-        }
-      },
+      // A node can only start a path, since it doesn't say how to get there
+      PathStep::Node(_) if self.start().is_some() => {
+        return Err(err!(
+          InvalidItem,
+          "Tried to add node {:?} to existing path {:?}",
+          step,
+          self.steps
+        ));
+      }
+      PathStep::Node(_) => self.steps.clear(),
+
       PathStep::Edge(edge) => {
-        // Make sure the next edge creates a continuous path
-        match self.end() {
-          None => (),
-          Some(end) => {
-            if end.get_guid() == edge.get_source().get_guid() {
-              return Err(err!(InvalidItem, "Attempting to add a non-continuous edge to a path:\n\tCurrent End: {:?}\n\tNew Edge: {:?}", end, edge));
-            }
+        // A path of only edges starts where its first edge does
+        let end = match self.end() {
+          Some(end) => end,
+          None => {
+            self.add_step(PathStep::Node(edge.get_source()))?;
+            edge.get_source()
           }
-        }
+        };
 
-        // Clear out nil/node if they are the only step
-        match self.steps.get(0) {
-          Some(PathStep::Nil) | Some(PathStep::Node(_)) => {
-            assert!(
-              self.steps.len() > 1,
-              "Path length is not 1 yet has a first node of nil or node"
-            );
-            self.steps = Vec::new();
-            hasher.write(NIL_PATH_GUID.as_bytes());
-          }
-          _ => hasher.write(self.guid.as_bytes()),
+        // Make sure the next edge creates a continuous path
+        let ends = [edge.get_source().get_guid(), edge.get_target().get_guid()];
+        if !ends.contains(&end.get_guid()) {
+          return Err(err!(InvalidItem, "Attempting to add a non-continuous edge to a path:\n\tCurrent End: {:?}\n\tNew Edge: {:?}", end, edge));
         }
-
-        // And add the new edge
-        self.steps.push(step);
       }
     }
 
-    // Calculate the new guid
-    // path.guid = Uuid::new_v5(&Uuid::NAMESPACE_OID, &hasher.finish().to_ne_bytes());
+    // Each step extends the guid of the path before it
+    let mut hasher = DefaultHasher::new();
+    hasher.write(self.guid.as_bytes());
+    hasher.write(
+      match &step {
+        PathStep::Node(node) => node.get_guid(),
+        PathStep::Edge(edge) => edge.get_guid(),
+        PathStep::Nil => *NIL_PATH_GUID,
+      }
+      .as_bytes(),
+    );
     self.guid = Uuid::new_v5(&Uuid::NAMESPACE_OID, &hasher.finish().to_ne_bytes());
+
+    self.steps.push(step);
     Ok(())
   }
 
@@ -0,0 +1,194 @@
+//! The shape a graph's data must have
+//!
+//! A schema is optional, and only checks the labels it declares. Node types list the properties
+//! every node of the type must have. Edge labels list the pairs of node types they may connect,
+//! the properties they must have, and how many of them a node may start or end.
+//!
+//! Edges are checked as they are built by `Edge::new`, and nodes and edges again as they are
+//! inserted into a DataSet, which is also where the number of edges per node is counted.
+
+use crate::{local::*, prelude::*};
+
+use std::collections::HashMap;
+
+/// The node types and edge labels of a graph, declared by `Graph::schema`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+  /// The properties every node must have, by the node's type label
+  nodes: HashMap<String, Vec<String>>,
+
+  /// The rules for each edge label
+  edges: HashMap<String, EdgeRule>,
+}
+
+/// How an edge label may be used
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeRule {
+  label: String,
+
+  /// The pairs of source and target type labels the edge may go between. Empty allows any.
+  connects: Vec<(String, String)>,
+
+  /// The properties every edge with the label must have
+  required: Vec<String>,
+
+  /// The most edges with the label that may start at a single node
+  max_out: Option<usize>,
+
+  /// The most edges with the label that may end at a single node
+  max_in: Option<usize>,
+}
+
+impl Schema {
+  pub fn new() -> Schema {
+    Schema {
+      nodes: HashMap::new(),
+      edges: HashMap::new(),
+    }
+  }
+
+  /// Declare a node type along with the properties each node of the type must have
+  pub fn node(mut self, type_label: &str, required: &[&str]) -> Schema {
+    self.nodes.insert(
+      type_label.to_string(),
+      required.iter().map(|x| x.to_string()).collect(),
+    );
+    self
+  }
+
+  /// Declare how an edge label may be used, replacing any earlier rule for the label
+  pub fn edge(mut self, rule: EdgeRule) -> Schema {
+    self.edges.insert(rule.label.clone(), rule);
+    self
+  }
+
+  pub fn get_edge(&self, label: &str) -> Option<&EdgeRule> {
+    self.edges.get(label)
+  }
+
+  /// Check that a node has the properties its type requires, and doesn't start more edges with a
+  /// label than the label allows
+  pub fn check_node<G: Graph>(&self, node: &Node<G>) -> GraphtResult<()> {
+    for rule in self.edges.values() {
+      let count = node.edges(&rule.label).len();
+      if let Some(max) = rule.max_out.filter(|max| count > *max) {
+        return Err(err!(
+          InvalidItem,
+          "Node {} starts {} {} edges, but only {} are allowed",
+          node.get_guid(),
+          count,
+          rule.label,
+          max
+        ));
+      }
+    }
+
+    let type_label = node.type_label();
+    for property in self.nodes.get(&type_label).into_iter().flatten() {
+      if node.get_property(property)? == Literal::Null {
+        return Err(err!(
+          InvalidItem,
+          "Node {} is a {} without the required property {}",
+          node.get_guid(),
+          type_label,
+          property
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  /// Check that an edge goes between the types of nodes its label allows, and has the properties
+  /// its label requires
+  pub fn check_edge<G: Graph>(&self, edge: &Edge<G>) -> GraphtResult<()> {
+    let label = edge.get_label();
+    let rule = match self.edges.get(&label) {
+      Some(rule) => rule,
+      None => return Ok(()),
+    };
+
+    let (source, target) = (
+      edge.get_source().type_label(),
+      edge.get_target().type_label(),
+    );
+    if !rule.allows(&source, &target) {
+      return Err(err!(
+        InvalidItem,
+        "A {} edge cannot go from a {} to a {}",
+        label,
+        source,
+        target
+      ));
+    }
+
+    for property in &rule.required {
+      if edge.get_property(property)? == Literal::Null {
+        return Err(err!(
+          InvalidItem,
+          "Edge {} is a {} without the required property {}",
+          edge.get_guid(),
+          label,
+          property
+        ));
+      }
+    }
+    Ok(())
+  }
+}
+
+impl EdgeRule {
+  pub fn new(label: &str) -> EdgeRule {
+    EdgeRule {
+      label: label.to_string(),
+      connects: Vec::new(),
+      required: Vec::new(),
+      max_out: None,
+      max_in: None,
+    }
+  }
+
+  /// Allow the edge to go from nodes of the source type to nodes of the target type
+  pub fn connects(mut self, source: &str, target: &str) -> EdgeRule {
+    self.connects.push((source.to_string(), target.to_string()));
+    self
+  }
+
+  /// Require the edge to have the properties
+  pub fn required(mut self, properties: &[&str]) -> EdgeRule {
+    self.required = properties.iter().map(|x| x.to_string()).collect();
+    self
+  }
+
+  /// Limit how many edges with the label may start at a node (eg. 1 for a single ChildOf parent)
+  pub fn max_out(mut self, max: usize) -> EdgeRule {
+    self.max_out = Some(max);
+    self
+  }
+
+  /// Limit how many edges with the label may end at a node
+  pub fn max_in(mut self, max: usize) -> EdgeRule {
+    self.max_in = Some(max);
+    self
+  }
+
+  pub fn get_label(&self) -> &str {
+    &self.label
+  }
+
+  pub fn get_max_out(&self) -> Option<usize> {
+    self.max_out
+  }
+
+  pub fn get_max_in(&self) -> Option<usize> {
+    self.max_in
+  }
+
+  /// Check if the edge may go from the source type to the target type
+  pub fn allows(&self, source: &str, target: &str) -> bool {
+    self.connects.is_empty()
+      || self
+        .connects
+        .iter()
+        .any(|(from, to)| from == source && to == target)
+  }
+}
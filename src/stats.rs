@@ -2,10 +2,10 @@
 //!
 //! This is used both as the result of a query and of a static data set
 
-use crate::{errors::GraphtError, local::*, utils::*};
+use crate::{errors::GraphtError, gquery::Literal, local::*, utils::*};
 
 use std::{
-  collections::{hash_map::Entry, HashMap},
+  collections::{hash_map::Entry, BTreeMap, HashMap},
   ops::{Add, AddAssign},
 };
 
@@ -42,6 +42,11 @@ impl StatCount {
     StatCount { count: 0 }
   }
 
+  /// The current value of the counter
+  pub fn count(&self) -> u128 {
+    self.count
+  }
+
   /// Increment the counter by 1
   pub fn incr(&mut self) {
     self.count += 1;
@@ -143,6 +148,11 @@ where
       data: HashMap::new(),
     }
   }
+
+  /// The statistic kept for the key, if anything has been recorded for it
+  pub fn get(&self, key: &T) -> Option<&U> {
+    self.data.get(key)
+  }
 }
 
 impl<T, U, I> Stats for StatMap<T, U, I>
@@ -226,6 +236,29 @@ where
     self.data.diff(&rhs.data, name)
   }
 }
+/// Count the properties that were given a new value and the ones that were removed between two
+/// versions of an entity, like RedisGraph's "Properties set" and "Properties removed"
+pub(crate) fn property_changes(
+  old: &BTreeMap<String, Literal>,
+  new: &BTreeMap<String, Literal>,
+) -> (
+  StatMap<String, StatCount, i128>,
+  StatMap<String, StatCount, i128>,
+) {
+  let mut set = StatMap::new();
+  for (key, value) in new {
+    if old.get(key) != Some(value) {
+      set.increase((key.clone(), 1));
+    }
+  }
+
+  let mut removed = StatMap::new();
+  for key in old.keys().filter(|key| !new.contains_key(*key)) {
+    removed.increase((key.clone(), 1));
+  }
+  (set, removed)
+}
+
 /// CRUD operation statistics created by wrapping existing statistics by mutation type
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CrudResultStats<T>
@@ -292,7 +325,7 @@ where
   }
 
   pub fn add_read(&mut self, stats: T) {
-    if let Some(s) = &mut self.created {
+    if let Some(s) = &mut self.read {
       *s += stats;
     } else {
       self.read = Some(stats);
@@ -307,6 +340,10 @@ where
     }
   }
 
+  pub fn read(&self) -> Option<T> {
+    self.read.clone()
+  }
+
   pub fn updated(&self) -> Option<T> {
     self.updated.clone()
   }
@@ -331,6 +368,21 @@ where
     }
   }
 
+  /// Convert each group of stats into a wider type, such as NodeStats into DataSetStats
+  pub fn convert<U>(self) -> CrudResultStats<U>
+  where
+    U: Stats + Add<Output = U> + Default,
+    T: Into<U>,
+  {
+    CrudResultStats {
+      created: self.created.map(Into::into),
+      read: self.read.map(Into::into),
+      updated: self.updated.map(Into::into),
+      deleted: self.deleted.map(Into::into),
+      errors: self.errors,
+    }
+  }
+
   /// Diff two sets of stats and throw an error if they are not the same
   ///
   /// This is primarily for testing, but handy to have integrated into the live code.
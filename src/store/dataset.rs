@@ -11,15 +11,12 @@
 //!
 //! TODO:
 //! - Ordered subsets
-//! - get_mut/update: Create a trait for getting a node from the set with a commit() function that
-//!   automatically pushes a patch into the set
 //! - Research/Add benchmarking to test optimizations
-//! - Lazy indices (build on demand)
 
-use crate::{local::*, prelude::*};
+use crate::{local::*, prelude::*, stats::property_changes};
 
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
   ops::{Add, AddAssign},
 }; // , VecDeque};
 
@@ -46,8 +43,8 @@ where
   /// All the vertices known to this set mapped by its guid
   edges: EdgeSet<G>,
 
-  // / Generic indices which apply to any/all of the values in the DataSet
-  // indices: Indices<G>,
+  /// Generic indices which apply to any/all of the values in the DataSet
+  indices: Indices<G>,
 
   // -----    Thinking/YAGNI items
   /// Statistics about the current DataSet
@@ -80,15 +77,94 @@ where
       guid: Uuid::new_v4(),
       nodes: NodeSet::new(),
       edges: EdgeSet::new(),
-      // indices: Indices::new(),
+      indices: Indices::new(),
       _stats: (),
       _diff: (),
     }
   }
 
   /// Basic database query, returning results in iterable lists
-  pub fn query(&self, _query: &str) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
-    todo!()
+  ///
+  /// Each column named in the RETURN clause is a key in the map, and each matched row adds one
+  /// value to every column. A query starting with EXPLAIN or PROFILE returns the lines of its plan
+  /// in a "plan" column instead, like RedisGraph's GRAPH.EXPLAIN and GRAPH.PROFILE.
+  pub fn query(&self, query: &str) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    self.query_with(query, &Params::new())
+  }
+
+  /// Run a query using the values in params for its $parameters
+  pub fn query_with(
+    &self,
+    query: &str,
+    params: &Params,
+  ) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    match PlanMode::split(query) {
+      (Some(PlanMode::Explain), query) => Ok(self.explain(query)?.to_columns()),
+      (Some(PlanMode::Profile), query) => {
+        Ok(query::profile(self, &GQuery::parse(query)?, params)?.to_columns())
+      }
+      (None, query) => self.execute_with(&GQuery::parse(query)?, params),
+    }
+  }
+
+  /// Describe the operators that would run a query, without running it
+  pub fn explain(&self, query: &str) -> GraphtResult<Operator> {
+    let (_, query) = PlanMode::split(query);
+    Ok(query::explain(self, &GQuery::parse(query)?))
+  }
+
+  /// Run a query, returning the operators that ran it along with the rows each produced, the items
+  /// each read from an index, and the time each took
+  pub fn profile(&self, query: &str) -> GraphtResult<Operator> {
+    let (_, query) = PlanMode::split(query);
+    query::profile(self, &GQuery::parse(query)?, &Params::new())
+  }
+
+  /// Run an already parsed query against the DataSet
+  pub fn execute(&self, query: &GQuery) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    self.execute_with(query, &Params::new())
+  }
+
+  /// Run an already parsed query with values for its $parameters. Parsing once and running with
+  /// different params avoids building the query text for each set of values.
+  pub fn execute_with(
+    &self,
+    query: &GQuery,
+    params: &Params,
+  ) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    query::execute(self, query, params)
+  }
+
+  /// Run a query that changes the DataSet with CREATE, MERGE, SET, REMOVE, or DELETE clauses
+  ///
+  /// The stats count what the query created, updated, and deleted, like the statistics returned
+  /// by RedisGraph. Any RETURN clause ends the query without its rows being collected, so use
+  /// `query` to read the changes afterwards.
+  pub fn query_mut(&mut self, query: &str) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    self.query_mut_with(query, &Params::new())
+  }
+
+  /// Run a changing query using the values in params for its $parameters
+  pub fn query_mut_with(
+    &mut self,
+    query: &str,
+    params: &Params,
+  ) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    self.execute_mut_with(&GQuery::parse(query)?, params)
+  }
+
+  /// Run an already parsed query that changes the DataSet
+  pub fn execute_mut(&mut self, query: &GQuery) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    self.execute_mut_with(query, &Params::new())
+  }
+
+  /// Run an already parsed query that changes the DataSet, with values for its $parameters
+  pub fn execute_mut_with(
+    &mut self,
+    query: &GQuery,
+    params: &Params,
+  ) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    update::execute(self, query, params)
   }
 
   /// Create a new DataSet that matches the values matching the query
@@ -99,33 +175,74 @@ where
     todo!()
   }
 
-  /// Return a list of all the nodes included in the DataSet
-  pub fn nodes(&self, _query: &str) -> GraphtResult<Vec<Node<G>>> {
-    let mut matches = Vec::new();
-    for node in self.nodes.into_iter() {
-      matches.push(node.clone())
+  /// Return a list of the nodes in the DataSet matching the query
+  ///
+  /// An empty query returns every node, otherwise all the distinct nodes found in the returned
+  /// columns are included.
+  pub fn nodes(&self, query: &str) -> GraphtResult<Vec<Node<G>>> {
+    if query.trim().is_empty() {
+      return Ok(self.nodes.into_iter().cloned().collect());
     }
-    Ok(matches)
+
+    let results = self.query(query)?;
+    Ok(nodeset::collect_nodes(results))
   }
 
   pub fn get_guid(&self) -> Uuid {
     self.guid.clone()
   }
 
+  /// Find a shortest path between two nodes in the DataSet, following the edges allowed by opts
+  ///
+  /// Paths are found by counting hops unless opts is weighted. None is returned when the target
+  /// can't be reached, and either node not being in the DataSet is a NotFound error.
+  pub fn shortest_path(
+    &self,
+    from: &Uuid,
+    to: &Uuid,
+    opts: &PathOpts<G>,
+  ) -> GraphtResult<Option<Path<G>>> {
+    let (from, to) = self.path_ends(from, to)?;
+    shortest::shortest_path(self, &from, &to, opts)
+  }
+
+  /// Find every path tied for the shortest between two nodes in the DataSet
+  pub fn all_shortest_paths(
+    &self,
+    from: &Uuid,
+    to: &Uuid,
+    opts: &PathOpts<G>,
+  ) -> GraphtResult<Vec<Path<G>>> {
+    let (from, to) = self.path_ends(from, to)?;
+    shortest::all_shortest_paths(self, &from, &to, opts)
+  }
+
+  fn path_ends(&self, from: &Uuid, to: &Uuid) -> GraphtResult<(Node<G>, Node<G>)> {
+    let get = |guid: &Uuid| match self.nodes.get(guid) {
+      Some(node) => Ok(node.clone()),
+      None => Err(err!(
+        NotFound,
+        "Could not get Node with Uuid {} in the DataSet {} for a shortest path",
+        guid,
+        self.guid
+      )),
+    };
+    Ok((get(from)?, get(to)?))
+  }
+
   /// Add a value and all its related values (properties, edges, etc) to a graph
   ///
   /// The primary purpose of this insert is to make sure the value is indexed properly within the
-  /// dataset. Errors are caused when finding mutated properties.
-  /// THINK:
-  /// - When should each node be hashed/diffed on insert so we can determine if there has been a
-  ///   change? (use/update Patchwork library)
-  /// - How are updated nodes handled?
-  /// - If an edge is added,
+  /// dataset. Values that are already stored are compared with the stored copy, so changes are
+  /// counted as updates and identical values as read.
   ///
-  /// FIXME:
-  /// - When edges are added to the
+  /// Nodes and edges that don't fit the Graph's schema are rejected as an InvalidItem, including
+  /// edges that would give a node more edges with a label than the schema allows. The whole value
+  /// is checked against the schema and the Graph's constraints before anything is stored, so a
+  /// rejected value leaves the DataSet as it was.
   pub fn insert(&mut self, value: Value<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
     let mut stats = CrudResultStats::<DataSetStats>::new();
+    self.check_insert(&value, &[])?;
 
     // To protect against stack overflows during recursion, we use a while loop containing all the
     // new nodes
@@ -143,141 +260,735 @@ where
       match value {
         // Only need to add the one node
         Value::Node(node) => {
-          // Insert the node
+          // Insert the node, or compare it with the stored copy if it is already known
           match self.nodes.insert(&node) {
-            // TODO: This is where we need to check if the items are actually different
             Err(err) => match err.is(Kind::DuplicateKey) {
-              true => continue,
+              true => stats += self.refresh_node(&node)?,
               false => return Err(err),
             },
             Ok(value_stats) => {
               if let Some(values) = value_stats.created() {
                 stats.add_created(values.into());
               }
+
+              // The stored node only knows about the incoming edges that are in the DataSet
+              if let Some(stored) = self.nodes.get(&node.get_guid()) {
+                stored.set_incoming(self.edges.incoming(&node.get_guid(), None))?;
+                let indexed = self.indices.index_value(&stored.clone().into())?;
+                if indexed != IndexStats::new() {
+                  stats.add_created(indexed.into());
+                }
+              }
             }
           }
 
-          // Add the edge and it's target for processing
+          // Add the edges for processing, which will add any targets that are new
           for edge in node.edges("") {
-            unprocessed.push(edge.get_target().into());
             unprocessed.push(edge.into())
           }
         }
 
         Value::Edge(edge) => {
-          // Skip if the edge already exists if the edge already exists.
+          // Compare a repeated edge with the stored copy, rather than adding it again
           if self.edges.contains(&edge.get_guid()) {
-            debug!(
-              "Skipping repeat edge {} {}",
-              edge.get_type_label(),
-              edge.get_guid()
-            );
+            stats += self.refresh_edge(&edge)?;
             continue;
           };
 
-          // Add the target to be processed
-          let target = edge.get_target();
-          unprocessed.push(target.into());
-
-          // Add the edge to the source node if it exists, otherwise just add to unprocessed
+          // Add the edge to the source node if it exists, otherwise process the source first and
+          // come back to the edge
           let source = edge.get_source();
           match self.nodes.get(&source.get_guid()) {
             Some(node) => {
               debug!("Adding the edge to the source node");
               if let Err(err) = node.add_edge(edge.clone()) {
                 match err.is(Kind::DuplicateKey) {
-                  true => {
-                    debug!("Couldn't add the edge because it already known by the node");
-                    continue;
-                  }
+                  true => debug!("The edge is already known by the node"),
                   false => return Err(err),
                 }
               };
-
-              // self.update_node_unchecked(node.clone())?;
             }
             None => {
+              unprocessed.push(edge.into());
               unprocessed.push(source.into());
               continue;
             }
           };
+
+          // Add the target to be processed, or let it know about the edge if it is already stored
+          let target = edge.get_target();
+          match self.nodes.get(&target.get_guid()) {
+            Some(node) => node.add_incoming(edge.clone())?,
+            None => unprocessed.push(target.into()),
+          }
+
+          // And index the edge so it can be found without going through the source
+          let edge_stats = self.edges.insert(&edge)?;
+          stats.add_created(edge_stats.into());
+          let indexed = self.indices.index_value(&edge.clone().into())?;
+          if indexed != IndexStats::new() {
+            stats.add_created(indexed.into());
+          }
+
+          // A TwoWay edge brings the edge going back along with it
+          if let Some(reciprocal) = edge.reciprocal() {
+            if !self.edges.contains(&reciprocal.get_guid()) {
+              unprocessed.push(reciprocal.into());
+            }
+          }
           debug!(
             "Finished adding the edge. Still have {} unprocessed",
             unprocessed.len()
           );
         }
 
-        // Simply convert the path to edges and trailing node and let it be processed normally
-        Value::Path(_) => todo!("Cannot insert paths yet"),
+        // Simply convert the path to its start node and edges and let them be processed normally
+        Value::Path(path) => {
+          unprocessed.extend(path.edges().into_iter().map(Value::Edge));
+          unprocessed.extend(path.start().map(Value::Node));
+        }
+
+        // Each item is inserted on its own
+        Value::List(values) => unprocessed.extend(values),
+
+        Value::Literal(value) => {
+          return Err(err!(
+            InvalidItem,
+            "Only nodes, edges, and paths can be inserted into a DataSet, not {}",
+            value
+          ))
+        }
       };
     }
     Ok(stats)
   }
 
-  // /// Insert a single node into the graph
-  // pub fn insert_node(&mut self, node: Node<G>) -> GraphtResult<Stats> {
-  //   let mut stats = Stats::new();
-  //   match self.nodes.entry(node.get_guid()) {
-  //     Entry::Vacant(entry) => {
-  //       debug!("Vacant node");
-  //       let mut node = node.deep_clone()?;
-  //       node.set_bound(true);
-  //       entry.insert(node);
-  //       stats.nodes.created = 1;
-  //     }
-  //     Entry::Occupied(_) => {
-  //       return Err(err!(
-  //         DuplicateKey,
-  //         "Node with Uuid {} already exists in the graph {}",
-  //         node.get_guid(),
-  //         self.guid
-  //       ));
-  //     }
-  //   };
-
-  //   stats += self.indices.index_value(Value::Node(node))?;
-  //   Ok(stats)
-  // }
-
-  // /// Update the properties, edges, and indices of a node and return the stats of the changes
-  // pub fn update_node(&mut self, _node: Node<G>) -> GraphtResult<Stats> {
-  //   todo!()
-  // }
-
-  // /// Replace an existing node with the current one without internal validation
-  // pub(crate) fn update_node_unchecked(&mut self, node: Node<G>) -> GraphtResult<()> {
-  //   debug!("Updating the node, unchecked");
-  //   // let mut stats = Stats::new();
-  //   match self.nodes.entry(node.get_guid()) {
-  //     Entry::Vacant(_) => {
-  //       return Err(err!(
-  //         NotFound,
-  //         "Could not get Node with Uuid {} in the graph {} for updating",
-  //         node.get_guid(),
-  //         self.guid
-  //       ))
-  //     }
-  //     Entry::Occupied(mut entry) => {
-  //       debug!("Found it occupied. Cloning and inserting");
-  //       // FIXME: This needs to be much more granular than a simple replacement, since the indices
-  //       // won't be updated
-  //       let mut node = node.deep_clone()?;
-  //       node.set_bound(true);
-  //       entry.insert(node);
-  //     }
-  //   };
-  //   Ok(())
-  // }
+  /// Check everything an insert of the value would add or change, without changing anything
+  ///
+  /// This walks the value the same way the insert does, collecting the nodes it would store or
+  /// update and the edges it would create, so they can be checked as a group. Edges in removed are
+  /// counted as already gone, for changes that delete some edges before inserting the value.
+  pub(crate) fn check_insert(&self, value: &Value<G>, removed: &[Uuid]) -> GraphtResult<()> {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut seen = HashSet::new();
+
+    let mut unprocessed = vec![value.clone()];
+    while let Some(value) = unprocessed.pop() {
+      match value {
+        Value::Node(node) => {
+          if seen.insert(node.get_guid()) {
+            unprocessed.extend(node.edges("").into_iter().map(Value::Edge));
+            nodes.push(node);
+          }
+        }
+        Value::Edge(edge) => {
+          if !seen.insert(edge.get_guid()) {
+            continue;
+          }
+          if let Some(reciprocal) = edge.reciprocal() {
+            unprocessed.push(reciprocal.into());
+          }
+
+          // Stored edges are refreshed rather than created, and their stored ends are left alone
+          if self.edges.contains(&edge.get_guid()) {
+            continue;
+          }
+          for end in [edge.get_source(), edge.get_target()] {
+            if !self.nodes.contains(&end.get_guid()) {
+              unprocessed.push(end.into());
+            }
+          }
+          edges.push(edge);
+        }
+        Value::Path(path) => {
+          unprocessed.extend(path.edges().into_iter().map(Value::Edge));
+          unprocessed.extend(path.start().map(Value::Node));
+        }
+        Value::List(values) => unprocessed.extend(values),
+        Value::Literal(value) => {
+          return Err(err!(
+            InvalidItem,
+            "Only nodes, edges, and paths can be inserted into a DataSet, not {}",
+            value
+          ))
+        }
+      }
+    }
+
+    if let Some(schema) = G::schema() {
+      for node in &nodes {
+        schema.check_node(node)?;
+      }
+      for edge in &edges {
+        self.check_edge(&schema, edge, &edges, removed)?;
+      }
+    }
+    self.nodes.check_all(&nodes)
+  }
+
+  /// Check an edge against the schema, counting the edges with its label already stored at either
+  /// end along with the others being added with it
+  fn check_edge(
+    &self,
+    schema: &Schema,
+    edge: &Edge<G>,
+    added: &[Edge<G>],
+    removed: &[Uuid],
+  ) -> GraphtResult<()> {
+    schema.check_edge(edge)?;
+    let (guid, label) = (edge.get_guid(), edge.get_label());
+    let rule = match schema.get_edge(&label) {
+      Some(rule) => rule,
+      None => return Ok(()),
+    };
+
+    let (source, target) = (edge.get_source().get_guid(), edge.get_target().get_guid());
+    let others: Vec<&Edge<G>> = added
+      .iter()
+      .filter(|x| x.get_guid() != guid && x.get_label() == label)
+      .collect();
+    let kept = |x: &&Edge<G>| {
+      x.get_label() == label
+        && self.edges.contains(&x.get_guid())
+        && !removed.contains(&x.get_guid())
+    };
+    let stored = match self.nodes.get(&source) {
+      Some(node) => node.edges("").iter().filter(kept).count(),
+      None => 0,
+    };
+    let outgoing = stored
+      + others
+        .iter()
+        .filter(|x| x.get_source().get_guid() == source)
+        .count();
+    let incoming = self
+      .edges
+      .incoming(&target, Some(&label))
+      .iter()
+      .filter(kept)
+      .count()
+      + others
+        .iter()
+        .filter(|x| x.get_target().get_guid() == target)
+        .count();
+    for (count, max, end) in [
+      (outgoing, rule.get_max_out(), "start at"),
+      (incoming, rule.get_max_in(), "end at"),
+    ] {
+      if let Some(max) = max.filter(|max| count >= *max) {
+        return Err(err!(
+          InvalidItem,
+          "Edge {} would make more than {} {} edges {} one node",
+          guid,
+          max,
+          label,
+          end
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  /// Update the stored copy of a node being inserted again, if anything about it has changed
+  ///
+  /// Nodes with the same properties and labels are counted as read. Only the edges being inserted
+  /// are compared, so edges the stored copy has that the new one doesn't are kept.
+  fn refresh_node(&mut self, node: &Node<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let stats = self.update_node(node)?;
+    if stats.updated().is_some() || stats.deleted().is_some() {
+      debug!("Updated the changed node {}", node.get_guid());
+      return Ok(stats);
+    }
+
+    debug!("Node {} is unchanged", node.get_guid());
+    let mut read = NodeStats::new();
+    read.total.increase(1);
+    read.typed.increase((node.type_label(), 1));
+
+    let mut stats = CrudResultStats::new();
+    stats.add_read(read.into());
+    Ok(stats)
+  }
+
+  /// Update the stored copy of an edge being inserted again, if its properties or weight changed
+  fn refresh_edge(&mut self, edge: &Edge<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let stats = self.update_edge(edge)?;
+    if stats.updated().is_some() || stats.deleted().is_some() {
+      debug!("Updated the changed edge {}", edge.get_guid());
+      return Ok(stats);
+    }
+
+    debug!(
+      "Edge {} {} is unchanged",
+      edge.get_type_label(),
+      edge.get_guid()
+    );
+    let mut read = EdgeStats::new();
+    read.total.increase(1);
+    read.typed.increase((edge.get_type_label(), 1));
+
+    let mut stats = CrudResultStats::new();
+    stats.add_read(read.into());
+    Ok(stats)
+  }
+
+  /// Look up a single edge by its guid
+  pub(crate) fn get_edge(&self, guid: &Uuid) -> Option<Edge<G>> {
+    self.edges.get_edge(guid).cloned()
+  }
+
+  /// Update the stored copies of the values, matched by their guids
+  ///
+  /// Nodes have their properties and labels replaced, and edges are swapped for the new version.
+  /// Paths and lists update each of their items. Values that aren't stored yet are NotFound, as
+  /// they need to be inserted instead.
+  pub fn update(&mut self, value: Value<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    match value {
+      Value::Node(node) => stats += self.update_node(&node)?,
+      Value::Edge(edge) => stats += self.update_edge(&edge)?,
+      Value::Path(path) => {
+        for node in path.nodes() {
+          stats += self.update_node(&node)?;
+        }
+        for edge in path.edges() {
+          stats += self.update_edge(&edge)?;
+        }
+      }
+      Value::List(values) => {
+        for value in values {
+          stats += self.update(value)?;
+        }
+      }
+      Value::Literal(value) => {
+        return Err(err!(
+          InvalidItem,
+          "Only nodes, edges, and paths can be updated in a DataSet, not {}",
+          value
+        ))
+      }
+    }
+    Ok(stats)
+  }
+
+  /// Remove the values from the DataSet, matched by their guids
+  ///
+  /// Nodes that still have edges are only removed when detach is set, which removes the edges as
+  /// well. A path has its edges removed before its nodes, so it can be deleted without detaching.
+  pub fn delete(
+    &mut self,
+    value: Value<G>,
+    detach: bool,
+  ) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    match value {
+      Value::Node(node) => stats += self.delete_node(&node.get_guid(), detach)?,
+      Value::Edge(edge) => stats += self.delete_edge(&edge.get_guid())?,
+      Value::Path(path) => {
+        for edge in path.edges() {
+          if self.edges.contains(&edge.get_guid()) {
+            stats += self.delete_edge(&edge.get_guid())?;
+          }
+        }
+        // Paths can pass through the same node more than once
+        for node in path.nodes() {
+          if self.nodes.contains(&node.get_guid()) {
+            stats += self.delete_node(&node.get_guid(), detach)?;
+          }
+        }
+      }
+      Value::List(values) => {
+        for value in values {
+          stats += self.delete(value, detach)?;
+        }
+      }
+      Value::Literal(value) => {
+        return Err(err!(
+          InvalidItem,
+          "Only nodes, edges, and paths can be deleted from a DataSet, not {}",
+          value
+        ))
+      }
+    }
+    Ok(stats)
+  }
+
+  /// Replace the properties and labels of a stored node with those of the given copy
+  pub fn update_node(&mut self, node: &Node<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    if let Some(schema) = G::schema() {
+      schema.check_node(node)?;
+    }
+    let mut stats = self.nodes.update(node)?.convert();
+
+    // The stored node was changed in place, so re-file it under its new properties and labels
+    if let Some(stored) = self.nodes.get(&node.get_guid()) {
+      stats += self.indices.update_value(&stored.clone().into())?.convert();
+    }
+    Ok(stats)
+  }
+
+  /// Swap a stored edge for a new version with the same guid, such as one with another weight
+  ///
+  /// The copies held by the nodes at either end are swapped as well.
+  ///
+  /// A TwoWay edge keeps its reciprocal in step, creating it when the edge becomes TwoWay and
+  /// deleting it when the edge becomes OneWay.
+  pub fn update_edge(&mut self, edge: &Edge<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let stored = self.get_edge(&edge.get_guid());
+    let mut stats = self.swap_edge(edge)?;
+
+    match (stored.and_then(|x| x.reciprocal()), edge.reciprocal()) {
+      (_, Some(reciprocal)) if self.edges.contains(&reciprocal.get_guid()) => {
+        stats += self.swap_edge(&reciprocal)?
+      }
+      (_, Some(reciprocal)) => stats += self.insert(reciprocal.into())?,
+      (Some(reciprocal), None) if self.edges.contains(&reciprocal.get_guid()) => {
+        stats += self.remove_edge(&reciprocal.get_guid())?
+      }
+      _ => (),
+    }
+    Ok(stats)
+  }
+
+  /// Swap the stored copies of a single edge for the new version
+  fn swap_edge(&mut self, edge: &Edge<G>) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = self.edges.update(edge)?.convert();
+    stats += self.indices.update_value(&edge.clone().into())?.convert();
+
+    let guid = edge.get_guid();
+    if let Some(source) = self.nodes.get(&edge.get_source().get_guid()) {
+      source.remove_edge(&guid);
+      source.add_edge(edge.clone())?;
+    }
+    if let Some(target) = self.nodes.get(&edge.get_target().get_guid()) {
+      target.remove_incoming(&guid);
+      target.add_incoming(edge.clone())?;
+    }
+    Ok(stats)
+  }
+
+  /// Swap an edge for a new version of it between the same nodes
+  ///
+  /// An edge's guid depends on its properties, so changing them means removing the old edge and
+  /// adding the new one. This is counted as an update rather than a delete and a create.
+  pub fn replace_edge(
+    &mut self,
+    old: &Edge<G>,
+    new: Edge<G>,
+  ) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    let (set, removed) = property_changes(
+      &old.get_properties().get_properties()?,
+      &new.get_properties().get_properties()?,
+    );
+    if set == StatMap::new() && removed == StatMap::new() {
+      return Ok(stats);
+    }
+
+    if old.get_guid() == new.get_guid() {
+      return self.update_edge(&new);
+    }
+    if self.edges.contains(&new.get_guid()) {
+      return Err(err!(
+        DuplicateKey,
+        "Cannot update edge {}, since the updated edge {} already exists",
+        old.get_guid(),
+        new.get_guid()
+      ));
+    }
+    self.delete_edge(&old.get_guid())?;
+    self.insert(new.into())?;
+
+    let mut updated = EdgeStats::new();
+    updated.total.increase(1);
+    updated.properties = set;
+    stats.add_updated(updated.into());
+    if removed != StatMap::new() {
+      let mut deleted = EdgeStats::new();
+      deleted.properties = removed;
+      stats.add_deleted(deleted.into());
+    }
+    Ok(stats)
+  }
+
+  /// Remove a node from the DataSet
+  ///
+  /// A node that still has edges is only removed when detach is set, which removes its edges as
+  /// well. Otherwise the edges would be left pointing at nothing.
+  pub fn delete_node(
+    &mut self,
+    guid: &Uuid,
+    detach: bool,
+  ) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let node = match self.nodes.get(guid) {
+      Some(node) => node.clone(),
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Node with Uuid {} in the DataSet {} for deleting",
+          guid,
+          self.guid
+        ))
+      }
+    };
+
+    // Self loops are both outgoing and incoming, but only need deleting once
+    let mut edges = node.edges("");
+    edges.extend(
+      self
+        .edges
+        .incoming(guid, None)
+        .into_iter()
+        .filter(|edge| edge.get_source().get_guid() != *guid),
+    );
+    if !edges.is_empty() && !detach {
+      return Err(err!(
+        InvalidItem,
+        "Cannot delete node {} while it still has {} edges. Use DETACH DELETE to delete them too",
+        guid,
+        edges.len()
+      ));
+    }
+
+    let mut stats = CrudResultStats::new();
+    for edge in edges {
+      // The reciprocal of a TwoWay edge goes with it
+      if self.edges.contains(&edge.get_guid()) {
+        stats += self.delete_edge(&edge.get_guid())?;
+      }
+    }
+    stats.add_deleted(self.nodes.delete(guid)?.into());
+    let unindexed = self.indices.remove_value(guid);
+    if unindexed != IndexStats::new() {
+      stats.add_deleted(unindexed.into());
+    }
+    Ok(stats)
+  }
+
+  /// Remove an edge from the DataSet and from the nodes at either end
+  ///
+  /// Deleting either edge of a TwoWay pair deletes the other one as well.
+  pub fn delete_edge(&mut self, guid: &Uuid) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let reciprocal = self.get_edge(guid).and_then(|edge| edge.reciprocal());
+    let mut stats = self.remove_edge(guid)?;
+    if let Some(reciprocal) = reciprocal {
+      if self.edges.contains(&reciprocal.get_guid()) {
+        stats += self.remove_edge(&reciprocal.get_guid())?;
+      }
+    }
+    Ok(stats)
+  }
+
+  /// Remove a single edge from the DataSet and the nodes at either end
+  fn remove_edge(&mut self, guid: &Uuid) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let edge = match self.edges.get_edge(guid) {
+      Some(edge) => edge.clone(),
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Edge with Uuid {} in the DataSet {} for deleting",
+          guid,
+          self.guid
+        ))
+      }
+    };
+    if let Some(source) = self.nodes.get(&edge.get_source().get_guid()) {
+      source.remove_edge(guid);
+    }
+    if let Some(target) = self.nodes.get(&edge.get_target().get_guid()) {
+      target.remove_incoming(guid);
+    }
+
+    let mut stats = CrudResultStats::new();
+    stats.add_deleted(self.edges.delete(guid)?.into());
+    let unindexed = self.indices.remove_value(guid);
+    if unindexed != IndexStats::new() {
+      stats.add_deleted(unindexed.into());
+    }
+    Ok(stats)
+  }
+
+  /// Check if a node or edge with the guid is in the DataSet
+  pub fn contains(&self, guid: &Uuid) -> bool {
+    self.nodes.contains(guid) || self.edges.contains(guid)
+  }
+
+  /// Open a handle for changing a stored node, which only changes the DataSet when committed
+  pub fn get_mut(&mut self, guid: &Uuid) -> GraphtResult<NodeEdit<'_, G>> {
+    let stored = match self.nodes.get(guid) {
+      Some(node) => node.clone(),
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Node with Uuid {} in the DataSet {} for editing",
+          guid,
+          self.guid
+        ))
+      }
+    };
+    NodeEdit::new(self, stored)
+  }
+
+  /// Declare an index, filling it from the nodes and edges already in the DataSet
+  ///
+  /// Property and sorted indices are kept in step as values are inserted, updated, and deleted,
+  /// and are used by queries that match a node by an exact property value. Tags start empty.
+  pub fn create_index(&mut self, index: Index) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let created = self.indices.create(index, self.values())?;
+
+    let mut stats = CrudResultStats::new();
+    stats.add_created(created.into());
+    Ok(stats)
+  }
+
+  /// Declare an index that is only built the first time a query would seek from it
+  ///
+  /// Once built it is kept in step like any other index, until the built lazy indices outgrow the
+  /// budget set by `set_index_budget` and the least recently used ones are dropped. A dropped
+  /// index is rebuilt by the next query that needs it.
+  pub fn create_lazy_index(&mut self, index: Index) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    stats.add_created(self.indices.create_lazy(index)?.into());
+    Ok(stats)
+  }
+
+  /// Limit the estimated bytes held by the built lazy indices, or lift the limit with None
+  pub fn set_index_budget(&mut self, budget: Option<usize>) {
+    self.indices.set_budget(budget)
+  }
+
+  /// Every node and edge in the DataSet, for filling an index
+  fn values(&self) -> Vec<Value<G>> {
+    let nodes = self.nodes.into_iter().cloned().map(Value::Node);
+    let edges = self.edges.get_typed(None).into_iter().map(Value::Edge);
+    nodes.chain(edges).collect()
+  }
+
+  /// Remove an index and all its entries
+  pub fn drop_index(&mut self, index: &Index) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    stats.add_deleted(self.indices.drop(index)?.into());
+    Ok(stats)
+  }
+
+  pub fn get_index(&self, index: &Index) -> Option<&Lookup<G>> {
+    self.indices.get(index)
+  }
+
+  /// Add a stored node or edge to a tag, creating the tag if it is new
+  pub fn tag(&mut self, name: &str, guid: &Uuid) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let value = match (self.nodes.get(guid), self.edges.get_edge(guid)) {
+      (Some(node), _) => Value::Node(node.clone()),
+      (_, Some(edge)) => Value::Edge(edge.clone()),
+      _ => {
+        return Err(err!(
+          NotFound,
+          "Could not get a value with Uuid {} in the DataSet {} for tagging",
+          guid,
+          self.guid
+        ))
+      }
+    };
+
+    let mut stats = CrudResultStats::new();
+    stats.add_created(self.indices.tag(name, value)?.into());
+    Ok(stats)
+  }
+
+  /// Take a node or edge out of a tag
+  pub fn untag(&mut self, name: &str, guid: &Uuid) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let mut stats = CrudResultStats::new();
+    let untagged = self.indices.untag(name, guid)?;
+    if untagged != IndexStats::new() {
+      stats.add_deleted(untagged.into());
+    }
+    Ok(stats)
+  }
+
+  /// The edges ending at the node with the guid, found without scanning every edge
+  pub fn in_edges(&self, guid: &Uuid) -> Vec<Edge<G>> {
+    self.edges.incoming(guid, None)
+  }
+
+  /// The edges going from the source to the target, limited to the type if there is one
+  pub fn edges_between(
+    &self,
+    source: &Uuid,
+    target: &Uuid,
+    edge_type: Option<&str>,
+  ) -> Vec<Edge<G>> {
+    self
+      .edges
+      .incoming(target, edge_type)
+      .into_iter()
+      .filter(|edge| edge.get_source().get_guid() == *source)
+      .collect()
+  }
 
   pub fn stats(&self) -> DataSetStats {
     let mut stats = DataSetStats::default();
     stats.nodes = self.nodes.stats();
     stats.edges = self.edges.stats();
+    stats.indices = self.indices.stats();
 
     stats
   }
 }
 
+impl<G> Matchable<G> for DataSet<G>
+where
+  G: Graph,
+{
+  fn get_node(&self, guid: &Uuid) -> Option<Node<G>> {
+    self.nodes.get(guid).cloned()
+  }
+
+  fn scan_nodes(&self, label: Option<&str>) -> Vec<Node<G>> {
+    self.nodes.scan_nodes(label)
+  }
+
+  fn in_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    self.edges.incoming(&node.get_guid(), None)
+  }
+
+  fn scan_edges(&self, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    self.edges.get_typed(edge_type)
+  }
+
+  fn count_nodes(&self, label: Option<&str>) -> usize {
+    self.nodes.count(label)
+  }
+
+  fn count_edges(&self, edge_type: Option<&str>) -> usize {
+    self.edges.count(edge_type)
+  }
+
+  /// Planning a query that could seek from a lazy index builds it
+  fn distinct_values(&self, label: &str, property: &str) -> Option<usize> {
+    self.indices.with_index(
+      label,
+      property,
+      || self.values(),
+      |lookup| lookup.distinct(),
+    )
+  }
+
+  fn seek_nodes(&self, label: &str, property: &str, value: &Literal) -> Option<Vec<Node<G>>> {
+    let values = self.indices.with_index(
+      label,
+      property,
+      || self.values(),
+      |lookup| lookup.find(value),
+    )?;
+    let nodes = values.into_iter().filter_map(|value| match value {
+      Value::Node(node) => Some(node),
+      _ => None,
+    });
+    Some(nodes.collect())
+  }
+}
+
 impl<G> fmt::Display for DataSet<G>
 where
   G: Graph,
@@ -286,7 +997,7 @@ where
     f.debug_struct("DataSet")
       .field("guid", &self.guid)
       .field("nodes", &self.nodes.stats())
-      // .field("indices", &format!("{}", self.indices))
+      .field("indices", &format!("{}", self.indices))
       .finish()
   }
 }
@@ -297,6 +1008,8 @@ pub struct DataSetStats {
   pub nodes: NodeStats,
   #[serde(default)]
   pub edges: EdgeStats,
+  #[serde(default)]
+  pub indices: IndexStats,
 }
 
 impl DataSetStats {
@@ -304,6 +1017,7 @@ impl DataSetStats {
     DataSetStats {
       nodes: NodeStats::new(),
       edges: EdgeStats::new(),
+      indices: IndexStats::new(),
     }
   }
 
@@ -323,6 +1037,7 @@ impl Add for DataSetStats {
     DataSetStats {
       nodes: self.nodes + rhs.nodes,
       edges: self.edges + rhs.edges,
+      indices: self.indices + rhs.indices,
     }
   }
 }
@@ -338,6 +1053,7 @@ impl Diff for DataSetStats {
     let mut diffs = Difference::new();
     diffs += self.nodes.diff(&rhs.nodes, Some("nodes"));
     diffs += self.edges.diff(&rhs.edges, Some("edges"));
+    diffs += self.indices.diff(&rhs.indices, Some("indices"));
     diffs.opt_tag(name)
   }
 }
@@ -347,6 +1063,7 @@ impl From<NodeStats> for DataSetStats {
     DataSetStats {
       nodes,
       edges: EdgeStats::new(),
+      indices: IndexStats::new(),
     }
   }
 }
@@ -356,6 +1073,17 @@ impl From<EdgeStats> for DataSetStats {
     DataSetStats {
       nodes: NodeStats::new(),
       edges,
+      indices: IndexStats::new(),
+    }
+  }
+}
+
+impl From<IndexStats> for DataSetStats {
+  fn from(indices: IndexStats) -> Self {
+    DataSetStats {
+      nodes: NodeStats::new(),
+      edges: EdgeStats::new(),
+      indices,
     }
   }
 }
@@ -474,6 +1202,53 @@ impl From<&str> for DataSetStats {
       todo!()
     }
 
+    /// Skip over a block that can't be parsed yet, such as a map keyed by index name
+    fn skip_block(input: &str) -> IResult<&str, ()> {
+      let (input, _) = trim(char('{'))(input)?;
+      let mut depth = 1;
+      for (i, c) in input.char_indices() {
+        match c {
+          '{' => depth += 1,
+          '}' if depth == 1 => {
+            let (remainder, _) = trim(opt(char(',')))(&input[i + 1..])?;
+            return Ok((remainder, ()));
+          }
+          '}' => depth -= 1,
+          _ => (),
+        }
+      }
+      Err(NomErr::Error(NomError::new(input, NomErrorKind::Eof)))
+    }
+
+    fn parse_indices(input: &str) -> IResult<&str, IndexStats> {
+      let mapping = move |key, mut remainder| -> IResult<&str, IndexStats> {
+        let value;
+        let mut stats = IndexStats::new();
+        match key {
+          "total" => {
+            (remainder, value) = parse_u128(remainder)?;
+            stats.total.increase(value as i128);
+          }
+          "memory" => {
+            (remainder, value) = parse_u128(remainder)?;
+            stats.memory.increase(value as i128);
+          }
+          // The per-index counts are keyed by names such as "property:Org.name"
+          "entries" | "built" | "dropped" => {
+            (remainder, _) = skip_block(remainder)?;
+          }
+          x => panic!("Received unknown field for building IndexStats: {:?}", x),
+        }
+        Ok((remainder, stats))
+      };
+
+      trim(fold_many0(
+        map_field(mapping),
+        IndexStats::new,
+        |acc: IndexStats, item| acc + item,
+      ))(input)
+    }
+
     let mapping = |key, mut remainder| -> IResult<&str, DataSetStats> {
       let (nodes, edges);
       let mut data_set_stats = DataSetStats::new();
@@ -489,6 +1264,11 @@ impl From<&str> for DataSetStats {
           (remainder, edges) = parse_block(parse_edges)(remainder)?;
           data_set_stats.edges = edges;
         }
+        "indices" => {
+          let indices;
+          (remainder, indices) = parse_block(parse_indices)(remainder)?;
+          data_set_stats.indices = indices;
+        }
         x => panic!("Received unknown field for building DataSetStats: {:?}", x),
       }
 
@@ -1,6 +1,6 @@
 //! A group of edges contained in a graph
 
-use crate::{local::*, prelude::*};
+use crate::{local::*, prelude::*, stats::property_changes};
 
 use std::{
   collections::{
@@ -28,6 +28,9 @@ where
   /// Group the edges by their type
   typed: HashMap<String, HashMap<Uuid, Edge<G>>>,
 
+  /// Group the edges by the guid of the node they end at, so they can be followed backwards
+  incoming: HashMap<Uuid, HashMap<Uuid, Edge<G>>>,
+
   /// Live statistics about the edges
   stats: EdgeStats,
 }
@@ -41,6 +44,7 @@ where
       guid: Uuid::new_v4(),
       edges: HashMap::new(),
       typed: HashMap::new(),
+      incoming: HashMap::new(),
       stats: EdgeStats::new(),
     }
   }
@@ -51,8 +55,66 @@ where
   }
 
   /// Queries and retrieves edges matching the query
-  pub fn get(&self, _query: &str) -> GraphtResult<Vec<Edge<G>>> {
-    todo!()
+  ///
+  /// Every distinct edge found in any of the returned columns is included
+  pub fn get(&self, query: &str) -> GraphtResult<Vec<Edge<G>>> {
+    let results = query::execute(self, &GQuery::parse(query)?, &Params::new())?;
+
+    let mut seen = std::collections::HashSet::new();
+    let mut edges = Vec::new();
+    for value in results.into_values().flatten() {
+      if let Value::Edge(edge) = value {
+        if seen.insert(edge.get_guid()) {
+          edges.push(edge);
+        }
+      }
+    }
+    Ok(edges)
+  }
+
+  /// Look up a single edge by its guid
+  pub fn get_edge(&self, guid: &Uuid) -> Option<&Edge<G>> {
+    self.edges.get(guid)
+  }
+
+  /// Get all the edges with the type, or every edge if there isn't one
+  pub fn get_typed(&self, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    match edge_type {
+      Some(edge_type) => match self.typed.get(edge_type) {
+        Some(edges) => edges.values().cloned().collect(),
+        None => Vec::new(),
+      },
+      None => self.edges.values().cloned().collect(),
+    }
+  }
+
+  /// Get the edges ending at the node with the guid, limited to the type if there is one
+  pub fn incoming(&self, target: &Uuid, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    match self.incoming.get(target) {
+      Some(edges) => edges
+        .values()
+        .filter(|edge| match edge_type {
+          Some(edge_type) => edge.get_type_label() == edge_type,
+          None => true,
+        })
+        .cloned()
+        .collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// The number of edges with the type, or of all edges, taken from the live statistics
+  pub fn count(&self, edge_type: Option<&str>) -> usize {
+    let count = match edge_type {
+      Some(edge_type) => self
+        .stats
+        .typed
+        .get(&edge_type.to_string())
+        .map(|x| x.count())
+        .unwrap_or(0),
+      None => self.stats.total.count(),
+    };
+    count as usize
   }
 
   // Insert the edge into the graph and fail if it already exists
@@ -79,6 +141,15 @@ where
     stats.total.increase(1);
     self.stats.total.increase(1);
 
+    stats.typed.increase((edge.get_type_label(), 1));
+    self.stats.typed.increase((edge.get_type_label(), 1));
+
+    self
+      .incoming
+      .entry(edge.get_target().get_guid())
+      .or_default()
+      .insert(edge.get_guid(), edge.clone());
+
     // Clone the edge for use with closures
     self
       .typed
@@ -95,13 +166,107 @@ where
   }
 
   /// Updates the original edge with the new edge, updating the internal indices as it goes
-  pub fn update(&mut self, _edge: &Edge<G>) -> GraphtResult<EdgeStats> {
-    todo!()
+  ///
+  /// Edges are copied into each index, so every copy is swapped for the new one. Properties given
+  /// a new value are counted as updated, while properties that were dropped are counted as deleted.
+  pub fn update(&mut self, edge: &Edge<G>) -> GraphtResult<CrudResultStats<EdgeStats>> {
+    let guid = edge.get_guid();
+    let stored = match self.edges.get(&guid) {
+      Some(stored) => stored.clone(),
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Edge with Uuid {} in the EdgeSet {} for updating",
+          guid,
+          self.guid
+        ))
+      }
+    };
+
+    let mut updated = EdgeStats::new();
+    let mut deleted = EdgeStats::new();
+    (updated.properties, deleted.properties) = property_changes(
+      &stored.get_properties().get_properties()?,
+      &edge.get_properties().get_properties()?,
+    );
+
+    // Move the edge to its new type, or replace the copy under the current one
+    let (old_type, new_type) = (stored.get_type_label(), edge.get_type_label());
+    if old_type != new_type {
+      if let Some(edges) = self.typed.get_mut(&old_type) {
+        edges.remove(&guid);
+        if edges.is_empty() {
+          self.typed.remove(&old_type);
+        }
+      }
+      self.stats.typed.increase((old_type.clone(), -1));
+      self.stats.typed.increase((new_type.clone(), 1));
+      updated.typed.increase((new_type.clone(), 1));
+      deleted.typed.increase((old_type, 1));
+    }
+    self
+      .typed
+      .entry(new_type)
+      .or_default()
+      .insert(guid, edge.clone());
+
+    self
+      .incoming
+      .entry(edge.get_target().get_guid())
+      .or_default()
+      .insert(guid, edge.clone());
+    self.edges.insert(guid, edge.clone());
+
+    // The weights and direction aren't properties, but changing them still changes the edge
+    let reoptioned = stored.get_options() != edge.get_options();
+
+    let mut stats = CrudResultStats::new();
+    if reoptioned || updated != EdgeStats::new() || deleted != EdgeStats::new() {
+      updated.total.increase(1);
+      stats.add_updated(updated);
+    }
+    if deleted != EdgeStats::new() {
+      stats.add_deleted(deleted);
+    }
+    Ok(stats)
   }
 
   /// Removes the edge and all its indices from the set
-  pub fn delete(&mut self, _query: ()) -> GraphtResult<EdgeStats> {
-    todo!()
+  pub fn delete(&mut self, guid: &Uuid) -> GraphtResult<EdgeStats> {
+    let edge = match self.edges.remove(guid) {
+      Some(edge) => edge,
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Edge with Uuid {} in the EdgeSet {} for deleting",
+          guid,
+          self.guid
+        ))
+      }
+    };
+
+    let mut stats = EdgeStats::new();
+    stats.total.increase(1);
+    self.stats.total.increase(-1);
+
+    let edge_type = edge.get_type_label();
+    if let Some(edges) = self.typed.get_mut(&edge_type) {
+      edges.remove(guid);
+      if edges.is_empty() {
+        self.typed.remove(&edge_type);
+      }
+    }
+    let target = edge.get_target().get_guid();
+    if let Some(edges) = self.incoming.get_mut(&target) {
+      edges.remove(guid);
+      if edges.is_empty() {
+        self.incoming.remove(&target);
+      }
+    }
+
+    stats.typed.increase((edge_type.clone(), 1));
+    self.stats.typed.increase((edge_type, -1));
+    Ok(stats)
   }
 
   pub fn stats(&self) -> EdgeStats {
@@ -122,6 +287,59 @@ where
   }
 }
 
+/// An EdgeSet on its own only knows about the nodes at either end of its edges
+impl<G> Matchable<G> for EdgeSet<G>
+where
+  G: Graph,
+{
+  fn get_node(&self, guid: &Uuid) -> Option<Node<G>> {
+    self.edges.values().find_map(|edge| {
+      [edge.get_source(), edge.get_target()]
+        .into_iter()
+        .find(|node| node.get_guid() == *guid)
+    })
+  }
+
+  fn scan_nodes(&self, label: Option<&str>) -> Vec<Node<G>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut nodes = Vec::new();
+    for edge in self.edges.values() {
+      for node in [edge.get_source(), edge.get_target()] {
+        let labeled = match label {
+          Some(label) => node.has_label(label),
+          None => true,
+        };
+        if labeled && seen.insert(node.get_guid()) {
+          nodes.push(node);
+        }
+      }
+    }
+    nodes
+  }
+
+  fn out_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    let guid = node.get_guid();
+    self
+      .edges
+      .values()
+      .filter(|edge| edge.get_source().get_guid() == guid)
+      .cloned()
+      .collect()
+  }
+
+  fn in_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    self.incoming(&node.get_guid(), None)
+  }
+
+  fn scan_edges(&self, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    self.get_typed(edge_type)
+  }
+
+  fn count_edges(&self, edge_type: Option<&str>) -> usize {
+    self.count(edge_type)
+  }
+}
+
 impl<'a, G> IntoIterator for &'a EdgeSet<G>
 where
   G: Graph,
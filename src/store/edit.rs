@@ -0,0 +1,149 @@
+//! Editing a stored node through a handle that applies every change at once
+//!
+//! Nodes returned by a DataSet are bound, so changing one directly unbinds it and the change never
+//! reaches the DataSet. A `NodeEdit` instead collects the changes on a private copy of the node,
+//! and only pushes them into the DataSet on `commit()`. Dropping the handle throws them away.
+
+use crate::{local::*, prelude::*};
+
+use uuid::Uuid;
+
+/// Pending changes to a node stored in a DataSet
+///
+/// The handle borrows the DataSet mutably, so nothing else can change it while an edit is open.
+#[derive(Debug)]
+pub struct NodeEdit<'a, G>
+where
+  G: Graph,
+{
+  data_set: &'a mut DataSet<G>,
+
+  /// The node as it is in the DataSet
+  stored: Node<G>,
+
+  /// An unbound copy of the node holding the changes
+  node: Node<G>,
+
+  /// Edges to add when committing
+  added: Vec<Edge<G>>,
+
+  /// Edges starting at the node to remove when committing
+  removed: Vec<Uuid>,
+}
+
+impl<'a, G> NodeEdit<'a, G>
+where
+  G: Graph,
+{
+  pub(crate) fn new(
+    data_set: &'a mut DataSet<G>,
+    stored: Node<G>,
+  ) -> GraphtResult<NodeEdit<'a, G>> {
+    let node = stored.deep_clone()?;
+    Ok(NodeEdit {
+      data_set,
+      stored,
+      node,
+      added: Vec::new(),
+      removed: Vec::new(),
+    })
+  }
+
+  /// The node with the changes made so far
+  pub fn node(&self) -> &Node<G> {
+    &self.node
+  }
+
+  /// Replace the properties of the node. They must keep the same key.
+  pub fn set_props(&mut self, props: G::Node) -> GraphtResult<()> {
+    self.node.set_props(props)
+  }
+
+  pub fn add_label(&mut self, label: &str) -> bool {
+    self.node.add_label(label)
+  }
+
+  /// Drop a label, except for the type label which every node keeps
+  pub fn drop_label(&mut self, label: &str) -> bool {
+    if label == self.node.type_label() {
+      return false;
+    }
+    self.node.drop_label(label)
+  }
+
+  /// Create a relationship from the node to the target, which is inserted on commit if it is new
+  pub fn create_edge(&mut self, props: G::Edge, target: Node<G>) -> GraphtResult<Edge<G>> {
+    let edge = Edge::new(&self.stored, &target, props)?;
+    self.add_edge(edge.clone())?;
+    Ok(edge)
+  }
+
+  /// Add an edge that has already been built, such as one with a weight
+  pub fn add_edge(&mut self, edge: Edge<G>) -> GraphtResult<()> {
+    if edge.get_source().get_guid() != self.stored.get_guid() {
+      return Err(err!(
+        InvalidItem,
+        "Edge {} starts at node {}, so it cannot be added to node {}",
+        edge.get_guid(),
+        edge.get_source().get_guid(),
+        self.stored.get_guid()
+      ));
+    }
+
+    self.node.add_edge(edge.clone())?;
+    self.removed.retain(|guid| *guid != edge.get_guid());
+    self.added.push(edge);
+    Ok(())
+  }
+
+  /// Remove one of the edges starting at the node, returning false if it doesn't have it
+  pub fn remove_edge(&mut self, guid: &Uuid) -> bool {
+    if !self.node.remove_edge(guid) {
+      return false;
+    }
+
+    let pending = self.added.len();
+    self.added.retain(|edge| edge.get_guid() != *guid);
+    if self.added.len() == pending {
+      self.removed.push(*guid);
+    }
+    true
+  }
+
+  /// Push the changes into the DataSet, re-indexing the node and its edges
+  ///
+  /// Everything is checked before the DataSet is touched, so a failed commit leaves it as it was.
+  /// The edited node and the edges being added, along with any new nodes they lead to, are checked
+  /// against the schema, the edge limits and the Graph's constraints with the removed edges gone.
+  pub fn commit(self) -> GraphtResult<CrudResultStats<DataSetStats>> {
+    let guid = self.stored.get_guid();
+    if let Some(missing) = self.removed.iter().find(|x| !self.data_set.contains(x)) {
+      return Err(err!(
+        NotFound,
+        "Could not find Edge {} to remove from node {}",
+        missing,
+        guid
+      ));
+    }
+    // Read the properties up front, since updating the node compares them
+    self.node.get_props().get_properties()?;
+    self
+      .data_set
+      .check_insert(&self.node.clone().into(), &self.removed)?;
+
+    debug!(
+      "Committing the edit of node {}, adding {} edges and removing {}",
+      guid,
+      self.added.len(),
+      self.removed.len()
+    );
+    let mut stats = self.data_set.update_node(&self.node)?;
+    for edge in &self.removed {
+      stats += self.data_set.delete_edge(edge)?;
+    }
+    for edge in self.added {
+      stats += self.data_set.insert(edge.into())?;
+    }
+    Ok(stats)
+  }
+}
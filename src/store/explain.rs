@@ -0,0 +1,276 @@
+//! Describing how a query is run (EXPLAIN) and what running it cost (PROFILE)
+//!
+//! A query runs as a pipeline of operators, each working on the rows produced by the one before
+//! it. The plan is a tree where each operator's children feed it rows, and it prints with the root
+//! first like RedisGraph's GRAPH.EXPLAIN and GRAPH.PROFILE.
+
+use crate::{gquery::ast::*, local::*, prelude::*, stats::*, store::planner::MatchPlan};
+
+use std::{
+  collections::HashMap,
+  ops::{Add, AddAssign},
+  time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What to return instead of the results of a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanMode {
+  /// Describe the plan without running the query
+  Explain,
+
+  /// Run the query, recording what each operator of the plan did
+  Profile,
+}
+
+impl PlanMode {
+  /// Split a leading EXPLAIN or PROFILE keyword off of a query
+  pub fn split(query: &str) -> (Option<PlanMode>, &str) {
+    let trimmed = query.trim_start();
+    let keyword_end = trimmed
+      .find(|c: char| !c.is_alphabetic())
+      .unwrap_or(trimmed.len());
+
+    let mode = match trimmed[..keyword_end].to_uppercase().as_str() {
+      "EXPLAIN" => PlanMode::Explain,
+      "PROFILE" => PlanMode::Profile,
+      _ => return (None, query),
+    };
+    (Some(mode), &trimmed[keyword_end..])
+  }
+}
+
+/// A single step of a query plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operator {
+  pub name: String,
+
+  /// The part of the query the operator handles (eg. the pattern for a scan)
+  pub detail: Option<String>,
+
+  /// What the operator did, which is only recorded when profiling
+  pub stats: Option<OperatorStats>,
+
+  /// The operators that produce the rows this one works on
+  pub children: Vec<Operator>,
+}
+
+impl Operator {
+  pub fn new<S: Into<String>>(name: S, detail: Option<String>) -> Operator {
+    Operator {
+      name: name.into(),
+      detail,
+      stats: None,
+      children: Vec::new(),
+    }
+  }
+
+  /// Find the first operator with the name, searching from the root
+  pub fn find(&self, name: &str) -> Option<&Operator> {
+    match self.name == name {
+      true => Some(self),
+      false => self.children.iter().find_map(|child| child.find(name)),
+    }
+  }
+
+  /// The stats of every operator in the tree added together
+  pub fn totals(&self) -> OperatorStats {
+    let mut totals = self.stats.clone().unwrap_or_default();
+    for child in &self.children {
+      totals += child.totals();
+    }
+    totals
+  }
+
+  /// Each operator on its own line, indented under the operator it feeds
+  pub fn lines(&self) -> Vec<String> {
+    let mut lines = Vec::new();
+    self.push_lines(0, &mut lines);
+    lines
+  }
+
+  fn push_lines(&self, depth: usize, lines: &mut Vec<String>) {
+    let mut line = format!("{}{}", "    ".repeat(depth), self.name);
+    if let Some(detail) = &self.detail {
+      line.push_str(&format!(" | {}", detail));
+    }
+    if let Some(stats) = &self.stats {
+      line.push_str(&format!(" | {}", stats));
+    }
+    lines.push(line);
+
+    for child in &self.children {
+      child.push_lines(depth + 1, lines);
+    }
+  }
+
+  /// Turn the lines into the columns returned by a query starting with EXPLAIN or PROFILE
+  pub fn to_columns<G: Graph>(&self) -> HashMap<String, Vec<Value<G>>> {
+    let lines = self
+      .lines()
+      .into_iter()
+      .map(|line| Value::Literal(line.into()))
+      .collect();
+    HashMap::from([(String::from("plan"), lines)])
+  }
+}
+
+impl Display for Operator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.lines().join("\n"))
+  }
+}
+
+impl Diff for Operator {
+  fn diff(&self, rhs: &Self, name: Option<&str>) -> Difference {
+    let mut diffs = Difference::new();
+    diffs += self.name.diff(&rhs.name, Some("name"));
+    diffs += self
+      .detail
+      .clone()
+      .unwrap_or_default()
+      .diff(&rhs.detail.clone().unwrap_or_default(), Some("detail"));
+    diffs += self.stats.diff(&rhs.stats, None).opt_tag(Some("stats"));
+    diffs += self.children.diff(&rhs.children, Some("children"));
+    diffs.opt_tag(name)
+  }
+}
+
+/// What a single operator did while profiling a query
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperatorStats {
+  /// The rows the operator passed on
+  pub rows: StatCount,
+
+  /// The items read from a label or type index
+  pub index_hits: StatCount,
+
+  /// The time spent in the operator, not counting the operators feeding it
+  pub elapsed: Duration,
+}
+
+impl OperatorStats {
+  pub fn new() -> OperatorStats {
+    OperatorStats {
+      rows: StatCount::new(),
+      index_hits: StatCount::new(),
+      elapsed: Duration::ZERO,
+    }
+  }
+
+  /// Stats for a single call of an operator
+  pub fn record(rows: usize, index_hits: usize, elapsed: Duration) -> OperatorStats {
+    let mut stats = OperatorStats::new();
+    stats.rows.increase(rows as i128);
+    stats.index_hits.increase(index_hits as i128);
+    stats.elapsed = elapsed;
+    stats
+  }
+}
+
+impl Display for OperatorStats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Records produced: {}, Index hits: {}, Execution time: {:.6} ms",
+      self.rows.count(),
+      self.index_hits.count(),
+      self.elapsed.as_secs_f64() * 1000.0
+    )
+  }
+}
+
+/// Timings change from run to run, so only the counts are compared
+impl Diff for OperatorStats {
+  fn diff(&self, rhs: &Self, name: Option<&str>) -> Difference {
+    let mut diffs = Difference::new();
+    diffs += self.rows.diff(&rhs.rows, Some("rows"));
+    diffs += self.index_hits.diff(&rhs.index_hits, Some("index_hits"));
+    diffs.opt_tag(name)
+  }
+}
+
+impl Add for OperatorStats {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self::Output {
+    OperatorStats {
+      rows: self.rows + rhs.rows,
+      index_hits: self.index_hits + rhs.index_hits,
+      elapsed: self.elapsed + rhs.elapsed,
+    }
+  }
+}
+
+impl AddAssign for OperatorStats {
+  fn add_assign(&mut self, rhs: Self) {
+    *self = self.clone().add(rhs);
+  }
+}
+
+impl Stats for OperatorStats {
+  type Item = OperatorStats;
+
+  fn increase(&mut self, value: Self::Item) {
+    *self += value;
+  }
+
+  fn clear(&mut self) {
+    *self = OperatorStats::new();
+  }
+}
+
+/// The operators for a clause in the order they run. The executor records its profile against the
+/// same positions, so the two have to stay in step.
+pub(crate) fn clause_operators(clause: &Clause, plan: Option<&MatchPlan>) -> Vec<Operator> {
+  match (clause, plan) {
+    (Clause::Match(matched), Some(plan)) => {
+      let mut operators: Vec<Operator> = plan.parts.iter().flat_map(|x| x.operators()).collect();
+      if let Some(filter) = &matched.filter {
+        operators.push(Operator::new("Filter", Some(filter.to_string())));
+      }
+      if matched.optional {
+        operators.push(Operator::new("Optional", None));
+      }
+      operators
+    }
+    (Clause::Unwind(unwind), _) => vec![Operator::new(
+      "Unwind",
+      Some(format!(
+        "{} AS {}",
+        unwind.expression,
+        escape_name(&unwind.variable)
+      )),
+    )],
+    (Clause::With(with), _) => {
+      let mut operators = vec![Operator::new("Project", Some(with.projection.to_string()))];
+      if let Some(filter) = &with.filter {
+        operators.push(Operator::new("Filter", Some(filter.to_string())));
+      }
+      operators
+    }
+    (Clause::Return(projection), _) => vec![
+      Operator::new("Project", Some(projection.to_string())),
+      Operator::new("Results", None),
+    ],
+    (Clause::Create(_), _) => vec![Operator::new("Create", Some(clause.to_string()))],
+    (Clause::Merge(_), _) => vec![Operator::new("Merge", Some(clause.to_string()))],
+    (Clause::Set(_), _) | (Clause::Remove(_), _) => {
+      vec![Operator::new("Update", Some(clause.to_string()))]
+    }
+    (Clause::Delete(_), _) => vec![Operator::new("Delete", Some(clause.to_string()))],
+    (Clause::Match(_), None) => unreachable!("Every MATCH clause is planned before it runs"),
+  }
+}
+
+/// Stack the operators into a tree, with the last one to run at the root
+pub(crate) fn to_tree(operators: Vec<Operator>) -> Operator {
+  operators
+    .into_iter()
+    .reduce(|child, mut parent| {
+      parent.children.push(child);
+      parent
+    })
+    .unwrap_or_else(|| Operator::new("Results", None))
+}
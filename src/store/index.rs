@@ -1,13 +1,178 @@
 //! Pre-compiled lookups for getting node, edge, and paths
+//!
+//! Indices are declared by the user on a label and a property. A property index finds the values
+//! with an exact property value from a hash map, while a sorted index keeps them in a B-tree so
+//! ranges can be read in order. Tags are ad-hoc groups that values are added to by hand. The
+//! DataSet keeps every index in step as values are inserted, updated, and deleted.
+//!
+//! Indices can also be declared lazy, in which case they are only built the first time a query
+//! would seek from them. Built lazy indices are dropped again, least recently used first, whenever
+//! they grow past the memory budget, and are rebuilt by the next query that needs them.
 
 use crate::{local::*, prelude::*};
 
 use std::{
-  collections::{hash_map::Entry, HashMap, HashSet},
-  hash::Hash,
+  cmp::Ordering,
+  collections::{BTreeMap, BTreeSet, HashMap},
+  hash::{Hash, Hasher},
+  mem::size_of,
+  ops::{Add, AddAssign, Bound},
+  sync::RwLock,
 };
+
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Definitions for how to build the given index
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Index {
+  /// An ephemeral label, temporarily grouping together of nodes and edges in memory
+  Tag(String),
+
+  /// The nodes and edges with a label, looked up by the exact value of a property
+  ///
+  /// Holds the label and the name of the property.
+  Property(String, String),
+
+  /// An ordered list of the nodes and edges with a label, sorted by a property so ranges of it
+  /// can be read in order
+  ///
+  /// Holds the label and the name of the property.
+  Sorted(String, String),
+  // A cache that can be used for optionally storing subsets that match a piece of a query
+  // QueryFragment(QueryFragment),
+
+  // A binary search tree of characters, where the search string is
+  // THINK:
+  // Is this a plugin/generic/crate or specifically coded in Grapht? This seems like finding
+  // a path to match is valid. Also, the index definition is not static, being updated with each
+  // node added
+  // TextSearch(),
+}
+
+impl Index {
+  /// The property value a node or edge is filed under, or None if it doesn't belong in the index
+  ///
+  /// Values are added to tags by hand, so nothing belongs to one on its own. Values without the
+  /// property (null) are left out, since null is never equal to anything.
+  pub fn key<G: Graph>(&self, value: &Value<G>) -> GraphtResult<Option<Literal>> {
+    let (label, property) = match self {
+      Index::Tag(_) => return Ok(None),
+      Index::Property(label, property) | Index::Sorted(label, property) => (label, property),
+    };
+
+    let key = match value {
+      Value::Node(node) if node.has_label(label) => node.get_property(property)?,
+      Value::Edge(edge) if edge.get_label() == *label => edge.get_property(property)?,
+      _ => return Ok(None),
+    };
+    match key {
+      Literal::Null => Ok(None),
+      key => Ok(Some(key)),
+    }
+  }
+
+  /// Checks if the value is filed in the index when it is inserted
+  pub fn belongs<G: Graph>(&self, value: &Value<G>) -> GraphtResult<bool> {
+    Ok(self.key(value)?.is_some())
+  }
+}
+
+impl Display for Index {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Index::Tag(name) => write!(f, "tag:{}", name),
+      Index::Property(label, property) => write!(f, "property:{}.{}", label, property),
+      Index::Sorted(label, property) => write!(f, "sorted:{}.{}", label, property),
+    }
+  }
+}
+
+/// A property value used as a lookup key
+///
+/// Keys compare the way a query does, so 1 and 1.0 are the same key.
+#[derive(Debug, Clone)]
+pub(crate) struct Key(pub(crate) Literal);
+
+impl PartialEq for Key {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.order(&other.0) == Ordering::Equal
+  }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Key {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.0.order(&other.0)
+  }
+}
+
+impl Hash for Key {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    fn hash_literal<H: Hasher>(value: &Literal, state: &mut H) {
+      match value {
+        // Numbers are hashed by value so integers and decimals that are equal land together
+        Literal::Integer(_) | Literal::Decimal(_) => {
+          value.as_decimal().map(|x| x.normalize()).hash(state)
+        }
+        Literal::List(items) => items.iter().for_each(|item| hash_literal(item, state)),
+        value => value.to_string().hash(state),
+      }
+    }
+    hash_literal(&self.0, state)
+  }
+}
+
+/// The guids filed under each key of a property index
+#[derive(Debug, Clone)]
+enum Keys {
+  /// Tags don't have keys
+  None,
+  Hashed(HashMap<Key, BTreeSet<Uuid>>),
+  Sorted(BTreeMap<Key, BTreeSet<Uuid>>),
+}
+
+impl Keys {
+  fn insert(&mut self, key: &Key, guid: Uuid) {
+    let guids = match self {
+      Keys::None => return,
+      Keys::Hashed(keys) => keys.entry(key.clone()).or_default(),
+      Keys::Sorted(keys) => keys.entry(key.clone()).or_default(),
+    };
+    guids.insert(guid);
+  }
+
+  /// Take the guid out from under the key, dropping the key once nothing is filed under it
+  fn remove(&mut self, key: &Key, guid: &Uuid) {
+    match self {
+      Keys::None => (),
+      Keys::Hashed(keys) => {
+        if let Some(guids) = keys.get_mut(key) {
+          guids.remove(guid);
+          if guids.is_empty() {
+            keys.remove(key);
+          }
+        }
+      }
+      Keys::Sorted(keys) => {
+        if let Some(guids) = keys.get_mut(key) {
+          guids.remove(guid);
+          if guids.is_empty() {
+            keys.remove(key);
+          }
+        }
+      }
+    }
+  }
+}
+
 /// The values that belong to the same index
 #[derive(Debug, Clone)]
 pub struct Lookup<G>
@@ -17,8 +182,10 @@ where
   /// The common definition that links all the saved values
   index: Index,
 
-  /// The known set of values that match the given index
-  values: HashSet<Value<G>>,
+  /// The known set of values that match the given index, along with the key each is filed under
+  values: HashMap<Uuid, (Value<G>, Option<Key>)>,
+
+  keys: Keys,
 }
 
 impl<G> Lookup<G>
@@ -26,74 +193,254 @@ where
   G: Graph,
 {
   pub fn new(index: Index) -> Lookup<G> {
+    let keys = match &index {
+      Index::Tag(_) => Keys::None,
+      Index::Property(..) => Keys::Hashed(HashMap::new()),
+      Index::Sorted(..) => Keys::Sorted(BTreeMap::new()),
+    };
     Lookup {
       index,
-      values: HashSet::new(),
+      values: HashMap::new(),
+      keys,
     }
   }
 
+  pub fn index(&self) -> &Index {
+    &self.index
+  }
+
   pub fn len(&self) -> usize {
     self.values.len()
   }
 
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  pub fn contains(&self, guid: &Uuid) -> bool {
+    self.values.contains_key(guid)
+  }
+
+  /// The number of distinct keys, which a query planner can use to guess how many values a key
+  /// finds
+  pub fn distinct(&self) -> usize {
+    match &self.keys {
+      Keys::None => usize::from(!self.values.is_empty()),
+      Keys::Hashed(keys) => keys.len(),
+      Keys::Sorted(keys) => keys.len(),
+    }
+  }
+
+  /// A rough estimate of the bytes the lookup holds on to
+  ///
+  /// Only the lookup's own tables are counted, since the nodes and edges are shared with the
+  /// DataSet. Keys holding strings or lists take more than this.
+  pub fn memory(&self) -> usize {
+    let value = size_of::<Uuid>() + size_of::<(Value<G>, Option<Key>)>();
+    let keyed = match &self.keys {
+      Keys::None => 0,
+      Keys::Hashed(_) | Keys::Sorted(_) => size_of::<Uuid>(),
+    };
+    let keys = self.distinct() * (size_of::<Key>() + size_of::<BTreeSet<Uuid>>());
+    self.values.len() * (value + keyed) + keys
+  }
+
   pub fn all(&self) -> Vec<Value<G>> {
-    self.values.iter().map(|x| x.clone()).collect()
+    self
+      .values
+      .values()
+      .map(|(value, _)| value.clone())
+      .collect()
   }
 
-  pub fn find_all(&self, _filter: &str) -> Vec<Value<G>> {
-    todo!("Lookup::find_all")
+  /// The values whose property equals the key. Tags don't have keys, so they find nothing.
+  pub fn find(&self, key: &Literal) -> Vec<Value<G>> {
+    let key = Key(key.clone());
+    let guids = match &self.keys {
+      Keys::None => None,
+      Keys::Hashed(keys) => keys.get(&key),
+      Keys::Sorted(keys) => keys.get(&key),
+    };
+    guids.map(|guids| self.collect(guids)).unwrap_or_default()
   }
 
-  pub fn insert(&mut self, value: Value<G>) -> GraphtResult<()> {
-    if self.values.contains(&value) {
-      Err(err!(
+  /// The values whose property is within the bounds, in order of the property
+  ///
+  /// Only sorted indices keep their keys in order.
+  pub fn range(
+    &self,
+    lower: Bound<&Literal>,
+    upper: Bound<&Literal>,
+  ) -> GraphtResult<Vec<Value<G>>> {
+    let keys = match &self.keys {
+      Keys::Sorted(keys) => keys,
+      _ => {
+        return Err(err!(
+          InvalidItem,
+          "Only sorted indices can read a range of values, and {} isn't sorted",
+          self.index
+        ))
+      }
+    };
+
+    let bound = |bound: Bound<&Literal>| bound.map(|x| Key(x.clone()));
+    Ok(
+      keys
+        .range((bound(lower), bound(upper)))
+        .flat_map(|(_, guids)| self.collect(guids))
+        .collect(),
+    )
+  }
+
+  fn collect(&self, guids: &BTreeSet<Uuid>) -> Vec<Value<G>> {
+    guids
+      .iter()
+      .filter_map(|guid| self.values.get(guid))
+      .map(|(value, _)| value.clone())
+      .collect()
+  }
+
+  /// Add a value to the lookup, filed under its key when the index has them
+  pub(crate) fn insert(&mut self, value: Value<G>) -> GraphtResult<()> {
+    let guid = value.get_guid();
+    if self.values.contains_key(&guid) {
+      return Err(err!(
         DuplicateKey,
-        "Value with Uuid {} already exists in the lookup {:?}",
-        value.get_guid(),
+        "Value with Uuid {} already exists in the lookup {}",
+        guid,
         self.index
-      ))
-    } else {
-      self.values.insert(value.clone());
-      Ok(())
+      ));
+    }
+
+    let key = match (&self.keys, self.index.key(&value)?) {
+      (Keys::None, _) => None,
+      (_, Some(key)) => Some(Key(key)),
+      (_, None) => {
+        return Err(err!(
+          InvalidItem,
+          "Value with Uuid {} doesn't belong in the index {}",
+          guid,
+          self.index
+        ))
+      }
+    };
+
+    if let Some(key) = &key {
+      self.keys.insert(key, guid);
+    }
+    self.values.insert(guid, (value, key));
+    Ok(())
+  }
+
+  /// Take a value out of the lookup, returning it if it was there
+  pub(crate) fn remove(&mut self, guid: &Uuid) -> Option<Value<G>> {
+    let (value, key) = self.values.remove(guid)?;
+    if let Some(key) = key {
+      self.keys.remove(&key, guid);
     }
+    Some(value)
   }
 }
 
-/// Definitions for how to build the given index
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Index {
-  /// An ephemeral label, temporarily grouping together of nodes and edges in memory
-  Tag(String),
+/// A full set of lookup tables used by a DataSet for improving query speed
+#[derive(Debug)]
+pub struct Indices<G>
+where
+  G: Graph,
+{
+  lookups: HashMap<Index, Lookup<G>>,
 
-  /// An ordered list of nodes based on a comparison criteria.
-  Sorted(String),
-  // A cache that can be used for optionally storing subsets that match a piece of a query
-  // QueryFragment(QueryFragment),
+  /// Indices that are built when a query first needs them. Queries only borrow the DataSet, so
+  /// these sit behind a lock to be built while reading.
+  lazy: RwLock<LazyLookups<G>>,
 
-  // A binary search tree of characters, where the search string is
-  // THINK:
-  // Is this a plugin/generic/crate or specifically coded in Grapht? This seems like finding
-  // a path to match is valid. Also, the index definition is not static, being updated with each
-  // node added
-  // TextSearch(),
+  /// The most bytes the built lazy indices may hold before some are dropped, if limited
+  budget: Option<usize>,
 }
 
-impl Index {
-  /// Checks if the value
-  pub fn belongs<G: Graph>(&self, _value: Value<G>) -> bool {
-    todo!("Index::belongs is not implemented yet")
+/// The declared lazy indices and the lookups of the ones that are currently built
+#[derive(Debug, Clone)]
+struct LazyLookups<G>
+where
+  G: Graph,
+{
+  declared: BTreeSet<Index>,
+
+  /// The built lookups, along with the tick they were last used on
+  built: HashMap<Index, (Lookup<G>, u64)>,
+
+  /// Counts up with each use, for finding the least recently used lookup
+  tick: u64,
+
+  /// Running counts of the lazy indices being built and dropped
+  events: IndexStats,
+}
+
+impl<G> LazyLookups<G>
+where
+  G: Graph,
+{
+  fn new() -> LazyLookups<G> {
+    LazyLookups {
+      declared: BTreeSet::new(),
+      built: HashMap::new(),
+      tick: 0,
+      events: IndexStats::new(),
+    }
+  }
+
+  fn memory(&self) -> usize {
+    self.built.values().map(|(lookup, _)| lookup.memory()).sum()
+  }
+
+  /// Drop the least recently used lookups until the rest fit in the budget
+  ///
+  /// The lookup being used is always kept, even when it doesn't fit on its own.
+  fn shrink(&mut self, budget: Option<usize>, keep: Option<&Index>) {
+    let budget = match budget {
+      Some(budget) => budget,
+      None => return,
+    };
+
+    while self.memory() > budget {
+      let oldest = self
+        .built
+        .iter()
+        .filter(|(index, _)| Some(*index) != keep)
+        .min_by_key(|(_, (_, used))| *used)
+        .map(|(index, _)| index.clone());
+      let index = match oldest {
+        Some(index) => index,
+        None => return,
+      };
+
+      debug!("Dropping the lazy index {} to fit the memory budget", index);
+      self.built.remove(&index);
+      self.events.dropped.increase((index.to_string(), 1));
+    }
   }
 }
 
-/// A full set of lookup tables used by a DataSet for improving query speed
-///
-/// THINK: How does this
-#[derive(Debug, Clone)]
-pub struct Indices<G>
+impl<G> Clone for Indices<G>
 where
   G: Graph,
 {
-  lookups: HashMap<Index, Lookup<G>>,
+  fn clone(&self) -> Self {
+    Indices {
+      lookups: self.lookups.clone(),
+      lazy: RwLock::new(self.lazy.read().unwrap().clone()),
+      budget: self.budget,
+    }
+  }
+}
+
+impl<G> Default for Indices<G>
+where
+  G: Graph,
+{
+  fn default() -> Self {
+    Indices::new()
+  }
 }
 
 impl<G> Indices<G>
@@ -103,103 +450,314 @@ where
   pub fn new() -> Indices<G> {
     Indices {
       lookups: HashMap::new(),
+      lazy: RwLock::new(LazyLookups::new()),
+      budget: None,
     }
   }
 
-  pub fn contains(&self, index: &Index, _guid: &Uuid) -> bool {
+  pub fn contains(&self, index: &Index, guid: &Uuid) -> bool {
     match self.lookups.get(index) {
-      Some(_lookup) => todo!(),
+      Some(lookup) => lookup.contains(guid),
       None => false,
     }
   }
 
-  /// Add a new value to each index to which it belongs.
+  /// Get an index that is always built. Lazy indices are only read by the queries that build them.
+  pub fn get(&self, index: &Index) -> Option<&Lookup<G>> {
+    self.lookups.get(index)
+  }
+
+  /// Check if an index has been declared, either built up front or lazily
+  pub fn is_declared(&self, index: &Index) -> bool {
+    self.lookups.contains_key(index) || self.lazy.read().unwrap().declared.contains(index)
+  }
+
+  /// Check if a lazy index is currently built
+  pub fn is_built(&self, index: &Index) -> bool {
+    self.lazy.read().unwrap().built.contains_key(index)
+  }
+
+  /// The index for finding values with the label by the property, preferring a property index
+  /// over a sorted one
+  pub fn find_index(&self, label: &str, property: &str) -> Option<&Lookup<G>> {
+    let (label, property) = (label.to_string(), property.to_string());
+    self
+      .lookups
+      .get(&Index::Property(label.clone(), property.clone()))
+      .or_else(|| self.lookups.get(&Index::Sorted(label, property)))
+  }
+
+  /// Read from the index on the label and property, building it first if it is lazy
   ///
-  /// Tags are skipped, as they are created separately and have no internal rules defining the
-  /// values that can be included.
-  pub fn index_value(&mut self, value: Value<G>) -> GraphtResult<Stats> {
-    let mut stats = Stats::default();
-
-    let labels: Vec<String> = {
-      match &value {
-        Value::Node(node) => node.get_labels().iter().map(|x| x.clone()).collect(),
-        Value::Edge(edge) => vec![edge.get_label()],
-        // This option doesn't make sense when indexing a value
-        Value::Path(_) => Vec::new(),
+  /// The values are only asked for when a lazy index needs building. None is returned when there
+  /// is no such index, or building it failed.
+  pub fn with_index<T, V>(
+    &self,
+    label: &str,
+    property: &str,
+    values: impl FnOnce() -> V,
+    read: impl FnOnce(&Lookup<G>) -> T,
+  ) -> Option<T>
+  where
+    V: IntoIterator<Item = Value<G>>,
+  {
+    if let Some(lookup) = self.find_index(label, property) {
+      return Some(read(lookup));
+    }
+
+    let (label, property) = (label.to_string(), property.to_string());
+    let mut lazy = self.lazy.write().unwrap();
+    let index = [
+      Index::Property(label.clone(), property.clone()),
+      Index::Sorted(label, property),
+    ]
+    .into_iter()
+    .find(|index| lazy.declared.contains(index))?;
+
+    lazy.tick += 1;
+    let tick = lazy.tick;
+    match lazy.built.get_mut(&index) {
+      Some((_, used)) => *used = tick,
+      None => {
+        let lookup = match Indices::build(&index, values()) {
+          Ok(lookup) => lookup,
+          Err(err) => {
+            warn!("Could not build the lazy index {}: {}", index, err);
+            return None;
+          }
+        };
+        debug!(
+          "Built the lazy index {} with {} values",
+          index,
+          lookup.len()
+        );
+        lazy.events.built.increase((index.to_string(), 1));
+        lazy.built.insert(index.clone(), (lookup, tick));
+        lazy.shrink(self.budget, Some(&index));
       }
+    }
+    lazy.built.get(&index).map(|(lookup, _)| read(lookup))
+  }
+
+  /// Fill a new lookup with the values that belong in the index
+  fn build(index: &Index, values: impl IntoIterator<Item = Value<G>>) -> GraphtResult<Lookup<G>> {
+    let mut lookup = Lookup::new(index.clone());
+    if !matches!(index, Index::Tag(_)) {
+      for value in values {
+        if index.belongs(&value)? {
+          lookup.insert(value)?;
+        }
+      }
+    }
+    Ok(lookup)
+  }
+
+  /// Build a new index from the values it applies to
+  ///
+  /// Tags always start empty, as values are added to them by hand.
+  pub fn create(
+    &mut self,
+    index: Index,
+    values: impl IntoIterator<Item = Value<G>>,
+  ) -> GraphtResult<IndexStats> {
+    if self.is_declared(&index) {
+      return Err(err!(DuplicateKey, "The index {} already exists", index));
+    }
+
+    let lookup = Indices::build(&index, values)?;
+    debug!("Created the index {} with {} values", index, lookup.len());
+
+    let mut stats = IndexStats::new();
+    stats.total.increase(1);
+    stats
+      .entries
+      .increase((index.to_string(), lookup.len() as i128));
+    self.lookups.insert(index, lookup);
+    Ok(stats)
+  }
+
+  /// Declare an index that is only built when a query first seeks from it
+  ///
+  /// Tags can't be lazy, as they are filled by hand rather than built.
+  pub fn create_lazy(&mut self, index: Index) -> GraphtResult<IndexStats> {
+    if let Index::Tag(_) = index {
+      return Err(err!(
+        InvalidItem,
+        "The tag {} is filled by hand, so it can't be built lazily",
+        index
+      ));
+    }
+    if self.is_declared(&index) {
+      return Err(err!(DuplicateKey, "The index {} already exists", index));
+    }
+
+    debug!("Declared the lazy index {}", index);
+    self.lazy.get_mut().unwrap().declared.insert(index);
+    let mut stats = IndexStats::new();
+    stats.total.increase(1);
+    Ok(stats)
+  }
+
+  /// Limit the bytes the built lazy indices hold, dropping the least recently used ones that no
+  /// longer fit. None lets them grow without limit.
+  pub fn set_budget(&mut self, budget: Option<usize>) {
+    self.budget = budget;
+    self.lazy.get_mut().unwrap().shrink(budget, None);
+  }
+
+  pub fn budget(&self) -> Option<usize> {
+    self.budget
+  }
+
+  /// Remove an index along with all its entries
+  pub fn drop(&mut self, index: &Index) -> GraphtResult<IndexStats> {
+    let lazy = self.lazy.get_mut().unwrap();
+    let lookup = match self.lookups.remove(index) {
+      Some(lookup) => Some(lookup),
+      None if lazy.declared.remove(index) => lazy.built.remove(index).map(|(lookup, _)| lookup),
+      None => return Err(err!(NotFound, "Could not find the index {} to drop", index)),
     };
 
-    for label in labels {
-      stats += self.add_value(&Index::Label(label), value.clone())?;
+    let mut stats = IndexStats::new();
+    stats.total.increase(1);
+    if let Some(lookup) = lookup {
+      stats
+        .entries
+        .increase((index.to_string(), lookup.len() as i128));
     }
+    Ok(stats)
+  }
 
+  /// Add a value to a tag, creating the tag if it is new
+  pub fn tag(&mut self, name: &str, value: Value<G>) -> GraphtResult<IndexStats> {
+    let index = Index::Tag(name.to_string());
+    let mut stats = IndexStats::new();
+    let lookup = self.lookups.entry(index.clone()).or_insert_with(|| {
+      stats.total.increase(1);
+      Lookup::new(index.clone())
+    });
+    lookup.insert(value)?;
+    stats.entries.increase((index.to_string(), 1));
     Ok(stats)
   }
 
-  /// Add a value to a lookup index
+  /// Take a value out of a tag, returning false if it wasn't tagged
+  pub fn untag(&mut self, name: &str, guid: &Uuid) -> GraphtResult<IndexStats> {
+    let index = Index::Tag(name.to_string());
+    let mut stats = IndexStats::new();
+    if let Some(lookup) = self.lookups.get_mut(&index) {
+      if lookup.remove(guid).is_some() {
+        stats.entries.increase((index.to_string(), 1));
+      }
+    }
+    Ok(stats)
+  }
+
+  /// Every lookup that is built, including the lazy ones
+  fn built_mut(&mut self) -> impl Iterator<Item = (&Index, &mut Lookup<G>)> {
+    let lazy = self.lazy.get_mut().unwrap();
+    self.lookups.iter_mut().chain(
+      lazy
+        .built
+        .iter_mut()
+        .map(|(index, (lookup, _))| (index, lookup)),
+    )
+  }
+
+  /// Add a new value to each index to which it belongs.
   ///
-  /// This double checks that the value being inserted matches the criteria for the index.
-  /// TODO: Add the validation
-  pub fn add_value(&mut self, key: &Index, value: Value<G>) -> GraphtResult<Stats> {
-    // key.belongs(&value)?;
-    self.add_value_unchecked(key, value)
+  /// Tags are skipped, as they are created separately and have no internal rules defining the
+  /// values that can be included.
+  pub fn index_value(&mut self, value: &Value<G>) -> GraphtResult<IndexStats> {
+    let mut stats = IndexStats::new();
+    for (index, lookup) in self.built_mut() {
+      if index.belongs(value)? {
+        lookup.insert(value.clone())?;
+        stats.entries.increase((index.to_string(), 1));
+      }
+    }
+
+    let budget = self.budget;
+    self.lazy.get_mut().unwrap().shrink(budget, None);
+    Ok(stats)
   }
 
-  /// Add a value to an index without validation
+  /// Re-file a changed value in each index, moving it between keys or in and out of the index
   ///
-  /// This is
-  pub fn add_value_unchecked(&mut self, key: &Index, value: Value<G>) -> GraphtResult<Stats> {
-    let mut stats = Stats::new();
-
-    // Get the lookup or create it if this is the first
-    if !self.lookups.contains_key(key) {
-      let _ = self.lookups.insert(key.clone(), Lookup::new(key.clone()));
-      match key {
-        Index::Label(value) | Index::Tag(value) => {
-          let mut mutation = Mutations::new();
-          mutation.created = 1;
-          stats.indices.labels.total += mutation.clone();
-          match stats.indices.labels.labels.entry(value.clone()) {
-            Entry::Vacant(entry) => {
-              entry.insert(mutation);
-            }
-            Entry::Occupied(mut entry) => *entry.get_mut() += mutation,
+  /// Tags keep the value whatever changed about it.
+  pub fn update_value(&mut self, value: &Value<G>) -> GraphtResult<CrudResultStats<IndexStats>> {
+    let guid = value.get_guid();
+    let (mut created, mut updated, mut deleted) =
+      (IndexStats::new(), IndexStats::new(), IndexStats::new());
+
+    for (index, lookup) in self.built_mut() {
+      let name = index.to_string();
+      let before = lookup.values.get(&guid).map(|(_, key)| key.clone());
+      match (before, index.key(value)?) {
+        (None, Some(_)) => {
+          lookup.insert(value.clone())?;
+          created.entries.increase((name, 1));
+        }
+        (Some(_), None) if !matches!(index, Index::Tag(_)) => {
+          lookup.remove(&guid);
+          deleted.entries.increase((name, 1));
+        }
+        (Some(before), after) => {
+          lookup.remove(&guid);
+          lookup.insert(value.clone())?;
+          if before.map(|x| x.0) != after {
+            updated.entries.increase((name, 1));
           }
-          stats.indices.labels.total.created += 1;
         }
-        _ => todo!("No stats ready except for Label"),
+        (None, None) => (),
       }
-    };
-
-    let _ = self.lookups.get_mut(key).unwrap().insert(value.clone());
+    }
 
+    let mut stats = CrudResultStats::new();
+    for (stat, add) in [
+      (created, CrudType::Create),
+      (updated, CrudType::Update),
+      (deleted, CrudType::Delete),
+    ] {
+      if stat != IndexStats::new() {
+        stats.add_crud(add, stat);
+      }
+    }
     Ok(stats)
   }
 
   /// Remove all references of the given value from each index
-  pub fn remove_value(&mut self, _value: Value<G>) -> GraphtResult<()> {
-    todo!("Indices::remove_value")
+  pub fn remove_value(&mut self, guid: &Uuid) -> IndexStats {
+    let mut stats = IndexStats::new();
+    for (index, lookup) in self.built_mut() {
+      if lookup.remove(guid).is_some() {
+        stats.entries.increase((index.to_string(), 1));
+      }
+    }
+    stats
   }
 
+  /// Counts of the declared indices and the values in the built ones, along with how often the
+  /// lazy indices have been built and dropped
   pub fn stats(&self) -> IndexStats {
+    let lazy = self.lazy.read().unwrap();
     let mut stats = IndexStats::new();
-    // Iterate through each lookup and gather the stats from it
-    for (index, lookup) in self.lookups {
-      match index {
-        Index::Edge(name) => match stats.edges.entry(name) {
-          Entry::Vacant(entry) => {
-            let mut edges = Mutations::new();
-            edges.created = lookup.len() as u128;
-            let _ = entry.insert(edges);
-          }
-          Entry::Occupied(entry) => {
-            let mut edges = entry.get_mut();
-            *edges.created = lookup.len() as u128;
-          }
-        },
-        _ => todo!("Only Index is available for stats right now"),
-      }
+    stats
+      .total
+      .increase((self.lookups.len() + lazy.declared.len()) as i128);
+
+    let built = lazy
+      .built
+      .iter()
+      .map(|(index, (lookup, _))| (index, lookup));
+    for (index, lookup) in self.lookups.iter().chain(built) {
+      stats
+        .entries
+        .increase((index.to_string(), lookup.len() as i128));
+      stats.memory.increase(lookup.memory() as i128);
     }
+    stats.built = lazy.events.built.clone();
+    stats.dropped = lazy.events.dropped.clone();
     stats
   }
 }
@@ -209,75 +767,121 @@ where
   G: Graph,
 {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    let mut output: &mut fmt::DebugStruct = &mut f.debug_struct("Indices");
-
-    // Group the indices by type so they can be formatted uniformly
-    let mut indices = HashMap::<&str, Vec<&Lookup<G>>>::new();
-    for value in self.lookups.values() {
-      let label = match value.index {
-        Index::Label(_) => "Label",
-        Index::Edge(_) => "Edge",
-        Index::Tag(_) => "Tag",
-        Index::Sorted(_) => "Sorted",
+    let lazy = self.lazy.read().unwrap();
+    let mut indices: Vec<&Index> = self.lookups.keys().chain(&lazy.declared).collect();
+    indices.sort();
+
+    let mut output = f.debug_struct("Indices");
+    for index in indices {
+      let description = match self
+        .lookups
+        .get(index)
+        .or_else(|| lazy.built.get(index).map(|(lookup, _)| lookup))
+      {
+        Some(lookup) => format!("{} Members, {} Keys", lookup.len(), lookup.distinct()),
+        None => "Not Built".to_string(),
       };
+      output.field(&index.to_string(), &description);
+    }
+    output.finish()
+  }
+}
 
-      if !indices.contains_key(label) {
-        indices.insert(label, Vec::new());
-      }
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexStats {
+  /// A count of the indices
+  #[serde(default)]
+  pub total: StatCount,
 
-      if let Some(x) = indices.get_mut(label) {
-        x.push(value)
-      }
-    }
+  /// Counts of the values filed in each index, by the index's name (eg. "property:Org.name")
+  #[serde(default)]
+  pub entries: StatMap<String, StatCount, <StatCount as Stats>::Item>,
 
-    // Labels only print a count of the nodes having that tag
-    if let Some(lookups) = indices.get_mut("Label") {
-      lookups.sort_by(|a, b| a.index.cmp(&b.index));
-      let values: Vec<String> = lookups
-        .iter()
-        .map(|lookup| {
-          if let Index::Label(name) = &lookup.index {
-            format!("{}: {} Members", name, lookup.values.len())
-          } else {
-            unreachable!("It's always a label")
-          }
-        })
-        .collect();
-      output = output.field("Labels", &values);
+  /// The estimated bytes held by the indices that are built
+  #[serde(default)]
+  pub memory: StatCount,
+
+  /// How many times each lazy index has been built
+  #[serde(default)]
+  pub built: StatMap<String, StatCount, <StatCount as Stats>::Item>,
+
+  /// How many times each lazy index has been dropped to stay within the memory budget
+  #[serde(default)]
+  pub dropped: StatMap<String, StatCount, <StatCount as Stats>::Item>,
+}
+
+impl IndexStats {
+  pub fn new() -> IndexStats {
+    IndexStats {
+      total: StatCount::new(),
+      entries: StatMap::new(),
+      memory: StatCount::new(),
+      built: StatMap::new(),
+      dropped: StatMap::new(),
     }
+  }
+}
 
-    // Edge only print a count of the edges of that type
-    if let Some(lookups) = indices.get_mut("Edge") {
-      lookups.sort_by(|a, b| a.index.cmp(&b.index));
-      let values: Vec<String> = lookups
-        .iter()
-        .map(|lookup| {
-          if let Index::Label(name) = &lookup.index {
-            format!("{}: {} Members", name, lookup.values.len())
-          } else {
-            unreachable!("It's always a label")
-          }
-        })
-        .collect();
-      output = output.field("Edges", &values);
+impl Diff for IndexStats {
+  fn diff(&self, rhs: &Self, name: Option<&str>) -> Difference {
+    let mut diffs = Difference::new();
+    diffs += self.total.diff(&rhs.total, Some("total"));
+    diffs += self.entries.diff(&rhs.entries, Some("entries"));
+    diffs += self.memory.diff(&rhs.memory, Some("memory"));
+    diffs += self.built.diff(&rhs.built, Some("built"));
+    diffs += self.dropped.diff(&rhs.dropped, Some("dropped"));
+    diffs.opt_tag(name)
+  }
+}
+
+impl Add for IndexStats {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self::Output {
+    IndexStats {
+      total: self.total + rhs.total,
+      entries: self.entries + rhs.entries,
+      memory: self.memory + rhs.memory,
+      built: self.built + rhs.built,
+      dropped: self.dropped + rhs.dropped,
     }
+  }
+}
 
-    // Tags print a count of the nodes with the tag
-    if let Some(lookups) = indices.get_mut("Tag") {
-      lookups.sort_by(|a, b| a.index.cmp(&b.index));
-      let values: Vec<String> = lookups
-        .iter()
-        .map(|lookup| {
-          if let Index::Label(name) = &lookup.index {
-            format!("{}: {} Members", name, lookup.values.len())
-          } else {
-            unreachable!("It's always a tag")
-          }
-        })
-        .collect();
-      output = output.field("Labels", &values);
+impl AddAssign for IndexStats {
+  fn add_assign(&mut self, rhs: Self) {
+    *self = self.clone().add(rhs);
+  }
+}
+
+/// A change to one of the counts in IndexStats, with the index's name for the per-index counts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexStat {
+  Total(i128),
+  Entries(String, i128),
+  Memory(i128),
+  Built(String, i128),
+  Dropped(String, i128),
+}
+
+impl Stats for IndexStats {
+  type Item = IndexStat;
+
+  fn increase(&mut self, value: Self::Item) {
+    match value {
+      IndexStat::Total(count) => self.total.increase(count),
+      IndexStat::Entries(name, count) => self.entries.increase((name, count)),
+      IndexStat::Memory(bytes) => self.memory.increase(bytes),
+      IndexStat::Built(name, count) => self.built.increase((name, count)),
+      IndexStat::Dropped(name, count) => self.dropped.increase((name, count)),
     }
+  }
 
-    output.finish()
+  fn clear(&mut self) {
+    self.total.clear();
+    self.entries.clear();
+    self.memory.clear();
+    self.built.clear();
+    self.dropped.clear();
   }
 }
@@ -15,8 +15,30 @@ pub use nodeset::*;
 pub mod edgeset;
 pub use edgeset::*;
 
-// pub mod index;
-// pub use index::*;
+// Changing a stored node through a handle that is committed in one go
+pub mod edit;
+pub use edit::NodeEdit;
+
+// User declared lookups on properties, along with ad-hoc tags
+pub mod index;
+pub use index::{Index, IndexStat, IndexStats, Indices, Lookup};
 
 pub mod value;
 pub use value::*;
+
+pub mod query;
+pub use query::{Matchable, Row};
+
+// Finding the shortest paths between nodes, by hops or by weight
+pub mod shortest;
+pub use shortest::{PathOpts, PathWeight};
+
+// Running CREATE, MERGE, SET, REMOVE, and DELETE against a DataSet
+pub mod update;
+
+// Choosing where to start matching patterns and in which order
+pub mod planner;
+
+// Describing and profiling the plan a query runs with
+pub mod explain;
+pub use explain::{Operator, OperatorStats, PlanMode};
@@ -1,6 +1,6 @@
 //! A group of nodes contained in a graph
 
-use crate::{local::*, prelude::*};
+use crate::{local::*, prelude::*, stats::property_changes, store::index::Key};
 
 use std::{
   collections::{
@@ -31,6 +31,9 @@ where
   /// Group the nodes by each individual label
   labels: HashMap<String, HashMap<Uuid, Node<G>>>,
 
+  /// The node holding each value of the properties the Graph declares unique
+  unique: HashMap<Constraint, HashMap<Key, Uuid>>,
+
   /// Live statistics about the nodes
   stats: NodeStats,
 }
@@ -45,6 +48,7 @@ where
       nodes: HashMap::new(),
       typed: HashMap::new(),
       labels: HashMap::new(),
+      unique: HashMap::new(),
       stats: NodeStats::new(),
     }
   }
@@ -59,14 +63,45 @@ where
     self.nodes.get(guid)
   }
 
+  /// Get all the nodes tagged with the given label
+  pub fn get_labeled(&self, label: &str) -> Vec<Node<G>> {
+    match self.labels.get(label) {
+      Some(nodes) => nodes.values().cloned().collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// The number of nodes with the label, or of all nodes, taken from the live statistics
+  pub fn count(&self, label: Option<&str>) -> usize {
+    let count = match label {
+      Some(label) => self
+        .stats
+        .labels
+        .get(&label.to_string())
+        .map(|x| x.count())
+        .unwrap_or(0),
+      None => self.stats.total.count(),
+    };
+    count as usize
+  }
+
   /// Queries and retrieves nodes matching the query
-  pub fn query(&self, _query: &str) -> GraphtResult<Vec<Node<G>>> {
-    todo!()
+  ///
+  /// Every distinct node found in any of the returned columns is included
+  pub fn query(&self, query: &str) -> GraphtResult<Vec<Node<G>>> {
+    let results = query::execute(self, &GQuery::parse(query)?, &Params::new())?;
+    Ok(collect_nodes(results))
   }
 
   /// Insert a new node into the graph and fail if it already exists
+  ///
+  /// Nodes breaking one of the Graph's constraints are rejected with a ConstraintViolation.
   pub fn insert(&mut self, node: &Node<G>) -> GraphtResult<CrudResultStats<NodeStats>> {
     let mut stats = NodeStats::new();
+    let unique = match self.nodes.contains_key(&node.get_guid()) {
+      true => Vec::new(),
+      false => self.check_constraints(node)?,
+    };
 
     // Make a copy of the node that cannot be directly accessed by calling code
     let mut new_node: Node<G>;
@@ -90,6 +125,7 @@ where
     // Update the stats for Node
     stats.total.increase(1);
     self.stats.total.increase(1);
+    self.file_unique(node.get_guid(), unique);
 
     // Clone the node for use with closures for indexing the node
     let node = new_node.clone();
@@ -128,13 +164,213 @@ where
   }
 
   /// Updates the original node with the new node, updating the internal indices as it goes
-  pub fn update(&mut self, _node: &Node<G>) -> GraphtResult<NodeStats> {
-    todo!()
+  ///
+  /// The stored node is changed in place, so every copy returned by the set sees the new values.
+  /// Properties given a new value and labels added are counted as updated, while properties and
+  /// labels that were dropped are counted as deleted.
+  pub fn update(&mut self, node: &Node<G>) -> GraphtResult<CrudResultStats<NodeStats>> {
+    let guid = node.get_guid();
+    let stored = match self.nodes.get(&guid) {
+      Some(stored) => stored.clone(),
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Node with Uuid {} in the NodeSet {} for updating",
+          guid,
+          self.guid
+        ))
+      }
+    };
+
+    // Check the new version before touching anything, so a violation leaves the node as it was
+    let unique = self.check_constraints(node)?;
+    let replaced = self.unique_keys(&stored)?;
+
+    let mut updated = NodeStats::new();
+    let mut deleted = NodeStats::new();
+    (updated.properties, deleted.properties) = property_changes(
+      &stored.get_props().get_properties()?,
+      &node.get_props().get_properties()?,
+    );
+
+    // Move the node to its new type
+    let (old_type, new_type) = (stored.type_label(), node.type_label());
+    if old_type != new_type {
+      self.remove_typed(&old_type, &guid);
+      self
+        .typed
+        .entry(new_type.clone())
+        .or_default()
+        .insert(guid, stored.clone());
+      self.stats.typed.increase((old_type.clone(), -1));
+      self.stats.typed.increase((new_type.clone(), 1));
+      updated.typed.increase((new_type, 1));
+      deleted.typed.increase((old_type, 1));
+    }
+
+    let (old_labels, new_labels) = (stored.get_labels(), node.get_labels());
+    for label in new_labels.difference(&old_labels) {
+      self
+        .labels
+        .entry(label.clone())
+        .or_default()
+        .insert(guid, stored.clone());
+      self.stats.labels.increase((label.clone(), 1));
+      updated.labels.increase((label.clone(), 1));
+    }
+    for label in old_labels.difference(&new_labels) {
+      self.remove_labeled(label, &guid);
+      self.stats.labels.increase((label.clone(), -1));
+      deleted.labels.increase((label.clone(), 1));
+    }
+
+    stored.assign(node);
+    self.unfile_unique(&guid, replaced);
+    self.file_unique(guid, unique);
+
+    let mut stats = CrudResultStats::new();
+    if updated != NodeStats::new() || deleted != NodeStats::new() {
+      updated.total.increase(1);
+      stats.add_updated(updated);
+    }
+    if deleted != NodeStats::new() {
+      stats.add_deleted(deleted);
+    }
+    Ok(stats)
   }
 
   /// Removes the node and all its indices from the set
-  pub fn delete(&mut self, _query: ()) -> GraphtResult<NodeStats> {
-    todo!()
+  ///
+  /// Only the node is removed, so any edges pointing at it need to be removed separately.
+  pub fn delete(&mut self, guid: &Uuid) -> GraphtResult<NodeStats> {
+    let mut node = match self.nodes.remove(guid) {
+      Some(node) => node,
+      None => {
+        return Err(err!(
+          NotFound,
+          "Could not get Node with Uuid {} in the NodeSet {} for deleting",
+          guid,
+          self.guid
+        ))
+      }
+    };
+    node.set_bound(false);
+    if let Ok(unique) = self.unique_keys(&node) {
+      self.unfile_unique(guid, unique);
+    }
+
+    let mut stats = NodeStats::new();
+    stats.total.increase(1);
+    self.stats.total.increase(-1);
+
+    let type_label = node.type_label();
+    self.remove_typed(&type_label, guid);
+    stats.typed.increase((type_label.clone(), 1));
+    self.stats.typed.increase((type_label, -1));
+
+    for label in node.get_labels() {
+      self.remove_labeled(&label, guid);
+      stats.labels.increase((label.clone(), 1));
+      self.stats.labels.increase((label, -1));
+    }
+    Ok(stats)
+  }
+
+  /// Check a node against the Graph's constraints, as if it replaced any stored node with its guid
+  ///
+  /// Returns the values the node needs to hold for the unique constraints it falls under.
+  fn check_constraints(&self, node: &Node<G>) -> GraphtResult<Vec<(Constraint, Key)>> {
+    let guid = node.get_guid();
+    for constraint in G::constraints() {
+      if let Constraint::Exists { label, property } = &constraint {
+        if node.has_label(label) && node.get_property(property)? == Literal::Null {
+          return Err(violation(constraint, vec![guid]));
+        }
+      }
+    }
+
+    let unique = self.unique_keys(node)?;
+    for (constraint, key) in &unique {
+      let holder = self.unique.get(constraint).and_then(|keys| keys.get(key));
+      if let Some(holder) = holder.filter(|holder| **holder != guid) {
+        return Err(violation(constraint.clone(), vec![guid, *holder]));
+      }
+    }
+    Ok(unique)
+  }
+
+  /// Check nodes that are about to be inserted or updated together, including two of them taking
+  /// the same unique value
+  pub(crate) fn check_all(&self, nodes: &[Node<G>]) -> GraphtResult<()> {
+    let mut taken: HashMap<(Constraint, Key), Uuid> = HashMap::new();
+    for node in nodes {
+      let guid = node.get_guid();
+      for key in self.check_constraints(node)? {
+        match taken.entry(key) {
+          Entry::Occupied(holder) if *holder.get() != guid => {
+            return Err(violation(holder.key().0.clone(), vec![guid, *holder.get()]))
+          }
+          entry => {
+            entry.or_insert(guid);
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// The values of the node's properties that fall under a unique constraint
+  fn unique_keys(&self, node: &Node<G>) -> GraphtResult<Vec<(Constraint, Key)>> {
+    let mut keys = Vec::new();
+    for constraint in G::constraints() {
+      if let Constraint::Unique { label, property } = &constraint {
+        if !node.has_label(label) {
+          continue;
+        }
+        match node.get_property(property)? {
+          Literal::Null => (),
+          value => keys.push((constraint, Key(value))),
+        }
+      }
+    }
+    Ok(keys)
+  }
+
+  fn file_unique(&mut self, guid: Uuid, keys: Vec<(Constraint, Key)>) {
+    for (constraint, key) in keys {
+      self.unique.entry(constraint).or_default().insert(key, guid);
+    }
+  }
+
+  /// Release the unique values held by the node
+  fn unfile_unique(&mut self, guid: &Uuid, keys: Vec<(Constraint, Key)>) {
+    for (constraint, key) in keys {
+      if let Some(keys) = self.unique.get_mut(&constraint) {
+        if keys.get(&key) == Some(guid) {
+          keys.remove(&key);
+        }
+      }
+    }
+  }
+
+  /// Drop a node from the type index, along with the type once it has no nodes
+  fn remove_typed(&mut self, type_label: &str, guid: &Uuid) {
+    if let Some(nodes) = self.typed.get_mut(type_label) {
+      nodes.remove(guid);
+      if nodes.is_empty() {
+        self.typed.remove(type_label);
+      }
+    }
+  }
+
+  /// Drop a node from the label index, along with the label once it has no nodes
+  fn remove_labeled(&mut self, label: &str, guid: &Uuid) {
+    if let Some(nodes) = self.labels.get_mut(label) {
+      nodes.remove(guid);
+      if nodes.is_empty() {
+        self.labels.remove(label);
+      }
+    }
   }
 
   pub fn stats(&self) -> NodeStats {
@@ -162,6 +398,60 @@ where
   }
 }
 
+impl<G> Matchable<G> for NodeSet<G>
+where
+  G: Graph,
+{
+  fn get_node(&self, guid: &Uuid) -> Option<Node<G>> {
+    self.nodes.get(guid).cloned()
+  }
+
+  fn scan_nodes(&self, label: Option<&str>) -> Vec<Node<G>> {
+    match label {
+      Some(label) => self.get_labeled(label),
+      None => self.nodes.values().cloned().collect(),
+    }
+  }
+
+  fn count_nodes(&self, label: Option<&str>) -> usize {
+    self.count(label)
+  }
+}
+
+/// Build the error for a node breaking a constraint
+fn violation(constraint: Constraint, guids: Vec<Uuid>) -> GraphtError {
+  let guids_list: Vec<String> = guids.iter().map(|x| x.to_string()).collect();
+  let comment = format!(
+    "Node {} breaks the constraint {}",
+    guids_list.join(" conflicts with node "),
+    constraint
+  );
+  GraphtError::new(Kind::ConstraintViolation(Box::new(Violation {
+    constraint,
+    guids,
+  })))
+  .comment(comment)
+}
+
+/// Pull the unique nodes out of a set of query results
+pub(crate) fn collect_nodes<G: Graph>(results: HashMap<String, Vec<Value<G>>>) -> Vec<Node<G>> {
+  let mut seen = std::collections::HashSet::new();
+  let mut nodes = Vec::new();
+  // Popped from the end, so reversed to keep the nodes in the order they were returned
+  let mut values: Vec<Value<G>> = results.into_values().flatten().collect();
+  values.reverse();
+  while let Some(value) = values.pop() {
+    match value {
+      Value::Node(node) if seen.insert(node.get_guid()) => nodes.push(node),
+      // Lists such as collect(org) and paths hold nodes as well
+      Value::List(items) => values.extend(items.into_iter().rev()),
+      Value::Path(path) => values.extend(path.nodes().into_iter().rev().map(Value::Node)),
+      _ => (),
+    }
+  }
+  nodes
+}
+
 impl<'a, G> IntoIterator for &'a NodeSet<G>
 where
   G: Graph,
@@ -0,0 +1,516 @@
+//! Choosing how to match the patterns of a MATCH clause
+//!
+//! Matching a pattern part can start from any of its nodes, or from any of its typed edges, and
+//! walk outwards from there. The planner estimates how many items each starting point would scan
+//! using the counts the store keeps for each label and edge type, then starts from the cheapest.
+//! Pattern parts are matched cheapest first, so variables bound by one part narrow down the next.
+//!
+//! Labels in the WHERE clause (eg. "WHERE o:Organization") count the same as labels in the
+//! pattern, so a label restricted MATCH never scans every node. A node's type is always one of its
+//! labels, so the label counts cover the type index as well.
+//!
+//! A node pattern with a constant property (eg. "(o:Organization {pretty_id: $id})") can instead
+//! seek the nodes from an index on that label and property. The index's count of distinct values
+//! estimates how many nodes a seek finds.
+
+use crate::{gquery::ast::*, local::*, prelude::*, store::explain::Operator};
+
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+  marker::PhantomData,
+};
+
+/// The plan for each pattern part of a MATCH, in the order they are matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchPlan {
+  pub parts: Vec<PartPlan>,
+}
+
+/// How to match a single pattern part
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartPlan {
+  /// The position of the part in the MATCH
+  pub index: usize,
+
+  /// Where matching starts
+  pub anchor: Anchor,
+
+  /// The steps of the chain to follow from the anchor, in order
+  pub expands: Vec<Expand>,
+
+  /// The estimated number of rows the part produces for each incoming row
+  pub estimate: f64,
+
+  pattern: PatternPart,
+}
+
+/// The starting point for matching a pattern part
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anchor {
+  /// The node at the position is already bound to the variable
+  Bound { node: usize, variable: String },
+
+  /// Scan the nodes with the label from the label index
+  Label { node: usize, label: String },
+
+  /// Seek the nodes with the label whose property equals the value from a property index
+  Index {
+    node: usize,
+    label: String,
+    property: String,
+    value: Expression,
+  },
+
+  /// Scan every node
+  AllNodes { node: usize },
+
+  /// Scan the edges with the types from the type index, binding the nodes at both ends
+  EdgeTypes { step: usize, types: Vec<String> },
+}
+
+/// Follow a step of the chain from a node that has already been matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expand {
+  /// The position of the step in the chain. Step N joins node N and node N + 1, where node 0 is
+  /// the start of the pattern
+  pub step: usize,
+
+  /// Walk from node N + 1 back to node N
+  pub reversed: bool,
+}
+
+impl Expand {
+  /// The positions of the nodes that the step goes from and to
+  pub fn nodes(&self) -> (usize, usize) {
+    match self.reversed {
+      false => (self.step, self.step + 1),
+      true => (self.step + 1, self.step),
+    }
+  }
+
+  /// The direction of the edge when walking the step
+  pub fn direction(&self, relationship: &RelationshipPattern) -> Direction {
+    match (self.reversed, relationship.direction) {
+      (true, Direction::Outgoing) => Direction::Incoming,
+      (true, Direction::Incoming) => Direction::Outgoing,
+      (_, direction) => direction,
+    }
+  }
+}
+
+/// Get the node pattern at a position in the chain
+pub fn node_at(part: &PatternPart, node: usize) -> &NodePattern {
+  match node {
+    0 => &part.start,
+    n => &part.chain[n - 1].node,
+  }
+}
+
+/// Plan the pattern parts of a MATCH, given the variables bound by earlier clauses
+pub fn plan_match<G, S>(source: &S, matched: &Match, bound: &HashSet<String>) -> MatchPlan
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let labels = filter_labels(matched.filter.as_ref());
+  let estimator = Estimator::new(source);
+
+  let mut bound = bound.clone();
+  let mut remaining: Vec<usize> = (0..matched.pattern.parts.len()).collect();
+  let mut parts = Vec::new();
+
+  // Greedily match the part that produces the fewest rows with what is bound so far
+  while !remaining.is_empty() {
+    let (position, plan) = remaining
+      .iter()
+      .enumerate()
+      .map(|(position, index)| {
+        let part = &matched.pattern.parts[*index];
+        (
+          position,
+          plan_part(&estimator, *index, part, &bound, &labels),
+        )
+      })
+      .min_by(|(_, lhs), (_, rhs)| lhs.estimate.total_cmp(&rhs.estimate))
+      .expect("There is always a remaining part");
+
+    remaining.remove(position);
+    bound.extend(plan.pattern.variables());
+    parts.push(plan);
+  }
+
+  let plan = MatchPlan { parts };
+  debug!("Planned MATCH {}\n{}", matched.pattern, plan);
+  plan
+}
+
+/// Plan a single pattern part on its own, such as a pattern used as a predicate
+pub fn plan_pattern<G, S>(source: &S, part: &PatternPart, bound: &HashSet<String>) -> PartPlan
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  plan_part(&Estimator::new(source), 0, part, bound, &HashMap::new())
+}
+
+/// Pick the cheapest anchor for a pattern part
+fn plan_part<G, S>(
+  estimator: &Estimator<'_, G, S>,
+  index: usize,
+  part: &PatternPart,
+  bound: &HashSet<String>,
+  filter_labels: &HashMap<String, Vec<String>>,
+) -> PartPlan
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let steps = part.chain.len();
+  let mut candidates: Vec<(f64, Anchor)> = Vec::new();
+
+  for node in 0..=steps {
+    let pattern = node_at(part, node);
+    if let Some(variable) = pattern.variable.as_ref().filter(|x| bound.contains(*x)) {
+      candidates.push((
+        1.0,
+        Anchor::Bound {
+          node,
+          variable: variable.clone(),
+        },
+      ));
+      continue;
+    }
+
+    let mut labels = pattern.labels.clone();
+    if let Some(variable) = &pattern.variable {
+      labels.extend(filter_labels.get(variable).cloned().unwrap_or_default());
+    }
+
+    // Seeking from an index is pushed first, so it wins a tie with scanning the same label
+    for (property, value) in &pattern.properties {
+      if !matches!(value, Expression::Literal(_) | Expression::Parameter(_)) {
+        continue;
+      }
+      for label in &labels {
+        if let Some(distinct) = estimator.distinct(label, property) {
+          candidates.push((
+            estimator.nodes(Some(label)) / distinct.max(1.0),
+            Anchor::Index {
+              node,
+              label: label.clone(),
+              property: property.clone(),
+              value: value.clone(),
+            },
+          ));
+        }
+      }
+    }
+
+    let smallest = labels
+      .into_iter()
+      .map(|label| (estimator.nodes(Some(&label)), label))
+      .min_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+    candidates.push(match smallest {
+      Some((count, label)) => (count, Anchor::Label { node, label }),
+      None => (estimator.nodes(None), Anchor::AllNodes { node }),
+    });
+  }
+
+  for (step, link) in part.chain.iter().enumerate() {
+    let relationship = &link.relationship;
+    if relationship.types.is_empty() || relationship.length.is_some() {
+      continue;
+    }
+    let count = relationship
+      .types
+      .iter()
+      .map(|edge_type| estimator.edges(Some(edge_type)))
+      .sum::<f64>();
+    // Undirected edges are walked both ways
+    let count = match relationship.direction {
+      Direction::Either => count * 2.0,
+      _ => count,
+    };
+    candidates.push((
+      count,
+      Anchor::EdgeTypes {
+        step,
+        types: relationship.types.clone(),
+      },
+    ));
+  }
+
+  // On a tie prefer anything over scanning every node, then the earlier candidate, so the plan
+  // follows the written order where it can
+  let scans_all = |anchor: &Anchor| matches!(anchor, Anchor::AllNodes { .. });
+  let (cost, anchor) = candidates
+    .into_iter()
+    .reduce(|best, next| {
+      let cheaper = next.0 < best.0;
+      let tied = next.0 == best.0 && scans_all(&best.1) && !scans_all(&next.1);
+      match cheaper || tied {
+        true => next,
+        false => best,
+      }
+    })
+    .expect("A pattern part always has a start node");
+
+  let (expands, mut estimate) = match &anchor {
+    Anchor::Bound { node, .. }
+    | Anchor::Label { node, .. }
+    | Anchor::Index { node, .. }
+    | Anchor::AllNodes { node } => (walk(*node, *node, steps), cost),
+    // Both ends of the edge are bound by the scan
+    Anchor::EdgeTypes { step, .. } => (walk(*step + 1, *step, steps), cost),
+  };
+  for expand in &expands {
+    estimate *= estimator.fanout(&part.chain[expand.step].relationship);
+  }
+
+  PartPlan {
+    index,
+    anchor,
+    expands,
+    estimate,
+    pattern: part.clone(),
+  }
+}
+
+/// Walk forward to the end of the chain from one node, then back to the start from another
+fn walk(forward: usize, backward: usize, steps: usize) -> Vec<Expand> {
+  let forward = (forward..steps).map(|step| Expand {
+    step,
+    reversed: false,
+  });
+  let backward = (0..backward).rev().map(|step| Expand {
+    step,
+    reversed: true,
+  });
+  forward.chain(backward).collect()
+}
+
+/// Labels that the WHERE clause requires a variable to have, from "AND"ed label checks
+fn filter_labels(filter: Option<&Expression>) -> HashMap<String, Vec<String>> {
+  let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+  let mut pending: Vec<&Expression> = filter.into_iter().collect();
+  while let Some(expression) = pending.pop() {
+    match expression {
+      Expression::Binary(BinaryOperator::And, lhs, rhs) => {
+        pending.push(lhs);
+        pending.push(rhs);
+      }
+      Expression::HasLabels(inner, names) => {
+        if let Expression::Variable(variable) = inner.as_ref() {
+          labels
+            .entry(variable.clone())
+            .or_default()
+            .extend(names.iter().cloned());
+        }
+      }
+      _ => (),
+    }
+  }
+  labels
+}
+
+/// The number of hops assumed when estimating a variable length step without a maximum
+const UNBOUNDED_HOPS: u64 = 3;
+
+/// Estimates the size of scans from the store's counts, remembering what it has already asked for
+struct Estimator<'a, G, S>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  source: &'a S,
+  nodes: RefCell<HashMap<Option<String>, f64>>,
+  edges: RefCell<HashMap<Option<String>, f64>>,
+  _graph: PhantomData<G>,
+}
+
+impl<'a, G, S> Estimator<'a, G, S>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  fn new(source: &'a S) -> Estimator<'a, G, S> {
+    Estimator {
+      source,
+      nodes: Default::default(),
+      edges: Default::default(),
+      _graph: PhantomData,
+    }
+  }
+
+  fn nodes(&self, label: Option<&str>) -> f64 {
+    *self
+      .nodes
+      .borrow_mut()
+      .entry(label.map(String::from))
+      .or_insert_with(|| self.source.count_nodes(label) as f64)
+  }
+
+  fn edges(&self, edge_type: Option<&str>) -> f64 {
+    *self
+      .edges
+      .borrow_mut()
+      .entry(edge_type.map(String::from))
+      .or_insert_with(|| self.source.count_edges(edge_type) as f64)
+  }
+
+  /// The number of distinct values in an index on the label and property, if there is one
+  fn distinct(&self, label: &str, property: &str) -> Option<f64> {
+    self
+      .source
+      .distinct_values(label, property)
+      .map(|distinct| distinct as f64)
+  }
+
+  /// The average number of matching edges leaving a node
+  fn fanout(&self, relationship: &RelationshipPattern) -> f64 {
+    let edges = match relationship.types.is_empty() {
+      true => self.edges(None),
+      false => relationship
+        .types
+        .iter()
+        .map(|edge_type| self.edges(Some(edge_type)))
+        .sum(),
+    };
+    let per_node = edges / self.nodes(None).max(1.0);
+    let per_node = match relationship.direction {
+      Direction::Either => per_node * 2.0,
+      _ => per_node,
+    };
+
+    // A variable length step reaches the nodes of every length it allows, assuming a few hops
+    // when it has no upper bound. Longer ranges are cut to the same few hops past the minimum so
+    // a huge bound can't stall planning
+    match relationship.length {
+      Some(length) => {
+        let min = length.min.unwrap_or(1);
+        let max = length.max.unwrap_or(min.max(UNBOUNDED_HOPS));
+        let max = max.min(min.saturating_add(UNBOUNDED_HOPS));
+        (min..=max).map(|hops| per_node.powf(hops as f64)).sum()
+      }
+      None => per_node,
+    }
+  }
+}
+
+impl Display for MatchPlan {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let parts: Vec<String> = self.parts.iter().map(|x| x.to_string()).collect();
+    write!(f, "{}", parts.join("\n"))
+  }
+}
+
+impl PartPlan {
+  /// The scan and expand operators that match the part, in the order they run
+  pub(crate) fn operators(&self) -> Vec<Operator> {
+    let (name, detail) = match &self.anchor {
+      Anchor::Bound { variable, .. } => ("Argument", escape_name(variable)),
+      Anchor::Label { node: index, label } => {
+        let mut pattern = self.node(*index);
+        pattern.labels.push(label.clone());
+        ("Node By Label Scan", pattern.to_string())
+      }
+      Anchor::Index {
+        node: index,
+        label,
+        property,
+        value,
+      } => (
+        "Node Index Seek",
+        self.seek_pattern(*index, label, property, value),
+      ),
+      Anchor::AllNodes { node: index } => ("All Node Scan", self.node(*index).to_string()),
+      Anchor::EdgeTypes { step, .. } => (
+        "Edge By Type Scan",
+        format!(
+          "{}{}{}",
+          self.node(*step),
+          self.pattern.chain[*step].relationship,
+          self.node(step + 1)
+        ),
+      ),
+    };
+
+    let mut operators = vec![Operator::new(name, Some(detail))];
+    for expand in &self.expands {
+      let name = match (
+        self.pattern.shortest,
+        self.pattern.chain[expand.step].relationship.length,
+      ) {
+        (Some(Shortest::Any), _) => "Shortest Path",
+        (Some(Shortest::All), _) => "All Shortest Paths",
+        (None, Some(_)) => "Conditional Variable Length Traverse",
+        (None, None) => "Conditional Traverse",
+      };
+      operators.push(Operator::new(name, Some(self.expand_pattern(expand))));
+    }
+    operators
+  }
+
+  /// The step walked by an expand, written in the direction it is walked
+  fn expand_pattern(&self, expand: &Expand) -> String {
+    let (from, to) = expand.nodes();
+    let mut relationship = self.pattern.chain[expand.step].relationship.clone();
+    relationship.direction = expand.direction(&relationship);
+    format!("{}{}{}", self.node(from), relationship, self.node(to))
+  }
+
+  /// The node sought from an index, with the label and property it is sought by
+  fn seek_pattern(&self, index: usize, label: &str, property: &str, value: &Expression) -> String {
+    let mut pattern = self.node(index);
+    pattern.labels.push(label.to_string());
+    pattern
+      .properties
+      .push((property.to_string(), value.clone()));
+    pattern.to_string()
+  }
+
+  /// The node at a position with only its variable, for describing the plan
+  fn node(&self, index: usize) -> NodePattern {
+    NodePattern {
+      variable: node_at(&self.pattern, index).variable.clone(),
+      ..NodePattern::default()
+    }
+  }
+}
+
+impl Display for PartPlan {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.anchor {
+      Anchor::Bound { variable, .. } => write!(f, "Argument({})", escape_name(variable))?,
+      Anchor::Label { node: index, label } => {
+        let mut pattern = self.node(*index);
+        pattern.labels.push(label.clone());
+        write!(f, "NodeByLabelScan{}", pattern)?
+      }
+      Anchor::Index {
+        node: index,
+        label,
+        property,
+        value,
+      } => write!(
+        f,
+        "NodeIndexSeek{}",
+        self.seek_pattern(*index, label, property, value)
+      )?,
+      Anchor::AllNodes { node: index } => write!(f, "AllNodesScan{}", self.node(*index))?,
+      Anchor::EdgeTypes { step, .. } => write!(
+        f,
+        "EdgeByTypeScan{}{}{}",
+        self.node(*step),
+        self.pattern.chain[*step].relationship,
+        self.node(step + 1)
+      )?,
+    }
+
+    for expand in &self.expands {
+      write!(f, " -> Expand{}", self.expand_pattern(expand))?;
+    }
+    write!(f, " (~{} rows)", self.estimate.round())
+  }
+}
@@ -0,0 +1,1703 @@
+//! Running a GQuery against the nodes and edges held in a store
+//!
+//! Matching works row by row. Each clause takes the rows produced by the previous clause and
+//! either extends them with newly matched items (MATCH, UNWIND), filters them (WHERE), projects
+//! them into new rows (WITH), or turns them into the final named columns (RETURN).
+
+use crate::{
+  gquery::ast::*,
+  local::*,
+  prelude::*,
+  store::{
+    explain::{self, Operator, OperatorStats},
+    planner::{self, node_at, Anchor, MatchPlan, PartPlan},
+    shortest,
+  },
+};
+
+use std::{
+  cell::RefCell,
+  cmp::Ordering,
+  collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+  marker::PhantomData,
+  time::Instant,
+};
+
+use uuid::Uuid;
+
+/// The named values matched so far
+pub type Row<G> = HashMap<String, Value<G>>;
+
+/// The edges walked to reach a node, in the order they were walked
+type Walk<G> = (Vec<Edge<G>>, Node<G>);
+
+/// A partial match of a pattern part
+#[derive(Clone)]
+struct PartState<G>
+where
+  G: Graph,
+{
+  row: Row<G>,
+
+  /// The edges used so far, since an edge can only be used once in a match
+  used: HashSet<Uuid>,
+
+  /// The node matched at each position of the chain
+  nodes: Vec<Option<Node<G>>>,
+
+  /// The edges matched by each step of the chain, from the left of the pattern to the right
+  edges: Vec<Vec<Edge<G>>>,
+}
+
+/// A collection of nodes and edges that can have patterns matched against it
+pub trait Matchable<G>
+where
+  G: Graph,
+{
+  /// Look up a node by its guid
+  fn get_node(&self, guid: &Uuid) -> Option<Node<G>>;
+
+  /// Get all the nodes that could match a label, or every node if there isn't one
+  fn scan_nodes(&self, label: Option<&str>) -> Vec<Node<G>>;
+
+  /// All the edges starting at the given node
+  fn out_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    node.edges("")
+  }
+
+  /// All the edges ending at the given node
+  ///
+  /// Edges are only stored with their source node, so this needs to scan every node.
+  fn in_edges(&self, node: &Node<G>) -> Vec<Edge<G>> {
+    let guid = node.get_guid();
+    self
+      .scan_nodes(None)
+      .iter()
+      .flat_map(|source| self.out_edges(source))
+      .filter(|edge| edge.get_target().get_guid() == guid)
+      .collect()
+  }
+
+  /// Get all the edges with a type, or every edge if there isn't one
+  fn scan_edges(&self, edge_type: Option<&str>) -> Vec<Edge<G>> {
+    self
+      .scan_nodes(None)
+      .iter()
+      .flat_map(|node| self.out_edges(node))
+      .filter(|edge| match edge_type {
+        Some(edge_type) => edge.get_label() == edge_type,
+        None => true,
+      })
+      .collect()
+  }
+
+  /// The number of nodes `scan_nodes` would return, which the planner uses to choose where to
+  /// start matching. Stores that keep statistics should override this, since it runs the scan.
+  fn count_nodes(&self, label: Option<&str>) -> usize {
+    self.scan_nodes(label).len()
+  }
+
+  /// The number of edges `scan_edges` would return
+  fn count_edges(&self, edge_type: Option<&str>) -> usize {
+    self.scan_edges(edge_type).len()
+  }
+
+  /// The number of distinct values of the property among the nodes with the label, if the store
+  /// has an index on them. Only stores that can `seek_nodes` should return a count.
+  fn distinct_values(&self, _label: &str, _property: &str) -> Option<usize> {
+    None
+  }
+
+  /// The nodes with the label whose property equals the value, read from an index, or None if the
+  /// store doesn't have an index on them
+  fn seek_nodes(&self, _label: &str, _property: &str, _value: &Literal) -> Option<Vec<Node<G>>> {
+    None
+  }
+}
+
+/// Run a query against a store and collect the results into named columns
+pub fn execute<G, S>(
+  source: &S,
+  query: &GQuery,
+  params: &Params,
+) -> GraphtResult<HashMap<String, Vec<Value<G>>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  Executor::new(source, params).run(query)
+}
+
+/// Describe the operators that would run the query, without running it
+pub fn explain<G, S>(source: &S, query: &GQuery) -> Operator
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  Executor::new(source, &Params::new()).explain(query)
+}
+
+/// Run the query, returning the plan with what each of its operators did
+pub fn profile<G, S>(source: &S, query: &GQuery, params: &Params) -> GraphtResult<Operator>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  Executor::profiling(source, params).profile(query)
+}
+
+/// Walks through the clauses of a query, producing rows of matching values
+pub(crate) struct Executor<'a, G, S>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  source: &'a S,
+
+  /// The values for the query's $parameters
+  params: &'a Params,
+
+  /// The stats for each operator of the plan, in the order they run, when profiling
+  profile: Option<RefCell<Vec<OperatorStats>>>,
+
+  _graph: PhantomData<G>,
+}
+
+impl<'a, G, S> Executor<'a, G, S>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  pub fn new(source: &'a S, params: &'a Params) -> Executor<'a, G, S> {
+    Executor {
+      source,
+      params,
+      profile: None,
+      _graph: PhantomData,
+    }
+  }
+
+  /// An executor that records what each operator does
+  pub fn profiling(source: &'a S, params: &'a Params) -> Executor<'a, G, S> {
+    Executor {
+      source,
+      params,
+      profile: Some(RefCell::new(Vec::new())),
+      _graph: PhantomData,
+    }
+  }
+
+  pub fn run(&self, query: &GQuery) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    self.run_planned(query, &self.plan(query))
+  }
+
+  /// Plan each MATCH clause, tracking the variables that earlier clauses bring into scope
+  pub(crate) fn plan(&self, query: &GQuery) -> Vec<Option<MatchPlan>> {
+    let mut scope = HashSet::new();
+    let mut plans = Vec::new();
+    for clause in &query.clauses {
+      let plan = match clause {
+        Clause::Match(matched) => Some(planner::plan_match(self.source, matched, &scope)),
+        _ => None,
+      };
+      plans.push(plan);
+
+      match clause {
+        Clause::Match(Match { pattern, .. }) | Clause::Create(pattern) => {
+          scope.extend(pattern.variables())
+        }
+        Clause::Merge(merge) => scope.extend(merge.pattern.variables()),
+        Clause::Unwind(unwind) => {
+          scope.insert(unwind.variable.clone());
+        }
+        Clause::With(with) => {
+          if !with.projection.all {
+            scope.clear();
+          }
+          scope.extend(with.projection.columns());
+        }
+        _ => (),
+      }
+    }
+    plans
+  }
+
+  /// The operators for each clause of the query, in the order they run
+  fn operators(&self, query: &GQuery, plans: &[Option<MatchPlan>]) -> Vec<Vec<Operator>> {
+    query
+      .clauses
+      .iter()
+      .zip(plans)
+      .map(|(clause, plan)| explain::clause_operators(clause, plan.as_ref()))
+      .collect()
+  }
+
+  pub fn explain(&self, query: &GQuery) -> Operator {
+    let plans = self.plan(query);
+    explain::to_tree(self.operators(query, &plans).concat())
+  }
+
+  /// Run the query, attaching the recorded stats to each operator of the plan
+  pub fn profile(&self, query: &GQuery) -> GraphtResult<Operator> {
+    let plans = self.plan(query);
+    let mut operators = self.operators(query, &plans).concat();
+    if let Some(profile) = &self.profile {
+      *profile.borrow_mut() = vec![OperatorStats::new(); operators.len()];
+    }
+
+    self.run_planned(query, &plans)?;
+
+    if let Some(profile) = &self.profile {
+      for (operator, stats) in operators.iter_mut().zip(profile.take()) {
+        operator.stats = Some(stats);
+      }
+    }
+    Ok(explain::to_tree(operators))
+  }
+
+  fn run_planned(
+    &self,
+    query: &GQuery,
+    plans: &[Option<MatchPlan>],
+  ) -> GraphtResult<HashMap<String, Vec<Value<G>>>> {
+    debug!("Running query: {}", query);
+    let mut rows: Vec<Row<G>> = vec![Row::new()];
+
+    // The position of the first operator of each clause, which is only needed when profiling
+    let slots: Vec<usize> = match &self.profile {
+      Some(_) => self
+        .operators(query, plans)
+        .iter()
+        .scan(0, |next, operators| {
+          let slot = *next;
+          *next += operators.len();
+          Some(slot)
+        })
+        .collect(),
+      None => vec![0; query.clauses.len()],
+    };
+
+    for ((clause, plan), slot) in query.clauses.iter().zip(plans).zip(slots) {
+      rows = match clause {
+        Clause::Return(projection) => {
+          let started = Instant::now();
+          let (columns, rows) = self.project(projection, rows)?;
+          self.record(slot, rows.len(), 0, started);
+
+          let started = Instant::now();
+          let columns = to_columns(columns, rows);
+          let count = columns.values().next().map(|x| x.len()).unwrap_or(0);
+          self.record(slot + 1, count, 0, started);
+          return Ok(columns);
+        }
+        clause => self.read_clause(clause, plan.as_ref(), rows, slot)?,
+      };
+    }
+
+    // Queries without a RETURN don't produce any columns
+    Ok(HashMap::new())
+  }
+
+  /// Run a MATCH, UNWIND, or WITH clause over the rows produced so far. The slot is the position
+  /// of the clause's first operator, for profiling.
+  pub(crate) fn read_clause(
+    &self,
+    clause: &Clause,
+    plan: Option<&MatchPlan>,
+    rows: Vec<Row<G>>,
+    slot: usize,
+  ) -> GraphtResult<Vec<Row<G>>> {
+    let started = Instant::now();
+    match clause {
+      Clause::Match(matched) => {
+        let plan = plan.expect("Every MATCH clause is planned");
+        self.match_clause(matched, plan, rows, slot)
+      }
+      Clause::Unwind(unwind) => {
+        let rows = self.unwind(unwind, rows)?;
+        self.record(slot, rows.len(), 0, started);
+        Ok(rows)
+      }
+      Clause::With(with) => {
+        let (_, rows) = self.project(&with.projection, rows)?;
+        self.record(slot, rows.len(), 0, started);
+        match &with.filter {
+          Some(filter) => {
+            let started = Instant::now();
+            let rows = self.filter(filter, rows)?;
+            self.record(slot + 1, rows.len(), 0, started);
+            Ok(rows)
+          }
+          None => Ok(rows),
+        }
+      }
+      clause => Err(err!(
+        NotImplemented,
+        "Updating clauses cannot be run against a read only store: {}",
+        clause
+      )),
+    }
+  }
+
+  /// Add to the stats of an operator, if the query is being profiled
+  fn record(&self, slot: usize, rows: usize, index_hits: usize, started: Instant) {
+    if let Some(profile) = &self.profile {
+      profile.borrow_mut()[slot] += OperatorStats::record(rows, index_hits, started.elapsed());
+    }
+  }
+
+  /// Only keep the rows where the predicate is true
+  fn filter(&self, filter: &Expression, rows: Vec<Row<G>>) -> GraphtResult<Vec<Row<G>>> {
+    let mut results = Vec::new();
+    for row in rows {
+      if self.is_true(filter, &row)? {
+        results.push(row);
+      }
+    }
+    Ok(results)
+  }
+
+  fn unwind(&self, unwind: &Unwind, rows: Vec<Row<G>>) -> GraphtResult<Vec<Row<G>>> {
+    let mut results = Vec::new();
+    for row in rows {
+      let values = match self.evaluate(&unwind.expression, &row)? {
+        Value::Literal(Literal::List(values)) => values.into_iter().map(Value::Literal).collect(),
+        Value::List(values) => values,
+        Value::Literal(Literal::Null) => Vec::new(),
+        value => vec![value],
+      };
+
+      for value in values {
+        let mut row = row.clone();
+        row.insert(unwind.variable.clone(), value);
+        results.push(row);
+      }
+    }
+    Ok(results)
+  }
+
+  //--- Matching
+
+  /// Match the planned pattern parts against each row. The slot is the position of the clause's
+  /// first operator, for profiling.
+  fn match_clause(
+    &self,
+    matched: &Match,
+    plan: &MatchPlan,
+    rows: Vec<Row<G>>,
+    slot: usize,
+  ) -> GraphtResult<Vec<Row<G>>> {
+    let mut part_slots = Vec::new();
+    let mut next_slot = slot;
+    for part_plan in &plan.parts {
+      part_slots.push(next_slot);
+      next_slot += 1 + part_plan.expands.len();
+    }
+    let filter_slot = next_slot;
+    let optional_slot = next_slot + matched.filter.iter().count();
+
+    let mut results = Vec::new();
+    for row in rows {
+      let mut row_nulls = row.clone();
+
+      // Each edge can only be used once per match, so track them alongside the row
+      let mut partial = vec![(row, HashSet::new())];
+      for (part_plan, part_slot) in plan.parts.iter().zip(&part_slots) {
+        let part = &matched.pattern.parts[part_plan.index];
+        let mut extended = Vec::new();
+        for (row, used) in partial {
+          self.match_part(part, part_plan, row, used, &mut extended, Some(*part_slot))?;
+        }
+        partial = extended;
+      }
+
+      let started = Instant::now();
+      let mut found = 0;
+      for (row, _) in partial {
+        if let Some(filter) = &matched.filter {
+          if !self.is_true(filter, &row)? {
+            continue;
+          }
+        }
+        found += 1;
+        results.push(row);
+      }
+      if matched.filter.is_some() {
+        self.record(filter_slot, found, 0, started);
+      }
+
+      // An optional match keeps the row, with anything it would have added set to null
+      if matched.optional {
+        let started = Instant::now();
+        if found == 0 {
+          for name in matched.pattern.variables() {
+            row_nulls
+              .entry(name)
+              .or_insert(Value::Literal(Literal::Null));
+          }
+          results.push(row_nulls);
+        }
+        self.record(optional_slot, found.max(1), 0, started);
+      }
+    }
+    Ok(results)
+  }
+
+  /// Find every way a single chain of nodes and edges fits onto the row, starting from the
+  /// planned anchor and following the planned steps. The slot of the anchor's operator is only
+  /// given when the part belongs to a MATCH clause, so predicates aren't profiled.
+  fn match_part(
+    &self,
+    part: &PatternPart,
+    plan: &PartPlan,
+    row: Row<G>,
+    used: HashSet<Uuid>,
+    results: &mut Vec<(Row<G>, HashSet<Uuid>)>,
+    slot: Option<usize>,
+  ) -> GraphtResult<()> {
+    let started = Instant::now();
+    let (mut states, index_hits) = self.match_anchor(part, &plan.anchor, row, used)?;
+    if let Some(slot) = slot {
+      self.record(slot, states.len(), index_hits, started);
+    }
+
+    for (position, expand) in plan.expands.iter().enumerate() {
+      let step = &part.chain[expand.step];
+      let started = Instant::now();
+      let (from, to) = expand.nodes();
+      let direction = expand.direction(&step.relationship);
+      let mut extended = Vec::new();
+      for state in states {
+        let current = state.nodes[from]
+          .clone()
+          .expect("The planner only expands from nodes that have been matched");
+
+        let walks = match (part.shortest, step.relationship.length) {
+          (Some(shortest), _) => self.shortest_walks(
+            &current,
+            &step.relationship,
+            node_at(part, to),
+            direction,
+            shortest == Shortest::All,
+            &state,
+          )?,
+          (None, Some(length)) => {
+            self.walks(&current, &step.relationship, length, direction, &state)?
+          }
+          (None, None) => neighbours(self.source, &current, direction)
+            .into_iter()
+            .filter(|(edge, _)| !state.used.contains(&edge.get_guid()))
+            .map(|(edge, next)| (vec![edge], next))
+            .collect(),
+        };
+
+        for (mut edges, next) in walks {
+          if step.relationship.length.is_none()
+            && !self.edge_matches(&edges[0], &step.relationship, &state.row)?
+          {
+            continue;
+          }
+          if !self.node_matches(&next, node_at(part, to), &state.row)? {
+            continue;
+          }
+
+          // Edges are kept in order from the left of the pattern to the right
+          if expand.reversed {
+            edges.reverse();
+          }
+          let mut state = state.clone();
+          if let Some(name) = &step.relationship.variable {
+            state
+              .row
+              .insert(name.clone(), step_value(&step.relationship, &edges));
+          }
+          if let Some(name) = &node_at(part, to).variable {
+            state.row.insert(name.clone(), Value::Node(next.clone()));
+          }
+          state.used.extend(edges.iter().map(|x| x.get_guid()));
+          state.nodes[to] = Some(next);
+          state.edges[expand.step] = edges;
+          extended.push(state);
+        }
+      }
+      if let Some(slot) = slot {
+        self.record(slot + 1 + position, extended.len(), 0, started);
+      }
+      states = extended;
+    }
+
+    for mut state in states {
+      if let Some(name) = &part.variable {
+        let path = Path::new(state.nodes[0].clone(), state.edges.concat());
+        state.row.insert(name.clone(), Value::Path(path));
+      }
+      results.push((state.row, state.used));
+    }
+    Ok(())
+  }
+
+  /// Find the starting points of a pattern part, along with the number of items read from an
+  /// index
+  fn match_anchor(
+    &self,
+    part: &PatternPart,
+    anchor: &Anchor,
+    row: Row<G>,
+    used: HashSet<Uuid>,
+  ) -> GraphtResult<(Vec<PartState<G>>, usize)> {
+    let empty = PartState {
+      row,
+      used,
+      nodes: vec![None; part.chain.len() + 1],
+      edges: vec![Vec::new(); part.chain.len()],
+    };
+    let mut states = Vec::new();
+
+    let (step, types) = match anchor {
+      Anchor::EdgeTypes { step, types } => (*step, types),
+      Anchor::Bound { node, .. }
+      | Anchor::Label { node, .. }
+      | Anchor::Index { node, .. }
+      | Anchor::AllNodes { node } => {
+        let pattern = node_at(part, *node);
+        let candidates = self.node_candidates(anchor, &empty.row)?;
+        let index_hits = match anchor {
+          Anchor::Label { .. } | Anchor::Index { .. } => candidates.len(),
+          _ => 0,
+        };
+
+        for candidate in candidates {
+          if !self.node_matches(&candidate, pattern, &empty.row)? {
+            continue;
+          }
+          let mut state = empty.clone();
+          if let Some(name) = &pattern.variable {
+            state
+              .row
+              .insert(name.clone(), Value::Node(candidate.clone()));
+          }
+          state.nodes[*node] = Some(candidate);
+          states.push(state);
+        }
+        return Ok((states, index_hits));
+      }
+    };
+
+    let relationship = &part.chain[step].relationship;
+    let (left, right) = (node_at(part, step), node_at(part, step + 1));
+    let mut index_hits = 0;
+    for edge_type in types {
+      for edge in self.source.scan_edges(Some(edge_type)) {
+        index_hits += 1;
+        if empty.used.contains(&edge.get_guid())
+          || !self.edge_matches(&edge, relationship, &empty.row)?
+        {
+          continue;
+        }
+
+        for (left_node, right_node) in self.edge_ends(&edge, relationship.direction) {
+          if !self.node_matches(&left_node, left, &empty.row)? {
+            continue;
+          }
+          let mut state = empty.clone();
+          if let Some(name) = &left.variable {
+            state
+              .row
+              .insert(name.clone(), Value::Node(left_node.clone()));
+          }
+          // Checked after binding the left node, in case both ends are the same variable
+          if !self.node_matches(&right_node, right, &state.row)? {
+            continue;
+          }
+          if let Some(name) = &right.variable {
+            state
+              .row
+              .insert(name.clone(), Value::Node(right_node.clone()));
+          }
+          if let Some(name) = &relationship.variable {
+            state.row.insert(name.clone(), Value::Edge(edge.clone()));
+          }
+
+          state.used.insert(edge.get_guid());
+          state.nodes[step] = Some(left_node);
+          state.nodes[step + 1] = Some(right_node);
+          state.edges[step] = vec![edge.clone()];
+          states.push(state);
+        }
+      }
+    }
+    Ok((states, index_hits))
+  }
+
+  /// Every walk along edges that fit a variable length relationship, with the node each one ends
+  /// at. Walks are found shortest first and never reuse an edge.
+  fn walks(
+    &self,
+    start: &Node<G>,
+    relationship: &RelationshipPattern,
+    length: VarLength,
+    direction: Direction,
+    state: &PartState<G>,
+  ) -> GraphtResult<Vec<Walk<G>>> {
+    let min = length.min.unwrap_or(1);
+    let mut walks = Vec::new();
+    let mut pending = VecDeque::from([(Vec::new(), start.clone())]);
+    while let Some((walk, node)) = pending.pop_front() {
+      let hops = walk.len() as u64;
+      if hops >= min {
+        walks.push((walk.clone(), node.clone()));
+      }
+      if matches!(length.max, Some(max) if hops >= max) {
+        continue;
+      }
+
+      for (edge, next) in neighbours(self.source, &node, direction) {
+        let guid = edge.get_guid();
+        if state.used.contains(&guid)
+          || walk.iter().any(|x: &Edge<G>| x.get_guid() == guid)
+          || !self.edge_matches(&edge, relationship, &state.row)?
+        {
+          continue;
+        }
+        let mut walk = walk.clone();
+        walk.push(edge);
+        pending.push_back((walk, next));
+      }
+    }
+    Ok(walks)
+  }
+
+  /// The shortest walks from a node to each node that fits the other end of a shortestPath() or
+  /// allShortestPaths() pattern
+  fn shortest_walks(
+    &self,
+    start: &Node<G>,
+    relationship: &RelationshipPattern,
+    end: &NodePattern,
+    direction: Direction,
+    all: bool,
+    state: &PartState<G>,
+  ) -> GraphtResult<Vec<Walk<G>>> {
+    let length = relationship.length.unwrap_or_default();
+    let mut opts = PathOpts::new().direction(direction);
+    if let Some(max) = length.max {
+      opts = opts.max_length(max);
+    }
+
+    let targets = match end.variable.as_ref().and_then(|name| state.row.get(name)) {
+      Some(Value::Node(node)) => vec![node.clone()],
+      Some(_) => Vec::new(),
+      None => self
+        .source
+        .scan_nodes(end.labels.first().map(|x| x.as_str())),
+    };
+
+    let mut walks = Vec::new();
+    for target in targets {
+      // Without a minimum of 0, a node has no shortest path to itself
+      if (length.min.unwrap_or(1) > 0 && target.get_guid() == start.get_guid())
+        || !self.node_matches(&target, end, &state.row)?
+      {
+        continue;
+      }
+      let paths = shortest::search(self.source, start, &target, &opts, all, |edge| {
+        Ok(
+          !state.used.contains(&edge.get_guid())
+            && self.edge_matches(edge, relationship, &state.row)?,
+        )
+      })?;
+      walks.extend(paths.into_iter().map(|path| (path.edges(), target.clone())));
+    }
+    Ok(walks)
+  }
+
+  /// Get all the nodes that could be the anchor of a pattern, before checking them against it
+  fn node_candidates(&self, anchor: &Anchor, row: &Row<G>) -> GraphtResult<Vec<Node<G>>> {
+    match anchor {
+      Anchor::Bound { variable, .. } => match row.get(variable) {
+        Some(Value::Node(node)) => Ok(vec![node.clone()]),
+        Some(Value::Literal(Literal::Null)) | None => Ok(Vec::new()),
+        Some(value) => Err(err!(
+          TypeMismatch,
+          "Variable {:?} is already bound to a non-node value {:?}",
+          variable,
+          value
+        )),
+      },
+      Anchor::Label { label, .. } => Ok(self.source.scan_nodes(Some(label))),
+      // Fall back to scanning the label if the index was dropped since planning
+      Anchor::Index {
+        label,
+        property,
+        value,
+        ..
+      } => match self.evaluate(value, row)? {
+        Value::Literal(value) => Ok(
+          self
+            .source
+            .seek_nodes(label, property, &value)
+            .unwrap_or_else(|| self.source.scan_nodes(Some(label))),
+        ),
+        _ => Ok(self.source.scan_nodes(Some(label))),
+      },
+      _ => Ok(self.source.scan_nodes(None)),
+    }
+  }
+
+  /// The nodes at the left and right of an edge that fits a relationship going in the direction
+  fn edge_ends(&self, edge: &Edge<G>, direction: Direction) -> Vec<(Node<G>, Node<G>)> {
+    let source = self.source.get_node(&edge.get_source().get_guid());
+    let target = self.source.get_node(&edge.get_target().get_guid());
+    let (source, target) = match (source, target) {
+      (Some(source), Some(target)) => (source, target),
+      _ => return Vec::new(),
+    };
+
+    match direction {
+      Direction::Outgoing => vec![(source, target)],
+      Direction::Incoming => vec![(target, source)],
+      // A self loop only fits one way round, and a TwoWay edge has its reciprocal for the way back
+      Direction::Either if source.get_guid() == target.get_guid() || edge.is_two_way() => {
+        vec![(source, target)]
+      }
+      Direction::Either => vec![(source.clone(), target.clone()), (target, source)],
+    }
+  }
+
+  fn node_matches(
+    &self,
+    node: &Node<G>,
+    pattern: &NodePattern,
+    row: &Row<G>,
+  ) -> GraphtResult<bool> {
+    if let Some(bound) = pattern.variable.as_ref().and_then(|name| row.get(name)) {
+      match bound {
+        Value::Node(bound) if bound.get_guid() == node.get_guid() => (),
+        _ => return Ok(false),
+      }
+    }
+
+    if !pattern.labels.iter().all(|label| node.has_label(label)) {
+      return Ok(false);
+    }
+
+    for (key, expected) in &pattern.properties {
+      let expected = self.evaluate(expected, row)?;
+      let actual = Value::Literal(node.get_property(key)?);
+      if self.equals(&actual, &expected)? != Some(true) {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  fn edge_matches(
+    &self,
+    edge: &Edge<G>,
+    pattern: &RelationshipPattern,
+    row: &Row<G>,
+  ) -> GraphtResult<bool> {
+    if let Some(bound) = pattern.variable.as_ref().and_then(|name| row.get(name)) {
+      match bound {
+        Value::Edge(bound) if bound.get_guid() == edge.get_guid() => (),
+        _ => return Ok(false),
+      }
+    }
+
+    if !pattern.types.is_empty() && !pattern.types.contains(&edge.get_label()) {
+      return Ok(false);
+    }
+
+    for (key, expected) in &pattern.properties {
+      let expected = self.evaluate(expected, row)?;
+      let actual = Value::Literal(edge.get_property(key)?);
+      if self.equals(&actual, &expected)? != Some(true) {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  //--- Projection
+
+  /// Turn each row into a new row holding only the projected values, returning the names of the
+  /// columns in order
+  fn project(
+    &self,
+    projection: &Projection,
+    rows: Vec<Row<G>>,
+  ) -> GraphtResult<(Vec<String>, Vec<Row<G>>)> {
+    let mut columns: Vec<String> = Vec::new();
+    if projection.all {
+      let mut names: Vec<String> = match rows.first() {
+        Some(row) => row.keys().cloned().collect(),
+        None => Vec::new(),
+      };
+      names.sort();
+      columns.extend(names);
+    }
+    for name in projection.columns() {
+      if !columns.contains(&name) {
+        columns.push(name);
+      }
+    }
+
+    // Each projected row, with the scope ORDER BY is evaluated in and the rows it aggregates
+    let rows = match projection.items.iter().any(|x| x.expression.is_aggregate()) {
+      true => self.aggregate_rows(projection, rows)?,
+      false => {
+        let mut projected = Vec::new();
+        for row in rows {
+          let mut values = Row::new();
+          if projection.all {
+            values.extend(row.iter().map(|(key, value)| (key.clone(), value.clone())));
+          }
+          for item in &projection.items {
+            values.insert(item.name(), self.evaluate(&item.expression, &row)?);
+          }
+          projected.push((values, row, Vec::new()));
+        }
+        projected
+      }
+    };
+
+    let mut seen = HashSet::new();
+    let mut projected = Vec::new();
+    for (values, scope, group) in rows {
+      if projection.distinct {
+        let key: Vec<Value<G>> = columns
+          .iter()
+          .map(|name| {
+            values
+              .get(name)
+              .cloned()
+              .unwrap_or(Value::Literal(Literal::Null))
+          })
+          .collect();
+        if !seen.insert(row_key(&key)) {
+          continue;
+        }
+      }
+
+      // ORDER BY can use the projected names as well as the variables from before the projection
+      let mut sort_keys = Vec::new();
+      if !projection.order.is_empty() {
+        let mut scope = scope;
+        scope.extend(
+          values
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        for item in &projection.order {
+          sort_keys.push(match item.expression.is_aggregate() {
+            true => self.aggregate(&item.expression, &group, &scope)?,
+            false => self.evaluate(&item.expression, &scope)?,
+          });
+        }
+      }
+      projected.push((sort_keys, values));
+    }
+
+    if !projection.order.is_empty() {
+      projected.sort_by(|(lhs, _), (rhs, _)| {
+        for ((lhs, rhs), item) in lhs.iter().zip(rhs).zip(&projection.order) {
+          let ordering = match item.descending {
+            true => compare_values(lhs, rhs).reverse(),
+            false => compare_values(lhs, rhs),
+          };
+          if ordering != Ordering::Equal {
+            return ordering;
+          }
+        }
+        Ordering::Equal
+      });
+    }
+
+    let skip = self.row_count(&projection.skip, "SKIP")?.unwrap_or(0);
+    let limit = self
+      .row_count(&projection.limit, "LIMIT")?
+      .unwrap_or(usize::MAX);
+    let rows = projected
+      .into_iter()
+      .map(|(_, row)| row)
+      .skip(skip)
+      .take(limit)
+      .collect();
+    Ok((columns, rows))
+  }
+
+  /// Group the rows by the values of the items that don't aggregate, then work out the aggregates
+  /// for each group. Each result holds the projected values, the scope of the group, and the rows
+  /// in the group.
+  #[allow(clippy::type_complexity)]
+  fn aggregate_rows(
+    &self,
+    projection: &Projection,
+    rows: Vec<Row<G>>,
+  ) -> GraphtResult<Vec<(Row<G>, Row<G>, Vec<Row<G>>)>> {
+    let (aggregates, keys): (Vec<&ProjectionItem>, Vec<&ProjectionItem>) = projection
+      .items
+      .iter()
+      .partition(|item| item.expression.is_aggregate());
+
+    // Groups are kept in the order they were first seen
+    let mut groups: Vec<(Row<G>, Vec<Row<G>>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for row in rows {
+      let mut values = Row::new();
+      if projection.all {
+        values.extend(row.iter().map(|(key, value)| (key.clone(), value.clone())));
+      }
+      for item in &keys {
+        values.insert(item.name(), self.evaluate(&item.expression, &row)?);
+      }
+
+      let mut names: Vec<&String> = values.keys().collect();
+      names.sort();
+      let key: Vec<Value<G>> = names.into_iter().map(|name| values[name].clone()).collect();
+      match positions.entry(row_key(&key)) {
+        Entry::Occupied(entry) => groups[*entry.get()].1.push(row),
+        Entry::Vacant(entry) => {
+          entry.insert(groups.len());
+          groups.push((values, vec![row]));
+        }
+      }
+    }
+
+    // Aggregating without any grouping keys always produces a row, even when nothing matched
+    if groups.is_empty() && keys.is_empty() && !projection.all {
+      groups.push((Row::new(), Vec::new()));
+    }
+
+    let mut results = Vec::new();
+    for (mut values, group) in groups {
+      // The keys are the same for every row in the group, so any row can fill in the rest
+      let mut scope = group.first().cloned().unwrap_or_default();
+      scope.extend(
+        values
+          .iter()
+          .map(|(key, value)| (key.clone(), value.clone())),
+      );
+      for item in &aggregates {
+        values.insert(
+          item.name(),
+          self.aggregate(&item.expression, &group, &scope)?,
+        );
+      }
+      results.push((values, scope, group));
+    }
+    Ok(results)
+  }
+
+  /// Evaluate an expression containing aggregates for a group of rows. Each aggregate is worked out
+  /// first and bound to a hidden variable, then the rest is evaluated against the group's scope.
+  fn aggregate(
+    &self,
+    expression: &Expression,
+    group: &[Row<G>],
+    scope: &Row<G>,
+  ) -> GraphtResult<Value<G>> {
+    let mut expression = expression.clone();
+    let mut scope = scope.clone();
+    self.replace_aggregates(&mut expression, group, &mut scope)?;
+    self.evaluate(&expression, &scope)
+  }
+
+  fn replace_aggregates(
+    &self,
+    expression: &mut Expression,
+    group: &[Row<G>],
+    scope: &mut Row<G>,
+  ) -> GraphtResult<()> {
+    let value = match expression {
+      Expression::CountAll => Value::Literal(Literal::Integer(group.len() as i64)),
+      Expression::Function(call) if call.is_aggregate() => self.aggregate_call(call, group)?,
+      expression => {
+        for child in expression.children_mut() {
+          self.replace_aggregates(child, group, scope)?;
+        }
+        return Ok(());
+      }
+    };
+
+    // Spaces can't appear in a bare variable name, so this can't hide one from the query
+    let name = format!(" aggregate {}", scope.len());
+    scope.insert(name.clone(), value);
+    *expression = Expression::Variable(name);
+    Ok(())
+  }
+
+  /// Work out an aggregate function over the rows of a group, ignoring nulls
+  fn aggregate_call(&self, call: &FunctionCall, group: &[Row<G>]) -> GraphtResult<Value<G>> {
+    let argument = match call.arguments.as_slice() {
+      [argument] => argument,
+      _ => {
+        return Err(err!(
+          InvalidItem,
+          "Aggregate function {}() takes exactly one argument",
+          call.name
+        ))
+      }
+    };
+
+    let mut seen = HashSet::new();
+    let mut values = Vec::new();
+    for row in group {
+      let value = self.evaluate(argument, row)?;
+      if matches!(value, Value::Literal(Literal::Null)) {
+        continue;
+      }
+      if call.distinct && !seen.insert(row_key(std::slice::from_ref(&value))) {
+        continue;
+      }
+      values.push(value);
+    }
+
+    let result = match call.name.to_lowercase().as_str() {
+      "count" => Literal::Integer(values.len() as i64),
+      "collect" => return Ok(Value::list(values)),
+      "min" | "max" => {
+        let is_max = call.name.eq_ignore_ascii_case("max");
+        let best = values.into_iter().reduce(|best, value| {
+          let ordering = compare_values(&value, &best);
+          match (is_max, ordering) {
+            (true, Ordering::Greater) | (false, Ordering::Less) => value,
+            _ => best,
+          }
+        });
+        return Ok(best.unwrap_or(Value::Literal(Literal::Null)));
+      }
+      // Sums stay as integers until a decimal is added, so they are always exact
+      "sum" | "avg" => {
+        let count = values.len();
+        let mut total = Literal::Integer(0);
+        for value in values {
+          total = arithmetic(BinaryOperator::Add, total, self.literal(value)?)?;
+        }
+
+        match (call.name.eq_ignore_ascii_case("avg"), count) {
+          (false, _) => total,
+          (true, 0) => Literal::Null,
+          (true, count) => {
+            let total = total
+              .as_decimal()
+              .expect("The sum of numbers is always a number");
+            Literal::Decimal(total / rust_decimal::Decimal::from(count as i64))
+          }
+        }
+      }
+      _ => unreachable!("Only aggregate functions are called for groups"),
+    };
+    Ok(Value::Literal(result))
+  }
+
+  /// Evaluate a SKIP or LIMIT value, which must be a non negative integer
+  fn row_count(
+    &self,
+    expression: &Option<Expression>,
+    clause: &str,
+  ) -> GraphtResult<Option<usize>> {
+    let expression = match expression {
+      Some(expression) => expression,
+      None => return Ok(None),
+    };
+
+    match self.literal(self.evaluate(expression, &Row::new())?)? {
+      Literal::Integer(count) if count >= 0 => Ok(Some(count as usize)),
+      value => Err(err!(
+        InvalidItem,
+        "{} requires a non negative integer, but {} evaluated to {}",
+        clause,
+        expression,
+        value
+      )),
+    }
+  }
+
+  //--- Expressions
+
+  fn is_true(&self, expression: &Expression, row: &Row<G>) -> GraphtResult<bool> {
+    match self.evaluate(expression, row)? {
+      Value::Literal(value) => Ok(value.as_bool()? == Some(true)),
+      value => Err(err!(
+        TypeMismatch,
+        "Expected a boolean predicate but {} evaluated to {:?}",
+        expression,
+        value
+      )),
+    }
+  }
+
+  pub(crate) fn evaluate(&self, expression: &Expression, row: &Row<G>) -> GraphtResult<Value<G>> {
+    match expression {
+      Expression::Literal(value) => Ok(Value::Literal(value.clone())),
+
+      Expression::Parameter(name) => Ok(Value::Literal(self.params.value(name)?.clone())),
+
+      Expression::Variable(name) => match row.get(name) {
+        Some(value) => Ok(value.clone()),
+        None => Err(err!(NotFound, "Variable '{}' is not defined", name)),
+      },
+
+      Expression::Property(inner, name) => {
+        let value = match self.evaluate(inner, row)? {
+          Value::Node(node) => node.get_property(name)?,
+          Value::Edge(edge) => edge.get_property(name)?,
+          Value::Literal(Literal::Map(mut values)) => values.remove(name).unwrap_or(Literal::Null),
+          Value::Literal(Literal::Null) => Literal::Null,
+          value => {
+            return Err(err!(
+              TypeMismatch,
+              "Cannot get property '{}' from {:?}",
+              name,
+              value
+            ))
+          }
+        };
+        Ok(Value::Literal(value))
+      }
+
+      Expression::List(values) => {
+        let mut result = Vec::new();
+        for value in values {
+          result.push(self.literal(self.evaluate(value, row)?)?);
+        }
+        Ok(Value::Literal(Literal::List(result)))
+      }
+
+      Expression::Map(values) => {
+        let mut result = std::collections::BTreeMap::new();
+        for (key, value) in values {
+          let _ = result.insert(key.clone(), self.literal(self.evaluate(value, row)?)?);
+        }
+        Ok(Value::Literal(Literal::Map(result)))
+      }
+
+      Expression::Unary(op, inner) => {
+        let value = self.literal(self.evaluate(inner, row)?)?;
+        let result = match (op, value) {
+          (_, Literal::Null) => Literal::Null,
+          (UnaryOperator::Not, value) => match value.as_bool()? {
+            Some(x) => Literal::Boolean(!x),
+            None => Literal::Null,
+          },
+          (UnaryOperator::Plus, Literal::Integer(x)) => Literal::Integer(x),
+          (UnaryOperator::Plus, Literal::Decimal(x)) => Literal::Decimal(x),
+          (UnaryOperator::Negate, Literal::Integer(x)) => match x.checked_neg() {
+            Some(x) => Literal::Integer(x),
+            None => return Err(err!(ConversionError, "Overflow calculating -{}", x)),
+          },
+          (UnaryOperator::Negate, Literal::Decimal(x)) => Literal::Decimal(-x),
+          (op, value) => {
+            return Err(err!(
+              TypeMismatch,
+              "Cannot apply {:?} to {} value {}",
+              op,
+              value.type_name(),
+              value
+            ))
+          }
+        };
+        Ok(Value::Literal(result))
+      }
+
+      Expression::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs, row),
+
+      Expression::IsNull(inner, negated) => {
+        let is_null = matches!(self.evaluate(inner, row)?, Value::Literal(Literal::Null));
+        Ok(Value::Literal(Literal::Boolean(is_null != *negated)))
+      }
+
+      Expression::HasLabels(inner, labels) => match self.evaluate(inner, row)? {
+        Value::Node(node) => Ok(Value::Literal(Literal::Boolean(
+          labels.iter().all(|label| node.has_label(label)),
+        ))),
+        Value::Literal(Literal::Null) => Ok(Value::Literal(Literal::Null)),
+        value => Err(err!(
+          TypeMismatch,
+          "Only nodes have labels, but received {:?}",
+          value
+        )),
+      },
+
+      Expression::Function(call) if call.is_aggregate() => Err(err!(
+        InvalidItem,
+        "Aggregate function {}() can only be used in RETURN and WITH",
+        call.name
+      )),
+      Expression::Function(call) => self.function(call, row),
+
+      Expression::CountAll => Err(err!(
+        InvalidItem,
+        "Aggregate functions such as count(*) can only be used in RETURN and WITH"
+      )),
+
+      Expression::Case(case) => {
+        let subject = match &case.subject {
+          Some(subject) => Some(self.evaluate(subject, row)?),
+          None => None,
+        };
+
+        for (condition, result) in &case.alternatives {
+          let matched = match &subject {
+            Some(subject) => self.equals(subject, &self.evaluate(condition, row)?)? == Some(true),
+            None => self.is_true(condition, row)?,
+          };
+          if matched {
+            return self.evaluate(result, row);
+          }
+        }
+
+        match &case.default {
+          Some(default) => self.evaluate(default, row),
+          None => Ok(Value::Literal(Literal::Null)),
+        }
+      }
+
+      Expression::PatternPredicate(part) => {
+        let bound = row.keys().cloned().collect();
+        let plan = planner::plan_pattern(self.source, part, &bound);
+        let mut results = Vec::new();
+        self.match_part(part, &plan, row.clone(), HashSet::new(), &mut results, None)?;
+        Ok(Value::Literal(Literal::Boolean(!results.is_empty())))
+      }
+    }
+  }
+
+  /// Convert a value into a literal, failing if it is a node, edge, or path
+  pub(crate) fn literal(&self, value: Value<G>) -> GraphtResult<Literal> {
+    match value {
+      Value::Literal(value) => Ok(value),
+      value => Err(err!(
+        TypeMismatch,
+        "Expected a literal value but received {:?}",
+        value
+      )),
+    }
+  }
+
+  /// Graph items are equal if they share the same guid, everything else uses literal equality
+  fn equals(&self, lhs: &Value<G>, rhs: &Value<G>) -> GraphtResult<Option<bool>> {
+    match (lhs, rhs) {
+      (Value::Literal(Literal::Null), _) | (_, Value::Literal(Literal::Null)) => Ok(None),
+      (Value::Literal(lhs), Value::Literal(rhs)) => Ok(lhs.equals(rhs)),
+      (Value::Literal(_), _) | (_, Value::Literal(_)) => Ok(Some(false)),
+      (lhs, rhs) => Ok(Some(lhs == rhs)),
+    }
+  }
+
+  fn binary(
+    &self,
+    op: BinaryOperator,
+    lhs: &Expression,
+    rhs: &Expression,
+    row: &Row<G>,
+  ) -> GraphtResult<Value<G>> {
+    use BinaryOperator::*;
+
+    // The boolean operators use three valued logic, where null is unknown
+    if let And | Or | Xor = op {
+      let lhs = self.literal(self.evaluate(lhs, row)?)?.as_bool()?;
+      // Short circuit before evaluating the right hand side
+      match (op, lhs) {
+        (And, Some(false)) => return Ok(Value::Literal(Literal::Boolean(false))),
+        (Or, Some(true)) => return Ok(Value::Literal(Literal::Boolean(true))),
+        _ => (),
+      }
+      let rhs = self.literal(self.evaluate(rhs, row)?)?.as_bool()?;
+      let result = match (op, lhs, rhs) {
+        (And, _, Some(false)) => Some(false),
+        (And, Some(true), Some(true)) => Some(true),
+        (Or, _, Some(true)) => Some(true),
+        (Or, Some(false), Some(false)) => Some(false),
+        (Xor, Some(l), Some(r)) => Some(l != r),
+        _ => None,
+      };
+      return Ok(Value::Literal(
+        result.map_or(Literal::Null, Literal::Boolean),
+      ));
+    }
+
+    let lhs = self.evaluate(lhs, row)?;
+    let rhs = self.evaluate(rhs, row)?;
+
+    if let Eq | Ne = op {
+      let result = self.equals(&lhs, &rhs)?.map(|x| x == (op == Eq));
+      return Ok(Value::Literal(
+        result.map_or(Literal::Null, Literal::Boolean),
+      ));
+    }
+
+    let lhs = self.literal(lhs)?;
+    let rhs = self.literal(rhs)?;
+    let result = match op {
+      Lt | Gt | Le | Ge => match lhs.compare(&rhs) {
+        None => Literal::Null,
+        Some(ordering) => Literal::Boolean(match op {
+          Lt => ordering == Ordering::Less,
+          Gt => ordering == Ordering::Greater,
+          Le => ordering != Ordering::Greater,
+          _ => ordering != Ordering::Less,
+        }),
+      },
+      Add | Subtract | Multiply | Divide | Modulo | Power => arithmetic(op, lhs, rhs)?,
+      In => match rhs {
+        Literal::Null => Literal::Null,
+        Literal::List(values) => {
+          let mut result = Literal::Boolean(false);
+          for value in values {
+            match lhs.equals(&value) {
+              Some(true) => {
+                result = Literal::Boolean(true);
+                break;
+              }
+              None => result = Literal::Null,
+              Some(false) => (),
+            }
+          }
+          result
+        }
+        x => return Err(err!(TypeMismatch, "IN requires a list, but received {}", x)),
+      },
+      StartsWith | EndsWith | Contains => match (lhs, rhs) {
+        (Literal::String(lhs), Literal::String(rhs)) => Literal::Boolean(match op {
+          StartsWith => lhs.starts_with(&rhs),
+          EndsWith => lhs.ends_with(&rhs),
+          _ => lhs.contains(&rhs),
+        }),
+        _ => Literal::Null,
+      },
+      And | Or | Xor | Eq | Ne => unreachable!("Handled before evaluating the literals"),
+    };
+
+    Ok(Value::Literal(result))
+  }
+
+  fn function(&self, call: &FunctionCall, row: &Row<G>) -> GraphtResult<Value<G>> {
+    let mut args = Vec::new();
+    for arg in &call.arguments {
+      args.push(self.evaluate(arg, row)?);
+    }
+
+    let name = call.name.to_lowercase();
+    let arg = |i: usize| -> GraphtResult<&Value<G>> {
+      args.get(i).ok_or_else(|| {
+        err!(
+          InvalidItem,
+          "Function {} expects at least {} argument(s)",
+          call.name,
+          i + 1
+        )
+      })
+    };
+
+    let result = match name.as_str() {
+      "id" => match arg(0)? {
+        Value::Literal(Literal::Null) => Literal::Null,
+        value => Literal::String(value.get_guid().to_string()),
+      },
+      "labels" => match arg(0)? {
+        Value::Node(node) => {
+          let mut labels: Vec<String> = node.get_labels().into_iter().collect();
+          labels.sort();
+          labels.into()
+        }
+        Value::Literal(Literal::Null) => Literal::Null,
+        value => {
+          return Err(err!(
+            TypeMismatch,
+            "labels() requires a node, not {:?}",
+            value
+          ))
+        }
+      },
+      "type" => match arg(0)? {
+        Value::Edge(edge) => Literal::String(edge.get_label()),
+        Value::Literal(Literal::Null) => Literal::Null,
+        value => {
+          return Err(err!(
+            TypeMismatch,
+            "type() requires an edge, not {:?}",
+            value
+          ))
+        }
+      },
+      "properties" => match arg(0)? {
+        Value::Node(node) => Literal::Map(node.get_props().get_properties()?),
+        Value::Edge(edge) => Literal::Map(edge.get_properties().get_properties()?),
+        Value::Literal(value) => value.clone(),
+        value => return Err(err!(TypeMismatch, "properties() cannot use {:?}", value)),
+      },
+      "length" => match arg(0)? {
+        Value::Path(path) => Literal::Integer(path.length() as i64),
+        Value::Literal(Literal::Null) => Literal::Null,
+        value => {
+          return Err(err!(
+            TypeMismatch,
+            "length() requires a path, not {:?}",
+            value
+          ))
+        }
+      },
+      "nodes" | "relationships" => match arg(0)? {
+        Value::Path(path) => {
+          return Ok(match name.as_str() {
+            "nodes" => Value::list(path.nodes().into_iter().map(Value::Node).collect()),
+            _ => Value::list(path.edges().into_iter().map(Value::Edge).collect()),
+          })
+        }
+        Value::Literal(Literal::Null) => Literal::Null,
+        value => {
+          return Err(err!(
+            TypeMismatch,
+            "{}() requires a path, not {:?}",
+            call.name,
+            value
+          ))
+        }
+      },
+      "coalesce" => args
+        .iter()
+        .find_map(|value| match value {
+          Value::Literal(Literal::Null) => None,
+          Value::Literal(value) => Some(Ok(value.clone())),
+          value => Some(self.literal(value.clone())),
+        })
+        .unwrap_or(Ok(Literal::Null))?,
+      "size" => match arg(0)? {
+        Value::List(values) => Literal::Integer(values.len() as i64),
+        value => match self.literal(value.clone())? {
+          Literal::Null => Literal::Null,
+          Literal::String(value) => Literal::Integer(value.chars().count() as i64),
+          Literal::List(values) => Literal::Integer(values.len() as i64),
+          x => return Err(err!(TypeMismatch, "size() cannot use {}", x)),
+        },
+      },
+      "toupper" | "tolower" | "trim" => match self.literal(arg(0)?.clone())? {
+        Literal::Null => Literal::Null,
+        Literal::String(value) => Literal::String(match name.as_str() {
+          "toupper" => value.to_uppercase(),
+          "tolower" => value.to_lowercase(),
+          _ => value.trim().to_string(),
+        }),
+        x => {
+          return Err(err!(
+            TypeMismatch,
+            "{}() requires a string, not {}",
+            call.name,
+            x
+          ))
+        }
+      },
+      "tostring" => match self.literal(arg(0)?.clone())? {
+        Literal::Null => Literal::Null,
+        Literal::String(value) => Literal::String(value),
+        x => Literal::String(x.to_string()),
+      },
+      _ => {
+        return Err(err!(
+          NotImplemented,
+          "Function {}() is not supported",
+          call.name
+        ))
+      }
+    };
+
+    Ok(Value::Literal(result))
+  }
+}
+
+/// The value bound to a relationship variable, which is a list of edges for a variable length step
+fn step_value<G: Graph>(relationship: &RelationshipPattern, edges: &[Edge<G>]) -> Value<G> {
+  match relationship.length {
+    Some(_) => Value::list(edges.iter().cloned().map(Value::Edge).collect()),
+    None => Value::Edge(edges[0].clone()),
+  }
+}
+
+/// All the edges leading away from a node in the given direction, paired with the node found at
+/// the other end
+pub(crate) fn neighbours<G, S>(
+  source: &S,
+  node: &Node<G>,
+  direction: Direction,
+) -> Vec<(Edge<G>, Node<G>)>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  let mut result = Vec::new();
+
+  if direction != Direction::Incoming {
+    for edge in source.out_edges(node) {
+      if let Some(target) = source.get_node(&edge.get_target().get_guid()) {
+        result.push((edge, target));
+      }
+    }
+  }
+
+  if direction != Direction::Outgoing {
+    for edge in source.in_edges(node) {
+      let source_guid = edge.get_source().get_guid();
+      // Self loops and the reciprocals of TwoWay edges were already found as outgoing edges
+      if direction == Direction::Either && (source_guid == node.get_guid() || edge.is_two_way()) {
+        continue;
+      }
+      if let Some(source) = source.get_node(&source_guid) {
+        result.push((edge, source));
+      }
+    }
+  }
+
+  result
+}
+
+/// Apply a math operator, staying with integers when possible so decimals are never approximated
+pub(crate) fn arithmetic(op: BinaryOperator, lhs: Literal, rhs: Literal) -> GraphtResult<Literal> {
+  use BinaryOperator::*;
+
+  let overflow = || {
+    err!(
+      ConversionError,
+      "Overflow calculating {} {} {}",
+      lhs,
+      op.symbol(),
+      rhs
+    )
+  };
+
+  let result = match (&lhs, &rhs) {
+    (Literal::Null, _) | (_, Literal::Null) => Literal::Null,
+    (Literal::String(l), Literal::String(r)) if op == Add => Literal::String(format!("{}{}", l, r)),
+    (Literal::List(l), r) if op == Add => {
+      let mut values = l.clone();
+      match r {
+        Literal::List(r) => values.extend(r.iter().cloned()),
+        r => values.push(r.clone()),
+      }
+      Literal::List(values)
+    }
+    (Literal::Integer(l), Literal::Integer(r)) if op != Power => {
+      let value = match op {
+        Add => l.checked_add(*r),
+        Subtract => l.checked_sub(*r),
+        Multiply => l.checked_mul(*r),
+        Divide if *r == 0 => return Err(err!(InvalidItem, "Division by zero")),
+        Divide => l.checked_div(*r),
+        _ if *r == 0 => return Err(err!(InvalidItem, "Division by zero")),
+        _ => l.checked_rem(*r),
+      };
+      Literal::Integer(value.ok_or_else(overflow)?)
+    }
+    _ => match (lhs.as_decimal(), rhs.as_decimal()) {
+      (Some(l), Some(r)) => {
+        let value = match op {
+          Add => l.checked_add(r),
+          Subtract => l.checked_sub(r),
+          Multiply => l.checked_mul(r),
+          Divide if r.is_zero() => return Err(err!(InvalidItem, "Division by zero")),
+          Divide => l.checked_div(r),
+          Modulo if r.is_zero() => return Err(err!(InvalidItem, "Division by zero")),
+          Modulo => l.checked_rem(r),
+          _ => match r.to_string().parse::<i64>() {
+            // Only whole number powers can be calculated exactly
+            Ok(exp) if exp.unsigned_abs() > MAX_EXPONENT => {
+              return Err(err!(
+                InvalidItem,
+                "Exponent {} is larger than the limit of {}",
+                exp,
+                MAX_EXPONENT
+              ))
+            }
+            Ok(exp) if exp < 0 && l.is_zero() => return Err(err!(InvalidItem, "Division by zero")),
+            Ok(exp) => power(l, exp),
+            Err(_) => {
+              return Err(err!(
+                NotImplemented,
+                "Only whole number exponents are supported, not {}",
+                r
+              ))
+            }
+          },
+        };
+        Literal::Decimal(value.ok_or_else(overflow)?)
+      }
+      _ => {
+        return Err(err!(
+          TypeMismatch,
+          "Cannot calculate {} {} {}",
+          lhs.type_name(),
+          op.symbol(),
+          rhs.type_name()
+        ))
+      }
+    },
+  };
+  Ok(result)
+}
+
+/// The largest exponent accepted by `^`. Any base other than -1, 0 or 1 overflows well before it.
+const MAX_EXPONENT: u64 = 4096;
+
+/// Raise a decimal to a whole number power
+///
+/// This squares the base for each bit of the exponent, so it takes O(log exp) multiplications
+fn power(base: rust_decimal::Decimal, exp: i64) -> Option<rust_decimal::Decimal> {
+  let (mut result, mut base, mut remaining) =
+    (rust_decimal::Decimal::ONE, base, exp.unsigned_abs());
+  while remaining > 0 {
+    if remaining & 1 == 1 {
+      result = result.checked_mul(base)?;
+    }
+    remaining >>= 1;
+    if remaining > 0 {
+      base = base.checked_mul(base)?;
+    }
+  }
+  match exp < 0 {
+    true => rust_decimal::Decimal::ONE.checked_div(result),
+    false => Some(result),
+  }
+}
+
+/// Convert projected rows into named columns
+fn to_columns<G: Graph>(columns: Vec<String>, rows: Vec<Row<G>>) -> HashMap<String, Vec<Value<G>>> {
+  let mut result: HashMap<String, Vec<Value<G>>> = columns
+    .iter()
+    .map(|name| (name.clone(), Vec::with_capacity(rows.len())))
+    .collect();
+
+  for mut row in rows {
+    for name in &columns {
+      let value = row.remove(name).unwrap_or(Value::Literal(Literal::Null));
+      if let Some(column) = result.get_mut(name) {
+        column.push(value);
+      }
+    }
+  }
+  result
+}
+
+/// A total order used for sorting values. Graph items come before literals and are sorted by guid
+pub(crate) fn compare_values<G: Graph>(lhs: &Value<G>, rhs: &Value<G>) -> Ordering {
+  match (lhs, rhs) {
+    (Value::Literal(lhs), Value::Literal(rhs)) => lhs.order(rhs),
+    (Value::Literal(_), _) => Ordering::Greater,
+    (_, Value::Literal(_)) => Ordering::Less,
+    (lhs, rhs) => lhs.get_guid().cmp(&rhs.get_guid()),
+  }
+}
+
+/// A string uniquely identifying a list of values, used for finding distinct rows
+pub(crate) fn row_key<G: Graph>(values: &[Value<G>]) -> String {
+  let keys: Vec<String> = values
+    .iter()
+    .map(|value| match value {
+      Value::Literal(Literal::Integer(x)) => format!("Number:{}", x),
+      Value::Literal(Literal::Decimal(x)) => format!("Number:{}", x.normalize()),
+      Value::Literal(x) => format!("{}:{}", x.type_name(), x),
+      x => x.get_guid().to_string(),
+    })
+    .collect();
+  keys.join("\u{1f}")
+}
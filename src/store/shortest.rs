@@ -0,0 +1,485 @@
+//! Finding the shortest paths between two nodes
+//!
+//! Unweighted searches count the hops with a breadth first search. Weighted searches add up the
+//! cost of each edge with Dijkstra's algorithm, which becomes A* when given a heuristic. Both can
+//! return a single shortest path or every path tied for the shortest, and are shared by
+//! `DataSet::shortest_path` and the shortestPath() and allShortestPaths() patterns of a query.
+
+use crate::{
+  gquery::ast::Direction,
+  local::{sync::Arc, *},
+  prelude::*,
+  store::query::neighbours,
+};
+
+use std::{
+  cmp::{Ordering, Reverse},
+  collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use rust_decimal::prelude::ToPrimitive;
+use uuid::Uuid;
+
+/// Estimates the remaining cost from a node to the target of a search
+///
+/// It must never overestimate the cost, otherwise the path found may not be the shortest one.
+pub type Heuristic<G> = Arc<dyn Fn(&Node<G>, &Node<G>) -> f32 + Send + Sync>;
+
+/// How much walking each edge costs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathWeight {
+  /// Every edge costs one hop
+  Hops,
+
+  /// The weight set on the edge, with unweighted edges costing 1
+  Edge,
+
+  /// A numeric property of the edge, with edges missing it costing 1
+  Property(String),
+}
+
+/// The edges a path search may follow and how it adds up their costs
+#[derive(Clone)]
+pub struct PathOpts<G>
+where
+  G: Graph,
+{
+  /// Only follow edges with one of these labels, or any edge if empty
+  labels: Vec<String>,
+
+  /// Which way the edges can be walked
+  direction: Direction,
+
+  /// The most edges a path can have
+  max_length: Option<u64>,
+
+  weight: PathWeight,
+
+  /// Turns a weighted search into A*
+  heuristic: Option<Heuristic<G>>,
+}
+
+impl<G> fmt::Debug for PathOpts<G>
+where
+  G: Graph,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PathOpts")
+      .field("labels", &self.labels)
+      .field("direction", &self.direction)
+      .field("max_length", &self.max_length)
+      .field("weight", &self.weight)
+      .field("heuristic", &self.heuristic.is_some())
+      .finish()
+  }
+}
+
+impl<G> Default for PathOpts<G>
+where
+  G: Graph,
+{
+  fn default() -> Self {
+    PathOpts::new()
+  }
+}
+
+impl<G> PathOpts<G>
+where
+  G: Graph,
+{
+  /// Follow any outgoing edge, counting the hops
+  pub fn new() -> PathOpts<G> {
+    PathOpts {
+      labels: Vec::new(),
+      direction: Direction::Outgoing,
+      max_length: None,
+      weight: PathWeight::Hops,
+      heuristic: None,
+    }
+  }
+
+  /// Only follow edges with this label. Calling it again allows more labels
+  pub fn label(mut self, label: &str) -> Self {
+    self.labels.push(label.to_string());
+    self
+  }
+
+  pub fn direction(mut self, direction: Direction) -> Self {
+    self.direction = direction;
+    self
+  }
+
+  pub fn max_length(mut self, max_length: u64) -> Self {
+    self.max_length = Some(max_length);
+    self
+  }
+
+  /// Add up the weights set on the edges instead of counting hops
+  pub fn weighted(mut self) -> Self {
+    self.weight = PathWeight::Edge;
+    self
+  }
+
+  /// Add up a numeric property of the edges instead of counting hops
+  pub fn weight_property(mut self, name: &str) -> Self {
+    self.weight = PathWeight::Property(name.to_string());
+    self
+  }
+
+  /// Guide a weighted search towards the target with an estimate of the remaining cost
+  pub fn heuristic<F>(mut self, heuristic: F) -> Self
+  where
+    F: Fn(&Node<G>, &Node<G>) -> f32 + Send + Sync + 'static,
+  {
+    self.heuristic = Some(Arc::new(heuristic));
+    self
+  }
+
+  fn follows(&self, edge: &Edge<G>) -> bool {
+    self.labels.is_empty() || self.labels.contains(&edge.get_label())
+  }
+
+  fn is_weighted(&self) -> bool {
+    self.weight != PathWeight::Hops || self.heuristic.is_some()
+  }
+
+  /// The cost of walking an edge, which can't be negative
+  fn cost(&self, edge: &Edge<G>) -> GraphtResult<f64> {
+    let cost = match &self.weight {
+      PathWeight::Hops => 1.0,
+      PathWeight::Edge => edge.get_weight().unwrap_or(1.0) as f64,
+      PathWeight::Property(name) => match edge.get_property(name)? {
+        Literal::Null => 1.0,
+        value => match value.as_decimal().and_then(|x| x.to_f64()) {
+          Some(cost) => cost,
+          None => {
+            return Err(err!(
+              TypeMismatch,
+              "The weight property {} of edge {} must be a number, not {}",
+              name,
+              edge.get_guid(),
+              value
+            ))
+          }
+        },
+      },
+    };
+    match cost >= 0.0 {
+      true => Ok(cost),
+      false => Err(err!(
+        InvalidItem,
+        "Edge {} has a negative weight of {}, which a shortest path can't use",
+        edge.get_guid(),
+        cost
+      )),
+    }
+  }
+}
+
+/// Find a shortest path between two nodes, if one exists. The shortest path from a node to itself
+/// has no edges.
+pub fn shortest_path<G, S>(
+  source: &S,
+  from: &Node<G>,
+  to: &Node<G>,
+  opts: &PathOpts<G>,
+) -> GraphtResult<Option<Path<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  Ok(search(source, from, to, opts, false, |_| Ok(true))?.pop())
+}
+
+/// Find every path tied for the shortest between two nodes
+pub fn all_shortest_paths<G, S>(
+  source: &S,
+  from: &Node<G>,
+  to: &Node<G>,
+  opts: &PathOpts<G>,
+) -> GraphtResult<Vec<Path<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+{
+  search(source, from, to, opts, true, |_| Ok(true))
+}
+
+/// A node reached by the search, along with the hops taken to reach it when the length is limited.
+/// With a limit, a costlier path with fewer hops may still be needed to reach the target in time.
+type Key = (Uuid, u64);
+
+/// The edges arriving at each key from the key before it on a shortest path
+type Previous<G> = HashMap<Key, Vec<(Edge<G>, Key)>>;
+
+/// Run the search, only following the edges that the filter accepts as well as the options
+pub(crate) fn search<G, S, F>(
+  source: &S,
+  from: &Node<G>,
+  to: &Node<G>,
+  opts: &PathOpts<G>,
+  all: bool,
+  mut accept: F,
+) -> GraphtResult<Vec<Path<G>>>
+where
+  G: Graph,
+  S: Matchable<G>,
+  F: FnMut(&Edge<G>) -> GraphtResult<bool>,
+{
+  let (start, target) = match (
+    source.get_node(&from.get_guid()),
+    source.get_node(&to.get_guid()),
+  ) {
+    (Some(start), Some(target)) => (start, target),
+    _ => return Ok(Vec::new()),
+  };
+  if start.get_guid() == target.get_guid() {
+    return Ok(vec![Path::new(Some(start), Vec::new())]);
+  }
+
+  let mut edges = |node: &Node<G>| -> GraphtResult<Vec<(Edge<G>, Node<G>)>> {
+    let mut result = Vec::new();
+    for (edge, next) in neighbours(source, node, opts.direction) {
+      if opts.follows(&edge) && accept(&edge)? {
+        result.push((edge, next));
+      }
+    }
+    Ok(result)
+  };
+
+  let (previous, ends) = match opts.is_weighted() {
+    false => breadth_first(&start, &target, opts, all, &mut edges)?,
+    true => cheapest_first(&start, &target, opts, all, &mut edges)?,
+  };
+  debug!(
+    "Found {} ends of a shortest path from {} to {}",
+    ends.len(),
+    start.get_guid(),
+    target.get_guid()
+  );
+
+  let mut walks = Vec::new();
+  let mut seen = HashSet::from([target.get_guid()]);
+  for end in ends {
+    let mut trail = Vec::new();
+    unwind(
+      &previous,
+      end,
+      start.get_guid(),
+      &mut trail,
+      &mut seen,
+      all,
+      &mut walks,
+    );
+  }
+  Ok(
+    walks
+      .into_iter()
+      .map(|walk| Path::new(Some(start.clone()), walk))
+      .collect(),
+  )
+}
+
+/// Count hops outwards from the start, one layer at a time, until the target is reached
+fn breadth_first<G, F>(
+  start: &Node<G>,
+  target: &Node<G>,
+  opts: &PathOpts<G>,
+  all: bool,
+  edges: &mut F,
+) -> GraphtResult<(Previous<G>, Vec<Key>)>
+where
+  G: Graph,
+  F: FnMut(&Node<G>) -> GraphtResult<Vec<(Edge<G>, Node<G>)>>,
+{
+  let mut hops = HashMap::from([(start.get_guid(), 0)]);
+  let mut previous: Previous<G> = HashMap::new();
+  let mut layer = vec![start.clone()];
+  let mut depth = 0;
+
+  while !layer.is_empty() && !hops.contains_key(&target.get_guid()) {
+    if matches!(opts.max_length, Some(max) if depth >= max) {
+      break;
+    }
+    depth += 1;
+
+    let mut next_layer = Vec::new();
+    for node in layer {
+      for (edge, next) in edges(&node)? {
+        let guid = next.get_guid();
+        match hops.get(&guid) {
+          None => {
+            hops.insert(guid, depth);
+            next_layer.push(next);
+          }
+          // Another way of reaching the node in as few hops only matters for all the paths
+          Some(found) if *found == depth && all => (),
+          Some(_) => continue,
+        }
+        previous
+          .entry((guid, 0))
+          .or_default()
+          .push((edge, (node.get_guid(), 0)));
+      }
+    }
+    layer = next_layer;
+  }
+
+  let ends = match hops.contains_key(&target.get_guid()) {
+    true => vec![(target.get_guid(), 0)],
+    false => Vec::new(),
+  };
+  Ok((previous, ends))
+}
+
+/// A key waiting to be settled, ordered so the lowest estimate is popped from the heap first
+struct Queued<G>
+where
+  G: Graph,
+{
+  estimate: f64,
+  cost: f64,
+  key: Key,
+  node: Node<G>,
+}
+
+impl<G: Graph> PartialEq for Queued<G> {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl<G: Graph> Eq for Queued<G> {}
+
+impl<G: Graph> PartialOrd for Queued<G> {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<G: Graph> Ord for Queued<G> {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.estimate.total_cmp(&other.estimate)
+  }
+}
+
+/// Settle the cheapest known key until the target is settled, estimating the remaining cost with
+/// the heuristic when there is one
+fn cheapest_first<G, F>(
+  start: &Node<G>,
+  target: &Node<G>,
+  opts: &PathOpts<G>,
+  all: bool,
+  edges: &mut F,
+) -> GraphtResult<(Previous<G>, Vec<Key>)>
+where
+  G: Graph,
+  F: FnMut(&Node<G>) -> GraphtResult<Vec<(Edge<G>, Node<G>)>>,
+{
+  let estimate = |node: &Node<G>| match &opts.heuristic {
+    Some(heuristic) => heuristic(node, target) as f64,
+    None => 0.0,
+  };
+
+  let start_key = (start.get_guid(), 0);
+  let mut costs = HashMap::from([(start_key, 0.0)]);
+  let mut previous: Previous<G> = HashMap::new();
+  let mut settled = HashSet::new();
+  let mut queue = BinaryHeap::from([Reverse(Queued {
+    estimate: estimate(start),
+    cost: 0.0,
+    key: start_key,
+    node: start.clone(),
+  })]);
+  let mut best: Option<f64> = None;
+  let mut ends = Vec::new();
+
+  while let Some(Reverse(queued)) = queue.pop() {
+    // Everything left would cost more than the paths already found
+    if matches!(best, Some(best) if queued.estimate > best) {
+      break;
+    }
+    if !settled.insert(queued.key) {
+      continue;
+    }
+
+    let (guid, hops) = queued.key;
+    if guid == target.get_guid() {
+      best = Some(queued.cost);
+      ends.push(queued.key);
+      match all {
+        true => continue,
+        false => break,
+      }
+    }
+    if matches!(opts.max_length, Some(max) if hops >= max) {
+      continue;
+    }
+
+    for (edge, next) in edges(&queued.node)? {
+      let cost = queued.cost + opts.cost(&edge)?;
+      // Hops only need telling apart when they limit the length
+      let key = (
+        next.get_guid(),
+        opts.max_length.map(|_| hops + 1).unwrap_or(0),
+      );
+      let known = costs.get(&key).copied();
+      match known {
+        Some(known) if cost > known => continue,
+        Some(known) if cost == known => {
+          if all {
+            previous.entry(key).or_default().push((edge, queued.key));
+          }
+          continue;
+        }
+        _ => (),
+      }
+
+      costs.insert(key, cost);
+      previous.insert(key, vec![(edge, queued.key)]);
+      queue.push(Reverse(Queued {
+        estimate: cost + estimate(&next),
+        cost,
+        key,
+        node: next,
+      }));
+    }
+  }
+
+  // With a limited length, the target can be reached with different hops for the same cost
+  ends.retain(|key| costs.get(key).copied() == best);
+  Ok((previous, ends))
+}
+
+/// Follow the edges back from a key to the start of the search, adding each walk found in the
+/// order it was walked. Nodes are never repeated, which stops edges that cost nothing from
+/// looping forever.
+fn unwind<G>(
+  previous: &Previous<G>,
+  key: Key,
+  start: Uuid,
+  trail: &mut Vec<Edge<G>>,
+  seen: &mut HashSet<Uuid>,
+  all: bool,
+  walks: &mut Vec<Vec<Edge<G>>>,
+) where
+  G: Graph,
+{
+  if key == (start, 0) {
+    walks.push(trail.iter().rev().cloned().collect());
+    return;
+  }
+
+  for (edge, prior) in previous.get(&key).into_iter().flatten() {
+    if !all && !walks.is_empty() {
+      return;
+    }
+    if !seen.insert(prior.0) {
+      continue;
+    }
+    trail.push(edge.clone());
+    unwind(previous, *prior, start, trail, seen, all, walks);
+    trail.pop();
+    seen.remove(&prior.0);
+  }
+}
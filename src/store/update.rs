@@ -0,0 +1,572 @@
+//! Running the clauses of a query that change a DataSet
+//!
+//! Reading clauses (MATCH, UNWIND, WITH) are handed to the query executor, while CREATE, MERGE,
+//! SET, REMOVE, and DELETE are applied to the DataSet one row at a time. The nodes held in the rows
+//! share their contents with the DataSet, so each clause sees the changes made before it.
+//!
+//! MERGE first matches its whole pattern the same way MATCH would, and only creates it when nothing
+//! matches. Nodes and edges are still identified by the keys of their entities, so a created item
+//! whose key is already stored is merged with it instead of being duplicated.
+
+use crate::{
+  gquery::ast::*,
+  local::*,
+  prelude::*,
+  store::{planner, query::Executor},
+};
+
+use std::collections::{BTreeMap, HashSet};
+
+/// Run a query against a DataSet, returning what it created, updated, and deleted
+pub fn execute<G: Graph>(
+  data_set: &mut DataSet<G>,
+  query: &GQuery,
+  params: &Params,
+) -> GraphtResult<CrudResultStats<DataSetStats>> {
+  let mut updater = Updater {
+    data_set,
+    params,
+    stats: CrudResultStats::new(),
+  };
+  updater.run(query)?;
+  Ok(updater.stats)
+}
+
+/// Applies the updating clauses of a query to each row, keeping count of the changes
+struct Updater<'a, G>
+where
+  G: Graph,
+{
+  data_set: &'a mut DataSet<G>,
+
+  /// The values for the query's $parameters
+  params: &'a Params,
+
+  stats: CrudResultStats<DataSetStats>,
+}
+
+impl<'a, G> Updater<'a, G>
+where
+  G: Graph,
+{
+  fn run(&mut self, query: &GQuery) -> GraphtResult<()> {
+    debug!("Running updating query: {}", query);
+    let plans = Executor::new(&*self.data_set, self.params).plan(query);
+
+    let mut rows: Vec<Row<G>> = vec![Row::new()];
+    for (clause, plan) in query.clauses.iter().zip(&plans) {
+      rows = match clause {
+        Clause::Match(_) | Clause::Unwind(_) | Clause::With(_) => {
+          Executor::new(&*self.data_set, self.params).read_clause(clause, plan.as_ref(), rows, 0)?
+        }
+        Clause::Return(_) => break,
+        Clause::Merge(merge) => {
+          let mut merged = Vec::new();
+          for row in rows {
+            merged.extend(self.merge(merge, row)?);
+          }
+          merged
+        }
+        clause => {
+          for row in &mut rows {
+            self.update_row(clause, row)?;
+          }
+          rows
+        }
+      };
+    }
+    Ok(())
+  }
+
+  fn update_row(&mut self, clause: &Clause, row: &mut Row<G>) -> GraphtResult<()> {
+    match clause {
+      Clause::Create(pattern) => pattern
+        .parts
+        .iter()
+        .try_for_each(|part| self.create_part(part, row, false).map(|_| ())),
+      Clause::Set(items) => items.iter().try_for_each(|item| self.set(item, row)),
+      Clause::Remove(items) => items.iter().try_for_each(|item| self.remove(item, row)),
+      Clause::Delete(delete) => delete.items.iter().try_for_each(|item| {
+        let value = self.evaluate(item, row)?;
+        self.delete(value, delete.detach)
+      }),
+      clause => unreachable!("Only updating clauses are run per row, not {}", clause),
+    }
+  }
+
+  //--- CREATE and MERGE
+
+  /// Match the pattern for a row, running ON MATCH for each match. When nothing matches, the
+  /// pattern is created and ON CREATE is run instead.
+  fn merge(&mut self, merge: &Merge, row: Row<G>) -> GraphtResult<Vec<Row<G>>> {
+    let matched = Match {
+      optional: false,
+      pattern: Pattern {
+        parts: vec![merge.pattern.clone()],
+      },
+      filter: None,
+    };
+    let bound: HashSet<String> = row.keys().cloned().collect();
+    let plan = planner::plan_match(&*self.data_set, &matched, &bound);
+    let mut rows = Executor::new(&*self.data_set, self.params).read_clause(
+      &Clause::Match(matched),
+      Some(&plan),
+      vec![row.clone()],
+      0,
+    )?;
+
+    let items = match rows.is_empty() {
+      true => {
+        let mut row = row;
+        self.create_part(&merge.pattern, &mut row, true)?;
+        rows = vec![row];
+        &merge.on_create
+      }
+      false => &merge.on_match,
+    };
+    for row in &mut rows {
+      items.iter().try_for_each(|item| self.set(item, row))?;
+    }
+    Ok(rows)
+  }
+
+  /// Create the nodes and edges of a pattern that aren't already bound, returning whether anything
+  /// was created. When merging, any that already exist are matched instead.
+  fn create_part(
+    &mut self,
+    part: &PatternPart,
+    row: &mut Row<G>,
+    merge: bool,
+  ) -> GraphtResult<bool> {
+    if let Some(name) = &part.variable {
+      return Err(err!(
+        NotImplemented,
+        "Named paths such as '{}' cannot be created yet",
+        name
+      ));
+    }
+
+    let (mut left, mut created) = self.create_node(&part.start, row, merge)?;
+    for step in &part.chain {
+      let (right, node_created) = self.create_node(&step.node, row, merge)?;
+      let edge_created = self.create_edge(&step.relationship, &left, &right, row, merge)?;
+      created |= node_created || edge_created;
+      left = right;
+    }
+    Ok(created)
+  }
+
+  /// Get the node for a pattern, creating it unless it is already bound. The first label names the
+  /// type of entity to build from the pattern's properties.
+  fn create_node(
+    &mut self,
+    pattern: &NodePattern,
+    row: &mut Row<G>,
+    merge: bool,
+  ) -> GraphtResult<(Node<G>, bool)> {
+    if let Some((name, value)) = pattern
+      .variable
+      .as_ref()
+      .and_then(|name| row.get(name).map(|value| (name, value)))
+    {
+      return match value {
+        Value::Node(node) if pattern.labels.is_empty() && pattern.properties.is_empty() => {
+          Ok((node.clone(), false))
+        }
+        Value::Node(_) => Err(err!(
+          InvalidItem,
+          "Variable '{}' is already bound, so it cannot be given labels or properties in {}",
+          name,
+          pattern
+        )),
+        value => Err(err!(
+          TypeMismatch,
+          "Variable '{}' must be a node to be used in a pattern, not {:?}",
+          name,
+          value
+        )),
+      };
+    }
+
+    let type_label = pattern.labels.first().ok_or_else(|| {
+      err!(
+        InvalidItem,
+        "Cannot create {} without a label naming its type",
+        pattern
+      )
+    })?;
+    let properties = self.properties(&pattern.properties, row)?;
+    let mut node = Node::new(G::Node::from_properties(type_label, properties)?);
+    for label in &pattern.labels {
+      node.add_label(label);
+    }
+
+    let guid = node.get_guid();
+    let (node, created) = match self.data_set.get_node(&guid) {
+      Some(existing) if merge => (existing, false),
+      Some(_) => {
+        return Err(err!(
+          DuplicateKey,
+          "Node {} already exists. Use MERGE to only create it when it is missing",
+          guid
+        ))
+      }
+      None => {
+        self.stats += self.data_set.insert(node.into())?;
+        let stored = self
+          .data_set
+          .get_node(&guid)
+          .expect("The node was just inserted");
+        (stored, true)
+      }
+    };
+
+    if let Some(name) = &pattern.variable {
+      row.insert(name.clone(), Value::Node(node.clone()));
+    }
+    Ok((node, created))
+  }
+
+  /// Create the edge for a relationship between two nodes, returning whether it was created
+  fn create_edge(
+    &mut self,
+    pattern: &RelationshipPattern,
+    left: &Node<G>,
+    right: &Node<G>,
+    row: &mut Row<G>,
+    merge: bool,
+  ) -> GraphtResult<bool> {
+    if let Some(name) = pattern
+      .variable
+      .as_ref()
+      .filter(|name| row.contains_key(*name))
+    {
+      return Err(err!(
+        InvalidItem,
+        "Variable '{}' is already bound, so the relationship {} cannot be created",
+        name,
+        pattern
+      ));
+    }
+    if pattern.length.is_some() {
+      return Err(err!(
+        InvalidItem,
+        "Cannot create the variable length relationship {}",
+        pattern
+      ));
+    }
+    let edge_type = match pattern.types.as_slice() {
+      [edge_type] => edge_type,
+      _ => {
+        return Err(err!(
+          InvalidItem,
+          "A relationship needs exactly one type to be created: {}",
+          pattern
+        ))
+      }
+    };
+
+    let properties = self.properties(&pattern.properties, row)?;
+    let entity = G::Edge::from_properties(edge_type, properties)?;
+    let edge = match pattern.direction {
+      Direction::Outgoing => Edge::new(left, right, entity)?,
+      Direction::Incoming => Edge::new(right, left, entity)?,
+      // Merging without a direction matches the edge going either way, or creates it left to right
+      Direction::Either if merge => match Edge::new(right, left, entity.clone()) {
+        Ok(reversed) if self.data_set.contains(&reversed.get_guid()) => reversed,
+        _ => Edge::new(left, right, entity)?,
+      },
+      Direction::Either => {
+        return Err(err!(
+          InvalidItem,
+          "A relationship needs a direction to be created: {}",
+          pattern
+        ))
+      }
+    };
+
+    let guid = edge.get_guid();
+    let (edge, created) = match self.data_set.get_edge(&guid) {
+      Some(existing) if merge => (existing, false),
+      Some(_) => {
+        return Err(err!(
+          DuplicateKey,
+          "Edge {} already exists. Use MERGE to only create it when it is missing",
+          guid
+        ))
+      }
+      None => {
+        self.stats += self.data_set.insert(edge.clone().into())?;
+        (edge, true)
+      }
+    };
+
+    if let Some(name) = &pattern.variable {
+      row.insert(name.clone(), Value::Edge(edge));
+    }
+    Ok(created)
+  }
+
+  /// Evaluate the properties given in a pattern, leaving out any that are null
+  fn properties(
+    &self,
+    properties: &[(String, Expression)],
+    row: &Row<G>,
+  ) -> GraphtResult<BTreeMap<String, Literal>> {
+    let mut values = BTreeMap::new();
+    for (key, expression) in properties {
+      let value = self.literal(expression, row)?;
+      if !value.is_null() {
+        values.insert(key.clone(), value);
+      }
+    }
+    Ok(values)
+  }
+
+  //--- SET and REMOVE
+
+  fn set(&mut self, item: &SetItem, row: &mut Row<G>) -> GraphtResult<()> {
+    match item {
+      SetItem::Property(lookup, value) => {
+        let (target, name) = self.lookup(lookup, row)?;
+        let value = self.literal(value, row)?;
+        self.change_properties(target, row, |properties| match value.is_null() {
+          true => {
+            properties.remove(name);
+          }
+          false => {
+            properties.insert(name.to_string(), value);
+          }
+        })
+      }
+      SetItem::Replace(name, value) => {
+        let target = self.evaluate(&Expression::variable(name), row)?;
+        let mut values = self.map(value, row)?;
+        values.retain(|_, value| !value.is_null());
+        self.change_properties(target, row, |properties| *properties = values)
+      }
+      SetItem::Update(name, value) => {
+        let target = self.evaluate(&Expression::variable(name), row)?;
+        let values = self.map(value, row)?;
+        self.change_properties(target, row, |properties| {
+          for (key, value) in values {
+            match value.is_null() {
+              true => properties.remove(&key),
+              false => properties.insert(key, value),
+            };
+          }
+        })
+      }
+      SetItem::Labels(name, labels) => self.change_labels(name, labels, row, true),
+    }
+  }
+
+  fn remove(&mut self, item: &RemoveItem, row: &mut Row<G>) -> GraphtResult<()> {
+    match item {
+      RemoveItem::Property(lookup) => {
+        let (target, name) = self.lookup(lookup, row)?;
+        self.change_properties(target, row, |properties| {
+          properties.remove(name);
+        })
+      }
+      RemoveItem::Labels(name, labels) => self.change_labels(name, labels, row, false),
+    }
+  }
+
+  /// Split a property lookup such as "org.name" into the item and the property name
+  fn lookup<'e>(&self, lookup: &'e Expression, row: &Row<G>) -> GraphtResult<(Value<G>, &'e str)> {
+    match lookup {
+      Expression::Property(target, name) => Ok((self.evaluate(target, row)?, name)),
+      lookup => Err(err!(
+        InvalidItem,
+        "Expected a property such as org.name to be updated, not {}",
+        lookup
+      )),
+    }
+  }
+
+  /// The properties to use for a whole item, given as a map or copied from another node or edge
+  fn map(&self, value: &Expression, row: &Row<G>) -> GraphtResult<BTreeMap<String, Literal>> {
+    match self.evaluate(value, row)? {
+      Value::Literal(Literal::Map(values)) => Ok(values),
+      Value::Node(node) => node.get_props().get_properties(),
+      Value::Edge(edge) => edge.get_properties().get_properties(),
+      value => Err(err!(
+        TypeMismatch,
+        "Properties can only be set from a map, node, or edge, not {:?}",
+        value
+      )),
+    }
+  }
+
+  /// Rebuild the entity of a node or edge with changed properties and store it in the DataSet
+  fn change_properties<F>(
+    &mut self,
+    target: Value<G>,
+    row: &mut Row<G>,
+    change: F,
+  ) -> GraphtResult<()>
+  where
+    F: FnOnce(&mut BTreeMap<String, Literal>),
+  {
+    match target {
+      Value::Node(node) => {
+        let mut properties = node.get_props().get_properties()?;
+        change(&mut properties);
+
+        let type_label = node.type_label();
+        let entity = G::Node::from_properties(&type_label, properties.clone())?;
+        check_properties(&type_label, &properties, &entity)?;
+        let mut updated = node.deep_clone()?;
+        updated.set_props(entity)?;
+        self.stats += self.data_set.update_node(&updated)?;
+      }
+      Value::Edge(edge) => {
+        let mut properties = edge.get_properties().get_properties()?;
+        change(&mut properties);
+
+        let type_label = edge.get_type_label();
+        let entity = G::Edge::from_properties(&type_label, properties.clone())?;
+        check_properties(&type_label, &properties, &entity)?;
+        let updated = Edge::new(&edge.get_source(), &edge.get_target(), entity)?;
+        self.stats += self.data_set.replace_edge(&edge, updated.clone())?;
+
+        // The edge's guid can change with its properties, so the row needs the new one
+        for value in row.values_mut() {
+          if matches!(value, Value::Edge(x) if x.get_guid() == edge.get_guid()) {
+            *value = Value::Edge(updated.clone());
+          }
+        }
+      }
+      // Updating a missing item, such as one from an OPTIONAL MATCH, does nothing
+      Value::Literal(Literal::Null) => (),
+      value => {
+        return Err(err!(
+          TypeMismatch,
+          "Only the properties of nodes and edges can be changed, not {:?}",
+          value
+        ))
+      }
+    }
+    Ok(())
+  }
+
+  fn change_labels(
+    &mut self,
+    name: &str,
+    labels: &[String],
+    row: &Row<G>,
+    add: bool,
+  ) -> GraphtResult<()> {
+    let node = match self.evaluate(&Expression::variable(name), row)? {
+      Value::Node(node) => node,
+      Value::Literal(Literal::Null) => return Ok(()),
+      value => {
+        return Err(err!(
+          TypeMismatch,
+          "Only nodes have labels, but '{}' is {:?}",
+          name,
+          value
+        ))
+      }
+    };
+
+    let mut updated = node.deep_clone()?;
+    for label in labels {
+      match add {
+        true => {
+          updated.add_label(label);
+        }
+        false if *label == node.type_label() => {
+          return Err(err!(
+            InvalidItem,
+            "Cannot remove the label {} from node {}, since it names the node's type",
+            label,
+            node.get_guid()
+          ))
+        }
+        false => {
+          updated.drop_label(label);
+        }
+      }
+    }
+    self.stats += self.data_set.update_node(&updated)?;
+    Ok(())
+  }
+
+  //--- DELETE
+
+  /// Delete a node or edge, skipping any that an earlier row already deleted
+  fn delete(&mut self, value: Value<G>, detach: bool) -> GraphtResult<()> {
+    match value {
+      Value::Node(node) => {
+        let guid = node.get_guid();
+        if self.data_set.get_node(&guid).is_some() {
+          self.stats += self.data_set.delete_node(&guid, detach)?;
+        }
+      }
+      Value::Edge(edge) => {
+        let guid = edge.get_guid();
+        if self.data_set.get_edge(&guid).is_some() {
+          self.stats += self.data_set.delete_edge(&guid)?;
+        }
+      }
+      Value::List(values) => {
+        for value in values {
+          self.delete(value, detach)?;
+        }
+      }
+      Value::Literal(Literal::Null) => (),
+      // Deleting a path deletes everything along it
+      Value::Path(path) => {
+        for edge in path.edges() {
+          self.delete(Value::Edge(edge), detach)?;
+        }
+        for node in path.nodes() {
+          self.delete(Value::Node(node), detach)?;
+        }
+      }
+      Value::Literal(value) => {
+        return Err(err!(
+          TypeMismatch,
+          "Only nodes and edges can be deleted, not {}",
+          value
+        ))
+      }
+    }
+    Ok(())
+  }
+
+  //--- Expressions
+
+  fn evaluate(&self, expression: &Expression, row: &Row<G>) -> GraphtResult<Value<G>> {
+    Executor::new(&*self.data_set, self.params).evaluate(expression, row)
+  }
+
+  fn literal(&self, expression: &Expression, row: &Row<G>) -> GraphtResult<Literal> {
+    let executor = Executor::new(&*self.data_set, self.params);
+    executor.literal(executor.evaluate(expression, row)?)
+  }
+}
+
+/// Make sure an entity rebuilt from changed properties kept all of them, since `from_properties`
+/// leaves out any that its type doesn't have
+fn check_properties<E: GraphtEntity>(
+  type_label: &str,
+  properties: &BTreeMap<String, Literal>,
+  entity: &E,
+) -> GraphtResult<()> {
+  let kept = entity.get_properties()?;
+  match properties
+    .iter()
+    .find(|(key, value)| !value.is_null() && !matches!(kept.get(*key), Some(x) if !x.is_null()))
+  {
+    Some((key, _)) => Err(err!(
+      InvalidItem,
+      "A {} does not have the property '{}'",
+      type_label,
+      key
+    )),
+    None => Ok(()),
+  }
+}
@@ -13,6 +13,13 @@ where
   Node(Node<G>),
   Edge(Edge<G>),
   Path(Path<G>),
+
+  /// A list holding nodes, edges, or paths, such as the result of collect(). Lists of only
+  /// literals are kept as a Literal
+  List(Vec<Value<G>>),
+
+  /// A scalar or collection, such as a property returned by a query
+  Literal(Literal),
 }
 
 impl<G> Value<G>
@@ -24,7 +31,30 @@ where
       Value::Node(node) => node.get_guid(),
       Value::Edge(edge) => edge.get_guid(),
       Value::Path(path) => path.get_guid(),
+      Value::List(values) => {
+        let guids: Vec<String> = values.iter().map(|x| x.get_guid().to_string()).collect();
+        Uuid::new_v5(
+          &Uuid::NAMESPACE_OID,
+          format!("List:[{}]", guids.join(",")).as_bytes(),
+        )
+      }
+      Value::Literal(value) => Uuid::new_v5(
+        &Uuid::NAMESPACE_OID,
+        format!("{}:{}", value.type_name(), value).as_bytes(),
+      ),
+    }
+  }
+
+  /// Make a list, which is a Literal unless it holds a node, edge, or path
+  pub fn list(values: Vec<Value<G>>) -> Value<G> {
+    let mut literals = Vec::new();
+    for value in &values {
+      match value {
+        Value::Literal(value) => literals.push(value.clone()),
+        _ => return Value::List(values),
+      }
     }
+    Value::Literal(Literal::List(literals))
   }
 }
 
@@ -55,6 +85,8 @@ where
       Value::Edge(edge) => edge.clone(),
       Value::Node(_) => panic!("Cannot convert a node to an edge"),
       Value::Path(_) => panic!("Cannot convert a path to an edge"),
+      Value::List(_) => panic!("Cannot convert a list to an edge"),
+      Value::Literal(_) => panic!("Cannot convert a literal to an edge"),
     }
   }
 }
@@ -68,6 +100,15 @@ where
   }
 }
 
+impl<G> From<Literal> for Value<G>
+where
+  G: Graph,
+{
+  fn from(value: Literal) -> Self {
+    Value::Literal(value)
+  }
+}
+
 impl<G> Hash for Value<G>
 where
   G: Graph,
@@ -0,0 +1,137 @@
+//! Test the graph analytics over a DataSet
+
+use grapht::{
+  algo::{self, View},
+  gquery::ast::Direction,
+  prelude::*,
+};
+
+#[macro_use]
+mod common;
+use common::invoicer::*;
+
+/// Root is the parent of heir and spare, heir is the parent of grand, and grand lists root as its
+/// child, closing a cycle. Loner has no edges at all.
+fn org_family() -> DataSet<FhlGraph> {
+  let mut data_set = DataSet::new();
+  data_set
+    .query_mut(
+      "CREATE (r:Organization {pretty_id: 'root'})-[:ParentOf]->(h:Organization {pretty_id: 'heir'}), \
+       (r)-[:ParentOf]->(:Organization {pretty_id: 'spare'}), \
+       (h)-[:ParentOf]->(g:Organization {pretty_id: 'grand'})-[:ChildOf]->(r), \
+       (:Organization {pretty_id: 'loner'})",
+    )
+    .expect("Could not create the family");
+  data_set
+}
+
+/// The pretty ids of the nodes, in order
+fn ids(nodes: &[Node<FhlGraph>]) -> Vec<String> {
+  nodes
+    .iter()
+    .map(|node| match node.get_property("pretty_id") {
+      Ok(Literal::String(id)) => id,
+      x => panic!("Expected a pretty_id, not {:?}", x),
+    })
+    .collect()
+}
+
+fn ranked_ids(ranking: &[(Node<FhlGraph>, f64)]) -> Vec<String> {
+  let nodes: Vec<Node<FhlGraph>> = ranking.iter().map(|(node, _)| node.clone()).collect();
+  ids(&nodes)
+}
+
+db_test_fn! {
+  fn test_components_and_cycles() {
+    let data_set = org_family();
+    let parents = View::new(&data_set).label("ParentOf");
+
+    let weak = algo::weak_components(&data_set);
+    assert_eq!(weak.iter().map(|x| x.len()).collect::<Vec<_>>(), vec![4, 1]);
+    assert_eq!(ids(&weak[1]), vec!["loner"]);
+
+    // The ChildOf edge closes the only loop
+    let strong = algo::strong_components(&data_set);
+    assert_eq!(strong.iter().map(|x| x.len()).collect::<Vec<_>>(), vec![3, 1, 1]);
+    let mut looped = ids(&strong[0]);
+    looped.sort();
+    assert_eq!(looped, vec!["grand", "heir", "root"]);
+    assert!(algo::strong_components(&parents).iter().all(|x| x.len() == 1));
+
+    let cycle = algo::find_cycle(&data_set).expect("Missing the cycle");
+    assert_eq!(cycle.length(), 3);
+    assert_eq!(cycle.start(), cycle.end());
+    assert!(algo::find_cycle(&parents).is_none());
+
+    // Only the tree of parents can be sorted
+    let err = algo::topological_sort(&data_set).expect_err("Sorted a cycle");
+    assert!(err.is(Kind::InvalidItem));
+    let sorted = ids(&algo::topological_sort(&parents).expect("Could not sort the parents"));
+    let position = |id: &str| sorted.iter().position(|x| x == id).expect("Missing a node");
+    assert!(position("root") < position("heir") && position("heir") < position("grand"));
+    assert!(position("root") < position("spare"));
+
+    // Tagged components can be filtered on by later queries
+    algo::tag_components("family", &weak);
+    let result = data_set
+      .query("MATCH (o:Organization) WHERE o.family = 0 RETURN count(o) AS total")
+      .expect("Query failed");
+    assert_eq!(result.get("total"), Some(&vec![Value::Literal(4.into())]));
+  }
+}
+
+db_test_fn! {
+  fn test_centrality() {
+    let data_set = org_family();
+    let parents = View::new(&data_set).label("ParentOf");
+
+    // Rank flows down the tree, and always adds up to one
+    let ranking = algo::page_rank(&parents, 0.85, 100);
+    assert_eq!(ranked_ids(&ranking)[0], "grand");
+    let total: f64 = ranking.iter().map(|(_, score)| score).sum();
+    assert!((total - 1.0).abs() < 1e-9, "PageRank added up to {}", total);
+
+    // Heir is the only way from root to grand, out of 4 * 3 pairs
+    let ranking = algo::betweenness(&parents);
+    assert_eq!(ranked_ids(&ranking)[0], "heir");
+    assert!((ranking[0].1 - 1.0 / 12.0).abs() < 1e-9);
+    assert!(ranking[1..].iter().all(|(_, score)| *score == 0.0));
+
+    let ranking = algo::degree_centrality(&data_set, Direction::Outgoing);
+    assert_eq!(ranked_ids(&ranking)[0], "root");
+    assert_eq!(ranking[0].1, 0.5);
+
+    // Scores are tagged as decimals
+    algo::tag_scores("rank", &algo::page_rank(&parents, 0.85, 100));
+    let result = data_set
+      .query("MATCH (o:Organization) WHERE o.rank > 0.25 RETURN o.pretty_id AS id")
+      .expect("Query failed");
+    assert_eq!(result.get("id"), Some(&vec![Value::Literal("grand".into())]));
+  }
+}
+
+db_test_fn! {
+  fn test_neighbourhood() {
+    let data_set = org_family();
+    let root = data_set
+      .nodes("MATCH (o {pretty_id: 'root'}) RETURN o")
+      .expect("Query failed")
+      .remove(0);
+
+    let near = |hops: u32, direction: Direction| -> Vec<(String, u32)> {
+      let mut near: Vec<(String, u32)> = algo::neighbourhood(&data_set, &root, hops, direction)
+        .into_iter()
+        .map(|(node, hops)| (ids(&[node]).remove(0), hops))
+        .collect();
+      near.sort();
+      near
+    };
+    assert_eq!(near(1, Direction::Outgoing), vec![("heir".into(), 1), ("spare".into(), 1)]);
+    assert_eq!(
+      near(2, Direction::Outgoing),
+      vec![("grand".into(), 2), ("heir".into(), 1), ("spare".into(), 1)]
+    );
+    assert_eq!(near(1, Direction::Incoming), vec![("grand".into(), 1)]);
+    assert_eq!(near(0, Direction::Either), vec![]);
+  }
+}
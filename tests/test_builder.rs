@@ -0,0 +1,89 @@
+//! Test building queries in code
+
+use grapht::{gquery::builder::*, prelude::*};
+
+#[macro_use]
+mod common;
+
+/// The built query has to match the parsed text, and print back out to text that parses the same
+fn check(built: GQuery, text: &str) {
+  let parsed =
+    GQuery::parse(text).unwrap_or_else(|err| panic!("Could not parse {:?}: {}", text, err));
+  assert_eq!(built, parsed);
+  assert_eq!(GQuery::parse(&built.to_string()).unwrap(), built);
+}
+
+db_test_fn! {
+  fn test_build_reading_queries() {
+    let query = GQuery::match_(node("o").label("Organization"))
+      .edge(out("ParentOf").var_len(0..=1))
+      .node("c")
+      .where_(prop("o", "name").eq(param("n")))
+      .ret(["o", "c"]);
+    assert_eq!(
+      query.to_string(),
+      "MATCH (o:Organization)-[:ParentOf*0..1]->(c) WHERE o.name = $n RETURN o, c"
+    );
+    check(query, "MATCH (o:Organization)-[:ParentOf*0..1]->(c) WHERE o.name = $n RETURN o, c");
+
+    // Multiple clauses, filters and projection modifiers
+    let query = GQuery::match_(node("o").prop("pretty_id", "root"))
+      .edge(in_("ChildOf").var("r"))
+      .node(node("c").label("Organization"))
+      .where_(prop("c", "balance").ge(lit(1)))
+      .where_(!path("c").edge(out("Paid")).exists())
+      .optional_match("c")
+      .edge(either(""))
+      .node("x")
+      .with([var("o").into(), call("count", [var("x")]).as_("total")])
+      .where_(var("total").gt(1))
+      .unwind(list([1, 2]), "n")
+      .ret([prop("o", "org_name").as_("name"), (var("total") + var("n")).as_("sum")])
+      .distinct()
+      .order_by([var("name").desc()])
+      .skip(1)
+      .limit(10);
+    check(
+      query,
+      r#"
+        MATCH (o {pretty_id: 'root'})<-[r:ChildOf]-(c:Organization)
+        WHERE c.balance >= 1 AND NOT (c)-[:Paid]->()
+        OPTIONAL MATCH (c)--(x)
+        WITH o, count(x) AS total WHERE total > 1
+        UNWIND [1, 2] AS n
+        RETURN DISTINCT o.org_name AS name, total + n AS sum ORDER BY name DESC SKIP 1 LIMIT 10
+      "#,
+    );
+
+    // Separate pattern parts and named paths
+    let query = GQuery::match_("a").node("b").edge(out("")).named("p").ret_all();
+    check(query, "MATCH (a), p = (b)-->() RETURN *");
+  }
+}
+
+db_test_fn! {
+  fn test_build_updating_queries() {
+    let query = GQuery::merge(node("o").prop("pretty_id", "root"))
+      .on_create_set(prop("o", "created"), true)
+      .on_match_set(prop("o", "seen"), prop("o", "seen") + lit(1))
+      .create("o")
+      .edge(out("ParentOf"))
+      .node(node("c").label("Organization").prop("balance", lit(0)))
+      .set(prop("c", "name"), "child")
+      .set_labels("c", &["New"])
+      .remove(prop("o", "old"))
+      .build();
+    check(
+      query,
+      r#"
+        MERGE (o {pretty_id: 'root'}) ON CREATE SET o.created = true ON MATCH SET o.seen = o.seen + 1
+        CREATE (o)-[:ParentOf]->(c:Organization {balance: 0})
+        SET c.name = 'child', c:New
+        REMOVE o.old
+      "#,
+    );
+
+    let query = GQuery::match_("o").where_(var("o").has_labels(&["Old"])).detach_delete(&["o"]).build();
+    check(query, "MATCH (o) WHERE o:Old DETACH DELETE o");
+  }
+}
@@ -0,0 +1,220 @@
+//! Test parsing OpenCypher into a GQuery
+
+use grapht::{
+  gquery::{ast::*, grammars::Dialect},
+  prelude::*,
+};
+
+#[macro_use]
+mod common;
+
+/// Parse the query, then make sure printing it back out parses into the same AST
+fn round_trip(query: &str) -> GQuery {
+  let parsed =
+    GQuery::parse(query).unwrap_or_else(|err| panic!("Could not parse {:?}: {}", query, err));
+  let printed = parsed.to_string();
+  let reparsed = GQuery::parse(&printed)
+    .unwrap_or_else(|err| panic!("Could not reparse {:?}: {}", printed, err));
+  assert_eq!(
+    parsed, reparsed,
+    "Printed query changed the AST: {}",
+    printed
+  );
+  parsed
+}
+
+db_test_fn! {
+  fn test_parse_reading_clauses() {
+    let query = round_trip(r#"
+      MATCH (o:Organization {pretty_id: 'root'})-[r:ParentOf|ChildOf*0..1]->(c)
+      OPTIONAL MATCH p = (c)<-[:Paid]-(payer:Organization)
+      WHERE NOT (payer)-[:ChildOf]->() AND payer.balance >= 1.5
+      WITH o, c, count(DISTINCT payer) AS payers
+      WHERE payers > 0
+      UNWIND [1, 2, 3] AS x
+      RETURN DISTINCT o.org_name AS name, c, CASE WHEN x > 1 THEN 'many' ELSE 'one' END AS amount
+      ORDER BY name DESC, c.pretty_id SKIP 1 LIMIT 10
+    "#);
+
+    assert_eq!(query.clauses.len(), 5);
+    assert_eq!(query.columns(), vec!["name", "c", "amount"]);
+    assert!(!query.is_updating());
+
+    match &query.clauses[0] {
+      Clause::Match(matched) => {
+        let relationship = &matched.pattern.parts[0].chain[0].relationship;
+        assert_eq!(relationship.types, vec!["ParentOf", "ChildOf"]);
+        assert_eq!(relationship.direction, Direction::Outgoing);
+        assert_eq!(relationship.length, Some(VarLength { min: Some(0), max: Some(1) }));
+      }
+      clause => panic!("Expected a MATCH, found {}", clause),
+    }
+
+    match &query.clauses[1] {
+      Clause::Match(matched) => {
+        assert!(matched.optional);
+        assert_eq!(matched.pattern.parts[0].variable, Some("p".to_string()));
+        assert!(matched.filter.is_some());
+      }
+      clause => panic!("Expected an OPTIONAL MATCH, found {}", clause),
+    }
+
+    match query.clauses.last() {
+      Some(Clause::Return(projection)) => {
+        assert!(projection.distinct);
+        assert_eq!(projection.order.len(), 2);
+        assert!(projection.order[0].descending);
+        assert_eq!(projection.skip, Some(Expression::Literal(Literal::Integer(1))));
+        assert_eq!(projection.limit, Some(Expression::Literal(Literal::Integer(10))));
+      }
+      clause => panic!("Expected a RETURN, found {:?}", clause),
+    }
+  }
+}
+
+db_test_fn! {
+  fn test_parse_updating_clauses() {
+    let query = round_trip(r#"
+      MATCH (o:Organization), (c:Organization {pretty_id: $child})
+      CREATE (o)-[:ParentOf {since: 2020}]->(c), (:Organization {org_name: "New \"Org\""})
+      MERGE (c)-[:ChildOf]->(o)
+        ON CREATE SET c.created = true
+        ON MATCH SET c += {seen: 1}, c:Seen
+      SET o.balance = o.balance + 1, o = {org_name: 'Replaced'}
+      REMOVE o.balance, c:Seen:Active
+      DETACH DELETE o, c
+    "#);
+
+    assert!(query.is_updating());
+    assert_eq!(query.columns(), Vec::<String>::new());
+
+    match &query.clauses[2] {
+      Clause::Merge(merge) => {
+        assert_eq!(merge.on_create.len(), 1);
+        assert_eq!(merge.on_match, vec![
+          SetItem::Update(
+            "c".to_string(),
+            Expression::Map(vec![("seen".to_string(), Literal::Integer(1).into())])
+          ),
+          SetItem::Labels("c".to_string(), vec!["Seen".to_string()]),
+        ]);
+      }
+      clause => panic!("Expected a MERGE, found {}", clause),
+    }
+
+    match query.clauses.last() {
+      Some(Clause::Delete(delete)) => {
+        assert!(delete.detach);
+        assert_eq!(delete.items, vec![Expression::variable("o"), Expression::variable("c")]);
+      }
+      clause => panic!("Expected a DELETE, found {:?}", clause),
+    }
+  }
+}
+
+db_test_fn! {
+  fn test_parse_errors() {
+    // The error points at the start of the unexpected token
+    let err = GQuery::parse("MATCH (o:Organization)\nRETURN o\nLIMT 5").expect_err("Misspelled LIMIT");
+    assert!(err.is(Kind::ParsingError));
+    let message = err.to_string();
+    assert!(message.contains("line 3, column 1"), "Wrong position: {}", message);
+    assert!(message.contains("3:1-3:5"), "Wrong span: {}", message);
+
+    // Clause order is checked after parsing
+    for query in [
+      "",
+      "MATCH (o)",
+      "RETURN 1 MATCH (o) RETURN o",
+      "MATCH (o) WITH o.name RETURN 1",
+      "CREATE (a)-->(b)",
+      "CREATE (a)-[:ParentOf]-(b)",
+      "MERGE (a)-[:ParentOf*]->(b)",
+      "MATCH p = shortestPath((a)-[:ParentOf]->(b)) RETURN p",
+      "MATCH p = allShortestPaths((a)-[*2..]->(b)) RETURN p",
+      "MATCH (o) RETURN o SKIP",
+    ] {
+      let result = GQuery::parse(query);
+      assert!(
+        matches!(&result, Err(err) if err.is(Kind::ParsingError)),
+        "Expected {:?} to fail, but got {:?}", query, result
+      );
+    }
+  }
+}
+
+db_test_fn! {
+  fn test_print_dialects() {
+    // OpenCypher prints the same text as Display, so it parses back into the same query
+    let query = round_trip(r#"
+      MATCH p = (o:Organization:Root)-[r:ParentOf*..2 {active: true}]->(c)
+      WHERE NOT (c)<--() AND o.name STARTS WITH $prefix
+      UNWIND [1, 2] AS x
+      MERGE (o)-[:Paid]->(:Invoice {amount: x}) ON CREATE SET o.paid = -(x + 1) * 2
+      SET c:Child
+      RETURN o, count(*) AS total ORDER BY total DESC
+    "#);
+    let printed = Dialect::Cypher.print(&query).unwrap();
+    assert_eq!(printed, query.to_string());
+    assert_eq!(GQuery::parse(&printed).unwrap(), query);
+
+    // GQL inserts, joins labels with '&', and quantifies edges after the arrow
+    let query = round_trip(
+      "MATCH (o:Organization:Root)-[:ParentOf*2..]->(c)<-[*]-(d) CREATE (c)-[:Seen]->(:Log) RETURN d",
+    );
+    assert_eq!(
+      query.to_dialect(Dialect::Gql).unwrap(),
+      "MATCH (o:Organization&Root)-[:ParentOf]->{2,}(c)<--{1,}(d) INSERT (c)-[:Seen]->(:Log) RETURN d"
+    );
+
+    // Shortest paths wrap the chain in OpenCypher, and prefix it in GQL
+    let query = round_trip(
+      "MATCH p = shortestPath((a {id: 1})-[:ParentOf*]->(b)), q = allShortestPaths((a)-[*..3]-(c)) RETURN p, q",
+    );
+    assert_eq!(
+      query.to_dialect(Dialect::Gql).unwrap(),
+      "MATCH p = ANY SHORTEST (a {id: 1})-[:ParentOf]->{1,}(b), q = ALL SHORTEST (a)--{1,3}(c) RETURN p, q"
+    );
+
+    // RedisGraph only differs by what it can't do
+    let query = round_trip("MATCH (o) SET o.name = 'a' REMOVE o.old RETURN o");
+    assert_eq!(query.to_dialect(Dialect::RedisGraph).unwrap(), query.to_string());
+
+    for (dialect, query) in [
+      (Dialect::Gql, "MERGE (o {id: 1})"),
+      (Dialect::Gql, "UNWIND [1] AS x RETURN x"),
+      (Dialect::RedisGraph, "MATCH (o) SET o:Root"),
+      (Dialect::RedisGraph, "MATCH (o) REMOVE o:Root"),
+    ] {
+      let err = dialect.print(&round_trip(query)).expect_err(query);
+      assert!(err.is(Kind::GrammarError), "Wrong error for {:?}: {}", query, err);
+    }
+  }
+}
+
+db_test_fn! {
+  fn test_bind_params() {
+    let query = round_trip("MATCH (o {pretty_id: $id}) WHERE o.balance > $min RETURN o LIMIT $limit");
+    let params = Params::new()
+      .set("id", "it's")
+      .set("min", rust_decimal::Decimal::new(25, 1))
+      .set("limit", 10);
+
+    // Binding replaces each parameter with its escaped value
+    let bound = query.bind(&params).unwrap();
+    assert_eq!(
+      bound.to_string(),
+      r"MATCH (o {pretty_id: 'it\'s'}) WHERE o.balance > 2.5 RETURN o LIMIT 10"
+    );
+    assert_eq!(Dialect::Cypher.print_with(&query, &params).unwrap(), bound.to_string());
+
+    // RedisGraph takes the values in a header, leaving the query text alone
+    assert_eq!(
+      Dialect::RedisGraph.print_with(&query, &params).unwrap(),
+      format!(r"CYPHER id='it\'s' limit=10 min=2.5 {}", query)
+    );
+
+    let err = query.bind(&Params::new().set("id", "root")).expect_err("Bound a missing param");
+    assert!(err.is(Kind::NotSet), "Wrong error for a missing param: {}", err);
+  }
+}
@@ -8,6 +8,8 @@ use common::invoicer::*;
 
 // use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::ops::Bound;
+use uuid::Uuid;
 use tracing::{debug, info};
 
 /*
@@ -207,6 +209,491 @@ db_test_fn! {
   }
 }
 
+db_test_fn! {
+  fn test_incoming_edges() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let mut root = node!(FhlGraph, Organization, "root", "Root", dec!(0));
+    let heir = node!(FhlGraph, Organization, "heir", "Heir", dec!(0));
+    let mut spare = node!(FhlGraph, Organization, "spare", "Spare", dec!(0));
+
+    // Unbound targets know about the edges pointing at them straight away
+    let parent_of = root.create_edge(edge!(FhlEdgeType::ParentOf), heir.clone()).expect("Could not create the edge");
+    root.create_edge(edge!(FhlEdgeType::ParentOf), spare.clone()).expect("Could not create the edge");
+    assert_eq!(heir.incoming(None), vec![parent_of.clone()]);
+    data_set.insert(root.into()).expect("Failed to insert the root");
+
+    // Inserting an edge between stored nodes updates the stored target
+    let child_of = spare.create_edge(edge!(FhlEdgeType::ChildOf), heir.clone()).expect("Could not create the edge");
+    data_set.insert(child_of.clone().into()).expect("Failed to insert the edge");
+
+    let stored = data_set.get_node(&heir.get_guid()).expect("Missing the heir");
+    assert_eq!(stored.incoming(Some("ChildOf")), vec![child_of.clone()]);
+    assert_eq!(stored.incoming(Some("ParentOf")), vec![parent_of]);
+    assert_eq!(data_set.in_edges(&heir.get_guid()).len(), 2);
+
+    // And deleting the edge removes it from both
+    data_set.query_mut("MATCH ()-[e:ChildOf]->() DELETE e").expect("Failed to delete the edge");
+    assert!(stored.incoming(Some("ChildOf")).is_empty());
+    assert_eq!(data_set.in_edges(&heir.get_guid()).len(), 1);
+  }
+}
+
+db_test_fn! {
+  fn test_update_and_delete() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let mut root = node!(FhlGraph, Organization, "root", "Root", dec!(0));
+    let heir = node!(FhlGraph, Organization, "heir", "Heir", dec!(0));
+    let edge = root.create_edge(edge!(FhlEdgeType::ParentOf), heir.clone()).expect("Could not create the edge");
+    data_set.insert(root.clone().into()).expect("Failed to insert the root");
+
+    // A new version of the node is matched on its key, and moves between the label indices
+    let mut renamed = node!(FhlGraph, Organization, "root", "Renamed Root", dec!(10));
+    renamed.add_label("RootOrganization");
+    let stats = data_set.update(renamed.into()).expect("Failed to update the root");
+    let updated = stats.updated().expect("Missing the updated stats");
+    assert_eq!(updated.nodes.total.count(), 1);
+    assert_eq!(updated.nodes.labels.get(&"RootOrganization".to_string()).map(|x| x.count()), Some(1));
+    assert_eq!(data_set.nodes("MATCH (o:RootOrganization) RETURN o").expect("Query failed").len(), 1);
+    let stored = data_set.get_node(&root.get_guid()).expect("Missing the root");
+    assert_eq!(stored.get_property("balance").expect("Missing the balance"), Literal::from(10));
+
+    // Edges are swapped everywhere they are held
+    data_set.update(edge.clone().with_weight(2.5).into()).expect("Failed to update the edge");
+    assert_eq!(stored.edges("")[0].get_weight(), Some(2.5));
+    assert_eq!(data_set.in_edges(&heir.get_guid())[0].get_weight(), Some(2.5));
+
+    // Things that aren't stored can't be updated
+    let stranger = node!(FhlGraph, Organization, "stranger", "Stranger", dec!(0));
+    let err = data_set.update(stranger.into()).expect_err("Updated a missing node");
+    assert!(err.is(Kind::NotFound));
+
+    // Nodes with edges need to be detached
+    let err = data_set.delete(heir.clone().into(), false).expect_err("Left a dangling edge");
+    assert!(err.is(Kind::InvalidItem));
+    let stats = data_set.delete(heir.clone().into(), true).expect("Failed to delete the heir");
+    let deleted = stats.deleted().expect("Missing the deleted stats");
+    assert_eq!((deleted.nodes.total.count(), deleted.edges.total.count()), (1, 1));
+    assert!(stored.edges("").is_empty());
+    assert_eq!(data_set.stats().nodes.total.count(), 1);
+    assert_eq!(data_set.stats().edges.total.count(), 0);
+  }
+}
+
+db_test_fn! {
+  fn test_reinsert() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let mut root = node!(FhlGraph, Organization, "root", "Root", dec!(0));
+    let heir = node!(FhlGraph, Organization, "heir", "Heir", dec!(0));
+    root.create_edge(edge!(FhlEdgeType::ParentOf), heir.clone()).expect("Could not create the edge");
+    data_set.insert(root.clone().into()).expect("Failed to insert the root");
+
+    // The same data again only reads the root and its edge
+    let stats = data_set.insert(root.clone().into()).expect("Failed to insert the root again");
+    assert!(stats.created().is_none() && stats.updated().is_none());
+    let read = stats.read().expect("Missing the read stats");
+    assert_eq!((read.nodes.total.count(), read.edges.total.count()), (1, 1));
+
+    // A refreshed record replaces the stale one, and new edges are still added
+    let mut refreshed = node!(FhlGraph, Organization, "root", "Root", dec!(25));
+    refreshed.create_edge(edge!(FhlEdgeType::ChildOf), heir.clone()).expect("Could not create the edge");
+    let stats = data_set.insert(refreshed.into()).expect("Failed to insert the refreshed root");
+    let updated = stats.updated().expect("Missing the updated stats");
+    assert_eq!(updated.nodes.properties.get(&"balance".to_string()).map(|x| x.count()), Some(1));
+    assert_eq!(stats.created().expect("Missing the created stats").edges.total.count(), 1);
+
+    let stored = data_set.get_node(&root.get_guid()).expect("Missing the root");
+    assert_eq!(stored.get_property("balance").expect("Missing the balance"), Literal::from(25));
+    assert_eq!(stored.edges("").len(), 2);
+  }
+}
+
+db_test_fn! {
+  fn test_edit_nodes() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let mut root = node!(FhlGraph, Organization, "root", "Root", dec!(0));
+    let heir = node!(FhlGraph, Organization, "heir", "Heir", dec!(0));
+    let parent_of = root.create_edge(edge!(FhlEdgeType::ParentOf), heir.clone()).expect("Could not create the edge");
+    data_set.insert(root.clone().into()).expect("Failed to insert the root");
+    let stored = data_set.get_node(&root.get_guid()).expect("Missing the root");
+
+    // Dropping the handle throws the changes away
+    {
+      let mut edit = data_set.get_mut(&root.get_guid()).expect("Could not edit the root");
+      edit.add_label("Abandoned");
+      assert!(edit.node().has_label("Abandoned"));
+    }
+    assert!(!stored.has_label("Abandoned"));
+
+    // Committing pushes every change in at once
+    let spare = node!(FhlGraph, Organization, "spare", "Spare", dec!(0));
+    let mut edit = data_set.get_mut(&root.get_guid()).expect("Could not edit the root");
+    edit.set_props(Organization::new("root", "Root", dec!(5)).into()).expect("Could not set the props");
+    edit.add_label("RootOrganization");
+    assert!(edit.remove_edge(&parent_of.get_guid()));
+    edit.create_edge(edge!(FhlEdgeType::ParentOf), spare.clone()).expect("Could not create the edge");
+    let stats = edit.commit().expect("Failed to commit the edit");
+
+    assert_eq!(stats.updated().expect("Missing the updated stats").nodes.total.count(), 1);
+    assert_eq!(stats.created().expect("Missing the created stats").nodes.total.count(), 1);
+    assert_eq!(stats.deleted().expect("Missing the deleted stats").edges.total.count(), 1);
+    assert!(stored.has_label("RootOrganization"));
+    assert_eq!(stored.get_property("balance").expect("Missing the balance"), Literal::from(5));
+    assert!(data_set.in_edges(&heir.get_guid()).is_empty());
+    assert_eq!(data_set.in_edges(&spare.get_guid()).len(), 1);
+
+    // Keys can't change
+    let mut edit = data_set.get_mut(&root.get_guid()).expect("Could not edit the root");
+    let err = edit.set_props(Organization::new("other", "Root", dec!(5)).into()).expect_err("Changed the key");
+    assert!(err.is(Kind::InvalidItem));
+  }
+}
+
+db_test_fn! {
+  fn test_multi_edges() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let mut payer = node!(FhlGraph, Organization, "payer", "Payer", dec!(0));
+    let payee = node!(FhlGraph, Organization, "payee", "Payee", dec!(0));
+
+    // Each payment between the same two orgs is its own edge
+    for amount in [dec!(10), dec!(20), dec!(30)] {
+      payer.create_edge(edge!(FhlEdgeType::Paid, amount), payee.clone()).expect("Could not add a payment");
+    }
+    let err = payer.create_edge(edge!(FhlEdgeType::Paid, dec!(10)), payee.clone()).expect_err("Paid twice");
+    assert!(err.is(Kind::DuplicateKey));
+    assert_eq!(payer.edges("").len(), 3);
+    assert_eq!(payer.edges_to(&payee.get_guid(), Some("Paid")).len(), 3);
+    assert_eq!(payer.edges_to(&payee.get_guid(), Some("ParentOf")).len(), 0);
+
+    // An EdgeMap holds them side by side too
+    let mut edges = EdgeMap::new();
+    for edge in payer.edges("Paid") {
+      edges.insert(edge.clone()).expect("Could not map a payment");
+    }
+    assert_eq!(edges.find_to("Paid", &payee.get_guid()).len(), 3);
+    let edge = edges.find_all("Paid").remove(0);
+    assert!(edges.insert(edge.clone()).expect_err("Mapped twice").is(Kind::DuplicateKey));
+    assert!(edges.remove(&edge.get_guid()).is_some());
+    assert_eq!(edges.all().len(), 2);
+
+    data_set.insert(payer.clone().into()).expect("Failed to insert the payer");
+    assert_eq!(data_set.stats().edges.typed.get(&"Paid".to_string()).map(|x| x.count()), Some(3));
+    assert_eq!(data_set.in_edges(&payee.get_guid()).len(), 3);
+    assert_eq!(data_set.edges_between(&payer.get_guid(), &payee.get_guid(), Some("Paid")).len(), 3);
+    assert_eq!(data_set.edges_between(&payee.get_guid(), &payer.get_guid(), None).len(), 0);
+
+    let result = data_set
+      .query("MATCH (:Organization {pretty_id: 'payer'})-[p:Paid]->(o) RETURN sum(p.amount) AS total, count(DISTINCT o) AS payees")
+      .expect("Query failed");
+    assert_eq!(result.get("total"), Some(&vec![Value::Literal(60.into())]));
+    assert_eq!(result.get("payees"), Some(&vec![Value::Literal(1.into())]));
+
+    // And every one of them is written out
+    let query = payer.to_create(None).expect("Could not print the payer");
+    assert_eq!(query.matches(":Paid").count(), 3);
+
+    // Deleting one payment leaves the others in place
+    let first = data_set.edges_between(&payer.get_guid(), &payee.get_guid(), None)[0].get_guid();
+    data_set.delete_edge(&first).expect("Failed to delete a payment");
+    assert_eq!(data_set.edges_between(&payer.get_guid(), &payee.get_guid(), None).len(), 2);
+    assert_eq!(data_set.get_node(&payer.get_guid()).unwrap().edges_to(&payee.get_guid(), None).len(), 2);
+  }
+}
+
+db_test_fn! {
+  fn test_two_way_edges() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    let root = node!(FhlGraph, Organization, "root", "Root", dec!(0));
+    let heir = node!(FhlGraph, Organization, "heir", "Heir", dec!(0));
+    let count = |data_set: &DataSet<FhlGraph>, query: &str| {
+      let result = data_set.query(query).expect("Query failed");
+      result.get("total").cloned().expect("Missing the total")
+    };
+
+    // Storing a TwoWay edge stores the edge going back, with its own weight
+    let edge = Edge::new(&root, &heir, edge!(FhlEdgeType::ParentOf)).expect("Invalid edge").with_weight(1.0).with_reverse_weight(3.0);
+    let reciprocal = edge.reciprocal().expect("Missing the reciprocal");
+    let stats = data_set.insert(edge.clone().into()).expect("Failed to insert the edge");
+    assert_eq!(stats.created().expect("Missing the created stats").edges.total.count(), 2);
+    let stored = data_set.get_node(&heir.get_guid()).expect("Missing the heir");
+    assert_eq!(stored.edges("").iter().map(|x| x.get_weight()).collect::<Vec<_>>(), vec![Some(3.0)]);
+
+    // Undirected patterns follow each edge forwards, so the pair only matches once each way
+    assert_eq!(count(&data_set, "MATCH (a)-[e]-(b) RETURN count(e) AS total"), vec![Value::Literal(2.into())]);
+    assert_eq!(
+      count(&data_set, "MATCH (:Organization {pretty_id: 'heir'})-[e]-(b) RETURN count(b) AS total"),
+      vec![Value::Literal(1.into())]
+    );
+
+    // Changes to the edge are copied to its reciprocal
+    data_set.update(edge.clone().with_reverse_weight(5.0).into()).expect("Failed to update the edge");
+    assert_eq!(data_set.get_node(&heir.get_guid()).expect("Missing the heir").edges("")[0].get_weight(), Some(5.0));
+    data_set.update(Edge::new(&root, &heir, edge!(FhlEdgeType::ParentOf)).expect("Invalid edge").into()).expect("Failed to update the edge");
+    assert_eq!(data_set.stats().edges.total.count(), 1);
+
+    // And deleting either edge of the pair deletes both
+    data_set.update(edge.into()).expect("Failed to update the edge");
+    assert_eq!(data_set.stats().edges.total.count(), 2);
+    data_set.delete(reciprocal.into(), false).expect("Failed to delete the reciprocal");
+    assert_eq!(data_set.stats().edges.total.count(), 0);
+  }
+}
+
+db_test_fn! {
+  fn test_indices() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    for (id, balance) in [("a", dec!(30)), ("b", dec!(10)), ("c", dec!(20)), ("d", dec!(10))] {
+      data_set.insert(node!(FhlGraph, Organization, id, id, balance).into()).expect("Failed to insert the org");
+    }
+    let ids = |values: Vec<Value<FhlGraph>>| {
+      values.iter().map(|x| match x {
+        Value::Node(node) => node.get_property("pretty_id").expect("Missing the pretty_id").to_string(),
+        _ => panic!("Expected a node, found {:?}", x),
+      }).collect::<Vec<_>>()
+    };
+
+    // Declared indices are filled from what is already stored
+    let by_id = Index::Property("Organization".to_string(), "pretty_id".to_string());
+    let by_balance = Index::Sorted("Organization".to_string(), "balance".to_string());
+    data_set.create_index(by_id.clone()).expect("Failed to create the property index");
+    data_set.create_index(by_balance.clone()).expect("Failed to create the sorted index");
+    let err = data_set.create_index(by_id.clone()).expect_err("Created the index twice");
+    assert!(err.is(Kind::DuplicateKey));
+
+    let lookup = data_set.get_index(&by_id).expect("Missing the property index");
+    assert_eq!(ids(lookup.find(&Literal::from("c"))), vec!["'c'"]);
+    let lookup = data_set.get_index(&by_balance).expect("Missing the sorted index");
+    let lower = Literal::from(10);
+    let range = lookup.range(Bound::Excluded(&lower), Bound::Unbounded).expect("Failed to read the range");
+    assert_eq!(ids(range), vec!["'c'", "'a'"]);
+    assert_eq!(lookup.find(&lower).len(), 2);
+
+    // Inserts, updates, and deletes are kept in step
+    data_set.insert(node!(FhlGraph, Organization, "e", "e", dec!(5)).into()).expect("Failed to insert the org");
+    data_set.update(node!(FhlGraph, Organization, "b", "b", dec!(50)).into()).expect("Failed to update the org");
+    data_set.delete(node!(FhlGraph, Organization, "a", "a", dec!(30)).into(), false).expect("Failed to delete the org");
+    let lookup = data_set.get_index(&by_balance).expect("Missing the sorted index");
+    let range = lookup.range(Bound::Unbounded, Bound::Unbounded).expect("Failed to read the range");
+    assert_eq!(ids(range), vec!["'e'", "'d'", "'c'", "'b'"]);
+
+    // Tags are only filled by hand, and forget deleted values
+    let d = node!(FhlGraph, Organization, "d", "d", dec!(10));
+    data_set.tag("flagged", &d.get_guid()).expect("Failed to tag the org");
+    let flagged = data_set.get_index(&Index::Tag("flagged".to_string())).expect("Missing the tag");
+    assert_eq!(ids(flagged.all()), vec!["'d'"]);
+    data_set.delete(d.into(), false).expect("Failed to delete the org");
+
+    let stats = data_set.stats().indices;
+    assert_eq!(stats.total.count(), 3);
+    assert_eq!(stats.entries.get(&by_id.to_string()).map(|x| x.count()), Some(3));
+    assert_eq!(stats.entries.get(&"tag:flagged".to_string()).map(|x| x.count()), Some(0));
+
+    // Matching on an indexed property seeks it rather than scanning the label
+    let plan = data_set.explain("MATCH (o:Organization {pretty_id: $id}) RETURN o").expect("Failed to explain");
+    assert!(plan.to_string().contains("Node Index Seek"), "Plan didn't use the index:\n{}", plan);
+    let result = data_set
+      .query_with("MATCH (o:Organization {pretty_id: $id}) RETURN o.balance AS balance", &Params::new().set("id", "b"))
+      .expect("Query failed");
+    assert_eq!(result.get("balance"), Some(&vec![Value::Literal(50.into())]));
+
+    // Index stats can be changed through the generic Stats trait, and read from a stats string
+    let mut stats = IndexStats::new();
+    stats.increase(IndexStat::Total(2));
+    stats.increase(IndexStat::Entries("tag:big".to_string(), 3));
+    assert_eq!(stats.total.count(), 2);
+    assert_eq!(stats.entries.get(&"tag:big".to_string()).map(|x| x.count()), Some(3));
+    let parsed = DataSetStats::from("indices: { total: 2, memory: 64, entries: { tag:big: 3 } }");
+    assert_eq!(parsed.indices.total.count(), 2);
+    assert_eq!(parsed.indices.memory.count(), 64);
+  }
+}
+
+db_test_fn! {
+  fn test_lazy_indices() {
+    let mut data_set: DataSet<FhlGraph> = DataSet::new();
+    for id in ["a", "b", "c", "d"] {
+      data_set.insert(node!(FhlGraph, Organization, id, &id.to_uppercase(), dec!(0)).into()).expect("Failed to insert the org");
+    }
+    let by_id = Index::Property("Organization".to_string(), "pretty_id".to_string());
+    let by_name = Index::Property("Organization".to_string(), "org_name".to_string());
+    let count = |stats: &StatMap<String, StatCount, i128>, index: &Index| stats.get(&index.to_string()).map(|x| x.count());
+    let find = |data_set: &DataSet<FhlGraph>, query: &str| {
+      let result = data_set.query(query).expect("Query failed");
+      result.get("id").cloned().expect("Missing the id column")
+    };
+
+    // Declaring a lazy index doesn't build it
+    data_set.create_lazy_index(by_id.clone()).expect("Failed to declare the index");
+    data_set.create_lazy_index(by_name.clone()).expect("Failed to declare the index");
+    let stats = data_set.stats().indices;
+    assert_eq!(stats.total.count(), 2);
+    assert_eq!(count(&stats.built, &by_id), None);
+
+    // Until a query seeks from it
+    assert_eq!(find(&data_set, "MATCH (o:Organization {pretty_id: 'b'}) RETURN o.pretty_id AS id"), vec![Value::Literal("b".into())]);
+    let stats = data_set.stats().indices;
+    assert_eq!(count(&stats.built, &by_id), Some(1));
+    assert_eq!(count(&stats.entries, &by_id), Some(4));
+
+    // Once built it is kept in step
+    data_set.insert(node!(FhlGraph, Organization, "e", "E", dec!(0)).into()).expect("Failed to insert the org");
+    assert_eq!(find(&data_set, "MATCH (o:Organization {pretty_id: 'e'}) RETURN o.pretty_id AS id"), vec![Value::Literal("e".into())]);
+    assert_eq!(count(&data_set.stats().indices.built, &by_id), Some(1));
+
+    // Only one fits in the budget, so building another drops the least recently used
+    data_set.set_index_budget(Some(data_set.stats().indices.memory.count() as usize));
+    assert_eq!(find(&data_set, "MATCH (o:Organization {org_name: 'C'}) RETURN o.pretty_id AS id"), vec![Value::Literal("c".into())]);
+    let stats = data_set.stats().indices;
+    assert_eq!(count(&stats.built, &by_name), Some(1));
+    assert_eq!(count(&stats.dropped, &by_id), Some(1));
+    assert_eq!(count(&stats.entries, &by_id), None);
+
+    // And the next query that needs it builds it again
+    assert_eq!(find(&data_set, "MATCH (o:Organization {pretty_id: 'a'}) RETURN o.pretty_id AS id"), vec![Value::Literal("a".into())]);
+    let stats = data_set.stats().indices;
+    assert_eq!(count(&stats.built, &by_id), Some(2));
+    assert_eq!(count(&stats.dropped, &by_name), Some(1));
+  }
+}
+
+/// The invoicer graph with a unique pretty_id and a required org_name on each Organization
+#[derive(Debug, Clone, PartialEq)]
+struct ConstrainedGraph;
+
+impl Graph for ConstrainedGraph {
+  type Node = FhlNode;
+  type Edge = FhlEdge;
+
+  fn constraints() -> Vec<Constraint> {
+    vec![Constraint::unique("Organization", "pretty_id"), Constraint::exists("Organization", "org_name")]
+  }
+}
+
+db_test_fn! {
+  fn test_constraints() {
+    let mut data_set: DataSet<ConstrainedGraph> = DataSet::new();
+    let root = node!(ConstrainedGraph, Organization, "root", "Root", dec!(0));
+    data_set.insert(root.clone().into()).expect("Failed to insert the root");
+
+    // A second org with the same pretty_id is rejected, naming both nodes
+    let mut twin = Organization::new("root", "Twin", dec!(0));
+    twin.guid = Uuid::new_v4();
+    let twin = Node::<ConstrainedGraph>::new(twin.into());
+    let err = data_set.insert(twin.clone().into()).expect_err("Inserted a duplicate pretty_id");
+    let violation = err.violation().expect("Expected a constraint violation");
+    assert_eq!(violation.constraint, Constraint::unique("Organization", "pretty_id"));
+    assert_eq!(violation.guids, vec![twin.get_guid(), root.get_guid()]);
+    assert_eq!(data_set.stats().nodes.total.count(), 1);
+
+    // A value is checked as a whole, so nothing in a rejected list is stored
+    let fresh = node!(ConstrainedGraph, Organization, "fresh", "Fresh", dec!(0));
+    let values = Value::List(vec![twin.clone().into(), fresh.clone().into()]);
+    assert!(data_set.insert(values).expect_err("Inserted a duplicate pretty_id").violation().is_some());
+    let mut copy = Organization::new("fresh", "Copy", dec!(0));
+    copy.guid = Uuid::new_v4();
+    let values = Value::List(vec![fresh.clone().into(), Node::<ConstrainedGraph>::new(copy.into()).into()]);
+    assert!(data_set.insert(values).expect_err("Inserted two orgs sharing a pretty_id").violation().is_some());
+    assert!(!data_set.contains(&fresh.get_guid()));
+    assert_eq!(data_set.stats().nodes.total.count(), 1);
+
+    // Updates are checked too, and a rejected update leaves the stored node alone
+    let mut other = Organization::new("other", "Other", dec!(0));
+    data_set.insert(Node::<ConstrainedGraph>::new(other.clone().into()).into()).expect("Failed to insert the other org");
+    other.pretty_id = "root".to_string();
+    let err = data_set.update(Node::<ConstrainedGraph>::new(other.clone().into()).into()).expect_err("Updated to a duplicate pretty_id");
+    assert_eq!(err.violation().map(|x| x.guids.clone()), Some(vec![other.guid, root.get_guid()]));
+    let stored = data_set.get_node(&other.guid).expect("Missing the other org");
+    assert_eq!(stored.get_property("pretty_id").expect("Missing the pretty_id"), Literal::from("other"));
+
+    // Once the root is gone its pretty_id is free again
+    data_set.delete(root.into(), false).expect("Failed to delete the root");
+    data_set.update(Node::<ConstrainedGraph>::new(other.into()).into()).expect("Failed to take over the pretty_id");
+
+    // A commit that fails part of the way through, on a new node an added edge leads to, leaves the
+    // DataSet as it was
+    let mut parent = node!(ConstrainedGraph, Organization, "parent", "Parent", dec!(0));
+    let child = node!(ConstrainedGraph, Organization, "child", "Child", dec!(0));
+    let parent_of = parent.create_edge(edge!(FhlEdgeType::ParentOf), child).expect("Could not create the edge");
+    data_set.insert(parent.clone().into()).expect("Failed to insert the parent");
+    let mut clash = Organization::new("child", "Clash", dec!(0));
+    clash.guid = Uuid::new_v4();
+
+    let mut edit = data_set.get_mut(&parent.get_guid()).expect("Could not edit the parent");
+    edit.set_props(Organization::new("parent", "Parent", dec!(5)).into()).expect("Could not set the props");
+    edit.add_label("Adopting");
+    assert!(edit.remove_edge(&parent_of.get_guid()));
+    edit.create_edge(edge!(FhlEdgeType::ParentOf), Node::new(clash.into())).expect("Could not create the edge");
+    assert!(edit.commit().expect_err("Committed a duplicate pretty_id").violation().is_some());
+
+    let stored = data_set.get_node(&parent.get_guid()).expect("Missing the parent");
+    assert!(!stored.has_label("Adopting"));
+    assert_eq!(stored.get_property("balance").expect("Missing the balance"), Literal::from(0));
+    assert!(data_set.contains(&parent_of.get_guid()));
+    assert_eq!(data_set.stats().nodes.total.count(), 3);
+    assert_eq!(data_set.stats().edges.total.count(), 1);
+  }
+}
+
+/// The invoicer graph with a schema: an org has at most one parent, payments need a memo, and
+/// only people can be parents
+#[derive(Debug, Clone, PartialEq)]
+struct SchemaGraph;
+
+impl Graph for SchemaGraph {
+  type Node = FhlNode;
+  type Edge = FhlEdge;
+
+  fn schema() -> Option<Schema> {
+    Some(
+      Schema::new()
+        .node("Organization", &["pretty_id", "org_name"])
+        .edge(EdgeRule::new("ChildOf").connects("Organization", "Organization").max_out(1))
+        .edge(EdgeRule::new("ParentOf").connects("Person", "Organization"))
+        .edge(EdgeRule::new("Paid").required(&["memo"])),
+    )
+  }
+}
+
+db_test_fn! {
+  fn test_schema() {
+    let mut data_set: DataSet<SchemaGraph> = DataSet::new();
+    let root = node!(SchemaGraph, Organization, "root", "Root", dec!(0));
+    let other = node!(SchemaGraph, Organization, "other", "Other", dec!(0));
+    let mut heir = node!(SchemaGraph, Organization, "heir", "Heir", dec!(0));
+
+    // Edges are checked as they are built
+    let err = Edge::new(&root, &heir, edge!(FhlEdgeType::ParentOf)).expect_err("Built a ParentOf between orgs");
+    assert!(err.is(Kind::InvalidItem));
+    let err = Edge::new(&root, &heir, edge!(FhlEdgeType::Paid, dec!(10))).expect_err("Built a Paid without a memo");
+    assert!(err.is(Kind::InvalidItem));
+
+    // A node starting more edges than allowed is rejected before anything is stored
+    let mut orphan = node!(SchemaGraph, Organization, "orphan", "Orphan", dec!(0));
+    orphan.create_edge(edge!(FhlEdgeType::ChildOf), root.clone()).expect("Failed to add a parent");
+    orphan.create_edge(edge!(FhlEdgeType::ChildOf), other.clone()).expect("Failed to add a parent");
+    let err = data_set.insert(orphan.into()).expect_err("Inserted an org with two parents");
+    assert!(err.is(Kind::InvalidItem));
+    assert_eq!(data_set.stats().nodes.total.count(), 0);
+    assert_eq!(data_set.stats().edges.total.count(), 0);
+    assert!(data_set.nodes("MATCH (o:Organization) RETURN o").expect("Query failed").is_empty());
+
+    // And so is an edge that would give a stored node one too many
+    let parent = heir.create_edge(edge!(FhlEdgeType::ChildOf), root.clone()).expect("Failed to add a parent");
+    data_set.insert(heir.clone().into()).expect("Failed to insert the heir");
+    let second = Edge::new(&heir, &other, edge!(FhlEdgeType::ChildOf)).expect("Failed to build the edge");
+    let err = data_set.insert(second.into()).expect_err("Inserted a second parent");
+    assert!(err.is(Kind::InvalidItem));
+    assert_eq!(data_set.stats().edges.total.count(), 1);
+
+    // Swapping the one parent for another fits, since the removed edge no longer counts
+    let mut edit = data_set.get_mut(&heir.get_guid()).expect("Could not edit the heir");
+    assert!(edit.remove_edge(&parent.get_guid()));
+    edit.create_edge(edge!(FhlEdgeType::ChildOf), other.clone()).expect("Failed to add a parent");
+    edit.commit().expect("Failed to swap the parent");
+    assert_eq!(data_set.edges_between(&heir.get_guid(), &other.get_guid(), None).len(), 1);
+    assert!(!data_set.contains(&parent.get_guid()));
+  }
+}
+
 db_test_fn! {
   fn test_insert() {
 
@@ -0,0 +1,117 @@
+//! Test entities built with #[derive(GraphtEntity)]
+
+use grapht::prelude::*;
+
+#[macro_use]
+mod common;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, GraphtEntity)]
+#[grapht(label = "Organization", key_from(pretty_id))]
+struct Organization {
+  pretty_id: String,
+  #[grapht(rename = "name")]
+  org_name: String,
+  balance: Decimal,
+  parent: Option<String>,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, GraphtEntity)]
+enum Party {
+  Organization(Organization),
+  Person {
+    #[grapht(key)]
+    guid: Uuid,
+    full_name: String,
+  },
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, GraphtEntity)]
+enum Link {
+  ParentOf,
+  ChildOf,
+  #[grapht(label = "PAID")]
+  Paid(#[grapht(rename = "amount")] Decimal),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DerivedGraph;
+
+impl Graph for DerivedGraph {
+  type Node = Party;
+  type Edge = Link;
+}
+
+fn org(pretty_id: &str, balance: Decimal) -> Organization {
+  Organization {
+    pretty_id: pretty_id.to_string(),
+    org_name: format!("The {} org", pretty_id),
+    balance,
+    parent: None,
+  }
+}
+
+db_test_fn! {
+  fn test_derive_entity() {
+    // Labels, keys and properties of a struct
+    let acme = org("acme", dec!(10.5));
+    assert_eq!(acme.get_type_label(), "Organization");
+    assert_eq!(acme.get_key(), Uuid::new_v5(&Uuid::NAMESPACE_OID, b"acme"));
+    assert_eq!(acme.get_property("name").unwrap(), Literal::from("The acme org"));
+    assert_eq!(acme.get_property("org_name").unwrap(), Literal::Null);
+    assert_eq!(acme.to_gql().unwrap(), "{balance: 10.5, name: 'The acme org', parent: null, pretty_id: 'acme'}");
+
+    // The GQL reads back into the same value, and missing options are None
+    assert_eq!(Organization::from_gql(acme.to_gql().unwrap().as_bytes()).unwrap(), acme);
+    let parsed = Organization::from_gql(b"{pretty_id: 'acme', name: 'The acme org', balance: 10.5}").unwrap();
+    assert_eq!(parsed, acme);
+    let err = Organization::from_gql(b"{pretty_id: 'acme', name: 3, balance: 1}").expect_err("Read a number as a name");
+    assert!(err.is(Kind::TypeMismatch));
+
+    // Wrapped variants take the inner entity's key and properties
+    let node = Party::Organization(acme.clone());
+    assert_eq!(node.get_key(), acme.get_key());
+    assert_eq!(node.get_properties().unwrap(), acme.get_properties().unwrap());
+
+    let guid = Uuid::new_v4();
+    let person = Party::Person { guid, full_name: "Jane".to_string() };
+    assert_eq!(person.get_type_label(), "Person");
+    assert_eq!(person.get_key(), guid);
+
+    // Enums find the variant that fits the properties, or use the label when given one
+    for node in [node, person.clone()] {
+      assert_eq!(Party::from_gql(node.to_gql().unwrap().as_bytes()).unwrap(), node);
+    }
+    let mut properties = BTreeMap::new();
+    properties.insert("full_name".to_string(), Literal::from("Jane"));
+    properties.insert("guid".to_string(), Literal::from(guid));
+    assert_eq!(Party::from_properties("Person", properties.clone()).unwrap(), person);
+    assert!(Party::from_properties("Robot", properties).unwrap_err().is(Kind::InvalidItem));
+
+    // Unit variants have no properties, so they can only be told apart by their label
+    let paid = Link::Paid(dec!(3));
+    assert_eq!(paid.get_type_label(), "PAID");
+    assert_eq!(paid.to_gql().unwrap(), "{amount: 3}");
+    assert_eq!(Link::from_gql(b"{amount: 3}").unwrap(), paid);
+    assert!(Link::from_gql(b"{}").unwrap_err().is(Kind::AmbiguousMatch));
+    assert_eq!(Link::from_properties("ParentOf", BTreeMap::new()).unwrap(), Link::ParentOf);
+    assert_ne!(paid.get_key(), Link::Paid(dec!(4)).get_key());
+  }
+}
+
+db_test_fn! {
+  fn test_derived_graph() {
+    // Derived entities work as the payload of a graph
+    let mut data_set: DataSet<DerivedGraph> = DataSet::new();
+    let mut child = Node::<DerivedGraph>::new(Party::Organization(org("child", dec!(1))));
+    let parent = Node::<DerivedGraph>::new(Party::Organization(org("parent", dec!(2))));
+    child.create_edge(Link::Paid(dec!(5)), parent.clone()).expect("Failed to add a payment");
+    data_set.insert(child.into()).expect("Failed to insert the nodes");
+    assert_eq!(data_set.stats().nodes.total.count(), 2);
+    assert_eq!(data_set.stats().edges.total.count(), 1);
+  }
+}
@@ -0,0 +1,78 @@
+//! Test parsing GQL into the same AST as OpenCypher
+
+use grapht::{gquery::grammars::Dialect, prelude::*};
+
+#[macro_use]
+mod common;
+
+fn parse_gql(query: &str) -> GQuery {
+  GQuery::from_dialect(query, Dialect::Gql)
+    .unwrap_or_else(|err| panic!("Could not parse {:?}: {}", query, err))
+}
+
+db_test_fn! {
+  fn test_parse_gql() {
+    // The same query in both languages
+    let gql = parse_gql(
+      r#"
+        MATCH (o IS Organization&Root)-[r:ParentOf|ChildOf {active: true}]->{1,3}(c)
+        WHERE c.balance > 1.5 AND NOT (c)<-[:Paid]-()
+        OPTIONAL MATCH p = (c)-[:Paid]-+(x)
+        WITH o, count(x) AS total WHERE total > 0
+        RETURN DISTINCT o.org_name AS name, total ORDER BY name DESC OFFSET 1 LIMIT 10
+      "#,
+    );
+    let cypher = GQuery::parse(
+      r#"
+        MATCH (o:Organization:Root)-[r:ParentOf|ChildOf*1..3 {active: true}]->(c)
+        WHERE c.balance > 1.5 AND NOT (c)<-[:Paid]-()
+        OPTIONAL MATCH p = (c)-[:Paid*1..]-(x)
+        WITH o, count(x) AS total WHERE total > 0
+        RETURN DISTINCT o.org_name AS name, total ORDER BY name DESC SKIP 1 LIMIT 10
+      "#,
+    )
+    .unwrap();
+    assert_eq!(gql, cypher);
+
+    // Updates, and printing back out as GQL
+    let query = "MATCH (o:Organization) INSERT (o)-[:ParentOf]->(:Organization {name: 'child'}) \
+      SET o.seen = true REMOVE o.old DETACH DELETE o";
+    let parsed = parse_gql(query);
+    assert_eq!(parsed.to_dialect(Dialect::Gql).unwrap(), query);
+    assert_eq!(
+      parsed.to_string(),
+      "MATCH (o:Organization) CREATE (o)-[:ParentOf]->(:Organization {name: 'child'}) \
+      SET o.seen = true REMOVE o.old DETACH DELETE o"
+    );
+
+    // Quantifiers
+    for (quantifier, cypher) in [("*", "*0.."), ("{2}", "*2"), ("{,3}", "*0..3"), ("{2,}", "*2..")] {
+      let gql = parse_gql(&format!("MATCH (a)-->{}(b) RETURN b", quantifier));
+      let printed = gql.to_dialect(Dialect::Gql).unwrap();
+      assert_eq!(parse_gql(&printed), gql);
+      assert_eq!(gql, GQuery::parse(&format!("MATCH (a)-[{}]->(b) RETURN b", cypher)).unwrap());
+    }
+
+    // Shortest path searches
+    assert_eq!(
+      parse_gql("MATCH p = ANY SHORTEST (a)-[:ParentOf]->+(b), ALL SHORTEST (a)-->{,4}(c) RETURN p"),
+      GQuery::parse("MATCH p = shortestPath((a)-[:ParentOf*1..]->(b)), allShortestPaths((a)-[*0..4]->(c)) RETURN p")
+        .unwrap()
+    );
+
+    // OpenCypher only clauses, and GQL features that aren't supported yet
+    for query in [
+      "MATCH (o:A:B) RETURN o",
+      "MERGE (o) RETURN o",
+      "CREATE (o)",
+      "MATCH (o) RETURN o GROUP BY o",
+      "MANDATORY MATCH (o) RETURN o",
+    ] {
+      let result = GQuery::from_dialect(query, Dialect::Gql);
+      assert!(
+        matches!(&result, Err(err) if err.is(Kind::ParsingError)),
+        "Expected {:?} to fail, but got {:?}", query, result
+      );
+    }
+  }
+}
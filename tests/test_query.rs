@@ -0,0 +1,618 @@
+//! Test running queries against a DataSet
+
+use grapht::{
+  gquery::ast::*,
+  prelude::*,
+  store::planner::{self, Anchor, Expand},
+};
+
+#[macro_use]
+mod common;
+use common::invoicer::*;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::{HashMap, HashSet};
+
+/// A root organization with two children
+fn org_tree() -> DataSet<FhlGraph> {
+  let mut root: Node<FhlGraph> =
+    Node::new(Organization::new("root", "Root Org", dec!(10.5)).into());
+  for (pretty_id, name) in [("heir", "Heir Org"), ("spare", "Spare Org")] {
+    let child = Node::new(Organization::new(pretty_id, name, dec!(5)).into());
+    root
+      .create_edge(FhlEdge::new(FhlEdgeType::ParentOf.into()), child)
+      .expect("Could not create the ParentOf edge");
+  }
+
+  let mut data_set = DataSet::new();
+  data_set
+    .insert(root.into())
+    .expect("Failed to insert the org tree");
+  data_set
+}
+
+db_test_fn! {
+  fn test_match_edges() {
+    let data_set = org_tree();
+
+    let result = data_set
+      .query("MATCH (o:Organization)-[:ParentOf]->(c) RETURN o, c")
+      .expect("Query failed");
+
+    let parents = result.get("o").expect("Missing column o");
+    let children = result.get("c").expect("Missing column c");
+    assert_eq!(parents.len(), 2);
+    assert_eq!(children.len(), 2);
+
+    // Reversing the arrow finds the same rows from the other end
+    let result = data_set
+      .query("MATCH (c)<-[:ParentOf]-(o) RETURN DISTINCT o")
+      .expect("Query failed");
+    assert_eq!(result.get("o").map(|x| x.len()), Some(1));
+  }
+}
+
+db_test_fn! {
+  fn test_where_and_properties() {
+    let data_set = org_tree();
+
+    let result = data_set
+      .query(r#"
+        MATCH (o:Organization)-[:ParentOf]->(c:Organization)
+        WHERE c.org_name STARTS WITH 'Heir' AND o.balance > 5
+        RETURN c.pretty_id AS id, o.balance
+      "#)
+      .expect("Query failed");
+
+    assert_eq!(result.get("id"), Some(&vec![Value::Literal("heir".into())]));
+    assert_eq!(result.get("o.balance"), Some(&vec![Value::Literal(dec!(10.5).into())]));
+
+    // Filtering on a list of ids only finds the root
+    let roots = data_set
+      .nodes("MATCH (o:Organization) WHERE o.pretty_id IN ['root', 'missing'] RETURN o")
+      .expect("Query failed");
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].get_property("org_name"), Ok("Root Org".into()));
+  }
+}
+
+db_test_fn! {
+  fn test_invalid_query() {
+    let data_set = org_tree();
+
+    let result = data_set.query("MATCH (o:Organization RETURN o");
+    assert!(result.expect_err("Query should not parse").is(Kind::ParsingError));
+
+    // Arithmetic that can't be represented is an error rather than a panic or a hang
+    let result = data_set.query("RETURN -(-9223372036854775807 - 1) AS x");
+    assert!(result.expect_err("Negated i64::MIN").is(Kind::ConversionError));
+    let result = data_set.query("RETURN 1 ^ 100000000 AS x");
+    assert!(result.expect_err("Exponent over the limit").is(Kind::InvalidItem));
+    let result = data_set.query("RETURN 0 ^ -1 AS x");
+    assert!(result.expect_err("Zero to a negative power").is(Kind::InvalidItem));
+    let result = data_set.query("RETURN 2 ^ 10 AS x, 0.5 ^ -2 AS y").expect("Query failed");
+    assert_eq!(result.get("x"), Some(&vec![Value::Literal(dec!(1024).into())]));
+    assert_eq!(result.get("y"), Some(&vec![Value::Literal(dec!(4).into())]));
+  }
+}
+
+db_test_fn! {
+  fn test_projection_clauses() {
+    let data_set = org_tree();
+
+    // WITH passes on named values and can be filtered, then sorted, skipped and limited
+    let result = data_set
+      .query(r#"
+        MATCH (o:Organization)
+        WITH o.pretty_id AS id, o.balance AS balance
+        WHERE balance < 10
+        RETURN id ORDER BY id DESC
+      "#)
+      .expect("Query failed");
+    assert_eq!(
+      result.get("id"),
+      Some(&vec![Value::Literal("spare".into()), Value::Literal("heir".into())])
+    );
+
+    let result = data_set
+      .query("MATCH (o:Organization) RETURN o.pretty_id AS id ORDER BY id SKIP 1 LIMIT 1")
+      .expect("Query failed");
+    assert_eq!(result.get("id"), Some(&vec![Value::Literal("root".into())]));
+
+    let result = data_set
+      .query("UNWIND [3, 1, 2] AS x RETURN x ORDER BY x")
+      .expect("Query failed");
+    assert_eq!(
+      result.get("x"),
+      Some(&vec![
+        Value::Literal(1.into()),
+        Value::Literal(2.into()),
+        Value::Literal(3.into()),
+      ])
+    );
+  }
+}
+
+db_test_fn! {
+  fn test_optional_match_and_pattern_predicates() {
+    let data_set = org_tree();
+
+    // Every org is returned, with a null parent for the root
+    let result = data_set
+      .query(r#"
+        MATCH (c:Organization)
+        OPTIONAL MATCH (p)-[:ParentOf]->(c)
+        RETURN c.pretty_id AS id, p.pretty_id AS parent ORDER BY id
+      "#)
+      .expect("Query failed");
+    assert_eq!(
+      result.get("parent"),
+      Some(&vec![
+        Value::Literal("root".into()),
+        Value::Literal(Literal::Null),
+        Value::Literal("root".into()),
+      ])
+    );
+
+    // The root is the only org without a parent
+    let roots = data_set
+      .nodes("MATCH (o:Organization) WHERE NOT ()-[:ParentOf]->(o) RETURN o")
+      .expect("Query failed");
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].get_property("pretty_id"), Ok("root".into()));
+
+    // Updates need a mutable store
+    let result = data_set.query("MATCH (o) DETACH DELETE o");
+    assert!(result.expect_err("Deleted from a read only store").is(Kind::NotImplemented));
+  }
+}
+
+/// Get the single number in a column, whether it is an integer or a decimal
+fn number(result: &HashMap<String, Vec<Value<FhlGraph>>>, column: &str) -> Option<Decimal> {
+  match result.get(column).map(|x| x.as_slice()) {
+    Some([Value::Literal(value)]) => value.as_decimal(),
+    _ => None,
+  }
+}
+
+/// Get the MATCH clause at the start of a query
+fn first_match(query: &str) -> Match {
+  match GQuery::parse(query)
+    .expect("Could not parse the query")
+    .clauses
+    .remove(0)
+  {
+    Clause::Match(matched) => matched,
+    clause => panic!("Expected a MATCH but found {}", clause),
+  }
+}
+
+db_test_fn! {
+  fn test_plan_match() {
+    let data_set = org_tree();
+    let nothing_bound = HashSet::new();
+
+    // Labels from the WHERE clause pick the start of the pattern
+    let matched = first_match("MATCH (c)<--(o) WHERE o:Organization RETURN c");
+    let plan = planner::plan_match(&data_set, &matched, &nothing_bound);
+    assert_eq!(
+      plan.parts[0].anchor,
+      Anchor::Label { node: 1, label: "Organization".to_string() }
+    );
+    assert_eq!(plan.parts[0].expands, vec![Expand { step: 0, reversed: true }]);
+    assert_eq!(plan.to_string(), "NodeByLabelScan(o:Organization) -> Expand(o)-->(c) (~2 rows)");
+
+    // There are fewer ParentOf edges than organizations
+    let matched = first_match("MATCH (o:Organization)-[:ParentOf]->(c) RETURN c");
+    let plan = planner::plan_match(&data_set, &matched, &nothing_bound);
+    assert_eq!(
+      plan.parts[0].anchor,
+      Anchor::EdgeTypes { step: 0, types: vec!["ParentOf".to_string()] }
+    );
+    assert!(plan.parts[0].expands.is_empty());
+
+    // Variables from earlier clauses are the cheapest place to start, and parts that use them
+    // are matched first
+    let matched = first_match("MATCH (x:Organization), (o:Organization)-[:ParentOf]->(c) RETURN c");
+    let bound = HashSet::from(["c".to_string()]);
+    let plan = planner::plan_match(&data_set, &matched, &bound);
+    assert_eq!(plan.parts[0].index, 1);
+    assert_eq!(plan.parts[0].anchor, Anchor::Bound { node: 1, variable: "c".to_string() });
+
+    // Undirected edge scans find each edge from both ends
+    let result = data_set
+      .query("MATCH (a)-[:ParentOf]-(b) RETURN a")
+      .expect("Query failed");
+    assert_eq!(result.get("a").map(|x| x.len()), Some(4));
+  }
+}
+
+db_test_fn! {
+  fn test_explain_and_profile() {
+    let data_set = org_tree();
+    let query = "MATCH (o:Organization)-[:ParentOf]->(c) WHERE c.balance > 1 RETURN o, c";
+
+    let plan = data_set.explain(query).expect("Explain failed");
+    assert_eq!(
+      plan.lines(),
+      vec![
+        "Results",
+        "    Project | o, c",
+        "        Filter | c.balance > 1",
+        "            Edge By Type Scan | (o)-[:ParentOf]->(c)",
+      ]
+    );
+    assert!(plan.totals().rows.count() == 0, "Explain shouldn't run the query");
+
+    // Timings vary between runs, so the diff only compares the counts
+    let plan = data_set.profile(query).expect("Profile failed");
+    let scan = plan.find("Edge By Type Scan").and_then(|x| x.stats.clone());
+    scan.diff(&Some(OperatorStats::record(2, 2, Default::default())), None).assert_empty();
+    let results = plan.stats.clone().expect("Missing the stats for the results");
+    assert_eq!(results.rows.count(), 2);
+
+    // Label scans count the nodes read from the label index
+    let plan = data_set
+      .query("PROFILE MATCH (o:Organization) RETURN o.pretty_id")
+      .expect("Query failed");
+    let lines = plan.get("plan").expect("Missing the plan column");
+    assert_eq!(lines.len(), 3);
+    match &lines[2] {
+      Value::Literal(Literal::String(line)) => assert!(
+        line.starts_with("        Node By Label Scan | (o:Organization) | Records produced: 3, Index hits: 3,"),
+        "Unexpected line {:?}",
+        line
+      ),
+      value => panic!("Expected a line of the plan but found {:?}", value),
+    }
+  }
+}
+
+db_test_fn! {
+  fn test_query_params() {
+    let data_set = org_tree();
+
+    let query = "MATCH (o:Organization {pretty_id: $id}) RETURN o.org_name AS name";
+    let result = data_set
+      .query_with(query, &Params::new().set("id", "heir"))
+      .expect("Query failed");
+    assert_eq!(result.get("name"), Some(&vec![Value::Literal("Heir Org".into())]));
+
+    // Values are never part of the query text, so quotes can't change its meaning
+    let result = data_set
+      .query_with(query, &Params::new().set("id", "heir'}) RETURN 1 //"))
+      .expect("Query failed");
+    assert_eq!(result.get("name").map(|x| x.len()), Some(0));
+
+    // A parsed query can be run again with different values
+    let query = GQuery::parse(r#"
+      UNWIND $ids AS id
+      MATCH (o:Organization {pretty_id: id}) WHERE o.balance >= $min
+      RETURN o.pretty_id AS id ORDER BY id SKIP $skip
+    "#).expect("Could not parse the query");
+    let params = Params::new()
+      .set("ids", vec!["root", "spare", "heir"])
+      .set("min", dec!(5))
+      .set("skip", 0);
+    let result = data_set.execute_with(&query, &params).expect("Query failed");
+    assert_eq!(result.get("id").map(|x| x.len()), Some(3));
+
+    let result = data_set
+      .execute_with(&query, &params.set("min", dec!(10)).set("skip", 0))
+      .expect("Query failed");
+    assert_eq!(result.get("id"), Some(&vec![Value::Literal("root".into())]));
+
+    let err = data_set.execute(&query).expect_err("Ran without the params");
+    assert!(err.is(Kind::NotSet), "Wrong error for missing params: {}", err);
+  }
+}
+
+db_test_fn! {
+  fn test_aggregations() {
+    let data_set = org_tree();
+
+    // Total balance per parent, grouped by the items that don't aggregate
+    let result = data_set
+      .query(r#"
+        MATCH (p:Organization)-[:ParentOf]->(c)
+        RETURN p.pretty_id AS parent, sum(c.balance) AS total, count(*) AS children,
+          collect(c.pretty_id) AS ids
+      "#)
+      .expect("Query failed");
+    assert_eq!(result.get("parent"), Some(&vec![Value::Literal("root".into())]));
+    assert_eq!(number(&result, "total"), Some(dec!(10)));
+    assert_eq!(result.get("children"), Some(&vec![Value::Literal(Literal::Integer(2))]));
+    match result.get("ids").map(|x| x.as_slice()) {
+      Some([Value::Literal(Literal::List(ids))]) => assert_eq!(ids.len(), 2),
+      ids => panic!("Expected a list of ids but found {:?}", ids),
+    }
+
+    // Decimal sums are exact, and every row is one group when nothing is grouped on
+    let result = data_set
+      .query(r#"
+        MATCH (o:Organization)
+        RETURN sum(o.balance) AS total, avg(o.balance) * 3 AS average, min(o.balance) AS low,
+          max(o.balance) AS high, count(DISTINCT o.balance) AS balances
+      "#)
+      .expect("Query failed");
+    let expected = [
+      ("total", dec!(20.5)),
+      ("average", dec!(20.5)),
+      ("low", dec!(5)),
+      ("high", dec!(10.5)),
+      ("balances", dec!(2)),
+    ];
+    for (column, value) in expected {
+      assert_eq!(number(&result, column), Some(value), "Wrong {}", column);
+    }
+
+    // Counting nothing is still a row
+    let result = data_set.query("MATCH (o:Missing) RETURN count(*) AS n").expect("Query failed");
+    assert_eq!(result.get("n"), Some(&vec![Value::Literal(Literal::Integer(0))]));
+
+    // WITH groups as well, and ORDER BY can use aggregates
+    let result = data_set
+      .query(r#"
+        MATCH (o:Organization) OPTIONAL MATCH (o)-[:ParentOf]->(c)
+        WITH o, count(c) AS children WHERE children < 2
+        RETURN o.pretty_id AS id ORDER BY id
+      "#)
+      .expect("Query failed");
+    assert_eq!(
+      result.get("id"),
+      Some(&vec![Value::Literal("heir".into()), Value::Literal("spare".into())])
+    );
+
+    // Collected nodes can still be pulled out of the results
+    let children = data_set
+      .nodes("MATCH (p)-[:ParentOf]->(c) RETURN p, collect(c) AS children")
+      .expect("Query failed");
+    assert_eq!(children.len(), 3);
+
+    let err = data_set
+      .query("MATCH (o) WHERE count(o) > 1 RETURN o")
+      .expect_err("Aggregated in a WHERE");
+    assert!(err.is(Kind::InvalidItem), "Wrong error: {}", err);
+  }
+}
+
+db_test_fn! {
+  fn test_mutating_queries() {
+    let mut data_set = org_tree();
+    let label_count = |stats: Option<DataSetStats>, label: &str| {
+      stats.and_then(|x| x.nodes.labels.get(&label.to_string()).map(|x| x.count()))
+    };
+
+    // CREATE can add to a node found by an earlier MATCH
+    let stats = data_set
+      .query_mut(r#"
+        MATCH (root:Organization {pretty_id: 'root'})
+        CREATE (root)-[:ParentOf]->(:Organization:Active {pretty_id: 'third', org_name: 'Third Org'})
+      "#)
+      .expect("Create failed");
+    let created = stats.created().expect("Nothing was created");
+    assert_eq!(created.nodes.total.count(), 1);
+    assert_eq!(created.edges.total.count(), 1);
+    assert_eq!(data_set.count_nodes(Some("Active")), 1);
+
+    // MERGE matches on the key, so an existing org only runs ON MATCH
+    let merge = r#"
+      MERGE (o:Organization {pretty_id: $id})
+      ON CREATE SET o.org_name = 'New Org'
+      ON MATCH SET o.balance = o.balance + 1
+    "#;
+    let stats = data_set
+      .query_mut_with(merge, &Params::new().set("id", "heir"))
+      .expect("Merge failed");
+    assert!(stats.created().is_none());
+    let updated = stats.updated().expect("Nothing was updated");
+    assert_eq!(updated.nodes.properties.get(&"balance".to_string()).map(|x| x.count()), Some(1));
+
+    let stats = data_set
+      .query_mut_with(merge, &Params::new().set("id", "fourth"))
+      .expect("Merge failed");
+    assert_eq!(stats.created().map(|x| x.nodes.total.count()), Some(1));
+
+    let result = data_set
+      .query("MATCH (o:Organization) WHERE o.pretty_id IN ['heir', 'fourth'] RETURN o.org_name AS name ORDER BY name")
+      .expect("Query failed");
+    assert_eq!(result.get("name"), Some(&vec![Value::Literal("Heir Org".into()), Value::Literal("New Org".into())]));
+    let result = data_set
+      .query("MATCH (o:Organization {pretty_id: 'heir'}) RETURN o.balance AS balance")
+      .expect("Query failed");
+    assert_eq!(number(&result, "balance"), Some(dec!(6)));
+
+    // MERGE matches the pattern's properties, not only its key, so a non-key property finds the org
+    let stats = data_set
+      .query_mut("MERGE (o:Organization {org_name: 'Heir Org'}) ON MATCH SET o.balance = 7")
+      .expect("Merge failed");
+    assert!(stats.created().is_none());
+    assert_eq!(data_set.count_nodes(Some("Organization")), 5);
+    let result = data_set
+      .query("MATCH (o:Organization {pretty_id: 'heir'}) RETURN o.balance AS balance")
+      .expect("Query failed");
+    assert_eq!(number(&result, "balance"), Some(dec!(7)));
+
+    // Setting a property the entity doesn't have is an error instead of being dropped
+    let error = data_set.query_mut("MATCH (o {pretty_id: 'heir'}) SET o.nonexistent = 1");
+    assert!(error.expect_err("Set an unknown property").is(Kind::InvalidItem));
+
+    // Labels added are updates, while labels removed are deletes
+    let stats = data_set
+      .query_mut("MATCH (o:Active) REMOVE o:Active SET o:Closed")
+      .expect("Updating labels failed");
+    assert_eq!(label_count(stats.updated(), "Closed"), Some(1));
+    assert_eq!(label_count(stats.deleted(), "Active"), Some(1));
+    assert_eq!(data_set.count_nodes(Some("Active")), 0);
+    assert_eq!(data_set.count_nodes(Some("Closed")), 1);
+    let error = data_set.query_mut("MATCH (o:Closed) REMOVE o:Organization");
+    assert!(error.expect_err("Removed the type label").is(Kind::InvalidItem));
+
+    // An edge's properties can be set like a node's
+    data_set
+      .query_mut("MATCH (a {pretty_id: 'heir'}), (b {pretty_id: 'spare'}) CREATE (a)-[:Paid {amount: 3}]->(b)")
+      .expect("Creating the payment failed");
+    let stats = data_set
+      .query_mut("MATCH ()-[p:Paid]->() SET p.amount = 4")
+      .expect("Updating the payment failed");
+    assert_eq!(stats.updated().map(|x| x.edges.total.count()), Some(1));
+    let result = data_set
+      .query("MATCH ()-[p:Paid]->() RETURN p.amount AS amount")
+      .expect("Query failed");
+    assert_eq!(number(&result, "amount"), Some(dec!(4)));
+
+    // A node with edges can only be deleted along with them
+    let error = data_set.query_mut("MATCH (o {pretty_id: 'root'}) DELETE o");
+    assert!(error.expect_err("Deleted a node with edges").is(Kind::InvalidItem));
+
+    let stats = data_set
+      .query_mut("MATCH (o {pretty_id: 'root'}) DETACH DELETE o")
+      .expect("Delete failed");
+    let deleted = stats.deleted().expect("Nothing was deleted");
+    assert_eq!(deleted.nodes.total.count(), 1);
+    assert_eq!(deleted.edges.typed.get(&"ParentOf".to_string()).map(|x| x.count()), Some(3));
+    assert_eq!(data_set.count_nodes(None), 4);
+    assert_eq!(data_set.count_edges(Some("ParentOf")), 0);
+    assert_eq!(data_set.count_edges(None), 1);
+  }
+}
+
+db_test_fn! {
+  fn test_variable_length_paths() {
+    let mut data_set = org_tree();
+    data_set
+      .query_mut("MATCH (h {pretty_id: 'heir'}) CREATE (h)-[:ParentOf]->(:Organization:Leaf {pretty_id: 'grand'})")
+      .expect("Create failed");
+    let ids = |query: &str| -> Vec<Value<FhlGraph>> {
+      data_set.query(query).expect("Query failed").remove("id").unwrap_or_default()
+    };
+
+    // Every descendant, however deep
+    assert_eq!(
+      ids("MATCH ({pretty_id: 'root'})-[:ParentOf*1..5]->(d) RETURN d.pretty_id AS id ORDER BY id"),
+      vec![Value::Literal("grand".into()), Value::Literal("heir".into()), Value::Literal("spare".into())]
+    );
+    assert_eq!(
+      ids("MATCH ({pretty_id: 'root'})-[:ParentOf*2]->(d) RETURN d.pretty_id AS id"),
+      vec![Value::Literal("grand".into())]
+    );
+    // Zero hops includes the start node
+    assert_eq!(
+      ids("MATCH ({pretty_id: 'heir'})-[:ParentOf*0..]->(d) RETURN d.pretty_id AS id ORDER BY id"),
+      vec![Value::Literal("grand".into()), Value::Literal("heir".into())]
+    );
+    // A huge upper bound is planned and walked as quickly as a small one
+    assert_eq!(
+      ids("MATCH ({pretty_id: 'root'})-[:ParentOf*1..2000000000]->(d) RETURN d.pretty_id AS id ORDER BY id"),
+      vec![Value::Literal("grand".into()), Value::Literal("heir".into()), Value::Literal("spare".into())]
+    );
+
+    // The relationship variable holds the list of edges walked
+    let result = data_set
+      .query("MATCH ({pretty_id: 'root'})-[rs:ParentOf*2]->() RETURN size(rs) AS hops")
+      .expect("Query failed");
+    assert_eq!(number(&result, "hops"), Some(dec!(2)));
+
+    // The leaf anchors the match, but the path still runs from left to right
+    let explain = data_set
+      .explain("MATCH p = ({pretty_id: 'root'})-[:ParentOf*]->(:Leaf) RETURN p")
+      .expect("Explain failed");
+    assert!(explain.find("Conditional Variable Length Traverse").is_some());
+    let mut result = data_set
+      .query("MATCH p = ({pretty_id: 'root'})-[:ParentOf*]->(:Leaf) RETURN p, length(p) AS hops")
+      .expect("Query failed");
+    assert_eq!(number(&result, "hops"), Some(dec!(2)));
+    let path = match result.remove("p").as_deref() {
+      Some([Value::Path(path)]) => path.clone(),
+      x => panic!("Expected a single path, not {:?}", x),
+    };
+    let names: Vec<Literal> = path
+      .nodes()
+      .iter()
+      .map(|node| node.get_property("pretty_id").expect("Missing pretty_id"))
+      .collect();
+    assert_eq!(names, vec!["root".into(), "heir".into(), "grand".into()]);
+
+    // Inserting a path adds everything along it
+    let path = match data_set.query("MATCH p = ({pretty_id: 'heir'})-->() RETURN p").expect("Query failed").remove("p").as_deref() {
+      Some([Value::Path(path)]) => path.clone(),
+      x => panic!("Expected a single path, not {:?}", x),
+    };
+    let mut copy = DataSet::new();
+    let stats = copy.insert(Value::Path(path)).expect("Insert failed");
+    assert_eq!(stats.created().map(|x| x.edges.total.count()), Some(1));
+    assert_eq!(copy.count_nodes(None), 2);
+  }
+}
+
+db_test_fn! {
+  fn test_shortest_paths() {
+    // Two ways down from the root to the grandchild, and a costly shortcut
+    let mut data_set = org_tree();
+    data_set
+      .query_mut(
+        "MATCH (h {pretty_id: 'heir'}), (s {pretty_id: 'spare'}) \
+         CREATE (h)-[:ParentOf]->(g:Organization {pretty_id: 'grand'})<-[:ParentOf]-(s)",
+      )
+      .expect("Create failed");
+    let node = |pretty_id: &str| -> Node<FhlGraph> {
+      let query = format!("MATCH (n {{pretty_id: '{}'}}) RETURN n", pretty_id);
+      data_set.nodes(&query).expect("Query failed").remove(0)
+    };
+    let (root, grand) = (node("root"), node("grand"));
+    let shortcut = Edge::new(&root, &grand, FhlEdge::new(FhlEdgeType::ChildOf.into())).expect("Invalid edge").with_weight(5.0);
+    data_set.insert(shortcut.into()).expect("Insert failed");
+
+    // Counting hops takes the shortcut, unless only ParentOf edges are followed
+    let path = data_set
+      .shortest_path(&root.get_guid(), &grand.get_guid(), &PathOpts::new())
+      .expect("Search failed")
+      .expect("No path found");
+    assert_eq!(path.length(), 1);
+    let parents = PathOpts::new().label("ParentOf");
+    let paths = data_set
+      .all_shortest_paths(&root.get_guid(), &grand.get_guid(), &parents)
+      .expect("Search failed");
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|path| path.length() == 2 && path.end() == Some(grand.clone())));
+
+    // Weighted, the shortcut costs more than either way round, with or without a heuristic
+    for opts in [PathOpts::new().weighted(), PathOpts::new().weighted().heuristic(|_, _| 0.5)] {
+      let paths = data_set
+        .all_shortest_paths(&root.get_guid(), &grand.get_guid(), &opts)
+        .expect("Search failed");
+      assert_eq!(paths.iter().map(|x| x.length()).collect::<Vec<_>>(), vec![2, 2]);
+    }
+    let opts = PathOpts::new().weighted().max_length(1);
+    let path = data_set.shortest_path(&root.get_guid(), &grand.get_guid(), &opts).expect("Search failed");
+    assert_eq!(path.map(|x| x.length()), Some(1));
+
+    // Edges are only walked backwards when asked
+    let reverse = |opts: PathOpts<FhlGraph>| {
+      data_set.shortest_path(&grand.get_guid(), &root.get_guid(), &opts).expect("Search failed")
+    };
+    assert!(reverse(parents.clone()).is_none());
+    assert_eq!(reverse(parents.direction(Direction::Incoming)).map(|x| x.length()), Some(2));
+
+    // Queries use the same search
+    let result = data_set
+      .query(
+        "MATCH p = shortestPath(({pretty_id: 'root'})-[:ParentOf*]->({pretty_id: 'grand'})) \
+         RETURN length(p) AS hops",
+      )
+      .expect("Query failed");
+    assert_eq!(number(&result, "hops"), Some(dec!(2)));
+    let result = data_set
+      .query(
+        "MATCH p = allShortestPaths((r {pretty_id: 'root'})-[:ParentOf*]->(g {pretty_id: 'grand'})) \
+         RETURN count(p) AS paths",
+      )
+      .expect("Query failed");
+    assert_eq!(number(&result, "paths"), Some(dec!(2)));
+    let explain = data_set
+      .explain("MATCH p = shortestPath((:Organization)-[*]->(:Organization)) RETURN p")
+      .expect("Explain failed");
+    assert!(explain.find("Shortest Path").is_some());
+  }
+}
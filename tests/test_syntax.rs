@@ -0,0 +1,82 @@
+//! Test the lossless syntax tree
+
+use grapht::{
+  gquery::syntax::{self, SyntaxKind},
+  prelude::*,
+};
+
+#[macro_use]
+mod common;
+
+db_test_fn! {
+  fn test_lossless_tree() {
+    let queries = [
+      "MATCH (o:Organization)-[:ParentOf*1..2]->(c) RETURN o, c",
+      r#"
+        // Find the roots
+        MATCH (org:Organization) /* no parents */
+        WHERE NOT (org)-[:ChildOf]->(:Organization) AND org.balance >= -1.5e2
+        WITH org, count(*) AS total, CASE org.name WHEN 'a' THEN 1 ELSE 2 END AS x
+        UNWIND [1, 2] AS n
+        RETURN DISTINCT org.`pretty id` AS id, $param, date.truncate('day', n)
+        ORDER BY id DESC SKIP 1 LIMIT 5;
+      "#,
+      "MERGE (a {id: 1})-[:ParentOf]->(b) ON CREATE SET a.x = 1, b:New ON MATCH SET a += {y: 2}",
+      "MATCH (a) SET a = {} REMOVE a.x, a:Old DETACH DELETE a",
+    ];
+
+    for query in queries {
+      let parse = syntax::parse(query);
+      assert!(parse.is_ok(), "Errors parsing {:?}: {:?}", query, parse.errors());
+      assert_eq!(parse.syntax().text().to_string(), query);
+
+      // A clean tree converts to the same query as the nom parser
+      assert_eq!(parse.to_query(), GQuery::parse(query));
+    }
+
+    // Comments are kept as tokens
+    let parse = syntax::parse("MATCH (o) // trailing\nRETURN o");
+    let comments: Vec<String> = parse
+      .syntax()
+      .descendants_with_tokens()
+      .filter(|x| x.kind() == SyntaxKind::COMMENT)
+      .map(|x| x.to_string())
+      .collect();
+    assert_eq!(comments, vec!["// trailing"]);
+  }
+}
+
+db_test_fn! {
+  fn test_error_recovery() {
+    // Half typed in an editor
+    let query = "MATCH (o:Organization)-[:ParentOf]->(c\nWHERE c. = 'x' AND\nRETURN o, c ORDER";
+    let parse = syntax::parse(query);
+    assert_eq!(parse.syntax().text().to_string(), query);
+    assert!(!parse.is_ok());
+
+    // Each clause still gets its own node
+    let clauses: Vec<SyntaxKind> = parse.syntax().children().map(|x| x.kind()).collect();
+    assert_eq!(clauses, vec![SyntaxKind::MATCH_CLAUSE, SyntaxKind::RETURN_CLAUSE]);
+    assert!(parse.syntax().descendants().any(|x| x.kind() == SyntaxKind::WHERE_CLAUSE));
+
+    // The first problem is the missing close parenthesis on the second line
+    let first = &parse.errors()[0];
+    assert_eq!(first.message, "Expected ')'");
+    assert_eq!(first.position(query), (2, 1));
+
+    let err = parse.to_query().expect_err("Converted a broken query");
+    assert!(err.is(Kind::ParsingError));
+
+    // Junk between clauses is wrapped up in an error node
+    let parse = syntax::parse("MATCH (o) ) ) RETURN o");
+    assert_eq!(parse.errors().len(), 1);
+    let kinds: Vec<SyntaxKind> = parse.syntax().children().map(|x| x.kind()).collect();
+    assert_eq!(kinds, vec![SyntaxKind::MATCH_CLAUSE, SyntaxKind::ERROR, SyntaxKind::RETURN_CLAUSE]);
+
+    // Unterminated strings are kept as error tokens
+    let query = "MATCH (o) RETURN 'abc";
+    let parse = syntax::parse(query);
+    assert_eq!(parse.syntax().text().to_string(), query);
+    assert_eq!(parse.errors().len(), 1);
+  }
+}